@@ -0,0 +1,85 @@
+//! Download throughput against a local mock CDN, to catch regressions in the chunk-to-file
+//! loop. Run with `cargo bench --bench throughput`; set `TIKD_BENCH_MIB` to change the size.
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tikd_r::downloader::{DownloadConfig, Downloader};
+use url::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn share_page(download_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"1","author":{{"uniqueId":"bench"}},"video":{{"downloadAddr":"{download_url}","playAddr":""}}}}}}}}}}}}</script></head><body></body></html>"#
+    )
+}
+
+fn throughput(c: &mut Criterion) {
+    let mib: usize = std::env::var("TIKD_BENCH_MIB")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(64);
+    let body = vec![0x5a; mib << 20];
+
+    let runtime = tokio::runtime::Runtime::new().expect("build runtime");
+    let server = runtime.block_on(async {
+        let server = MockServer::start().await;
+        let uri = server.uri();
+        Mock::given(method("GET"))
+            .and(path("/@bench/video/1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(share_page(&format!("{uri}/media/1.mp4")), "text/html"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/media/1.mp4"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "video/mp4"))
+            .mount(&server)
+            .await;
+        server
+    });
+    let uri = server.uri();
+
+    let mut group = c.benchmark_group("download");
+    group
+        .throughput(Throughput::Bytes((mib << 20) as u64))
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+    group.bench_function(format!("{mib} MiB"), |b| {
+        b.to_async(&runtime).iter_batched(
+            || {
+                // A fresh directory each run, so the file is never skipped as already
+                // downloaded.
+                let dir = tempfile::tempdir().unwrap();
+                let config = DownloadConfig {
+                    output_dir: Some(dir.path().to_path_buf()),
+                    scrape_base_url: Some(Url::parse(&uri).unwrap()),
+                    ..DownloadConfig::default()
+                };
+                (
+                    dir,
+                    Downloader::with_config(config).expect("build downloader"),
+                )
+            },
+            |(dir, downloader)| {
+                let url = format!("{uri}/@bench/video/1");
+                async move {
+                    downloader
+                        .download_one(&url)
+                        .await
+                        .expect("download succeeds");
+                    dir
+                }
+            },
+            // Dropping the directory removes the download, outside the timed routine.
+            BatchSize::PerIteration,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
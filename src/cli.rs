@@ -1,31 +1,81 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
+#[cfg(feature = "simulate-network")]
+use crate::downloader::NetworkSimulation;
+use crate::downloader::{
+    ActiveHours, ContentDedupe, DedupeMode, FileOwner, FilenameStyle, MatchFilter,
+    MediaHeaderPreset, Quality, SanitizePolicy,
+};
 use crate::error::Error;
+use crate::export::ExportFormat;
+use crate::input::ExportSection;
+use crate::scraper::Geo;
+use crate::template::OutputTemplate;
 
 /// Command line arguments supported by the TikD-R binary.
-#[derive(Debug, Parser)]
+#[derive(Debug, Default, Parser)]
 #[command(
     name = "tikd-r",
     about = "Download TikTok videos via a fast Rust CLI.",
     version,
     author,
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Download a single TikTok video by URL.
     #[arg(value_name = "VIDEO_URL")]
     pub url: Option<String>,
 
-    /// Path to a file with line-delimited TikTok URLs for batch downloads.
+    /// Path to a file with line-delimited TikTok URLs for batch downloads (`-` reads stdin).
     #[arg(long, value_name = "PATH")]
     pub file: Option<PathBuf>,
 
+    /// `user_data.json` from TikTok's "Download your data" export; its video links are queued.
+    #[arg(long, value_name = "PATH")]
+    pub tiktok_export: Option<PathBuf>,
+
+    /// Sections of --tiktok-export to queue.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "liked,favorites",
+        requires = "tiktok_export"
+    )]
+    pub export_sections: Vec<ExportSection>,
+
+    /// Entries in --file are separated by NUL bytes instead of newlines (e.g. `find -print0`).
+    #[arg(short = '0', long)]
+    pub null: bool,
+
     /// Maximum number of concurrent downloads.
     #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
     pub max_concurrent: Option<usize>,
 
+    /// Maximum concurrent media transfers for any one creator.
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub max_concurrent_per_author: Option<usize>,
+
+    /// Download at most this many videos per creator in a batch; the rest are skipped and
+    /// written to the resume file for a later run.
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub max_per_author: Option<usize>,
+
+    /// Scrape in a separate stage that stays at most this many items ahead of the downloads.
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub scrape_queue: Option<usize>,
+
+    /// Share pages scraped at once by the separate scrape stage (default: 2).
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub scrape_workers: Option<usize>,
+
     /// Maximum retry attempts per URL on transient failures.
     #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
     pub max_retries: Option<usize>,
@@ -37,15 +87,478 @@ pub struct Cli {
     /// Output directory for downloaded videos (default: current directory).
     #[arg(short, long, value_name = "DIR")]
     pub output_dir: Option<PathBuf>,
+
+    /// Name files after the video ID, the caption, or both.
+    #[arg(long, value_name = "STYLE", value_enum)]
+    pub filename_style: Option<FilenameStyle>,
+
+    /// Path of each video under the output directory, without extension, built from
+    /// `{author}`, `{video_id}`, `{upload_date}` (YYYYMMDD), `{desc}` (caption slug), and
+    /// `{title}` (caption as written), e.g. `{author}/{upload_date}-{video_id}`.
+    #[arg(long, value_name = "TEMPLATE", value_parser = OutputTemplate::parse, conflicts_with = "filename_style")]
+    pub output_template: Option<OutputTemplate>,
+
+    /// Name files and write --write-info-json sidecars like another downloader, so scripts
+    /// written for it keep working; --output-template and --filename-style still apply.
+    #[arg(long, value_name = "TOOL", value_enum)]
+    pub compat: Option<Compat>,
+
+    /// Romanize captions in title-based file names (Thai to Latin, accents removed).
+    #[arg(long)]
+    pub transliterate: bool,
+
+    /// Characters allowed in file and folder names; pick the target filesystem's rules.
+    #[arg(long, value_name = "POLICY", value_enum)]
+    pub sanitize: Option<SanitizePolicy>,
+
+    /// Longest file or folder name in bytes; longer names are cut and end in a hash of the ID.
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(usize))]
+    pub max_filename_length: Option<usize>,
+
+    /// Longest output path in bytes, e.g. 260 for Windows tools without long-path support.
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(usize))]
+    pub max_path_length: Option<usize>,
+
+    /// Reuse copies of a video already present elsewhere in the archive.
+    #[arg(long, value_name = "MODE", value_enum)]
+    pub dedupe: Option<DedupeMode>,
+
+    /// Skip videos listed in this yt-dlp-compatible archive file and add each one saved.
+    #[arg(long, value_name = "PATH")]
+    pub download_archive: Option<PathBuf>,
+
+    /// Record the size and CRC-32 of each saved video in the output directory's
+    /// `tikd-r-checksums.jsonl`, for `tikd-r verify`.
+    #[arg(long)]
+    pub write_checksums: bool,
+
+    /// Compare finished downloads byte for byte with videos saved under other IDs.
+    #[arg(long, value_name = "MODE", value_enum)]
+    pub content_dedupe: Option<ContentDedupe>,
+
+    /// Stop starting new downloads after this many bytes (accepts K, M, G suffixes).
+    #[arg(long, value_name = "BYTES", value_parser = parse_byte_size)]
+    pub max_total_bytes: Option<u64>,
+
+    /// Refuse new downloads once the output directory holds this much (e.g. `500G`).
+    /// Needs --output-dir.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        value_parser = parse_byte_size,
+        requires = "output_dir"
+    )]
+    pub max_archive_size: Option<u64>,
+
+    /// With --max-archive-size, delete the oldest downloaded videos, and their sidecar
+    /// files, instead of refusing.
+    #[arg(long, requires = "max_archive_size")]
+    pub prune_oldest: bool,
+
+    /// Stop starting new downloads after this long, e.g. `90s`, `30m`, `1h30m`.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_runtime: Option<Duration>,
+
+    /// Abort a transfer if no data arrives for this many seconds (0 disables).
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64))]
+    pub stall_timeout: Option<u64>,
+
+    /// Switch to an alternate URL when throughput stays below this rate per second (e.g. `200K`).
+    #[arg(long, value_name = "BYTES", value_parser = parse_byte_size)]
+    pub min_speed: Option<u64>,
+
+    /// Buffer this much received media before each disk write (default: 256K, at most 64M).
+    #[arg(long, value_name = "BYTES", value_parser = parse_write_buffer_size)]
+    pub write_buffer_size: Option<usize>,
+
+    /// Test mode: delay every request and drop requests and body chunks at random, e.g.
+    /// `latency=200ms,loss=1%`.
+    #[cfg(feature = "simulate-network")]
+    #[arg(long, hide = true, value_name = "SETTINGS")]
+    pub simulate_network: Option<NetworkSimulation>,
+
+    /// Preferred HLS rendition: `best`, `worst`, or a maximum height such as `720p`.
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(Quality))]
+    pub quality: Option<Quality>,
+
+    /// Header preset for CDN media requests.
+    #[arg(long, value_name = "PRESET", value_enum)]
+    pub media_headers: Option<MediaHeaderPreset>,
+
+    /// Referer sent with media requests, overriding the preset.
+    #[arg(long, value_name = "URL")]
+    pub referer: Option<String>,
+
+    /// Origin sent with media requests, overriding the preset.
+    #[arg(long, value_name = "URL")]
+    pub origin: Option<String>,
+
+    /// Cookie header sent with media requests instead of the session cookies.
+    #[arg(long, value_name = "COOKIES")]
+    pub media_cookie: Option<String>,
+
+    /// Logged-in TikTok cookies (e.g. `sessionid=...`) for age-restricted videos.
+    #[arg(long, value_name = "COOKIES")]
+    pub session_cookie: Option<String>,
+
+    /// Session file written by `tikd-r login` and read on later runs.
+    #[cfg(feature = "cookies")]
+    #[arg(long, value_name = "PATH", global = true)]
+    pub session_file: Option<PathBuf>,
+
+    /// Region to claim on API requests (e.g. `TH`), for region-restricted items without a proxy.
+    #[arg(long, value_name = "REGION", value_parser = clap::value_parser!(Geo))]
+    pub geo: Option<Geo>,
+
+    /// Time zone sent with --geo instead of the region's default (e.g. `Asia/Bangkok`).
+    #[arg(long, value_name = "TZ", requires = "geo")]
+    pub geo_tz: Option<String>,
+
+    /// Proxy for share-page scraping only (e.g. `socks5://host:1080`).
+    #[arg(long, value_name = "URL")]
+    pub scrape_proxy: Option<String>,
+
+    /// Proxy for CDN media downloads only.
+    #[arg(long, value_name = "URL")]
+    pub media_proxy: Option<String>,
+
+    /// Ignore `HTTP_PROXY`, `HTTPS_PROXY`, and `NO_PROXY` from the environment.
+    #[arg(long)]
+    pub no_env_proxy: bool,
+
+    /// Follow at most this many redirects per request (default: 10; 0 follows none).
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub max_redirects: Option<usize>,
+
+    /// Resolve short links such as `vm.tiktok.com/...` with HEAD requests, so only the
+    /// final page's body is downloaded.
+    #[arg(long)]
+    pub short_links_via_head: bool,
+
+    /// Pause all downloads this long after any 429 response, doubling on repeats (0 disables).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub rate_limit_cooldown: Option<Duration>,
+
+    /// Cache scraped video metadata in this directory and reuse it for the same video ID.
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// How long cached metadata stays valid, e.g. `30m` (media URLs expire within hours).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "cache_dir")]
+    pub cache_ttl: Option<Duration>,
+
+    /// Scrape again when cached or queued media URLs are older than this, e.g. `30m`.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_url_age: Option<Duration>,
+
+    /// ffmpeg binary for merging HLS streams that keep audio in a separate rendition.
+    #[arg(long, value_name = "PATH")]
+    pub ffmpeg: Option<PathBuf>,
+
+    /// Check each media URL with a HEAD request first and report its size.
+    #[arg(long)]
+    pub probe: bool,
+
+    /// Resolve and probe every URL without downloading, then estimate the batch size.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Run the batch up to this many times, re-running only items that failed with a
+    /// retryable error (rate limits, server errors, timeouts).
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(usize))]
+    pub batch_passes: Option<usize>,
+
+    /// Wait this long between batch passes (default: 1m).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub pass_cooldown: Option<Duration>,
+
+    /// Only start downloads within this daily local-time window, e.g. `01:00-07:00`;
+    /// items reached outside it wait for the window to open.
+    #[arg(long, value_name = "HH:MM-HH:MM", value_parser = clap::value_parser!(ActiveHours))]
+    pub active_hours: Option<ActiveHours>,
+
+    /// Download the valid URLs of a batch even if some entries are not TikTok links.
+    #[arg(long)]
+    pub skip_invalid: bool,
+
+    /// Ask before downloading a batch of more than this many URLs (0 to never ask).
+    #[arg(long, value_name = "NUM", default_value_t = 500, value_parser = clap::value_parser!(usize))]
+    pub confirm_over: usize,
+
+    /// Also ask when probing the batch estimates more than this many bytes (e.g. `20G`).
+    #[arg(long, value_name = "BYTES", value_parser = parse_byte_size)]
+    pub confirm_over_size: Option<u64>,
+
+    /// Start large batches without asking for confirmation.
+    #[arg(short = 'y', long)]
+    pub yes: bool,
+
+    /// Only download items matching this filter, e.g. `desc~=(?i)recipe & hashtag!=ad`.
+    /// Repeat to accept items matching any of several filters.
+    #[arg(long, value_name = "EXPR", value_parser = MatchFilter::parse)]
+    pub match_filter: Vec<MatchFilter>,
+
+    /// Download ads and paid promotions from --tiktok-export feeds instead of skipping them.
+    #[arg(long)]
+    pub include_ads: bool,
+
+    /// Save the creator's profile details as `author.json` next to their videos.
+    #[arg(long)]
+    pub write_author_json: bool,
+
+    /// Save the creator's largest avatar next to their videos as `avatar.<ext>`.
+    #[arg(long)]
+    pub write_avatar: bool,
+
+    /// Save everything scraped about each video (caption, stats, music, timestamps) as
+    /// `<video>.info.json` next to it.
+    #[arg(long)]
+    pub write_info_json: bool,
+
+    /// Save each video's cover and full-size first frame next to it as
+    /// `<video>.cover.<ext>` and `<video>.origin-cover.<ext>`.
+    #[arg(long)]
+    pub write_thumbnail: bool,
+
+    /// Save each video's subtitle tracks next to it as `<video>.<language>.vtt`.
+    #[arg(long)]
+    pub write_subs: bool,
+
+    /// Subtitle languages to save, comma-separated, e.g. `eng-US,tha-TH`; `eng` matches
+    /// every English track. Defaults to all of them.
+    #[arg(
+        long,
+        value_name = "LANGS",
+        value_delimiter = ',',
+        requires = "write_subs"
+    )]
+    pub sub_langs: Vec<String>,
+
+    /// Also copy each finished download into this directory (repeatable).
+    #[arg(long, value_name = "DIR")]
+    pub also_copy_to: Vec<PathBuf>,
+
+    /// Permission bits for finished files, in octal (e.g. `0644`). Unix only.
+    #[arg(long, value_name = "MODE", value_parser = parse_file_mode)]
+    pub chmod: Option<u32>,
+
+    /// Owner for finished files as `user:group`, `user`, or `:group`. Unix only.
+    #[arg(long, value_name = "USER:GROUP", value_parser = FileOwner::parse)]
+    pub chown: Option<FileOwner>,
+
+    /// Leave the `.part` file of a failed or cancelled download and resume it with a range
+    /// request next time. HLS downloads start over.
+    #[arg(long, overrides_with = "no_keep_partial")]
+    pub keep_partial: bool,
+
+    /// Delete the `.part` file of a failed or cancelled download (the default).
+    #[arg(long, overrides_with = "keep_partial")]
+    pub no_keep_partial: bool,
+
+    /// Print how many attempts were retried, grouped by cause, after the batch.
+    #[arg(long)]
+    pub retry_summary: bool,
+
+    /// Where to write URLs left over when a budget or --max-per-author stops the batch early.
+    #[arg(long, value_name = "PATH")]
+    pub resume_file: Option<PathBuf>,
+
+    /// Write one JSON object per URL (outcome, retries, and timings) to this file.
+    #[arg(long, value_name = "PATH")]
+    pub report_json: Option<PathBuf>,
+
+    /// Log every HTTP request (method, host, status, size, timing) as JSON Lines to this file.
+    #[arg(long, value_name = "PATH")]
+    pub trace_http: Option<PathBuf>,
+}
+
+/// Downloader whose output `--compat` mimics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compat {
+    /// yt-dlp: `<title> [<id>].mp4` in the output directory, Unicode-safe names, and
+    /// yt-dlp's `.info.json` field names.
+    YtDlp,
+}
+
+/// Subcommands besides the default download mode.
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Log in by scanning a QR code with the TikTok app and save the session.
+    #[cfg(feature = "cookies")]
+    Login,
+    /// Delete old downloads from an archive according to a retention policy.
+    #[command(group(
+        clap::ArgGroup::new("policy")
+            .required(true)
+            .multiple(true)
+            .args(["keep_days", "keep_per_author"])
+    ))]
+    Prune {
+        /// Archive directory (the --output-dir used for downloads).
+        #[arg(long, value_name = "DIR")]
+        dir: PathBuf,
+        /// Delete videos older than this many days.
+        #[arg(long, value_name = "DAYS")]
+        keep_days: Option<u64>,
+        /// Keep only this many of the newest videos per creator.
+        #[arg(long, value_name = "NUM")]
+        keep_per_author: Option<usize>,
+        /// List what would be deleted without deleting anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the videos in an archive as CSV or JSON Lines.
+    Export {
+        /// Archive directory (the --output-dir used for downloads).
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// File to write instead of standard output.
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Check which archived videos have since been removed from TikTok.
+    VerifyRemote {
+        /// Archive directory (the --output-dir used for downloads).
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+        /// Also write every result as JSON Lines to this file.
+        #[arg(long, value_name = "PATH")]
+        report_json: Option<PathBuf>,
+        /// Pause between checks to stay clear of rate limits.
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "1s")]
+        delay: Duration,
+    },
+    /// Re-hash downloads recorded with --write-checksums and report corrupted or missing files.
+    Verify {
+        /// Archive directory (the --output-dir used for downloads).
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+        /// Write the share URLs of corrupted and missing videos to this file, for `--file`.
+        /// Corrupted files are renamed to `<name>.corrupt` so they are downloaded again.
+        #[arg(long, value_name = "PATH")]
+        requeue: Option<PathBuf>,
+    },
+    /// Save a minimized, scrubbed copy of a share page as a scraper test fixture.
+    #[command(hide = true)]
+    Fixture {
+        /// Share page to capture.
+        #[arg(value_name = "URL")]
+        url: String,
+        /// Directory the fixture is written to.
+        #[arg(long, value_name = "DIR", default_value = "tests/fixtures")]
+        dir: PathBuf,
+        /// File name without extension (default: `share_<video id>`).
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+    },
 }
 
 impl Cli {
     /// Ensure the caller supplies either a single URL or a file path.
     pub fn validate(&self) -> Result<(), Error> {
-        match (self.url.as_ref(), self.file.as_ref()) {
-            (Some(_), Some(_)) => Err(Error::InputConflict),
-            (None, None) => Err(Error::MissingInput),
-            _ => Ok(()),
+        if self.command.is_some() {
+            return Ok(());
+        }
+        let inputs = [
+            self.url.is_some(),
+            self.file.is_some(),
+            self.tiktok_export.is_some(),
+        ];
+        match inputs.iter().filter(|given| **given).count() {
+            0 => Err(Error::MissingInput),
+            1 => Ok(()),
+            _ => Err(Error::InputConflict),
         }
     }
 }
+
+/// Parse an octal permission mode such as `644` or `0640`.
+pub fn parse_file_mode(input: &str) -> Result<u32, String> {
+    let digits = input.trim();
+    let digits = digits.strip_prefix("0o").unwrap_or(digits);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if !digits.is_empty() && mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("invalid octal file mode: {input}")),
+    }
+}
+
+/// Parse a byte count such as `1048576`, `500M`, or `2G` (binary multiples).
+pub fn parse_byte_size(input: &str) -> Result<u64, String> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (digits, suffix) = trimmed.split_at(split);
+
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid byte size: {input}"))?;
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("unknown byte size suffix in: {input}")),
+    };
+
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("byte size too large: {input}"))
+}
+
+/// Largest `--write-buffer-size`; every concurrent download holds a buffer this big.
+pub const MAX_WRITE_BUFFER_SIZE: usize = 64 << 20;
+
+/// Parse a byte size as `parse_byte_size` does, up to `MAX_WRITE_BUFFER_SIZE`.
+pub fn parse_write_buffer_size(input: &str) -> Result<usize, String> {
+    let size = parse_byte_size(input)?;
+    usize::try_from(size)
+        .ok()
+        .filter(|&size| size <= MAX_WRITE_BUFFER_SIZE)
+        .ok_or_else(|| format!("write buffer size too large: {input} (at most 64M)"))
+}
+
+/// Parse a duration such as `45`, `90s`, `30m`, or `1h30m` (bare numbers are seconds).
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    if let Ok(secs) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in trimmed.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let value: u64 = digits
+            .parse()
+            .map_err(|_| format!("invalid duration: {input}"))?;
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(format!("unknown duration unit '{c}' in: {input}")),
+        };
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| format!("duration too large: {input}"))?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() {
+        return Err(format!("missing unit after {digits} in: {input}"));
+    }
+
+    Ok(Duration::from_secs(total))
+}
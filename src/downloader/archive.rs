@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// yt-dlp's extractor key for TikTok, the first word of its archive lines.
+const EXTRACTOR: &str = "tiktok";
+
+/// Line recording `video_id` in a yt-dlp `--download-archive` file.
+pub fn yt_dlp_archive_line(video_id: &str) -> String {
+    format!("{EXTRACTOR} {video_id}")
+}
+
+/// TikTok video IDs listed in yt-dlp archive text; other extractors' lines are ignored.
+fn parse_archive(text: &str) -> HashSet<String> {
+    text.lines()
+        .filter_map(|line| {
+            let (extractor, id) = line.trim().split_once(char::is_whitespace)?;
+            let id = id.trim();
+            (extractor.eq_ignore_ascii_case(EXTRACTOR) && !id.is_empty()).then(|| id.to_string())
+        })
+        .collect()
+}
+
+/// A `--download-archive` file shared with yt-dlp: videos listed in it are skipped, and
+/// every video that ends up on disk is appended to it.
+///
+/// The file is read on first use; a missing file is an empty archive.
+#[derive(Clone, Debug)]
+pub(crate) struct DownloadArchive {
+    path: PathBuf,
+    entries: Arc<Mutex<Option<Entries>>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    ids: HashSet<String>,
+    /// The file's last line has no line break yet, as when another tool wrote it.
+    unterminated: bool,
+}
+
+impl DownloadArchive {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(crate) async fn contains(&self, video_id: &str) -> io::Result<bool> {
+        let mut entries = self.entries.lock().await;
+        Ok(self.loaded(&mut entries).await?.ids.contains(video_id))
+    }
+
+    /// Append `video_id` unless it is already listed.
+    pub(crate) async fn record(&self, video_id: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().await;
+        let entries = self.loaded(&mut entries).await?;
+        if entries.ids.contains(video_id) {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut line = yt_dlp_archive_line(video_id) + "\n";
+        if entries.unterminated {
+            line.insert(0, '\n');
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        entries.unterminated = false;
+        entries.ids.insert(video_id.to_string());
+        Ok(())
+    }
+
+    async fn loaded<'a>(&self, entries: &'a mut Option<Entries>) -> io::Result<&'a mut Entries> {
+        if entries.is_none() {
+            let text = match tokio::fs::read_to_string(&self.path).await {
+                Ok(text) => text,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(err),
+            };
+            *entries = Some(Entries {
+                ids: parse_archive(&text),
+                unterminated: !text.is_empty() && !text.ends_with('\n'),
+            });
+        }
+        Ok(entries.get_or_insert_with(Entries::default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_yt_dlp_archives() {
+        let ids = parse_archive("tiktok 7001\nyoutube dQw4w9WgXcQ\nTikTok  7002 \n\nbroken\n");
+        let mut ids: Vec<_> = ids.into_iter().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["7001", "7002"]);
+    }
+
+    #[tokio::test]
+    async fn records_new_ids_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.txt");
+        std::fs::write(&path, "youtube abc\ntiktok 1").unwrap();
+
+        let archive = DownloadArchive::new(path.clone());
+        assert!(archive.contains("1").await.unwrap());
+        assert!(!archive.contains("2").await.unwrap());
+        archive.record("2").await.unwrap();
+        archive.record("2").await.unwrap();
+        archive.record("1").await.unwrap();
+        assert!(archive.contains("2").await.unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "youtube abc\ntiktok 1\ntiktok 2\n"
+        );
+    }
+}
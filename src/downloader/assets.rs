@@ -0,0 +1,174 @@
+//! Files fetched from the CDN next to a finished download: the creator's profile and
+//! avatar, cover images, and subtitles.
+
+use std::path::{Path, PathBuf};
+
+use super::{author, sanitize_component, subtitles, write_via_partial, Downloader};
+use crate::error::{Error, Result};
+use crate::http_trace;
+use crate::scraper::VideoDescriptor;
+
+impl Downloader {
+    /// Snapshot the creator's profile and avatar into the directory of a finished
+    /// download, once per creator, directory and run. Failures are logged, not returned.
+    pub(super) async fn write_author_assets(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) {
+        if !self.config.write_author_json && !self.config.write_avatar {
+            return;
+        }
+        let Some(profile) = &descriptor.author_profile else {
+            tracing::debug!("No author details scraped for {}", descriptor.video_id);
+            return;
+        };
+        let dir = output_path.parent().unwrap_or(Path::new("."));
+        if !self.author_assets.claim(dir, &descriptor.author) {
+            return;
+        }
+        // Default paths always put a creator's videos in a directory of their own.
+        let shared_dir = self
+            .config
+            .output_template
+            .as_ref()
+            .is_some_and(|template| !template.dir_is_author());
+        let shared_name = shared_dir.then(|| {
+            match sanitize_component(&descriptor.author, self.config.sanitize) {
+                name if name.is_empty() => "unknown".to_string(),
+                name => name,
+            }
+        });
+        let stem = |asset| author::asset_stem(dir, shared_name.as_deref(), asset);
+
+        if self.config.write_author_json {
+            match author::write_author_json(&stem("author"), profile).await {
+                Ok(path) => self.set_file_options(&path).await,
+                Err(err) => {
+                    tracing::warn!("Could not write author.json in {}: {}", dir.display(), err)
+                }
+            }
+        }
+        if self.config.write_avatar {
+            let Some(url) = &profile.avatar_url else {
+                tracing::debug!("No avatar URL scraped for {}", profile.unique_id);
+                return;
+            };
+            match self.download_image(url, share_url, stem("avatar")).await {
+                Ok(path) => self.set_file_options(&path).await,
+                Err(err) => {
+                    tracing::warn!("Could not save avatar of {}: {}", profile.unique_id, err)
+                }
+            }
+        }
+    }
+
+    /// Fetch the cover images next to a finished download. Failures are logged, not
+    /// returned.
+    pub(super) async fn write_thumbnails(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) {
+        if !self.config.write_thumbnail {
+            return;
+        }
+        let covers = [
+            ("cover", &descriptor.cover_url),
+            ("origin-cover", &descriptor.origin_cover_url),
+        ];
+        if covers.iter().all(|(_, url)| url.is_none()) {
+            tracing::debug!("No cover images scraped for {}", descriptor.video_id);
+        }
+        for (name, url) in covers {
+            let Some(url) = url else {
+                continue;
+            };
+            let stem = output_path.with_extension(name);
+            match self.download_image(url, share_url, stem).await {
+                Ok(path) => self.set_file_options(&path).await,
+                Err(err) => {
+                    tracing::warn!(
+                        "Could not save the {} of {}: {}",
+                        name,
+                        descriptor.video_id,
+                        err
+                    )
+                }
+            }
+        }
+    }
+
+    /// Fetch the selected subtitle tracks next to a finished download. Failures are
+    /// logged, not returned.
+    pub(super) async fn write_subtitles(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) {
+        if !self.config.write_subs {
+            return;
+        }
+        let mut tracks =
+            subtitles::select(&descriptor.subtitles, &self.config.sub_langs).peekable();
+        if tracks.peek().is_none() {
+            tracing::debug!("No matching subtitles scraped for {}", descriptor.video_id);
+        }
+        for track in tracks {
+            let path = subtitles::subtitle_path(output_path, &track.language);
+            match self.download_subtitle(&track.url, share_url, &path).await {
+                Ok(()) => self.set_file_options(&path).await,
+                Err(err) => tracing::warn!(
+                    "Could not save the {} subtitles of {}: {}",
+                    track.language,
+                    descriptor.video_id,
+                    err
+                ),
+            }
+        }
+    }
+
+    async fn download_subtitle(&self, url: &str, share_url: &str, path: &Path) -> Result<()> {
+        let (_, body) = self.fetch_asset(url, share_url).await?;
+        if !subtitles::is_webvtt(&body) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the response is not a WebVTT file",
+            )
+            .into());
+        }
+        write_via_partial(path, &body).await?;
+        Ok(())
+    }
+
+    /// Fetch an image into `stem.<ext>`, naming the extension after the image type.
+    async fn download_image(&self, url: &str, share_url: &str, stem: PathBuf) -> Result<PathBuf> {
+        let (content_type, bytes) = self.fetch_asset(url, share_url).await?;
+        let mut path = stem.into_os_string();
+        path.push(".");
+        path.push(author::image_extension(&content_type, url));
+        let path = PathBuf::from(path);
+        write_via_partial(&path, &bytes).await?;
+        Ok(path)
+    }
+
+    /// GET a small file from the media CDN, returning its `Content-Type` and body.
+    async fn fetch_asset(&self, url: &str, share_url: &str) -> Result<(String, Vec<u8>)> {
+        let response = http_trace::send(self.media_get(url, share_url)).await?;
+        if let Err(err) = response.error_for_status_ref() {
+            return Err(Error::Network(err));
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let bytes = response.bytes().await?;
+        self.record_bytes(bytes.len());
+        Ok((content_type, bytes.to_vec()))
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use super::cache::unix_now;
+use crate::scraper::AuthorProfile;
+
+/// Creators whose assets this run already wrote, per directory, shared by clones.
+#[derive(Clone, Default)]
+pub(crate) struct AuthorAssets(Arc<Mutex<HashSet<(PathBuf, String)>>>);
+
+impl AuthorAssets {
+    /// True the first time `author` is claimed in `dir` in this run, so each snapshot is
+    /// written once even when several of the creator's videos finish together.
+    pub(crate) fn claim(&self, dir: &Path, author: &str) -> bool {
+        let mut claimed = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        claimed.insert((dir.to_path_buf(), author.to_ascii_lowercase()))
+    }
+}
+
+/// Where the creator's `asset` (`author` or `avatar`) goes in `dir`, without extension.
+///
+/// In a directory of the creator's own the plain name is used; where creators share a
+/// directory, `shared_name` prefixes it, as in `chef.author.json`, so they don't overwrite
+/// each other's files.
+pub(crate) fn asset_stem(dir: &Path, shared_name: Option<&str>, asset: &str) -> PathBuf {
+    match shared_name {
+        Some(name) => dir.join(format!("{name}.{asset}")),
+        None => dir.join(asset),
+    }
+}
+
+#[derive(Serialize)]
+struct AuthorSnapshot<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    profile: &'a AuthorProfile,
+    fetched_unix: u64,
+}
+
+/// Write `profile` to `<stem>.json`, replacing the snapshot from an earlier run.
+pub(crate) async fn write_author_json(
+    stem: &Path,
+    profile: &AuthorProfile,
+) -> std::io::Result<PathBuf> {
+    let snapshot = AuthorSnapshot {
+        schema_version: crate::schema::SCHEMA_VERSION,
+        profile,
+        fetched_unix: unix_now(),
+    };
+    let mut path = stem.as_os_str().to_owned();
+    path.push(".json");
+    let path = PathBuf::from(path);
+    let partial = path.with_extension("json.part");
+    tokio::fs::write(&partial, serde_json::to_vec_pretty(&snapshot)?).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(path)
+}
+
+/// File extension for an avatar or cover image, from its `Content-Type` or else its URL
+/// path.
+pub(crate) fn image_extension(content_type: &str, url: &str) -> &'static str {
+    const KNOWN: [(&str, &str); 5] = [
+        ("jpeg", "jpg"),
+        ("jpg", "jpg"),
+        ("png", "png"),
+        ("webp", "webp"),
+        ("heic", "heic"),
+    ];
+    let by_type = content_type
+        .split(';')
+        .next()
+        .and_then(|mime| mime.trim().strip_prefix("image/"));
+    // TikTok avatar paths end in e.g. `~tplv-tiktokx-cropcenter:1080:1080.jpeg`.
+    let by_path = url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.path().rsplit_once('.').map(|(_, ext)| ext.to_string()));
+
+    [by_type.map(str::to_string), by_path]
+        .into_iter()
+        .flatten()
+        .find_map(|ext| {
+            KNOWN
+                .iter()
+                .find(|(name, _)| ext.eq_ignore_ascii_case(name))
+                .map(|(_, ext)| *ext)
+        })
+        .unwrap_or("jpg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_is_written_once_per_creator_and_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets = AuthorAssets::default();
+        assert!(assets.claim(dir.path(), "chef"));
+        assert!(!assets.clone().claim(dir.path(), "Chef"));
+        assert!(assets.claim(dir.path(), "baker"));
+        assert_eq!(
+            asset_stem(dir.path(), Some("baker"), "author"),
+            dir.path().join("baker.author")
+        );
+
+        let profile = AuthorProfile {
+            unique_id: "chef".into(),
+            follower_count: Some(1200),
+            verified: true,
+            ..AuthorProfile::default()
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let path = rt
+            .block_on(write_author_json(
+                &asset_stem(dir.path(), None, "author"),
+                &profile,
+            ))
+            .unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["unique_id"], "chef");
+        assert_eq!(written["follower_count"], 1200);
+        assert_eq!(written["verified"], true);
+        assert!(written["fetched_unix"].as_u64().unwrap() > 0);
+        assert!(!dir.path().join("author.json.part").exists());
+        crate::schema::tests::assert_matches_schema(&written, crate::schema::AUTHOR_SCHEMA);
+    }
+
+    #[test]
+    fn image_extension_prefers_content_type() {
+        let url = "https://p16.tiktokcdn.com/abc~tplv-tiktokx-cropcenter:1080:1080.jpeg?x=1";
+        assert_eq!(image_extension("image/webp", url), "webp");
+        assert_eq!(image_extension("application/octet-stream", url), "jpg");
+        assert_eq!(
+            image_extension("", "https://p16.tiktokcdn.com/a.PNG"),
+            "png"
+        );
+        assert_eq!(image_extension("", "https://p16.tiktokcdn.com/a"), "jpg");
+    }
+}
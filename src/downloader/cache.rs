@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::scraper::VideoDescriptor;
+
+/// On-disk cache of scraped descriptors, one JSON file per video ID.
+///
+/// Only URLs that name their video ID (`/@user/video/<id>`) can be looked up before
+/// scraping; short links are scraped and then cached under the ID they resolve to.
+#[derive(Clone, Debug)]
+pub(crate) struct DescriptorCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_unix: u64,
+    descriptor: VideoDescriptor,
+}
+
+impl DescriptorCache {
+    pub(crate) fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// A descriptor for `share_url` scraped less than the TTL ago.
+    pub(crate) async fn get(&self, share_url: &str) -> Option<VideoDescriptor> {
+        let id = canonical_video_id(share_url)?;
+        let contents = tokio::fs::read(self.entry_path(&id)).await.ok()?;
+        let entry: CacheEntry = match serde_json::from_slice(&contents) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::debug!("Ignoring unreadable cache entry for {}: {}", id, err);
+                return None;
+            }
+        };
+
+        let age = unix_now().saturating_sub(entry.fetched_unix);
+        (age < self.ttl.as_secs()).then(|| {
+            tracing::debug!("Using cached descriptor for {} ({}s old)", id, age);
+            let mut descriptor = entry.descriptor;
+            // Entries written before descriptors carried their own timestamp.
+            descriptor.extracted_unix.get_or_insert(entry.fetched_unix);
+            descriptor
+        })
+    }
+
+    /// Store `descriptor`; failures only cost a later re-scrape, so they are logged.
+    pub(crate) async fn put(&self, descriptor: &VideoDescriptor) {
+        if let Err(err) = self.write(descriptor).await {
+            tracing::warn!(
+                "Could not cache descriptor for {}: {}",
+                descriptor.video_id,
+                err
+            );
+        }
+    }
+
+    /// Forget the entry for `video_id`, e.g. after its media URLs stopped working.
+    pub(crate) async fn invalidate(&self, video_id: &str) {
+        let _ = tokio::fs::remove_file(self.entry_path(video_id)).await;
+    }
+
+    async fn write(&self, descriptor: &VideoDescriptor) -> std::io::Result<()> {
+        let entry = CacheEntry {
+            fetched_unix: unix_now(),
+            descriptor: descriptor.clone(),
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.entry_path(&descriptor.video_id);
+        // Write then rename so concurrent readers never see a partial entry.
+        let partial = path.with_extension("json.part");
+        tokio::fs::write(&partial, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&partial, &path).await
+    }
+
+    fn entry_path(&self, video_id: &str) -> PathBuf {
+        let name: String = video_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        self.dir.join(Path::new(&format!("{name}.json")))
+    }
+}
+
+/// The numeric ID in `/video/<id>` or `/photo/<id>` share URLs.
+pub(crate) fn canonical_video_id(share_url: &str) -> Option<String> {
+    let url = Url::parse(share_url).ok()?;
+    let segments: Vec<_> = url.path_segments()?.collect();
+    segments.windows(2).find_map(|pair| match pair {
+        [kind, id]
+            if matches!(*kind, "video" | "photo")
+                && !id.is_empty()
+                && id.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            Some((*id).to_string())
+        }
+        _ => None,
+    })
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(id: &str) -> VideoDescriptor {
+        VideoDescriptor {
+            video_id: id.into(),
+            download_url: Some("https://cdn.example/v.mp4".into()),
+            mirror_urls: Vec::new(),
+            play_url: None,
+            author: "user".into(),
+            extracted_unix: Some(1_700_000_000),
+            ..VideoDescriptor::default()
+        }
+    }
+
+    #[test]
+    fn ids_come_only_from_canonical_urls() {
+        assert_eq!(
+            canonical_video_id("https://www.tiktok.com/@user/video/7551290370794016007?lang=en"),
+            Some("7551290370794016007".into())
+        );
+        assert_eq!(canonical_video_id("https://vt.tiktok.com/ZSabc/"), None);
+        assert_eq!(
+            canonical_video_id("https://www.tiktok.com/@user/video/abc"),
+            None
+        );
+    }
+
+    #[test]
+    fn entries_expire_and_can_be_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://www.tiktok.com/@user/video/42";
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let cache = DescriptorCache::new(dir.path().to_path_buf(), Duration::from_secs(60));
+            assert_eq!(cache.get(url).await, None);
+
+            cache.put(&descriptor("42")).await;
+            assert_eq!(cache.get(url).await, Some(descriptor("42")));
+
+            let expired = DescriptorCache::new(dir.path().to_path_buf(), Duration::ZERO);
+            assert_eq!(expired.get(url).await, None);
+
+            cache.invalidate("42").await;
+            assert_eq!(cache.get(url).await, None);
+        });
+    }
+
+    #[test]
+    fn untimed_entries_take_the_cache_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let cache = DescriptorCache::new(dir.path().to_path_buf(), Duration::from_secs(60));
+            let untimed = VideoDescriptor {
+                extracted_unix: None,
+                ..descriptor("43")
+            };
+            cache.put(&untimed).await;
+            let cached = cache.get("https://www.tiktok.com/@user/video/43").await;
+            assert!(cached.unwrap().extracted_unix.unwrap() >= unix_now() - 5);
+        });
+    }
+}
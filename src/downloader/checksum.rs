@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::schema::{ChecksumRecord, SCHEMA_VERSION};
+use crate::scraper::VideoDescriptor;
+
+use super::cache::unix_now;
+
+/// Manifest `--write-checksums` appends to in the output directory.
+pub const CHECKSUM_FILE: &str = "tikd-r-checksums.jsonl";
+
+/// Size and CRC-32 of the file at `path`.
+fn checksum(path: &Path) -> io::Result<(u64, u32)> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut bytes = 0u64;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok((bytes, hasher.finalize()));
+        }
+        hasher.update(&buf[..read]);
+        bytes += read as u64;
+    }
+}
+
+/// Appends a `ChecksumRecord` to `root/CHECKSUM_FILE` for every finished download.
+#[derive(Clone)]
+pub(crate) struct ChecksumLog {
+    root: PathBuf,
+    // Serializes appends so concurrent workers never interleave lines.
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ChecksumLog {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Hash `path` and add it to the manifest; a file outside `root` is recorded by its
+    /// path as given. Only the append holds the lock, so workers hash in parallel.
+    pub(crate) async fn record(&self, descriptor: &VideoDescriptor, path: &Path) -> io::Result<()> {
+        let root = self.root.clone();
+        let path = path.to_path_buf();
+        let (video_id, author) = (descriptor.video_id.clone(), descriptor.author.clone());
+        let line = tokio::task::spawn_blocking(move || {
+            let (bytes, crc32) = checksum(&path)?;
+            let record = ChecksumRecord {
+                schema_version: SCHEMA_VERSION,
+                video_id,
+                author,
+                path: path.strip_prefix(&root).unwrap_or(&path).to_path_buf(),
+                bytes,
+                crc32: format!("{crc32:08x}"),
+                recorded_unix: unix_now(),
+            };
+            io::Result::Ok(serde_json::to_string(&record)?)
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        let _guard = self.lock.lock().await;
+        let manifest_path = self.root.join(CHECKSUM_FILE);
+        tokio::task::spawn_blocking(move || {
+            let mut manifest = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(manifest_path)?;
+            writeln!(manifest, "{line}")
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+/// State of one recorded file when re-hashed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileCheck {
+    Ok,
+    /// The file is gone.
+    Missing,
+    /// The file has another size than when it was written, e.g. it was truncated.
+    SizeChanged {
+        bytes: u64,
+    },
+    /// Same size, different bytes.
+    Corrupted,
+}
+
+impl FileCheck {
+    pub fn is_ok(&self) -> bool {
+        *self == FileCheck::Ok
+    }
+}
+
+/// Re-hash every file recorded in `root`'s checksum manifest, in path order.
+///
+/// Only the latest record per path counts, so re-downloads replace their earlier entry.
+/// Returns the checks and the number of manifest lines that could not be read.
+pub fn verify_checksums(root: &Path) -> io::Result<(Vec<(ChecksumRecord, FileCheck)>, usize)> {
+    let manifest = BufReader::new(File::open(root.join(CHECKSUM_FILE))?);
+    let mut latest = BTreeMap::new();
+    let mut unreadable = 0;
+    for line in manifest.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ChecksumRecord>(&line) {
+            Ok(record) => {
+                latest.insert(record.path.clone(), record);
+            }
+            Err(_) => unreadable += 1,
+        }
+    }
+
+    let mut checks = Vec::with_capacity(latest.len());
+    for (path, record) in latest {
+        let check = match checksum(&root.join(&path)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => FileCheck::Missing,
+            Err(err) => return Err(err),
+            Ok((bytes, _)) if bytes != record.bytes => FileCheck::SizeChanged { bytes },
+            Ok((_, crc32)) if format!("{crc32:08x}") != record.crc32 => FileCheck::Corrupted,
+            Ok(_) => FileCheck::Ok,
+        };
+        checks.push((record, check));
+    }
+    Ok((checks, unreadable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn descriptor(video_id: &str) -> VideoDescriptor {
+        VideoDescriptor {
+            video_id: video_id.into(),
+            author: "a".into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_missing_truncated_and_corrupted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("a")).unwrap();
+        let log = ChecksumLog::new(root.to_path_buf());
+        for (id, body) in [("1", "one"), ("2", "two"), ("3", "three"), ("4", "four")] {
+            let path = root.join(format!("a/{id}.mp4"));
+            fs::write(&path, body).unwrap();
+            log.record(&descriptor(id), &path).await.unwrap();
+        }
+        // A re-download replaces the earlier record.
+        fs::write(root.join("a/4.mp4"), "fourth").unwrap();
+        log.record(&descriptor("4"), &root.join("a/4.mp4"))
+            .await
+            .unwrap();
+        let mut manifest = fs::OpenOptions::new()
+            .append(true)
+            .open(root.join(CHECKSUM_FILE))
+            .unwrap();
+        writeln!(manifest, "not json").unwrap();
+
+        fs::remove_file(root.join("a/1.mp4")).unwrap();
+        fs::write(root.join("a/2.mp4"), "tw").unwrap();
+        fs::write(root.join("a/3.mp4"), "THREE").unwrap();
+
+        let (checks, unreadable) = verify_checksums(root).unwrap();
+        let checks: Vec<_> = checks
+            .iter()
+            .map(|(record, check)| (record.path.to_string_lossy().into_owned(), *check))
+            .collect();
+        assert_eq!(
+            checks,
+            vec![
+                ("a/1.mp4".to_string(), FileCheck::Missing),
+                ("a/2.mp4".to_string(), FileCheck::SizeChanged { bytes: 2 }),
+                ("a/3.mp4".to_string(), FileCheck::Corrupted),
+                ("a/4.mp4".to_string(), FileCheck::Ok),
+            ]
+        );
+        assert_eq!(unreadable, 1);
+    }
+}
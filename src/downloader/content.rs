@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OnceCell;
+
+use super::quota::{files_under, is_video};
+
+type BySize = Mutex<HashMap<u64, Vec<PathBuf>>>;
+
+/// Videos under the archive root by size, built with one walk when first needed and
+/// extended as downloads finish, shared by clones.
+///
+/// Only files of the same size can have the same bytes, so a finished download is
+/// compared with those alone, and never while other workers wait on a lock.
+#[derive(Clone, Default)]
+pub(crate) struct ContentIndex(Arc<OnceCell<BySize>>);
+
+impl ContentIndex {
+    /// Add `file`, of `len` bytes, to the index and return the videos indexed before it
+    /// with the same size.
+    ///
+    /// Of two identical downloads finishing together, only the later one sees the other,
+    /// so they can never both be discarded as copies of each other.
+    pub(crate) async fn register(
+        &self,
+        root: &Path,
+        file: &Path,
+        len: u64,
+    ) -> io::Result<Vec<PathBuf>> {
+        let index = self
+            .0
+            .get_or_try_init(|| async {
+                let root = root.to_path_buf();
+                let walk = tokio::task::spawn_blocking(move || files_under(&root));
+                let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+                for video in walk.await.map_err(io::Error::other)?? {
+                    if is_video(&video.path) {
+                        by_size.entry(video.len).or_default().push(video.path);
+                    }
+                }
+                io::Result::Ok(Mutex::new(by_size))
+            })
+            .await?;
+        let mut by_size = index
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let same_size = by_size.entry(len).or_default();
+        let earlier = same_size
+            .iter()
+            .filter(|path| *path != file)
+            .cloned()
+            .collect();
+        if !same_size.iter().any(|path| path == file) {
+            same_size.push(file.to_path_buf());
+        }
+        Ok(earlier)
+    }
+
+    /// Forget `file`, e.g. after it was deleted as a copy.
+    pub(crate) fn remove(&self, file: &Path) {
+        if let Some(index) = self.0.get() {
+            let mut by_size = index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            for paths in by_size.values_mut() {
+                paths.retain(|path| path != file);
+            }
+        }
+    }
+}
+
+/// The first of `candidates` with exactly the bytes of `file` but another file name,
+/// i.e. the same content saved under a different video ID.
+///
+/// Copies with the same name are hard links or re-downloads of one ID, which
+/// `DedupeMode` already covers, so they never match. Candidates deleted since they were
+/// indexed are passed over.
+pub(crate) fn find_same_content(
+    file: &Path,
+    mut candidates: Vec<PathBuf>,
+) -> io::Result<Option<PathBuf>> {
+    let name = file.file_name();
+    candidates.retain(|candidate| candidate.file_name() != name);
+    candidates.sort();
+
+    for candidate in candidates {
+        match same_bytes(file, &candidate) {
+            Ok(true) => return Ok(Some(candidate)),
+            Ok(false) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+fn same_bytes(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        match b.read_exact(&mut buf_b[..read]) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(err) => return Err(err),
+        }
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn matches_identical_bytes_under_another_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, bytes: &[u8]| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+        let original = write("alice/1.mp4", b"same video");
+        write("bob/2.mp4", b"same vidEO");
+        write("bob/notes.txt", b"same video");
+        write("bob/5.mp4", b"other size");
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let index = ContentIndex::default();
+        let check = |path: &Path| {
+            let len = fs::metadata(path).unwrap().len();
+            let candidates = rt.block_on(index.register(dir.path(), path, len)).unwrap();
+            find_same_content(path, candidates).unwrap()
+        };
+
+        let upload = write("carol/3.mp4", b"same video");
+        assert_eq!(check(&upload), Some(original.clone()));
+        fs::remove_file(&upload).unwrap();
+        index.remove(&upload);
+
+        let same_id = write("dave/1.mp4", b"same video");
+        assert_eq!(check(&same_id), None);
+
+        let fresh = write("erin/4.mp4", b"different!");
+        assert_eq!(check(&fresh), None);
+        // Indexed as it finished, so a later copy of it is found without another walk.
+        let copy = write("frank/6.mp4", b"different!");
+        assert_eq!(check(&copy), Some(fresh.clone()));
+
+        fs::remove_file(&fresh).unwrap();
+        let late = write("gina/7.mp4", b"different!");
+        assert_eq!(check(&late), Some(copy));
+    }
+}
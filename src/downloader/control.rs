@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+
+use super::DownloadRequest;
+
+/// Handle for pausing, resuming, and reordering a running `download_all_stream` batch.
+#[derive(Clone, Debug)]
+pub struct DownloadControl {
+    inner: Arc<ControlState>,
+}
+
+#[derive(Debug)]
+struct ControlState {
+    paused: watch::Sender<bool>,
+    queue: Mutex<VecDeque<(usize, DownloadRequest)>>,
+}
+
+impl DownloadControl {
+    pub(crate) fn new(requests: Vec<DownloadRequest>) -> Self {
+        let (paused, _) = watch::channel(false);
+        Self {
+            inner: Arc::new(ControlState {
+                paused,
+                queue: Mutex::new(requests.into_iter().enumerate().collect()),
+            }),
+        }
+    }
+
+    /// Stop starting queued items and hold in-flight transfers between chunks.
+    pub fn pause(&self) {
+        self.inner.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.inner.paused.borrow()
+    }
+
+    /// Move a queued URL to the front of the queue; returns false if it is not queued.
+    pub fn prioritize(&self, url: &str) -> bool {
+        self.reposition(url, true)
+    }
+
+    /// Move a queued URL to the back of the queue; returns false if it is not queued.
+    pub fn deprioritize(&self, url: &str) -> bool {
+        self.reposition(url, false)
+    }
+
+    /// URLs that have not been started yet, in the order they will run.
+    pub fn queued(&self) -> Vec<String> {
+        self.lock_queue()
+            .iter()
+            .map(|(_, request)| request.url.clone())
+            .collect()
+    }
+
+    pub(crate) fn next(&self) -> Option<(usize, DownloadRequest)> {
+        self.lock_queue().pop_front()
+    }
+
+    /// Resolves immediately unless paused, otherwise waits for `resume`.
+    pub(crate) async fn wait_if_paused(&self) {
+        // Checked for every chunk, so skip the subscription while running.
+        if !self.is_paused() {
+            return;
+        }
+        let mut paused = self.inner.paused.subscribe();
+        // The sender lives in `self`, so this can only fail if the state is dropped.
+        let _ = paused.wait_for(|paused| !*paused).await;
+    }
+
+    fn reposition(&self, url: &str, front: bool) -> bool {
+        let mut queue = self.lock_queue();
+        let Some(position) = queue.iter().position(|(_, request)| request.url == url) else {
+            return false;
+        };
+        if let Some(entry) = queue.remove(position) {
+            if front {
+                queue.push_front(entry);
+            } else {
+                queue.push_back(entry);
+            }
+        }
+        true
+    }
+
+    fn lock_queue(&self) -> std::sync::MutexGuard<'_, VecDeque<(usize, DownloadRequest)>> {
+        // A panic while holding the lock cannot leave the queue half-updated.
+        self.inner
+            .queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(urls: &[&str]) -> DownloadControl {
+        DownloadControl::new(urls.iter().map(|url| DownloadRequest::new(*url)).collect())
+    }
+
+    #[test]
+    fn prioritize_reorders_queue() {
+        let control = control(&["a", "b", "c"]);
+        assert!(control.prioritize("c"));
+        assert!(control.deprioritize("a"));
+        assert!(!control.prioritize("missing"));
+        assert_eq!(control.queued(), vec!["c", "b", "a"]);
+
+        let (idx, request) = control.next().unwrap();
+        assert_eq!((idx, request.url.as_str()), (2, "c"));
+    }
+
+    #[test]
+    fn pause_state_round_trips() {
+        let control = control(&[]);
+        assert!(!control.is_paused());
+        control.pause();
+        assert!(control.is_paused());
+        control.resume();
+        assert!(!control.is_paused());
+    }
+}
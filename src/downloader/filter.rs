@@ -0,0 +1,181 @@
+use std::fmt;
+
+use regex::Regex;
+
+use crate::scraper::VideoDescriptor;
+
+/// A `--match-filter` expression: `||`-separated alternatives, each made of `&` (or `&&`)
+/// separated conditions that must all hold. `&` binds tighter, so `a || b & c` is
+/// `a || (b & c)`.
+///
+/// Each condition is `field OP value` with `OP` one of `=`, `!=` (case-insensitive
+/// equality), `~=`, or `!~=` (regex search). Fields are `desc`, `author`, `id`, `lang`
+/// (caption language), and `hashtag`, which holds if any of the item's hashtags matches.
+#[derive(Clone, Debug)]
+pub struct MatchFilter {
+    source: String,
+    alternatives: Vec<Vec<Condition>>,
+}
+
+#[derive(Clone, Debug)]
+struct Condition {
+    field: Field,
+    test: Test,
+    negated: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Desc,
+    Author,
+    Id,
+    Lang,
+    Hashtag,
+}
+
+#[derive(Clone, Debug)]
+enum Test {
+    Equals(String),
+    Regex(Regex),
+}
+
+impl MatchFilter {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        // Split at the loosest operator first, so each `&` group stays whole.
+        let alternatives = expression
+            .split("||")
+            .map(|alternative| {
+                alternative
+                    .split("&&")
+                    .flat_map(|conditions| conditions.split('&'))
+                    .map(|condition| Condition::parse(condition.trim()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            source: expression.trim().to_string(),
+            alternatives,
+        })
+    }
+
+    pub fn matches(&self, descriptor: &VideoDescriptor) -> bool {
+        self.alternatives.iter().any(|conditions| {
+            conditions
+                .iter()
+                .all(|condition| condition.matches(descriptor))
+        })
+    }
+}
+
+impl fmt::Display for MatchFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Condition {
+    fn parse(condition: &str) -> Result<Self, String> {
+        // Longest operators first so `!~=` is not read as `!` + `~=`.
+        let (name, op, value) = ["!~=", "~=", "!=", "="]
+            .iter()
+            .find_map(|op| {
+                let (name, value) = condition.split_once(op)?;
+                Some((name.trim(), *op, value.trim()))
+            })
+            .ok_or_else(|| {
+                format!("expected `field=value` or `field~=regex`, got `{condition}`")
+            })?;
+
+        let field = match name.to_ascii_lowercase().as_str() {
+            "desc" | "description" => Field::Desc,
+            "author" => Field::Author,
+            "id" => Field::Id,
+            "lang" | "language" => Field::Lang,
+            "hashtag" | "hashtags" => Field::Hashtag,
+            _ => return Err(format!("unknown filter field `{name}`")),
+        };
+        let test = if op.contains('~') {
+            Test::Regex(Regex::new(value).map_err(|err| format!("invalid regex `{value}`: {err}"))?)
+        } else {
+            // A leading `#` on hashtags is how people write them, not part of the name.
+            let value = match field {
+                Field::Hashtag => value.trim_start_matches('#'),
+                _ => value,
+            };
+            Test::Equals(value.to_lowercase())
+        };
+
+        Ok(Self {
+            field,
+            test,
+            negated: op.starts_with('!'),
+        })
+    }
+
+    fn matches(&self, descriptor: &VideoDescriptor) -> bool {
+        let values: Vec<&str> = match self.field {
+            Field::Desc => vec![descriptor.description.as_str()],
+            Field::Author => vec![descriptor.author.as_str()],
+            Field::Id => vec![descriptor.video_id.as_str()],
+            Field::Lang => descriptor.text_language.as_deref().into_iter().collect(),
+            Field::Hashtag => descriptor.hashtags.iter().map(String::as_str).collect(),
+        };
+        let found = values.iter().any(|value| match &self.test {
+            Test::Equals(expected) => value.to_lowercase() == *expected,
+            Test::Regex(regex) => regex.is_match(value),
+        });
+        found != self.negated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(description: &str, hashtags: &[&str]) -> VideoDescriptor {
+        VideoDescriptor {
+            video_id: "7551".into(),
+            author: "chef".into(),
+            description: description.into(),
+            hashtags: hashtags.iter().map(|tag| tag.to_string()).collect(),
+            text_language: Some("en".into()),
+            ..VideoDescriptor::default()
+        }
+    }
+
+    #[test]
+    fn conditions_combine_with_and() {
+        let filter = MatchFilter::parse("desc~=(?i)recipe & author=Chef").unwrap();
+        assert!(filter.matches(&video("Easy RECIPE for pad thai", &[])));
+        assert!(!filter.matches(&video("Dance challenge", &[])));
+
+        let negated = MatchFilter::parse("hashtag=#ad & lang!=th").unwrap();
+        assert!(negated.matches(&video("", &["food", "Ad"])));
+        let no_ads = MatchFilter::parse("hashtag!~=^(ad|sponsored)$").unwrap();
+        assert!(!no_ads.matches(&video("", &["food", "sponsored"])));
+        assert!(no_ads.matches(&video("", &[])));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // `author=chef || (desc~=recipe && lang=th)`; grouped the other way round, the
+        // false `lang=th` would reject every item.
+        let filter = MatchFilter::parse("author=chef || desc~=recipe && lang=th").unwrap();
+        assert!(filter.matches(&video("dance", &[])));
+
+        let filter = MatchFilter::parse("author=someone || desc~=recipe && lang=th").unwrap();
+        assert!(!filter.matches(&video("pad thai recipe", &[])));
+        let mut thai = video("pad thai recipe", &[]);
+        thai.text_language = Some("th".into());
+        assert!(filter.matches(&thai));
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        assert!(MatchFilter::parse("desc").is_err());
+        assert!(MatchFilter::parse("views>100").is_err());
+        assert!(MatchFilter::parse("desc~=(").is_err());
+        assert!(MatchFilter::parse("desc=a ||").is_err());
+        assert!(MatchFilter::parse("desc=a & & author=b").is_err());
+    }
+}
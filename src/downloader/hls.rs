@@ -0,0 +1,613 @@
+//! HLS playlist parsing into typed master and media playlists.
+
+use std::time::Duration;
+
+use url::Url;
+
+use super::Quality;
+use crate::error::{Error, Result};
+
+/// A parsed `.m3u8`, which lists either variant streams or media segments.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+impl Playlist {
+    /// Parse `body`, resolving relative URIs against the playlist's own URL.
+    pub(crate) fn parse(body: &str, base: &Url) -> Result<Self> {
+        let lines = logical_lines(body);
+        if lines
+            .iter()
+            .any(|line| line.text.starts_with("#EXT-X-STREAM-INF"))
+        {
+            Ok(Playlist::Master(MasterPlaylist::parse(&lines, base)))
+        } else {
+            MediaPlaylist::parse(&lines, base).map(Playlist::Media)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MasterPlaylist {
+    pub(crate) variants: Vec<Variant>,
+    /// `#EXT-X-MEDIA` entries of `TYPE=AUDIO`.
+    pub(crate) audio: Vec<Rendition>,
+}
+
+/// One `#EXT-X-STREAM-INF` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Variant {
+    pub(crate) uri: Url,
+    pub(crate) bandwidth: u64,
+    /// `(width, height)` as declared by `RESOLUTION`.
+    pub(crate) resolution: Option<(u32, u32)>,
+    /// `GROUP-ID` of the audio renditions this variant plays with.
+    pub(crate) audio_group: Option<String>,
+}
+
+/// An alternative audio rendition from `#EXT-X-MEDIA`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rendition {
+    pub(crate) group_id: String,
+    pub(crate) name: Option<String>,
+    /// Media playlist of the rendition; `None` when its audio is muxed into the variant.
+    pub(crate) uri: Option<Url>,
+    pub(crate) default: bool,
+}
+
+impl Variant {
+    /// Short side of the resolution, so `720p` also matches portrait 720x1280 renditions.
+    pub(crate) fn height(&self) -> Option<u32> {
+        self.resolution.map(|(width, height)| width.min(height))
+    }
+}
+
+impl MasterPlaylist {
+    fn parse(lines: &[Line], base: &Url) -> Self {
+        let mut variants = Vec::new();
+        let mut audio = Vec::new();
+        let mut pending: Option<Attributes> = None;
+
+        for line in lines {
+            if let Some(list) = tag_value(&line.text, "#EXT-X-MEDIA") {
+                let attributes = Attributes::parse(list);
+                if attributes.get("TYPE") != Some("AUDIO") {
+                    continue;
+                }
+                let Some(group_id) = attributes.get("GROUP-ID") else {
+                    tracing::warn!("Audio rendition at line {} has no GROUP-ID", line.number);
+                    continue;
+                };
+                let uri = match attributes.get("URI").map(|uri| resolve_uri(base, uri)) {
+                    Some(Ok(uri)) => Some(uri),
+                    Some(Err(e)) => {
+                        tracing::warn!("Failed to resolve audio rendition URL: {}", e);
+                        continue;
+                    }
+                    None => None,
+                };
+                audio.push(Rendition {
+                    group_id: group_id.to_string(),
+                    name: attributes.get("NAME").map(ToOwned::to_owned),
+                    uri,
+                    default: attributes.get("DEFAULT") == Some("YES"),
+                });
+                continue;
+            }
+            if let Some(list) = tag_value(&line.text, "#EXT-X-STREAM-INF") {
+                if pending.is_some() {
+                    tracing::warn!("Variant before line {} has no URI", line.number);
+                }
+                pending = Some(Attributes::parse(list));
+                continue;
+            }
+            if line.text.starts_with('#') {
+                continue;
+            }
+            let Some(attributes) = pending.take() else {
+                continue;
+            };
+
+            match resolve_uri(base, &line.text) {
+                Ok(uri) => {
+                    let variant = Variant {
+                        uri,
+                        bandwidth: attributes
+                            .get("BANDWIDTH")
+                            .and_then(|value| value.parse().ok())
+                            .unwrap_or(0),
+                        resolution: attributes.get("RESOLUTION").and_then(parse_resolution),
+                        audio_group: attributes.get("AUDIO").map(ToOwned::to_owned),
+                    };
+                    tracing::debug!(
+                        "Found variant {} with bandwidth: {}, height: {:?}",
+                        variants.len() + 1,
+                        variant.bandwidth,
+                        variant.height()
+                    );
+                    variants.push(variant);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to resolve variant URL '{}': {}", line.text, e);
+                }
+            }
+        }
+        if pending.is_some() {
+            tracing::warn!("Last variant has no URI");
+        }
+
+        Self { variants, audio }
+    }
+
+    /// Separate audio playlist `variant` needs, preferring the group's default rendition.
+    ///
+    /// Returns `None` when the variant carries its own audio.
+    pub(crate) fn audio_for(&self, variant: &Variant) -> Option<&Rendition> {
+        let group = variant.audio_group.as_deref()?;
+        let mut renditions = self
+            .audio
+            .iter()
+            .filter(|rendition| rendition.group_id == group);
+        let chosen = renditions
+            .clone()
+            .find(|rendition| rendition.default)
+            .or_else(|| renditions.next())?;
+        chosen.uri.is_some().then_some(chosen)
+    }
+
+    /// The variant `quality` picks, if the playlist has any.
+    pub(crate) fn select(&self, quality: Quality) -> Option<&Variant> {
+        let ranking: Vec<(u64, Option<u32>)> = self
+            .variants
+            .iter()
+            .map(|variant| (variant.bandwidth, variant.height()))
+            .collect();
+        quality.pick(&ranking).map(|idx| &self.variants[idx])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MediaPlaylist {
+    pub(crate) segments: Vec<Segment>,
+    /// Whether `#EXT-X-ENDLIST` was present; live and event playlists omit it until
+    /// the stream is over.
+    pub(crate) ended: bool,
+    /// `#EXT-X-TARGETDURATION`, the upper bound on segment length.
+    pub(crate) target_duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    /// Media sequence number, which identifies the segment across playlist reloads.
+    pub(crate) sequence: u64,
+    pub(crate) uri: Url,
+    pub(crate) range: Option<ByteRange>,
+    /// Initialization section (`#EXT-X-MAP`) in effect for this segment.
+    pub(crate) init: Option<InitSection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InitSection {
+    pub(crate) uri: Url,
+    pub(crate) range: Option<ByteRange>,
+}
+
+impl MediaPlaylist {
+    fn parse(lines: &[Line], base: &Url) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut ended = false;
+        let mut target_duration = None;
+        let mut first_sequence = 0;
+        let mut current_init = None;
+        // `EXT-X-BYTERANGE` applies to the next URI; without an offset it continues
+        // where the previous sub-range of the same resource ended.
+        let mut pending_range: Option<&str> = None;
+        let mut range_end: Option<(Url, u64)> = None;
+
+        for line in lines {
+            let text = line.text.as_str();
+
+            if let Some(list) = tag_value(text, "#EXT-X-KEY") {
+                let method = Attributes::parse(list)
+                    .get("METHOD")
+                    .unwrap_or("NONE")
+                    .to_string();
+                tracing::debug!("Found encryption key: METHOD={}", method);
+                if method != "NONE" {
+                    return Err(Error::UnsupportedStream(format!(
+                        "HLS encryption method {method} is not supported"
+                    )));
+                }
+                continue;
+            }
+
+            if let Some(list) = tag_value(text, "#EXT-X-MAP") {
+                let attributes = Attributes::parse(list);
+                if let Some(uri) = attributes.get("URI") {
+                    let init_url = resolve_uri(base, uri)
+                        .map_err(|_| Error::InvalidUrl(format!("init segment: {uri}")))?;
+                    let range = attributes
+                        .get("BYTERANGE")
+                        .map(|value| ByteRange::parse(value, &init_url, None))
+                        .transpose()?;
+                    current_init = Some(InitSection {
+                        uri: init_url,
+                        range,
+                    });
+                }
+                continue;
+            }
+
+            if let Some(value) = tag_value(text, "#EXT-X-TARGETDURATION") {
+                target_duration = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite() && *secs > 0.0)
+                    .map(Duration::from_secs_f64);
+                continue;
+            }
+
+            if let Some(value) = tag_value(text, "#EXT-X-MEDIA-SEQUENCE") {
+                first_sequence = value.parse().unwrap_or(0);
+                continue;
+            }
+
+            if tag_value(text, "#EXT-X-ENDLIST").is_some() {
+                ended = true;
+                continue;
+            }
+
+            if let Some(value) = tag_value(text, "#EXT-X-BYTERANGE") {
+                pending_range = Some(value);
+                continue;
+            }
+
+            if text.starts_with('#') {
+                continue;
+            }
+
+            let uri = resolve_uri(base, text).map_err(|_| {
+                Error::InvalidUrl(format!("segment at line {}: {}", line.number, text))
+            })?;
+            let range = match pending_range.take() {
+                Some(value) => {
+                    let range = ByteRange::parse(value, &uri, range_end.as_ref())?;
+                    range_end = Some((uri.clone(), range.end()));
+                    Some(range)
+                }
+                None => {
+                    range_end = None;
+                    None
+                }
+            };
+            segments.push(Segment {
+                sequence: first_sequence + segments.len() as u64,
+                uri,
+                range,
+                init: current_init.clone(),
+            });
+        }
+
+        Ok(Self {
+            segments,
+            ended,
+            target_duration,
+        })
+    }
+}
+
+/// A resolved byte range: `length` bytes starting at `offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ByteRange {
+    pub(crate) offset: u64,
+    pub(crate) length: u64,
+}
+
+impl ByteRange {
+    /// Parse the `<length>[@<offset>]` form used by both the tag and the `EXT-X-MAP`
+    /// attribute, filling in a missing offset from the end of the previous sub-range
+    /// of the same URL.
+    fn parse(value: &str, url: &Url, previous: Option<&(Url, u64)>) -> Result<Self> {
+        let invalid = || Error::UnsupportedStream(format!("invalid HLS byte range `{value}`"));
+        let (length, offset) = match value.trim().split_once('@') {
+            Some((length, offset)) => (length, Some(offset.parse().map_err(|_| invalid())?)),
+            None => (value.trim(), None),
+        };
+        let length: u64 = length.parse().map_err(|_| invalid())?;
+        if length == 0 {
+            return Err(invalid());
+        }
+
+        let offset = match (offset, previous) {
+            (Some(offset), _) => offset,
+            (None, Some((previous_url, end))) if previous_url == url => *end,
+            (None, _) => {
+                return Err(Error::UnsupportedStream(format!(
+                    "HLS byte range for {url} has no offset and no preceding sub-range"
+                )))
+            }
+        };
+        // Keeps `end` from overflowing on absurd values.
+        if offset.checked_add(length).is_none() {
+            return Err(invalid());
+        }
+        Ok(Self { offset, length })
+    }
+
+    /// First byte after the range; `parse` rejects ranges where this would overflow.
+    pub(crate) fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+
+    pub(crate) fn header_value(&self) -> String {
+        format!("bytes={}-{}", self.offset, self.end() - 1)
+    }
+}
+
+/// Attribute list of a tag such as `BANDWIDTH=800000,CODECS="avc1.64001f,mp4a.40.2"`.
+#[derive(Debug, Default)]
+struct Attributes(Vec<(String, String)>);
+
+impl Attributes {
+    fn parse(list: &str) -> Self {
+        let mut attributes = Vec::new();
+        let mut rest = list.trim();
+
+        while !rest.is_empty() {
+            let Some((name, after)) = rest.split_once('=') else {
+                break;
+            };
+            let after = after.trim_start();
+            let (value, remainder) = match after.strip_prefix('"') {
+                // Quoted strings may contain commas; they end at the next quote.
+                Some(quoted) => match quoted.split_once('"') {
+                    Some((value, remainder)) => (value, remainder),
+                    None => (quoted, ""),
+                },
+                None => match after.split_once(',') {
+                    Some((value, remainder)) => (value.trim_end(), remainder),
+                    None => (after.trim_end(), ""),
+                },
+            };
+            attributes.push((name.trim().to_ascii_uppercase(), value.to_string()));
+            rest = remainder.trim_start().trim_start_matches(',').trim_start();
+        }
+
+        Self(attributes)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A non-empty playlist line, with continuation lines already joined.
+struct Line {
+    /// 1-based number of the line the entry starts on.
+    number: usize,
+    text: String,
+}
+
+/// Trimmed non-empty lines, joining tags split across lines with a trailing `\`.
+fn logical_lines(body: &str) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut continuing = false;
+
+    for (idx, raw) in body.lines().enumerate() {
+        let trimmed = raw.trim();
+        let (text, continues) = match trimmed.strip_suffix('\\') {
+            Some(text) => (text.trim_end(), true),
+            None => (trimmed, false),
+        };
+
+        match lines.last_mut() {
+            Some(last) if continuing => last.text.push_str(text.trim_start()),
+            _ if text.is_empty() => {}
+            _ => lines.push(Line {
+                number: idx + 1,
+                text: text.to_string(),
+            }),
+        }
+        continuing = continues;
+    }
+
+    lines
+}
+
+/// Value of `tag` on `line`, such as the attribute list after `#EXT-X-KEY:`.
+fn tag_value<'a>(line: &'a str, tag: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(tag)?;
+    match rest.strip_prefix(':') {
+        Some(value) => Some(value.trim()),
+        None if rest.is_empty() => Some(""),
+        None => None,
+    }
+}
+
+fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X'])?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+/// Resolve a URI relative to the playlist URL, or use it as-is if it's absolute.
+pub(crate) fn resolve_uri(base: &Url, uri: &str) -> Result<Url> {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return Url::parse(uri).map_err(|e| Error::InvalidUrl(format!("{uri}: {e}")));
+    }
+    base.join(uri)
+        .map_err(|e| Error::InvalidUrl(format!("URL join failed for '{uri}': {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://cdn.example.com/v/master.m3u8").unwrap()
+    }
+
+    fn media(body: &str) -> MediaPlaylist {
+        match Playlist::parse(body, &base()).unwrap() {
+            Playlist::Media(media) => media,
+            Playlist::Master(_) => panic!("expected a media playlist"),
+        }
+    }
+
+    #[test]
+    fn master_playlist_selection_honors_quality() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1080x1920\n\
+            hi.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=720x1280\n\
+            mid.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=300000,RESOLUTION=480x854\n\
+            low.m3u8\n";
+        let Playlist::Master(master) = Playlist::parse(playlist, &base()).unwrap() else {
+            panic!("expected a master playlist");
+        };
+
+        let pick = |quality| master.select(quality).unwrap().uri.to_string();
+        assert_eq!(pick(Quality::Best), "https://cdn.example.com/v/hi.m3u8");
+        assert_eq!(pick(Quality::Worst), "https://cdn.example.com/v/low.m3u8");
+        assert_eq!(
+            pick(Quality::MaxHeight(720)),
+            "https://cdn.example.com/v/mid.m3u8"
+        );
+    }
+
+    #[test]
+    fn audio_renditions_are_matched_by_group() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",DEFAULT=NO,URI=\"audio/en.m3u8\"\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Main\",DEFAULT=YES,URI=\"audio/main.m3u8\"\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"muxed\",NAME=\"Main\",DEFAULT=YES\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO=\"aac\"\n\
+            split.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=600000,AUDIO=\"muxed\"\n\
+            muxed.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=400000\n\
+            plain.m3u8\n";
+        let Playlist::Master(master) = Playlist::parse(playlist, &base()).unwrap() else {
+            panic!("expected a master playlist");
+        };
+
+        let audio: Vec<_> = master
+            .variants
+            .iter()
+            .map(|variant| {
+                master
+                    .audio_for(variant)
+                    .and_then(|rendition| rendition.uri.as_ref())
+                    .map(Url::to_string)
+            })
+            .collect();
+        assert_eq!(
+            audio,
+            vec![
+                Some("https://cdn.example.com/v/audio/main.m3u8".to_string()),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_attributes_may_contain_commas() {
+        let attributes = Attributes::parse(
+            r#"CODECS="avc1.64001f,mp4a.40.2",BANDWIDTH=800000, RESOLUTION=720x1280"#,
+        );
+        assert_eq!(attributes.get("CODECS"), Some("avc1.64001f,mp4a.40.2"));
+        assert_eq!(attributes.get("BANDWIDTH"), Some("800000"));
+        assert_eq!(attributes.get("RESOLUTION"), Some("720x1280"));
+        assert_eq!(attributes.get("AUDIO"), None);
+    }
+
+    #[test]
+    fn continued_tags_are_joined() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,\\\n  CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=720x1280\n\
+            mid.m3u8\n";
+        let Playlist::Master(master) = Playlist::parse(playlist, &base()).unwrap() else {
+            panic!("expected a master playlist");
+        };
+        assert_eq!(master.variants.len(), 1);
+        assert_eq!(master.variants[0].bandwidth, 800000);
+        assert_eq!(master.variants[0].height(), Some(720));
+    }
+
+    #[test]
+    fn media_playlist_lists_segments_with_init_section() {
+        let playlist = media(
+            "#EXTM3U\r\n#EXT-X-MAP:URI=\"init.mp4\"\r\n#EXTINF:2,\r\nseg0.m4s\r\n#EXTINF:2,\r\nhttps://other.example/seg1.m4s\r\n#EXT-X-ENDLIST\r\n",
+        );
+
+        let uris: Vec<_> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            vec![
+                "https://cdn.example.com/v/seg0.m4s",
+                "https://other.example/seg1.m4s"
+            ]
+        );
+        assert_eq!(
+            playlist.segments[0]
+                .init
+                .as_ref()
+                .map(|init| init.uri.as_str()),
+            Some("https://cdn.example.com/v/init.mp4")
+        );
+        assert_eq!(playlist.segments[1].init, playlist.segments[0].init);
+        assert!(playlist.ended);
+        assert_eq!(playlist.target_duration, None);
+    }
+
+    #[test]
+    fn live_playlists_number_segments_from_media_sequence() {
+        let playlist = media(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-MEDIA-SEQUENCE:41\n#EXTINF:4,\nseg41.ts\n#EXTINF:4,\nseg42.ts\n",
+        );
+        let sequences: Vec<_> = playlist.segments.iter().map(|s| s.sequence).collect();
+        assert_eq!(sequences, vec![41, 42]);
+        assert!(!playlist.ended);
+        assert_eq!(playlist.target_duration, Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn byte_ranges_continue_from_previous_sub_range() {
+        let playlist =
+            media("#EXTM3U\n#EXT-X-BYTERANGE:1000@0\nvideo.ts\n#EXT-X-BYTERANGE:500\nvideo.ts\n");
+        let ranges: Vec<_> = playlist
+            .segments
+            .iter()
+            .map(|segment| segment.range.unwrap().header_value())
+            .collect();
+        assert_eq!(ranges, vec!["bytes=0-999", "bytes=1000-1499"]);
+
+        for body in [
+            "#EXTM3U\n#EXT-X-BYTERANGE:1000@0\nvideo.ts\n#EXT-X-BYTERANGE:500\nother.ts\n",
+            "#EXTM3U\n#EXT-X-BYTERANGE:0@10\nvideo.ts\n",
+            "#EXTM3U\n#EXT-X-BYTERANGE:abc\nvideo.ts\n",
+            "#EXTM3U\n#EXT-X-BYTERANGE:2@18446744073709551615\nvideo.ts\n",
+            "#EXTM3U\n#EXT-X-BYTERANGE:18446744073709551615@0\nvideo.ts\n#EXT-X-BYTERANGE:1\nvideo.ts\n",
+        ] {
+            assert!(Playlist::parse(body, &base()).is_err(), "{body}");
+        }
+    }
+
+    #[test]
+    fn encrypted_media_playlists_are_rejected() {
+        let result = Playlist::parse(
+            "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"enc.key\"\nseg0.ts\n",
+            &base(),
+        );
+        assert!(matches!(result, Err(Error::UnsupportedStream(_))));
+
+        let clear = media("#EXTM3U\n#EXT-X-KEY:METHOD=NONE\nseg0.ts\n");
+        assert_eq!(clear.segments.len(), 1);
+    }
+}
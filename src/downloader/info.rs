@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::cache::unix_now;
+use super::{naming, InfoJsonFormat};
+use crate::scraper::VideoDescriptor;
+use crate::template::upload_date;
+
+#[derive(Serialize)]
+struct InfoSnapshot<'a> {
+    schema_version: u32,
+    share_url: &'a str,
+    #[serde(flatten)]
+    descriptor: &'a VideoDescriptor,
+    written_unix: u64,
+}
+
+/// The fields of yt-dlp's TikTok `.info.json` that a scrape can fill in, under yt-dlp's
+/// names. Like yt-dlp, unknown values are left out rather than written as `null`.
+#[derive(Serialize)]
+struct YtDlpInfo<'a> {
+    id: &'a str,
+    display_id: &'a str,
+    title: String,
+    fulltitle: String,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upload_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+    uploader: &'a str,
+    uploader_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    view_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    like_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repost_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    save_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artist: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    artists: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    thumbnails: Vec<YtDlpThumbnail<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    subtitles: BTreeMap<&'a str, [YtDlpSubtitle<'a>; 1]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    ext: &'static str,
+    webpage_url: &'a str,
+    original_url: &'a str,
+    extractor: &'static str,
+    extractor_key: &'static str,
+    #[serde(rename = "_type")]
+    kind: &'static str,
+    filename: String,
+    #[serde(rename = "_filename")]
+    private_filename: String,
+    epoch: u64,
+}
+
+#[derive(Serialize)]
+struct YtDlpThumbnail<'a> {
+    id: &'static str,
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+struct YtDlpSubtitle<'a> {
+    ext: &'static str,
+    url: &'a str,
+}
+
+impl<'a> YtDlpInfo<'a> {
+    fn new(video_path: &Path, descriptor: &'a VideoDescriptor, share_url: &'a str) -> Self {
+        let title = naming::caption_line(&descriptor.description, &descriptor.video_id, false);
+        let stats = descriptor.stats.clone().unwrap_or_default();
+        let music = descriptor.music.as_ref();
+        let artist = music
+            .map(|music| music.author.as_str())
+            .filter(|author| !author.is_empty());
+        let thumbnails = [
+            ("cover", &descriptor.cover_url),
+            ("origin_cover", &descriptor.origin_cover_url),
+        ]
+        .into_iter()
+        .filter_map(|(id, url)| {
+            Some(YtDlpThumbnail {
+                id,
+                url: url.as_deref()?,
+            })
+        })
+        .collect();
+        let subtitles = descriptor
+            .subtitles
+            .iter()
+            .map(|track| {
+                let subtitle = YtDlpSubtitle {
+                    ext: "vtt",
+                    url: &track.url,
+                };
+                (track.language.as_str(), [subtitle])
+            })
+            .collect();
+        let filename = video_path.to_string_lossy().into_owned();
+        Self {
+            id: &descriptor.video_id,
+            display_id: &descriptor.video_id,
+            fulltitle: title.clone(),
+            title,
+            description: &descriptor.description,
+            timestamp: descriptor.create_time,
+            upload_date: descriptor.create_time.map(upload_date),
+            duration: descriptor.duration_secs,
+            uploader: &descriptor.author,
+            uploader_url: format!("https://www.tiktok.com/@{}", descriptor.author),
+            channel: descriptor
+                .author_profile
+                .as_ref()
+                .map(|profile| profile.nickname.as_str())
+                .filter(|nickname| !nickname.is_empty()),
+            view_count: stats.play_count,
+            like_count: stats.like_count,
+            comment_count: stats.comment_count,
+            repost_count: stats.share_count,
+            save_count: stats.collect_count,
+            track: music
+                .map(|music| music.title.as_str())
+                .filter(|title| !title.is_empty()),
+            artist,
+            artists: artist.into_iter().collect(),
+            // yt-dlp picks the cover for `--embed-thumbnail` and media managers.
+            thumbnail: descriptor.cover_url.as_deref(),
+            thumbnails,
+            subtitles,
+            url: descriptor.download_url.as_deref(),
+            ext: "mp4",
+            webpage_url: share_url,
+            original_url: share_url,
+            extractor: "TikTok",
+            extractor_key: "TikTok",
+            kind: "video",
+            private_filename: filename.clone(),
+            filename,
+            epoch: unix_now(),
+        }
+    }
+}
+
+/// Write everything scraped about a video to `<stem>.info.json` next to `video_path`,
+/// replacing the file from an earlier download.
+pub(crate) async fn write_info_json(
+    video_path: &Path,
+    descriptor: &VideoDescriptor,
+    share_url: &str,
+    format: InfoJsonFormat,
+) -> std::io::Result<PathBuf> {
+    let json = match format {
+        InfoJsonFormat::TikdR => serde_json::to_vec_pretty(&InfoSnapshot {
+            schema_version: crate::schema::SCHEMA_VERSION,
+            share_url,
+            descriptor,
+            written_unix: unix_now(),
+        })?,
+        InfoJsonFormat::YtDlp => {
+            serde_json::to_vec_pretty(&YtDlpInfo::new(video_path, descriptor, share_url))?
+        }
+    };
+    let path = video_path.with_extension("info.json");
+    let partial = video_path.with_extension("info.json.part");
+    tokio::fs::write(&partial, json).await?;
+    tokio::fs::rename(&partial, &path).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraper::{AuthorProfile, MusicInfo, SubtitleTrack, VideoStats};
+
+    #[tokio::test]
+    async fn info_json_sits_next_to_the_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor = VideoDescriptor {
+            video_id: "7551".into(),
+            author: "chef".into(),
+            description: "pad thai #food".into(),
+            create_time: Some(1_735_689_600),
+            stats: Some(VideoStats {
+                like_count: Some(120),
+                ..VideoStats::default()
+            }),
+            music: Some(MusicInfo {
+                id: "6800".into(),
+                original: true,
+                ..MusicInfo::default()
+            }),
+            ..VideoDescriptor::default()
+        };
+        let video = dir.path().join("pad.thai-7551.mp4");
+        let path = write_info_json(
+            &video,
+            &descriptor,
+            "https://www.tiktok.com/@chef/video/7551",
+            InfoJsonFormat::TikdR,
+        )
+        .await
+        .unwrap();
+        assert_eq!(path, dir.path().join("pad.thai-7551.info.json"));
+        assert!(!dir.path().join("pad.thai-7551.info.json.part").exists());
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["video_id"], "7551");
+        assert_eq!(
+            written["share_url"],
+            "https://www.tiktok.com/@chef/video/7551"
+        );
+        assert_eq!(written["create_time"], 1_735_689_600);
+        assert_eq!(written["stats"]["like_count"], 120);
+        assert_eq!(written["music"]["original"], true);
+        crate::schema::tests::assert_matches_schema(&written, crate::schema::INFO_SCHEMA);
+    }
+
+    #[tokio::test]
+    async fn yt_dlp_info_uses_yt_dlp_field_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor = VideoDescriptor {
+            video_id: "7551".into(),
+            author: "chef".into(),
+            description: "pad thai\n#food".into(),
+            create_time: Some(1_735_689_600),
+            duration_secs: Some(14),
+            stats: Some(VideoStats {
+                play_count: Some(900),
+                share_count: Some(3),
+                ..VideoStats::default()
+            }),
+            music: Some(MusicInfo {
+                title: "original sound".into(),
+                author: "Chef".into(),
+                ..MusicInfo::default()
+            }),
+            author_profile: Some(AuthorProfile {
+                unique_id: "chef".into(),
+                nickname: "Chef Noi".into(),
+                ..AuthorProfile::default()
+            }),
+            cover_url: Some("https://p16.tiktokcdn.com/cover.jpeg".into()),
+            subtitles: vec![SubtitleTrack {
+                language: "eng-US".into(),
+                url: "https://v16.tiktokcdn.com/eng.vtt".into(),
+                auto_generated: true,
+            }],
+            download_url: Some("https://v16.tiktokcdn.com/7551.mp4".into()),
+            ..VideoDescriptor::default()
+        };
+        let video = dir.path().join("pad thai #food [7551].mp4");
+        let path = write_info_json(
+            &video,
+            &descriptor,
+            "https://www.tiktok.com/@chef/video/7551",
+            InfoJsonFormat::YtDlp,
+        )
+        .await
+        .unwrap();
+        assert_eq!(path, dir.path().join("pad thai #food [7551].info.json"));
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["id"], "7551");
+        assert_eq!(written["title"], "pad thai #food");
+        assert_eq!(written["upload_date"], "20250101");
+        assert_eq!(written["timestamp"], 1_735_689_600);
+        assert_eq!(written["uploader"], "chef");
+        assert_eq!(written["channel"], "Chef Noi");
+        assert_eq!(written["view_count"], 900);
+        assert_eq!(written["repost_count"], 3);
+        assert_eq!(written["artists"], serde_json::json!(["Chef"]));
+        assert_eq!(written["thumbnail"], "https://p16.tiktokcdn.com/cover.jpeg");
+        assert_eq!(written["thumbnails"][0]["id"], "cover");
+        assert_eq!(written["subtitles"]["eng-US"][0]["ext"], "vtt");
+        assert_eq!(
+            written["webpage_url"],
+            "https://www.tiktok.com/@chef/video/7551"
+        );
+        assert_eq!(written["_type"], "video");
+        assert_eq!(written["_filename"], video.to_string_lossy().as_ref());
+        // Unknown counts are left out, and nothing of tikd-r's own format leaks in.
+        for key in [
+            "like_count",
+            "save_count",
+            "schema_version",
+            "video_id",
+            "stats",
+        ] {
+            assert!(written.get(key).is_none(), "{key}");
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::OnceCell;
+
+use super::quota::is_video;
+use super::{naming, sanitize_component, SanitizePolicy};
+use crate::template::{upload_date, OutputTemplate, PathMatcher};
+
+/// Output templates that downloads into an output directory used, one JSON object per
+/// line, so archive maintenance can read creators and video IDs back out of their paths.
+pub const LAYOUTS_FILE: &str = "tikd-r-layouts.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct LayoutRecord {
+    template: String,
+    sanitize: SanitizePolicy,
+}
+
+impl LayoutRecord {
+    fn matcher(&self) -> Option<PathMatcher> {
+        let template = OutputTemplate::parse(&self.template).ok()?;
+        let sanitize = self.sanitize;
+        Some(template.matcher(move |text| sanitize_component(text, sanitize)))
+    }
+}
+
+fn read_records(root: &Path) -> io::Result<Vec<LayoutRecord>> {
+    let file = match fs::File::open(root.join(LAYOUTS_FILE)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => tracing::warn!("Ignoring a line of {}: {}", LAYOUTS_FILE, err),
+        }
+    }
+    Ok(records)
+}
+
+/// Adds the downloader's output template to `root/LAYOUTS_FILE` before its first download.
+#[derive(Clone)]
+pub(crate) struct LayoutLog {
+    root: PathBuf,
+    record: LayoutRecord,
+    recorded: Arc<OnceCell<()>>,
+}
+
+impl LayoutLog {
+    pub(crate) fn new(root: PathBuf, template: &OutputTemplate, sanitize: SanitizePolicy) -> Self {
+        Self {
+            root,
+            record: LayoutRecord {
+                template: template.to_string(),
+                sanitize,
+            },
+            recorded: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Record the template unless the file already lists it. Runs once per downloader and
+    /// its clones; a failure is logged, and only means maintenance may skip these videos.
+    pub(crate) async fn record(&self) {
+        self.recorded
+            .get_or_init(|| async {
+                let (root, record) = (self.root.clone(), self.record.clone());
+                let written = tokio::task::spawn_blocking(move || -> io::Result<()> {
+                    if read_records(&root)?.contains(&record) {
+                        return Ok(());
+                    }
+                    fs::create_dir_all(&root)?;
+                    let mut file = fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(root.join(LAYOUTS_FILE))?;
+                    writeln!(file, "{}", serde_json::to_string(&record)?)
+                })
+                .await
+                .map_err(io::Error::other)
+                .and_then(|written| written);
+                if let Err(err) = written {
+                    tracing::warn!(
+                        "Could not record the output template in {}: {}",
+                        LAYOUTS_FILE,
+                        err
+                    );
+                }
+            })
+            .await;
+    }
+}
+
+/// Video ID carried in a downloaded file's name: the whole stem for `--filename-style id`,
+/// or the digits after the last `-` for `title-id`.
+pub(crate) fn video_id_of(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    let id = match stem.rsplit_once('-') {
+        Some((_, id)) => id,
+        None => stem,
+    };
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then_some(id)
+}
+
+/// A video under an archive root at a path the downloader itself would have chosen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) video_id: String,
+    /// `None` for templates without `{author}`.
+    pub(crate) author: Option<String>,
+}
+
+/// The paths downloads into an archive root can have: the default `<author>/<stem>.mp4`
+/// and those of the templates in its `LAYOUTS_FILE`.
+pub(crate) struct Layout {
+    root: PathBuf,
+    templates: Vec<PathMatcher>,
+}
+
+impl Layout {
+    pub(crate) fn load(root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            templates: read_records(root)?
+                .iter()
+                .filter_map(LayoutRecord::matcher)
+                .collect(),
+        })
+    }
+
+    /// Recognize `path` as a video the downloader wrote below the root. Anything else may
+    /// not be ours, so archive maintenance leaves it alone.
+    pub(crate) fn recognize(&self, path: &Path) -> Option<Entry> {
+        if !is_video(path) {
+            return None;
+        }
+        let relative = path.strip_prefix(&self.root).ok()?.with_extension("");
+        let mut components = Vec::new();
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                return None;
+            };
+            components.push(name.to_str()?);
+        }
+        if let [author, stem] = components[..] {
+            if let Some(video_id) = video_id_of(path) {
+                // A `title` name only looks like `title-id` when its caption ends in digits.
+                let recorded = (stem != video_id)
+                    .then(|| read_info_json(path))
+                    .flatten()
+                    .filter(|info| info.tikd_r);
+                let video_id =
+                    recorded.map_or_else(|| video_id.to_string(), |info| info.entry.video_id);
+                return Some(Entry {
+                    video_id,
+                    author: Some(author.to_string()),
+                });
+            }
+        }
+        let matched = self.templates.iter().find_map(|template| {
+            let (video_id, author) = template.capture(&components)?;
+            Some(Entry { video_id, author })
+        });
+        match matched {
+            Some(Entry {
+                video_id,
+                author: None,
+            }) => {
+                let author = read_info_json(path)
+                    .filter(|info| info.entry.video_id == video_id)
+                    .and_then(|info| info.entry.author);
+                Some(Entry { video_id, author })
+            }
+            Some(entry) => Some(entry),
+            None => {
+                // yt-dlp writes the same `.info.json` for other sites, so its fields only
+                // count for a name shortened past its ID at a recorded template's depth.
+                let info = read_info_json(path)?;
+                let shortened = self
+                    .templates
+                    .iter()
+                    .any(|template| template.depth() == components.len())
+                    && components
+                        .last()
+                        .is_some_and(|name| naming::is_shortened_for(name, &info.entry.video_id));
+                (info.tikd_r || shortened).then_some(info.entry)
+            }
+        }
+    }
+}
+
+/// The video ID in the `.info.json` next to `video`, if one was written.
+pub(crate) fn info_json_video_id(video: &Path) -> Option<String> {
+    read_info_json(video).map(|info| info.entry.video_id)
+}
+
+/// The `.info.json` next to a video.
+struct InfoJson {
+    entry: Entry,
+    /// Written in tikd-r's own format, not `--compat yt-dlp`'s.
+    tikd_r: bool,
+}
+
+/// Read the video ID and creator from the `.info.json` next to `video`, under tikd-r's
+/// field names or yt-dlp's.
+fn read_info_json(video: &Path) -> Option<InfoJson> {
+    let info = load_info_json(video)?;
+    let tikd_r = in_tikd_r_format(&info);
+    let (id_key, author_key) = if tikd_r {
+        ("video_id", "author")
+    } else {
+        ("id", "uploader")
+    };
+    let field = |key: &str| {
+        info.get(key)?
+            .as_str()
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+    };
+    let video_id = field(id_key)?;
+    if !video_id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(InfoJson {
+        entry: Entry {
+            video_id,
+            author: field(author_key),
+        },
+        tikd_r,
+    })
+}
+
+/// Upload date and engagement counts from the `.info.json` next to a video.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct InfoStats {
+    /// `YYYYMMDD`, like yt-dlp's `upload_date`.
+    pub upload_date: Option<String>,
+    pub view_count: Option<u64>,
+    pub like_count: Option<u64>,
+}
+
+/// Read the upload date, views, and likes from the `.info.json` next to `video`, under
+/// tikd-r's field names or yt-dlp's; all empty without one.
+pub(crate) fn info_json_stats(video: &Path) -> InfoStats {
+    let Some(info) = load_info_json(video) else {
+        return InfoStats::default();
+    };
+    let count = |value: Option<&serde_json::Value>| value?.as_u64();
+    if in_tikd_r_format(&info) {
+        let stats = info.get("stats");
+        InfoStats {
+            upload_date: count(info.get("create_time")).map(upload_date),
+            view_count: count(stats.and_then(|stats| stats.get("play_count"))),
+            like_count: count(stats.and_then(|stats| stats.get("like_count"))),
+        }
+    } else {
+        InfoStats {
+            upload_date: info
+                .get("upload_date")
+                .and_then(|date| date.as_str())
+                .filter(|date| date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()))
+                .map(str::to_owned),
+            view_count: count(info.get("view_count")),
+            like_count: count(info.get("like_count")),
+        }
+    }
+}
+
+fn load_info_json(video: &Path) -> Option<serde_json::Value> {
+    serde_json::from_slice(&fs::read(video.with_extension("info.json")).ok()?).ok()
+}
+
+/// Whether an `.info.json` holds tikd-r's own fields rather than `--compat yt-dlp`'s.
+fn in_tikd_r_format(info: &serde_json::Value) -> bool {
+    info.get("schema_version").is_some_and(|v| v.is_u64()) && info.get("video_id").is_some()
+}
+
+/// Files saved alongside `video`: its `.info.json`, `.<language>.vtt` subtitles, and
+/// `.cover.<ext>` / `.origin-cover.<ext>` images.
+pub(crate) fn sidecars(video: &Path) -> io::Result<Vec<PathBuf>> {
+    let (Some(dir), Some(stem)) = (video.parent(), video.file_stem().and_then(|s| s.to_str()))
+    else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let prefix = format!("{stem}.");
+    let mut sidecars = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_sidecar = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .is_some_and(is_sidecar_suffix);
+        if is_sidecar && entry.file_type()?.is_file() {
+            sidecars.push(entry.path());
+        }
+    }
+    sidecars.sort();
+    Ok(sidecars)
+}
+
+fn is_sidecar_suffix(suffix: &str) -> bool {
+    let word = |part: &str, extra: char| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == extra)
+    };
+    if suffix == "info.json" {
+        return true;
+    }
+    if let Some(language) = suffix.strip_suffix(".vtt") {
+        return word(language, '-');
+    }
+    match suffix.split_once('.') {
+        Some(("cover" | "origin-cover", ext)) => word(ext, '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_id_from_default_names() {
+        let id = |name: &str| video_id_of(Path::new(name)).map(str::to_owned);
+        assert_eq!(
+            id("a/7551290370794016007.mp4").as_deref(),
+            Some("7551290370794016007")
+        );
+        assert_eq!(id("a/morning-run-123.mp4").as_deref(), Some("123"));
+        assert_eq!(id("a/morning-run.mp4"), None);
+        assert_eq!(id("a/holiday-.mp4"), None);
+    }
+
+    #[test]
+    fn recognizes_only_the_default_layout() {
+        let root = Path::new("out");
+        let layout = Layout::load(root).unwrap();
+        let entry = |path: &str| layout.recognize(&root.join(path));
+        assert_eq!(
+            entry("chef/pad-thai-7551.mp4"),
+            Some(Entry {
+                video_id: "7551".into(),
+                author: Some("chef".into()),
+            })
+        );
+        assert_eq!(
+            entry("chef/7551.mp4").map(|e| e.video_id).as_deref(),
+            Some("7551")
+        );
+        for path in [
+            "7551.mp4",
+            "chef/holiday.mp4",
+            "a/b/7551.mp4",
+            "chef/7551.mov",
+        ] {
+            assert_eq!(entry(path), None, "{path}");
+        }
+        assert_eq!(layout.recognize(Path::new("elsewhere/chef/7551.mp4")), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let layout = Layout::load(dir.path()).unwrap();
+        let video = dir.path().join("chef/run-2024.mp4");
+        assert_eq!(
+            layout.recognize(&video).map(|e| e.video_id).as_deref(),
+            Some("2024")
+        );
+        fs::create_dir_all(video.parent().unwrap()).unwrap();
+        fs::write(
+            video.with_extension("info.json"),
+            r#"{"schema_version":1,"video_id":"7558","author":"chef"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            layout.recognize(&video).map(|e| e.video_id).as_deref(),
+            Some("7558"),
+            "a title name's sidecar knows better"
+        );
+    }
+
+    #[tokio::test]
+    async fn recognizes_the_layouts_of_recorded_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (template, sanitize) in [
+            (
+                crate::template::YT_DLP_TEMPLATE,
+                SanitizePolicy::UnicodeSafe,
+            ),
+            (
+                "tiktok/{author}/{upload_date} {video_id}",
+                SanitizePolicy::StrictAscii,
+            ),
+        ] {
+            let template = OutputTemplate::parse(template).unwrap();
+            let log = LayoutLog::new(root.to_path_buf(), &template, sanitize);
+            log.record().await;
+            log.record().await;
+            LayoutLog::new(root.to_path_buf(), &template, sanitize)
+                .record()
+                .await;
+        }
+        let recorded = fs::read_to_string(root.join(LAYOUTS_FILE)).unwrap();
+        assert_eq!(recorded.lines().count(), 2);
+
+        let layout = Layout::load(root).unwrap();
+        let entry = |path: &str| layout.recognize(&root.join(path));
+        assert_eq!(
+            entry("Pad thai [7551].mp4"),
+            Some(Entry {
+                video_id: "7551".into(),
+                author: None,
+            })
+        );
+        assert_eq!(
+            entry("tiktok/chef/202501017552.mp4"),
+            Some(Entry {
+                video_id: "7552".into(),
+                author: Some("chef".into()),
+            })
+        );
+        assert_eq!(entry("Pad thai.mp4"), None);
+        assert_eq!(entry("clips/other/7551.mp4"), None);
+
+        fs::write(
+            root.join("Pad thai [7551].info.json"),
+            r#"{"id":"7551","uploader":"chef"}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("Pad thai.info.json"),
+            r#"{"schema_version":1,"video_id":"7553","author":"cook"}"#,
+        )
+        .unwrap();
+        fs::write(root.join("Soup [7554].info.json"), r#"{"id":"7555"}"#).unwrap();
+        let shortened = naming::fit_name(&"Soup ".repeat(30), "7556", 40).unwrap();
+        fs::write(
+            root.join(format!("{shortened}.info.json")),
+            r#"{"id":"7556","uploader":"chef"}"#,
+        )
+        .unwrap();
+        fs::write(root.join("clip.info.json"), r#"{"id":"7557"}"#).unwrap();
+        assert_eq!(
+            entry("Pad thai [7551].mp4")
+                .and_then(|e| e.author)
+                .as_deref(),
+            Some("chef")
+        );
+        assert_eq!(
+            entry("Pad thai.mp4"),
+            Some(Entry {
+                video_id: "7553".into(),
+                author: Some("cook".into()),
+            })
+        );
+        assert_eq!(
+            entry("Soup [7554].mp4"),
+            Some(Entry {
+                video_id: "7554".into(),
+                author: None,
+            })
+        );
+        assert_eq!(
+            entry(&format!("{shortened}.mp4")),
+            Some(Entry {
+                video_id: "7556".into(),
+                author: Some("chef".into()),
+            })
+        );
+        assert_eq!(entry("clip.mp4"), None, "another site's yt-dlp download");
+    }
+
+    #[test]
+    fn reads_stats_from_either_info_json_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |id: &str, info: serde_json::Value| {
+            fs::write(dir.path().join(format!("{id}.info.json")), info.to_string()).unwrap();
+            dir.path().join(format!("{id}.mp4"))
+        };
+        let ours = write(
+            "1",
+            serde_json::json!({
+                "schema_version": 1,
+                "video_id": "1",
+                "create_time": 1_735_689_600,
+                "stats": {"play_count": 900, "like_count": 120},
+            }),
+        );
+        let yt_dlp = write(
+            "2",
+            serde_json::json!({"id": "2", "upload_date": "20250102", "view_count": 5}),
+        );
+
+        assert_eq!(
+            info_json_stats(&ours),
+            InfoStats {
+                upload_date: Some("20250101".into()),
+                view_count: Some(900),
+                like_count: Some(120),
+            }
+        );
+        assert_eq!(
+            info_json_stats(&yt_dlp),
+            InfoStats {
+                upload_date: Some("20250102".into()),
+                view_count: Some(5),
+                like_count: None,
+            }
+        );
+        assert_eq!(
+            info_json_stats(&dir.path().join("3.mp4")),
+            InfoStats::default()
+        );
+    }
+
+    #[test]
+    fn finds_the_sidecars_of_a_video() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "7551.mp4",
+            "7551.info.json",
+            "7551.eng-US.vtt",
+            "7551.cover.jpeg",
+            "7551.origin-cover.webp",
+            "75510.info.json",
+            "7551.mp4.part",
+            "7551.notes.txt",
+            "author.json",
+        ] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        let names: Vec<_> = sidecars(&dir.path().join("7551.mp4"))
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "7551.cover.jpeg",
+                "7551.eng-US.vtt",
+                "7551.info.json",
+                "7551.origin-cover.webp",
+            ]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use url::Url;
+
+/// Per-CDN-host transfer outcomes, shared by a downloader and its clones so later items
+/// of a batch try the hosts that have been working first.
+#[derive(Clone, Default)]
+pub(crate) struct HostHealth(Arc<Mutex<HashMap<String, HostStats>>>);
+
+#[derive(Clone, Copy, Default)]
+struct HostStats {
+    succeeded: u32,
+    failed: u32,
+}
+
+impl HostStats {
+    /// Success rate with one imagined success and failure, so an unseen host scores 0.5
+    /// and a single result doesn't decide everything.
+    fn score(self) -> f64 {
+        (f64::from(self.succeeded) + 1.0) / (f64::from(self.succeeded + self.failed) + 2.0)
+    }
+}
+
+impl HostHealth {
+    fn with_hosts<T>(&self, f: impl FnOnce(&mut HashMap<String, HostStats>) -> T) -> T {
+        let mut hosts = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut hosts)
+    }
+
+    pub(crate) fn record(&self, url: &str, succeeded: bool) {
+        let Some(host) = host_of(url) else {
+            return;
+        };
+        self.with_hosts(|hosts| {
+            let stats = hosts.entry(host).or_default();
+            if succeeded {
+                stats.succeeded += 1;
+            } else {
+                stats.failed += 1;
+            }
+        });
+    }
+
+    /// `candidates` ordered by host score, best first; ties keep the descriptor's order.
+    pub(crate) fn rank(&self, candidates: &[String]) -> Vec<String> {
+        let scores: Vec<f64> = self.with_hosts(|hosts| {
+            candidates
+                .iter()
+                .map(|url| {
+                    host_of(url)
+                        .and_then(|host| hosts.get(&host).copied())
+                        .unwrap_or_default()
+                        .score()
+                })
+                .collect()
+        });
+        let mut ranked: Vec<_> = candidates.iter().cloned().zip(scores).collect();
+        ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        ranked.into_iter().map(|(url, _)| url).collect()
+    }
+}
+
+fn host_of(url: &str) -> Option<String> {
+    Some(Url::parse(url).ok()?.host_str()?.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn healthy_hosts_move_ahead() {
+        let health = HostHealth::default();
+        let candidates = vec![
+            "https://v16.flaky.example/a.mp4".to_string(),
+            "https://v19.unseen.example/a.mp4".to_string(),
+            "https://v26.steady.example/a.mp4".to_string(),
+        ];
+        assert_eq!(health.rank(&candidates), candidates);
+
+        health.record("https://v16.flaky.example/b.mp4", false);
+        health.record("https://v16.flaky.example/c.mp4", false);
+        health.record("https://V26.steady.example/b.mp4", true);
+        assert_eq!(
+            health.rank(&candidates),
+            vec![
+                candidates[2].clone(),
+                candidates[1].clone(),
+                candidates[0].clone()
+            ]
+        );
+    }
+}
@@ -0,0 +1,3830 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::stream::{self, BoxStream, StreamExt};
+use reqwest::{redirect::Policy, Client, StatusCode};
+#[cfg(feature = "cookies")]
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt, BufWriter},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep, timeout, Duration},
+};
+
+use crate::error::{Error, Result};
+use crate::http_trace;
+use crate::scraper::{Geo, Scraper, VideoDescriptor};
+use crate::session::DeviceIds;
+use crate::template::{self, OutputTemplate, Placeholder};
+use url::Url;
+
+mod archive;
+mod assets;
+mod author;
+mod cache;
+mod checksum;
+mod content;
+mod control;
+mod filter;
+mod hls;
+mod info;
+mod layout;
+mod mirrors;
+mod naming;
+mod ownership;
+mod postprocess;
+mod probe;
+mod progress;
+mod quota;
+mod request;
+mod retry;
+mod run_id;
+mod schedule;
+#[cfg(feature = "simulate-network")]
+mod simulate;
+mod subtitles;
+mod timing;
+mod warning;
+
+pub use archive::yt_dlp_archive_line;
+use archive::DownloadArchive;
+use author::AuthorAssets;
+use cache::DescriptorCache;
+use checksum::ChecksumLog;
+pub use checksum::{verify_checksums, FileCheck, CHECKSUM_FILE};
+use content::ContentIndex;
+pub use control::DownloadControl;
+pub use filter::MatchFilter;
+use hls::{ByteRange, InitSection, MediaPlaylist, Playlist};
+use layout::LayoutLog;
+pub use layout::LAYOUTS_FILE;
+use mirrors::HostHealth;
+pub use ownership::FileOwner;
+pub use probe::MediaProbe;
+use probe::ProbeLog;
+pub use progress::{BatchStatus, ProgressEvent, ProgressSender};
+use quota::ArchiveQuota;
+pub use quota::{list_archive, prune_archive, ArchivedVideo, RetentionPolicy};
+pub use request::{DownloadRequest, Quality};
+use retry::RateGate;
+pub use retry::{RetryReason, RetryRecord};
+pub use run_id::RunId;
+pub use schedule::ActiveHours;
+#[cfg(feature = "simulate-network")]
+pub use simulate::NetworkSimulation;
+use timing::ItemTimer;
+pub use timing::ItemTimings;
+pub use tokio_util::sync::CancellationToken;
+pub use warning::DownloadWarning;
+
+/// Throughput is judged over windows of this length when `min_speed` is set.
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Default `write_buffer_size`, large enough that a fast link writes in few syscalls.
+const DEFAULT_WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Default `max_redirects`, matching reqwest's own limit.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// How often `download_requests` refreshes `ProgressEvent::Batch` while items run.
+const BATCH_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+     Chrome/131.0.0.0 Safari/537.36";
+
+/// How to treat a video that already exists elsewhere in the archive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DedupeMode {
+    /// Always download into the target path.
+    #[default]
+    Off,
+    /// Hard-link an existing copy of the same video ID instead of downloading it again.
+    Hardlink,
+}
+
+/// What to do with a download whose bytes match a video saved under another ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ContentDedupe {
+    /// Don't compare downloads with the archive.
+    #[default]
+    Off,
+    /// Keep the download and report the earlier copy.
+    Flag,
+    /// Delete the download and report the item as skipped.
+    Skip,
+}
+
+/// How default output file names are built.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FilenameStyle {
+    /// `<video id>.mp4`.
+    #[default]
+    Id,
+    /// The caption without hashtags, slugified; the ID when the caption is empty. When
+    /// another video already has that name, the `TitleId` name is used instead.
+    Title,
+    /// The caption without hashtags, slugified, followed by `-<video id>`; just the ID
+    /// when the caption is empty.
+    TitleId,
+}
+
+/// Field names of the `write_info_json` sidecar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InfoJsonFormat {
+    /// The whole `VideoDescriptor`, described by `schemas/info.v1.json`.
+    #[default]
+    TikdR,
+    /// The fields yt-dlp writes for TikTok videos, under its names (`id`, `uploader`,
+    /// `view_count`, ...), for scripts that read yt-dlp's `.info.json`.
+    YtDlp,
+}
+
+/// Which characters survive in output directory and file names.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SanitizePolicy {
+    /// ASCII letters, digits, `-`, `_` and `.` only; safe on any filesystem.
+    #[default]
+    StrictAscii,
+    /// Any script and punctuation; only separators, control characters and emoji are removed.
+    UnicodeSafe,
+    /// `unicode-safe` minus the characters, trailing dots and device names Windows and
+    /// FAT32 reject.
+    WindowsSafe,
+}
+
+/// Baseline header set sent with CDN media requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MediaHeaderPreset {
+    /// Use the share URL as Referer.
+    #[default]
+    Share,
+    /// Present requests as coming from the tiktok.com web app (Referer and Origin).
+    Site,
+    /// Send neither Referer nor Origin.
+    None,
+}
+
+/// Referer, Origin, and Cookie handling for media requests.
+#[derive(Clone, Debug, Default)]
+pub struct MediaHeaders {
+    pub preset: MediaHeaderPreset,
+    /// Explicit Referer, overriding the preset.
+    pub referer: Option<String>,
+    /// Explicit Origin, overriding the preset.
+    pub origin: Option<String>,
+    /// Raw Cookie header; replaces cookies from the session store for media requests.
+    pub cookie: Option<String>,
+    /// Additional headers appended after the preset.
+    pub extra: reqwest::header::HeaderMap,
+}
+
+impl MediaHeaders {
+    fn header_pairs(&self, share_url: &str) -> Vec<(reqwest::header::HeaderName, String)> {
+        const SITE: &str = "https://www.tiktok.com";
+
+        let (referer, origin) = match self.preset {
+            MediaHeaderPreset::Share => (Some(share_url.to_string()), None),
+            MediaHeaderPreset::Site => (Some(format!("{SITE}/")), Some(SITE.to_string())),
+            MediaHeaderPreset::None => (None, None),
+        };
+
+        let mut pairs = Vec::new();
+        if let Some(referer) = self.referer.clone().or(referer) {
+            pairs.push((reqwest::header::REFERER, referer));
+        }
+        if let Some(origin) = self.origin.clone().or(origin) {
+            pairs.push((reqwest::header::ORIGIN, origin));
+        }
+        if let Some(cookie) = self.cookie.clone() {
+            pairs.push((reqwest::header::COOKIE, cookie));
+        }
+        for (name, value) in &self.extra {
+            if let Ok(value) = value.to_str() {
+                pairs.push((name.clone(), value.to_string()));
+            }
+        }
+        pairs
+    }
+}
+
+/// Bounded hand-off between scraping and downloading, see `DownloadConfig::pipeline`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// Items scraped at the same time.
+    pub scrape_workers: usize,
+    /// Scraped items waiting for a download worker; scraping pauses while it is full.
+    pub queue_depth: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloadConfig {
+    pub max_retries: usize,
+    pub initial_backoff_ms: u64,
+    /// Run a batch up to this many times, re-running only the items that failed with a
+    /// retryable error after the previous pass.
+    pub batch_passes: usize,
+    /// Wait between batch passes, long enough for a throttling CDN to recover.
+    pub pass_cooldown: Duration,
+    /// Only start downloads inside this daily window of local time; items reached outside
+    /// it wait for the window to open. Transfers already running are not interrupted.
+    pub active_hours: Option<ActiveHours>,
+    pub max_concurrent_downloads: usize,
+    /// Cap on simultaneous media transfers for one creator (`None` for no cap).
+    pub max_concurrent_per_author: Option<usize>,
+    /// Download at most this many videos per creator in a run, skipping the rest with
+    /// `SkipReason::AuthorLimit`. Videos already on disk don't count.
+    pub max_per_author: Option<usize>,
+    /// Scrape batch items in a separate stage that runs ahead of the downloads by at most
+    /// `queue_depth` items. `None` scrapes each item in its download worker.
+    pub pipeline: Option<PipelineConfig>,
+    pub output_dir: Option<PathBuf>,
+    /// What default output file names are made of.
+    pub filename_style: FilenameStyle,
+    /// Output path under `output_dir`, without extension, instead of
+    /// `<author>/<filename_style name>`. `filename_style` is then unused.
+    pub output_template: Option<OutputTemplate>,
+    /// Romanize captions (Thai to Latin, accents folded) in title-based file names.
+    /// Always done under `SanitizePolicy::StrictAscii`.
+    pub transliterate: bool,
+    /// Characters allowed in creator directory and file names.
+    pub sanitize: SanitizePolicy,
+    /// Longest directory or file name, in bytes, including temporary `.part` suffixes.
+    /// Longer names are cut and end in a hash of the video ID instead.
+    pub max_name_length: usize,
+    /// Longest output path, in bytes, as given (relative paths don't count the working
+    /// directory). File names are shortened to fit.
+    pub max_path_length: Option<usize>,
+    pub dedupe: DedupeMode,
+    /// Compare each finished download with the archive to catch re-uploads under new IDs.
+    pub content_dedupe: ContentDedupe,
+    /// Refuse new downloads once the output directory holds this many bytes. Only
+    /// enforced with an explicit `output_dir`, never against the working directory.
+    pub max_archive_size: Option<u64>,
+    /// Delete the oldest videos instead of refusing when over `max_archive_size`. Ignored
+    /// by `dry_run`, which refuses instead.
+    pub prune_oldest: bool,
+    /// Stop starting new downloads once this many bytes have been transferred.
+    pub max_total_bytes: Option<u64>,
+    /// Stop starting new downloads once this much time has passed since construction.
+    pub max_runtime: Option<Duration>,
+    /// Abort a transfer when no body bytes arrive for this long.
+    pub stall_timeout: Option<Duration>,
+    /// Abandon a transfer whose throughput stays below this many bytes per second.
+    pub min_speed: Option<u64>,
+    /// Bytes of received media held in memory before each write to disk.
+    pub write_buffer_size: usize,
+    /// Delay and drop requests and received chunks, to test recovery from a bad network.
+    #[cfg(feature = "simulate-network")]
+    pub simulate_network: Option<NetworkSimulation>,
+    pub media_headers: MediaHeaders,
+    /// Proxy used only for share-page scraping.
+    pub scrape_proxy: Option<String>,
+    /// Proxy used only for CDN media transfers.
+    pub media_proxy: Option<String>,
+    /// Use `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` when no explicit proxy is set.
+    pub env_proxy: bool,
+    /// Redirects followed per request before it fails; zero follows none.
+    pub max_redirects: usize,
+    /// Resolve `vm.tiktok.com`-style short links with HEAD requests before fetching the
+    /// page they point to.
+    pub short_links_via_head: bool,
+    /// Leave the `.part` file of a failed or cancelled download instead of deleting it,
+    /// and resume a direct download from it with a range request.
+    pub keep_partial: bool,
+    /// Extra directories each finished download is copied into, mirroring its path under
+    /// `output_dir`. A failed copy is logged and does not fail the download.
+    pub also_copy_to: Vec<PathBuf>,
+    /// Permission bits set on finished files, e.g. `0o644` (Unix only).
+    pub file_mode: Option<u32>,
+    /// Owner and/or group set on finished files (Unix only; usually needs root).
+    pub file_owner: Option<FileOwner>,
+    /// Items are skipped unless they satisfy at least one of these; empty downloads everything.
+    pub match_filters: Vec<MatchFilter>,
+    /// Skip items TikTok marks as ads or paid promotions.
+    pub skip_ads: bool,
+    /// Save the creator's profile details as `author.json` next to their downloads.
+    pub write_author_json: bool,
+    /// Save the creator's largest avatar image next to their downloads.
+    pub write_avatar: bool,
+    /// Save everything scraped about each video as `<video>.info.json` next to it.
+    pub write_info_json: bool,
+    /// Field names of the `write_info_json` sidecars.
+    pub info_json_format: InfoJsonFormat,
+    /// Save each video's cover and origin cover images as `<video>.cover.<ext>` and
+    /// `<video>.origin-cover.<ext>` next to it.
+    pub write_thumbnail: bool,
+    /// Save each video's WebVTT subtitle tracks as `<video>.<language>.vtt` next to it.
+    pub write_subs: bool,
+    /// Subtitle languages `write_subs` saves, e.g. `eng-US` or `eng` for every English
+    /// track; empty saves all of them.
+    pub sub_langs: Vec<String>,
+    /// Identifies this run in reports; a fresh one is generated by default.
+    pub run_id: RunId,
+    /// Variant preference for HLS streams.
+    pub quality: Quality,
+    /// Logged-in session cookies, used for age-restricted videos.
+    pub session_cookie: Option<String>,
+    /// Device identity for API-based extraction.
+    pub device: Option<DeviceIds>,
+    /// Region claimed on API requests.
+    pub geo: Option<Geo>,
+    /// Site root for share pages and API endpoints instead of `https://www.tiktok.com/`.
+    pub scrape_base_url: Option<Url>,
+    /// Pause every worker this long after any 429, doubling on repeats (zero disables).
+    pub rate_limit_cooldown: Duration,
+    /// Receives progress events such as finished HLS segments.
+    pub progress: Option<ProgressSender>,
+    /// ffmpeg binary used to mux HLS variants whose audio is a separate rendition.
+    pub ffmpeg: PathBuf,
+    /// Directory for cached scrape results, reused by later downloads of the same video ID.
+    pub descriptor_cache_dir: Option<PathBuf>,
+    /// How long a cached descriptor is trusted; signed media URLs expire after a few hours.
+    pub descriptor_cache_ttl: Duration,
+    /// yt-dlp-style archive of video IDs: listed videos are skipped as already downloaded,
+    /// and each video saved or found on disk is appended.
+    pub download_archive: Option<PathBuf>,
+    /// Record the size and CRC-32 of each finished download in `CHECKSUM_FILE` under
+    /// `output_dir`, for `verify_checksums` to check later.
+    pub write_checksums: bool,
+    /// Scrape again instead of downloading when a cached or queued descriptor was
+    /// extracted longer ago than this.
+    pub max_url_age: Option<Duration>,
+    /// Check the media URL with a HEAD (or one-byte GET) before downloading.
+    pub probe: bool,
+    /// Scrape and probe every item without writing any media.
+    pub dry_run: bool,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 500,
+            batch_passes: 1,
+            pass_cooldown: Duration::from_secs(60),
+            active_hours: None,
+            max_concurrent_downloads: 4,
+            max_concurrent_per_author: None,
+            max_per_author: None,
+            pipeline: None,
+            output_dir: None,
+            filename_style: FilenameStyle::Id,
+            output_template: None,
+            transliterate: false,
+            sanitize: SanitizePolicy::StrictAscii,
+            max_name_length: 255,
+            max_path_length: None,
+            dedupe: DedupeMode::Off,
+            content_dedupe: ContentDedupe::Off,
+            max_archive_size: None,
+            prune_oldest: false,
+            max_total_bytes: None,
+            max_runtime: None,
+            stall_timeout: Some(Duration::from_secs(30)),
+            min_speed: None,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            #[cfg(feature = "simulate-network")]
+            simulate_network: None,
+            media_headers: MediaHeaders::default(),
+            scrape_proxy: None,
+            media_proxy: None,
+            env_proxy: true,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            short_links_via_head: false,
+            keep_partial: false,
+            also_copy_to: Vec::new(),
+            file_mode: None,
+            file_owner: None,
+            match_filters: Vec::new(),
+            skip_ads: false,
+            write_author_json: false,
+            write_avatar: false,
+            write_info_json: false,
+            info_json_format: InfoJsonFormat::TikdR,
+            write_thumbnail: false,
+            write_subs: false,
+            sub_langs: Vec::new(),
+            run_id: RunId::new(),
+            quality: Quality::Best,
+            session_cookie: None,
+            device: None,
+            geo: None,
+            scrape_base_url: None,
+            rate_limit_cooldown: Duration::from_secs(2),
+            progress: None,
+            ffmpeg: PathBuf::from("ffmpeg"),
+            descriptor_cache_dir: None,
+            descriptor_cache_ttl: Duration::from_secs(60 * 60),
+            download_archive: None,
+            write_checksums: false,
+            max_url_age: None,
+            probe: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// Expose a configured HTTP client shared by the downloader and integration tests.
+pub fn build_http_client() -> Result<Client> {
+    build_http_client_with(new_cookie_store(), None)
+}
+
+fn build_scraper(client: Client, config: &DownloadConfig) -> Scraper {
+    let mut scraper = Scraper::new(client);
+    if let Some(cookie) = config.session_cookie.clone() {
+        scraper = scraper.with_session_cookie(cookie);
+    }
+    if let Some(device) = config.device.clone() {
+        scraper = scraper.with_device(device);
+    }
+    if let Some(geo) = config.geo.clone() {
+        scraper = scraper.with_geo(geo);
+    }
+    if let Some(base_url) = config.scrape_base_url.clone() {
+        scraper = scraper.with_base_url(base_url);
+    }
+    if config.short_links_via_head {
+        scraper = scraper.with_short_links_via_head();
+    }
+    scraper
+}
+
+/// Cookies shared by the clients of a downloader. Without the `cookies` feature clients
+/// keep no cookies, and the jar is empty.
+#[cfg(feature = "cookies")]
+pub type CookieJar = Arc<CookieStoreMutex>;
+#[cfg(not(feature = "cookies"))]
+pub type CookieJar = Arc<()>;
+
+/// Create an empty cookie jar that can be shared between several clients.
+pub fn new_cookie_store() -> CookieJar {
+    #[cfg(feature = "cookies")]
+    let jar = CookieStoreMutex::new(CookieStore::default());
+    #[cfg(not(feature = "cookies"))]
+    let jar = ();
+    Arc::new(jar)
+}
+
+/// Build a client on top of an existing cookie jar, optionally routed through a proxy.
+///
+/// Without an explicit proxy, the standard proxy environment variables apply.
+pub fn build_http_client_with(cookie_store: CookieJar, proxy: Option<&str>) -> Result<Client> {
+    build_client(cookie_store, proxy, true, DEFAULT_MAX_REDIRECTS)
+}
+
+fn build_client(
+    cookie_store: CookieJar,
+    proxy: Option<&str>,
+    env_proxy: bool,
+    max_redirects: usize,
+) -> Result<Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::ACCEPT,
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8"
+            .parse().unwrap(),
+    );
+    headers.insert(
+        reqwest::header::ACCEPT_LANGUAGE,
+        "en-US,en;q=0.9".parse().unwrap(),
+    );
+
+    let mut builder = Client::builder()
+        .user_agent(DEFAULT_USER_AGENT)
+        .default_headers(headers)
+        .redirect(Policy::limited(max_redirects));
+    #[cfg(feature = "cookies")]
+    {
+        builder = builder.cookie_provider(cookie_store);
+    }
+    #[cfg(not(feature = "cookies"))]
+    let _ = cookie_store;
+
+    // An explicit proxy replaces the environment's proxies.
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|err| Error::InvalidProxy(format!("{proxy}: {err}")))?;
+        builder = builder.proxy(proxy);
+    } else if !env_proxy {
+        builder = builder.no_proxy();
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Why a batch item was left out without counting as a failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The output file already existed.
+    AlreadyDownloaded,
+    /// Rejected by `match_filters` or `skip_ads`.
+    Filtered,
+    /// Same video as an earlier batch item, or the same bytes as an archived video under
+    /// `ContentDedupe::Skip`.
+    Duplicate,
+    /// The run already downloaded `max_per_author` videos of the creator.
+    AuthorLimit,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::AlreadyDownloaded => "already downloaded",
+            SkipReason::Filtered => "filtered",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::AuthorLimit => "over the per-author limit",
+        }
+    }
+}
+
+/// How a batch item ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Skipped(SkipReason),
+    Failed,
+}
+
+/// Detailed download outcome for reporting and summaries.
+#[derive(Debug)]
+pub struct DownloadReport {
+    pub url: String,
+    pub result: Result<PathBuf>,
+    /// Earlier URL in the same batch that resolved to the same output file.
+    pub duplicate_of: Option<String>,
+    /// Failed attempts that were retried before the final result.
+    pub retries: Vec<RetryRecord>,
+    /// Preflight result when probing or dry-running.
+    pub probe: Option<MediaProbe>,
+    /// Scrape, first-byte, and transfer durations of the last attempt.
+    pub timings: ItemTimings,
+    /// Metadata the item resolved to, when scraping got that far.
+    pub descriptor: Option<VideoDescriptor>,
+    /// Archived video with identical bytes under another ID (`ContentDedupe::Flag`).
+    pub same_content_as: Option<PathBuf>,
+    /// The output file existed before the item started, so nothing was downloaded.
+    pub already_downloaded: bool,
+    /// Fallbacks the last attempt had to take, in the order they happened.
+    pub warnings: Vec<DownloadWarning>,
+}
+
+impl DownloadReport {
+    fn from_outcome(url: String, outcome: Result<PathBuf>) -> Self {
+        match outcome {
+            Ok(path) => Self::success(url, path),
+            Err(Error::DuplicateOutput { path, first_url }) => Self {
+                url,
+                result: Ok(path),
+                duplicate_of: Some(first_url),
+                retries: Vec::new(),
+                probe: None,
+                timings: ItemTimings::default(),
+                descriptor: None,
+                same_content_as: None,
+                already_downloaded: false,
+                warnings: Vec::new(),
+            },
+            Err(err) => Self::failure(url, err),
+        }
+    }
+
+    fn success(url: String, path: PathBuf) -> Self {
+        Self {
+            url,
+            result: Ok(path),
+            duplicate_of: None,
+            retries: Vec::new(),
+            probe: None,
+            timings: ItemTimings::default(),
+            descriptor: None,
+            same_content_as: None,
+            already_downloaded: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn failure(url: String, err: Error) -> Self {
+        Self {
+            url,
+            result: Err(err),
+            duplicate_of: None,
+            retries: Vec::new(),
+            probe: None,
+            timings: ItemTimings::default(),
+            descriptor: None,
+            same_content_as: None,
+            already_downloaded: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.duplicate_of.is_some()
+    }
+
+    /// Downloaded by this item; duplicates and other skips are not successes even when
+    /// they carry a path.
+    pub fn is_success(&self) -> bool {
+        self.outcome() == Outcome::Success
+    }
+
+    /// Success, skip, or failure; a skip may still have a path when the file was
+    /// already there or another item of the batch produced it.
+    pub fn outcome(&self) -> Outcome {
+        match &self.result {
+            Ok(_) if self.is_duplicate() => Outcome::Skipped(SkipReason::Duplicate),
+            Ok(_) if self.already_downloaded => Outcome::Skipped(SkipReason::AlreadyDownloaded),
+            Ok(_) => Outcome::Success,
+            Err(Error::Skipped { reason, .. }) => Outcome::Skipped(*reason),
+            Err(_) => Outcome::Failed,
+        }
+    }
+
+    /// Left out rather than failed; see `outcome` for the reason.
+    pub fn is_skipped(&self) -> bool {
+        matches!(self.outcome(), Outcome::Skipped(_))
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.result.as_ref().ok()
+    }
+
+    pub fn error(&self) -> Option<&Error> {
+        self.result.as_ref().err()
+    }
+}
+
+/// High-level orchestrator for downloading one or many TikTok videos.
+#[derive(Clone)]
+pub struct Downloader {
+    client: Client,
+    scraper: Scraper,
+    config: DownloadConfig,
+    cookie_store: CookieJar,
+    bytes_downloaded: Arc<AtomicU64>,
+    started_at: Instant,
+    cancel: CancellationToken,
+    request_cancel: Option<CancellationToken>,
+    control: Option<DownloadControl>,
+    batch_claims: Option<OutputClaims>,
+    batch_probes: Option<ProbeLog>,
+    item_timer: Option<ItemTimer>,
+    item_descriptor: Option<ItemSlot<VideoDescriptor>>,
+    item_content_match: Option<ItemSlot<PathBuf>>,
+    item_existing: Option<ItemSlot<()>>,
+    item_warnings: Option<ItemSlot<Vec<DownloadWarning>>>,
+    item_prefetched: Option<ItemSlot<Prefetched>>,
+    reused_scrapes: ReusedScrapes,
+    rate_gate: RateGate,
+    host_health: HostHealth,
+    author_slots: AuthorSlots,
+    author_counts: AuthorCounts,
+    author_assets: AuthorAssets,
+    content_index: ContentIndex,
+    archive_quota: Option<ArchiveQuota>,
+    descriptor_cache: Option<DescriptorCache>,
+    download_archive: Option<DownloadArchive>,
+    checksum_log: Option<ChecksumLog>,
+    layout_log: Option<LayoutLog>,
+}
+
+/// A value a batch item produces along the way, kept for its report.
+struct ItemSlot<T>(Arc<std::sync::Mutex<Option<T>>>);
+
+impl<T> Clone for ItemSlot<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Default for ItemSlot<T> {
+    fn default() -> Self {
+        Self(Arc::new(std::sync::Mutex::new(None)))
+    }
+}
+
+impl<T> ItemSlot<T> {
+    fn set(&self, value: T) {
+        let mut slot = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *slot = Some(value);
+    }
+
+    fn take(&self) -> Option<T> {
+        let mut slot = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        slot.take()
+    }
+}
+
+impl<T> ItemSlot<Vec<T>> {
+    fn push(&self, value: T) {
+        let mut slot = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        slot.get_or_insert_with(Vec::new).push(value);
+    }
+}
+
+/// A descriptor the pipeline's scrape stage resolved ahead of the download.
+struct Prefetched {
+    descriptor: VideoDescriptor,
+    scrape_time: Duration,
+}
+
+/// Descriptors handed over from an earlier run by `reuse_scrapes`, keyed by share URL.
+/// Each is used once, by the first item with its URL.
+#[derive(Clone, Default)]
+struct ReusedScrapes(Arc<std::sync::Mutex<HashMap<String, Prefetched>>>);
+
+impl ReusedScrapes {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Prefetched>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.lock().contains_key(url)
+    }
+
+    fn take(&self, url: &str) -> Option<Prefetched> {
+        self.lock().remove(url)
+    }
+}
+
+/// Output paths taken by items of the current batch, mapped to the item that claimed
+/// them, and how the items of the batch ended.
+#[derive(Clone, Default)]
+struct OutputClaims(Arc<ClaimState>);
+
+#[derive(Default)]
+struct ClaimState {
+    claims: std::sync::Mutex<Claims>,
+    finished: tokio::sync::Notify,
+}
+
+#[derive(Default)]
+struct Claims {
+    paths: HashMap<PathBuf, Claimant>,
+    outcomes: HashMap<String, Outcome>,
+}
+
+/// The item that claimed an output path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Claimant {
+    url: String,
+    video_id: String,
+}
+
+impl OutputClaims {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Claims> {
+        self.0
+            .claims
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Claim `path` for the item of `url`, returning the item of another URL if it
+    /// claimed the path first.
+    fn claim(&self, path: &Path, url: &str, video_id: &str) -> Option<Claimant> {
+        let mut claims = self.lock();
+        match claims.paths.get(path) {
+            Some(owner) if owner.url != url => Some(owner.clone()),
+            Some(_) => None,
+            None => {
+                let owner = Claimant {
+                    url: url.to_string(),
+                    video_id: video_id.to_string(),
+                };
+                claims.paths.insert(path.to_path_buf(), owner);
+                None
+            }
+        }
+    }
+
+    fn release(&self, path: &Path, url: &str) {
+        let mut claims = self.lock();
+        if claims.paths.get(path).is_some_and(|owner| owner.url == url) {
+            claims.paths.remove(path);
+        }
+    }
+
+    /// Record how the item for `url` ended, after all of its retries.
+    fn finish(&self, url: &str, outcome: Outcome) {
+        self.lock().outcomes.insert(url.to_string(), outcome);
+        self.0.finished.notify_waiters();
+    }
+
+    /// Wait until the item for `url` has finished and return how it ended.
+    async fn outcome_of(&self, url: &str) -> Outcome {
+        loop {
+            let finished = self.0.finished.notified();
+            tokio::pin!(finished);
+            // Registers for the next notification before looking, so none is missed.
+            finished.as_mut().enable();
+            if let Some(outcome) = self.lock().outcomes.get(url) {
+                return *outcome;
+            }
+            finished.await;
+        }
+    }
+}
+
+/// Per-creator semaphores enforcing `max_concurrent_per_author`, shared by clones.
+#[derive(Clone, Default)]
+struct AuthorSlots(Arc<std::sync::Mutex<HashMap<String, Arc<Semaphore>>>>);
+
+impl AuthorSlots {
+    async fn acquire(&self, author: &str, limit: usize) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut slots = self
+                .0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            slots
+                .entry(author.to_ascii_lowercase())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit.max(1))))
+                .clone()
+        };
+        // The semaphores are never closed.
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+/// Downloads started per creator in this run, enforcing `max_per_author`, shared by clones.
+#[derive(Clone, Default)]
+struct AuthorCounts(Arc<std::sync::Mutex<HashMap<String, usize>>>);
+
+impl AuthorCounts {
+    /// Count one more download of `author`, unless `limit` are already counted.
+    fn claim(&self, author: &str, limit: usize) -> bool {
+        let mut counts = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = counts.entry(author.to_ascii_lowercase()).or_default();
+        if *count >= limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// Give back a claim whose download did not end up on disk.
+    fn release(&self, author: &str) {
+        let mut counts = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(count) = counts.get_mut(&author.to_ascii_lowercase()) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+impl Downloader {
+    /// Build a downloader with sane defaults for TikTok endpoints.
+    pub fn new() -> Result<Self> {
+        let client = build_http_client()?;
+        Ok(Self::with_client_and_config(
+            client,
+            DownloadConfig::default(),
+        ))
+    }
+
+    /// Construct a downloader from a pre-configured HTTP client.
+    pub fn with_client(client: Client) -> Self {
+        Self::with_client_and_config(client, DownloadConfig::default())
+    }
+
+    pub fn with_config(config: DownloadConfig) -> Result<Self> {
+        // Both clients share one cookie jar so session cookies set while scraping
+        // are still sent to the CDN.
+        let cookie_store = new_cookie_store();
+        let scrape_client = build_client(
+            cookie_store.clone(),
+            config.scrape_proxy.as_deref(),
+            config.env_proxy,
+            config.max_redirects,
+        )?;
+        let media_client = if config.scrape_proxy == config.media_proxy {
+            scrape_client.clone()
+        } else {
+            build_client(
+                cookie_store.clone(),
+                config.media_proxy.as_deref(),
+                config.env_proxy,
+                config.max_redirects,
+            )?
+        };
+        Ok(Self::from_parts(
+            scrape_client,
+            media_client,
+            cookie_store,
+            config,
+        ))
+    }
+
+    pub fn with_client_and_config(client: Client, config: DownloadConfig) -> Self {
+        Self::with_clients(client.clone(), client, config)
+    }
+
+    /// Use separate clients for share-page scraping and media transfers.
+    pub fn with_clients(scrape_client: Client, client: Client, config: DownloadConfig) -> Self {
+        Self::from_parts(scrape_client, client, new_cookie_store(), config)
+    }
+
+    fn from_parts(
+        scrape_client: Client,
+        client: Client,
+        cookie_store: CookieJar,
+        config: DownloadConfig,
+    ) -> Self {
+        let scraper = build_scraper(scrape_client, &config);
+        let rate_gate = RateGate::new(config.rate_limit_cooldown);
+        let archive_quota = match (config.max_archive_size, &config.output_dir) {
+            // A dry run only reports that the archive is full; it must not delete anything.
+            (Some(limit), Some(root)) => Some(ArchiveQuota::new(
+                root.clone(),
+                limit,
+                config.prune_oldest && !config.dry_run,
+            )),
+            (Some(_), None) => {
+                tracing::warn!("max_archive_size is ignored without an output directory");
+                None
+            }
+            (None, _) => None,
+        };
+        let descriptor_cache = config
+            .descriptor_cache_dir
+            .clone()
+            .map(|dir| DescriptorCache::new(dir, config.descriptor_cache_ttl));
+        let download_archive = config.download_archive.clone().map(DownloadArchive::new);
+        let checksum_log = config.write_checksums.then(|| {
+            ChecksumLog::new(
+                config
+                    .output_dir
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(".")),
+            )
+        });
+        let layout_log = match (&config.output_dir, &config.output_template) {
+            (Some(root), Some(template)) => {
+                Some(LayoutLog::new(root.clone(), template, config.sanitize))
+            }
+            _ => None,
+        };
+        Self {
+            client,
+            scraper,
+            config,
+            cookie_store,
+            bytes_downloaded: Arc::new(AtomicU64::new(0)),
+            started_at: Instant::now(),
+            cancel: CancellationToken::new(),
+            request_cancel: None,
+            control: None,
+            batch_claims: None,
+            batch_probes: None,
+            item_timer: None,
+            item_descriptor: None,
+            item_content_match: None,
+            item_existing: None,
+            item_warnings: None,
+            item_prefetched: None,
+            reused_scrapes: ReusedScrapes::default(),
+            rate_gate,
+            host_health: HostHealth::default(),
+            author_slots: AuthorSlots::default(),
+            author_counts: AuthorCounts::default(),
+            author_assets: AuthorAssets::default(),
+            content_index: ContentIndex::default(),
+            archive_quota,
+            descriptor_cache,
+            download_archive,
+            checksum_log,
+            layout_log,
+        }
+    }
+
+    /// Token that cancels every download started by this downloader and its clones.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+            || self
+                .request_cancel
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Resolves once either the downloader-wide or the per-request token fires.
+    async fn cancelled(&self) {
+        match &self.request_cancel {
+            Some(token) => {
+                tokio::select! {
+                    _ = self.cancel.cancelled() => {}
+                    _ = token.cancelled() => {}
+                }
+            }
+            None => self.cancel.cancelled().await,
+        }
+    }
+
+    /// Derive a downloader that applies a request's overrides while sharing counters and cookies.
+    fn for_request(&self, request: &DownloadRequest) -> Result<Self> {
+        let mut derived = self.clone();
+        derived.request_cancel = request.cancel.clone();
+        if let Some(quality) = request.quality {
+            derived.config.quality = quality;
+        }
+
+        for (name, value) in &request.headers {
+            let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| Error::InvalidHeader(name.clone()))?;
+            let header_value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| Error::InvalidHeader(format!("{name}: {value}")))?;
+            derived
+                .config
+                .media_headers
+                .extra
+                .append(header_name, header_value);
+        }
+
+        if let Some(proxy) = request.proxy.as_deref() {
+            let client = build_client(
+                self.cookie_store.clone(),
+                Some(proxy),
+                true,
+                self.config.max_redirects,
+            )?;
+            derived.scraper = build_scraper(client.clone(), &self.config);
+            derived.client = client;
+        }
+
+        Ok(derived)
+    }
+
+    pub fn run_id(&self) -> RunId {
+        self.config.run_id
+    }
+
+    /// Total media bytes written to disk by this downloader and its clones.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bytes_downloaded.load(Ordering::Relaxed)
+    }
+
+    fn record_bytes(&self, count: usize) {
+        self.bytes_downloaded
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn ensure_byte_budget(&self) -> Result<()> {
+        match self.config.max_total_bytes {
+            Some(limit) if self.bytes_downloaded() >= limit => {
+                Err(Error::ByteBudgetExhausted { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn ensure_runtime_budget(&self) -> Result<()> {
+        match self.config.max_runtime {
+            Some(limit) if self.started_at.elapsed() >= limit => {
+                Err(Error::RuntimeBudgetExhausted { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Hold the item until `active_hours` next opens, or fail with `Cancelled`.
+    async fn wait_for_active_hours(&self) -> Result<()> {
+        let Some(hours) = self.config.active_hours else {
+            return Ok(());
+        };
+        let wait = hours.wait_now();
+        if wait.is_zero() {
+            return Ok(());
+        }
+        tracing::info!(
+            "Outside active hours {hours}; waiting {}m",
+            wait.as_secs().div_ceil(60)
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => Ok(()),
+            _ = self.cancelled() => Err(Error::Cancelled),
+        }
+    }
+
+    /// Use the descriptors an earlier run scraped, such as a `dry_run` probe of the same
+    /// batch, instead of scraping those URLs again. Media URLs are signed and expire, so a
+    /// download that is refused with one still scrapes afresh.
+    pub fn reuse_scrapes(&self, reports: &[DownloadReport]) {
+        let mut reused = self.reused_scrapes.lock();
+        for report in reports {
+            if let Some(descriptor) = &report.descriptor {
+                reused.insert(
+                    report.url.clone(),
+                    Prefetched {
+                        descriptor: descriptor.clone(),
+                        scrape_time: report.timings.scrape.unwrap_or_default(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Download all share URLs, returning per-URL outcomes.
+    /// Progress is printed to stderr as each download completes.
+    pub async fn download_all(&self, urls: &[String]) -> Vec<DownloadReport> {
+        let requests: Vec<DownloadRequest> = urls.iter().map(DownloadRequest::new).collect();
+        self.download_requests(&requests).await
+    }
+
+    /// Download a batch of individually configured requests, returning per-item outcomes.
+    ///
+    /// With `batch_passes` above one, items that failed with a retryable error are run
+    /// again after `pass_cooldown`; their reports keep the retries of every pass.
+    pub async fn download_requests(&self, requests: &[DownloadRequest]) -> Vec<DownloadReport> {
+        let mut reports = self.run_pass(requests).await;
+
+        for pass in 2..=self.config.batch_passes {
+            let failed: Vec<usize> = (0..reports.len())
+                .filter(|&idx| reports[idx].error().is_some_and(should_retry))
+                .collect();
+            if failed.is_empty() || self.is_cancelled() {
+                break;
+            }
+            tracing::warn!(
+                "Pass {}/{}: retrying {} failed items in {}s",
+                pass,
+                self.config.batch_passes,
+                failed.len(),
+                self.config.pass_cooldown.as_secs()
+            );
+            tokio::select! {
+                _ = sleep(self.config.pass_cooldown) => {}
+                _ = self.cancel.cancelled() => break,
+            }
+
+            let again: Vec<DownloadRequest> =
+                failed.iter().map(|&idx| requests[idx].clone()).collect();
+            for (idx, mut report) in failed.into_iter().zip(self.run_pass(&again).await) {
+                let mut retries = std::mem::take(&mut reports[idx].retries);
+                retries.append(&mut report.retries);
+                report.retries = retries;
+                reports[idx] = report;
+            }
+        }
+        reports
+    }
+
+    /// One pass over `requests`, reporting each item as it finishes.
+    async fn run_pass(&self, requests: &[DownloadRequest]) -> Vec<DownloadReport> {
+        if requests.is_empty() {
+            return Vec::new();
+        }
+
+        let total = requests.len();
+        let mut results: Vec<(usize, DownloadReport)> = Vec::with_capacity(total);
+        let mut completed = 0usize;
+        let mut failed = 0usize;
+        let started = Instant::now();
+        let start_bytes = self.bytes_downloaded();
+        let status = |done, failed| BatchStatus {
+            done,
+            total,
+            failed,
+            bytes: self.bytes_downloaded().saturating_sub(start_bytes),
+            elapsed: started.elapsed(),
+        };
+
+        let (_control, tasks) = self.download_all_stream(requests.to_vec());
+        let mut ticker = tokio::time::interval(BATCH_STATUS_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        futures::pin_mut!(tasks);
+        loop {
+            let (idx, report) = tokio::select! {
+                next = tasks.next() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+                _ = ticker.tick(), if self.config.progress.is_some() => {
+                    self.emit_progress(ProgressEvent::Batch(status(completed, failed)));
+                    continue;
+                }
+            };
+            completed += 1;
+            if report.outcome() == Outcome::Failed {
+                failed += 1;
+            }
+            if self.config.progress.is_some() {
+                // The progress consumer owns the terminal and prints the item line itself.
+                self.emit_progress(ProgressEvent::ItemFinished {
+                    url: report.url.clone(),
+                    outcome: report.outcome(),
+                    status: status(completed, failed),
+                });
+            } else if total > 1 {
+                let status = match report.outcome() {
+                    Outcome::Success => "ok",
+                    Outcome::Skipped(SkipReason::Duplicate) => "duplicate",
+                    Outcome::Skipped(_) => "skipped",
+                    Outcome::Failed => "FAILED",
+                };
+                eprintln!("[{}/{}] {} ... {}", completed, total, report.url, status);
+            }
+            results.push((idx, report));
+        }
+
+        results.sort_by_key(|(idx, _)| *idx);
+        results.into_iter().map(|(_, report)| report).collect()
+    }
+
+    /// Stream reports as downloads finish, tagged with the request's position in `requests`.
+    ///
+    /// The returned control handle can pause, resume, or reorder the queue while the stream
+    /// is being consumed. Work only progresses while the stream is polled.
+    pub fn download_all_stream(
+        &self,
+        requests: Vec<DownloadRequest>,
+    ) -> (
+        DownloadControl,
+        impl futures::Stream<Item = (usize, DownloadReport)> + Send + 'static,
+    ) {
+        let control = DownloadControl::new(requests);
+        let mut downloader = self.clone();
+        downloader.control = Some(control.clone());
+        downloader.batch_claims = Some(OutputClaims::default());
+        let probes = ProbeLog::default();
+        downloader.batch_probes = Some(probes.clone());
+
+        if let Some(pipeline) = self.config.pipeline {
+            let stream = downloader.pipelined(control.clone(), probes, pipeline);
+            return (control, stream);
+        }
+
+        let workers: Vec<_> = (0..self.config.max_concurrent_downloads.max(1))
+            .map(|_| {
+                let state = (downloader.clone(), control.clone(), probes.clone());
+                stream::unfold(state, |(downloader, control, probes)| async move {
+                    control.wait_if_paused().await;
+                    let (idx, request) = control.next()?;
+                    let report = downloader.run_item(&probes, request, None).await;
+                    Some(((idx, report), (downloader, control, probes)))
+                })
+                .boxed()
+            })
+            .collect();
+
+        (control, stream::select_all(workers).boxed())
+    }
+
+    /// Batch stream with a scrape stage feeding the download workers through a bounded
+    /// queue. The control handle pauses and reorders the scrape stage; items already
+    /// queued still download.
+    fn pipelined(
+        &self,
+        control: DownloadControl,
+        probes: ProbeLog,
+        pipeline: PipelineConfig,
+    ) -> BoxStream<'static, (usize, DownloadReport)> {
+        let (queue, scraped) = tokio::sync::mpsc::channel(pipeline.queue_depth.max(1));
+        let scraped = Arc::new(tokio::sync::Mutex::new(scraped));
+
+        let scrapers: Vec<_> = (0..pipeline.scrape_workers.max(1))
+            .map(|_| {
+                let (downloader, control, queue) = (self.clone(), control.clone(), queue.clone());
+                async move {
+                    loop {
+                        control.wait_if_paused().await;
+                        let Some((idx, request)) = control.next() else {
+                            break;
+                        };
+                        let prefetched = downloader.prefetch(&request).await;
+                        if queue.send((idx, request, prefetched)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            })
+            .collect();
+        drop(queue);
+        // Polled alongside the downloads so scraping progresses; it yields no reports.
+        let scrape_stage = stream::once(futures::future::join_all(scrapers))
+            .filter_map(|_| async { None::<(usize, DownloadReport)> });
+
+        let workers: Vec<_> = (0..self.config.max_concurrent_downloads.max(1))
+            .map(|_| {
+                let state = (self.clone(), scraped.clone(), probes.clone());
+                stream::unfold(state, |(downloader, scraped, probes)| async move {
+                    let next = scraped.lock().await.recv().await;
+                    let (idx, request, prefetched) = next?;
+                    let report = downloader.run_item(&probes, request, prefetched).await;
+                    Some(((idx, report), (downloader, scraped, probes)))
+                })
+                .boxed()
+            })
+            .collect();
+
+        stream::select(scrape_stage, stream::select_all(workers)).boxed()
+    }
+
+    /// Download one batch item and collect everything its report needs.
+    async fn run_item(
+        &self,
+        probes: &ProbeLog,
+        request: DownloadRequest,
+        prefetched: Option<Prefetched>,
+    ) -> DownloadReport {
+        let mut retries = Vec::new();
+        let timer = ItemTimer::default();
+        let descriptor = ItemSlot::default();
+        let content_match = ItemSlot::default();
+        let existing = ItemSlot::default();
+        let warnings = ItemSlot::default();
+        let prefetch = ItemSlot::default();
+        if let Some(prefetched) = prefetched.or_else(|| self.reused_scrapes.take(&request.url)) {
+            prefetch.set(prefetched);
+        }
+        let mut item = self.clone();
+        item.item_timer = Some(timer.clone());
+        item.item_descriptor = Some(descriptor.clone());
+        item.item_content_match = Some(content_match.clone());
+        item.item_existing = Some(existing.clone());
+        item.item_warnings = Some(warnings.clone());
+        item.item_prefetched = Some(prefetch);
+        let outcome = item.download_request_tracked(&request, &mut retries).await;
+        let probe = probes.take(&request.url);
+        let mut report = DownloadReport::from_outcome(request.url, outcome);
+        report.already_downloaded = existing.take().is_some();
+        if let Some(claims) = &self.batch_claims {
+            claims.finish(&report.url, report.outcome());
+        }
+        report.retries = retries;
+        report.probe = probe;
+        report.timings = timer.timings();
+        report.descriptor = descriptor.take();
+        report.same_content_as = content_match.take();
+        report.warnings = warnings.take().unwrap_or_default();
+        report
+    }
+
+    /// Scrape `request` for the pipeline's scrape stage. Failures are left to the
+    /// download worker, which scrapes again with the usual retries.
+    async fn prefetch(&self, request: &DownloadRequest) -> Option<Prefetched> {
+        if self.is_cancelled() || self.reused_scrapes.contains(&request.url) {
+            return None;
+        }
+        if let Some(cache) = &self.descriptor_cache {
+            if cache.get(&request.url).await.is_some() {
+                return None;
+            }
+        }
+        let derived;
+        let downloader = if request.has_overrides() {
+            derived = self.for_request(request).ok()?;
+            &derived
+        } else {
+            self
+        };
+
+        let _permit = downloader.rate_gate.enter().await;
+        let started = Instant::now();
+        match downloader.scrape(&request.url).await {
+            Ok(descriptor) => Some(Prefetched {
+                descriptor,
+                scrape_time: started.elapsed(),
+            }),
+            Err(err) => {
+                if is_rate_limited(&err) {
+                    downloader.rate_gate.on_rate_limited();
+                }
+                tracing::debug!("Early scrape of {} failed: {}", request.url, err);
+                None
+            }
+        }
+    }
+
+    /// Download a single TikTok share URL to disk and return the output path.
+    pub async fn download_one(&self, share_url: &str) -> Result<PathBuf> {
+        self.download_request(&DownloadRequest::new(share_url))
+            .await
+    }
+
+    /// Download one request, applying its per-item overrides, and return the output path.
+    pub async fn download_request(&self, request: &DownloadRequest) -> Result<PathBuf> {
+        self.download_request_tracked(request, &mut Vec::new())
+            .await
+    }
+
+    /// Like `download_request`, recording every retried attempt in `retries`.
+    async fn download_request_tracked(
+        &self,
+        request: &DownloadRequest,
+        retries: &mut Vec<RetryRecord>,
+    ) -> Result<PathBuf> {
+        let derived;
+        let downloader = if request.has_overrides() {
+            derived = self.for_request(request)?;
+            &derived
+        } else {
+            self
+        };
+        downloader.wait_for_active_hours().await?;
+
+        self.ensure_byte_budget()?;
+        self.ensure_runtime_budget()?;
+        if let Some(quota) = &self.archive_quota {
+            quota.check().await?;
+        }
+        let output = request
+            .output
+            .as_ref()
+            .map(|path| match &self.config.output_dir {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path.clone(),
+            });
+
+        let mut attempt = 0;
+
+        loop {
+            if downloader.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let outcome = tokio::select! {
+                permit = downloader.rate_gate.enter() => {
+                    let outcome = downloader.download_once(&request.url, output.as_deref()).await;
+                    drop(permit);
+                    outcome
+                }
+                _ = downloader.cancelled() => return Err(Error::Cancelled),
+            };
+
+            match outcome {
+                Ok(path) => return Ok(path),
+                Err(err) => {
+                    if is_rate_limited(&err) {
+                        downloader.rate_gate.on_rate_limited();
+                    }
+                    attempt += 1;
+                    if attempt > self.config.max_retries || !should_retry(&err) {
+                        return Err(err);
+                    }
+
+                    let backoff = Duration::from_millis(
+                        self.config
+                            .initial_backoff_ms
+                            .saturating_mul(1u64 << (attempt.saturating_sub(1))),
+                    );
+                    let reason = RetryReason::classify(&err);
+                    tracing::warn!(
+                        "Retrying {} in {:?} (attempt {}/{}, {}): {}",
+                        request.url,
+                        backoff,
+                        attempt,
+                        self.config.max_retries,
+                        reason,
+                        err
+                    );
+                    retries.push(RetryRecord {
+                        attempt,
+                        reason,
+                        message: err.to_string(),
+                        backoff,
+                    });
+                    tokio::select! {
+                        _ = sleep(backoff) => {}
+                        _ = downloader.cancelled() => return Err(Error::Cancelled),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn download_once(&self, share_url: &str, output: Option<&Path>) -> Result<PathBuf> {
+        if let Some(timer) = &self.item_timer {
+            timer.attempt_started();
+        }
+        if let Some(warnings) = &self.item_warnings {
+            warnings.take();
+        }
+        if let Some(video_id) = cache::canonical_video_id(share_url) {
+            // Spares the scrape for links that name their video.
+            self.check_download_archive(&video_id).await?;
+        }
+        let (mut descriptor, reused) = self.resolve_descriptor(share_url).await?;
+        let too_old = |descriptor: &VideoDescriptor| {
+            self.config
+                .max_url_age
+                .zip(descriptor.age())
+                .is_some_and(|(max, age)| age >= max)
+        };
+        if reused && too_old(&descriptor) {
+            tracing::info!("Media URLs of {} are too old; scraping again", share_url);
+            descriptor = self.fresh_descriptor(share_url).await?;
+        }
+
+        let result = self
+            .download_descriptor(&descriptor, share_url, output)
+            .await;
+        if let Some(cache) = &self.descriptor_cache {
+            if result
+                .as_ref()
+                .is_err_and(|err| !matches!(err, Error::Skipped { .. }))
+            {
+                // Media URLs are signed and expire; the retry should scrape afresh.
+                cache.invalidate(&descriptor.video_id).await;
+            }
+        }
+        result
+    }
+
+    /// The item's descriptor, and whether it was scraped before this attempt (by the
+    /// pipeline's scrape stage, a run handed over by `reuse_scrapes`, or an earlier run
+    /// via the cache).
+    async fn resolve_descriptor(&self, share_url: &str) -> Result<(VideoDescriptor, bool)> {
+        if let Some(prefetched) = self.item_prefetched.as_ref().and_then(ItemSlot::take) {
+            if let Some(timer) = &self.item_timer {
+                timer.scraped(prefetched.scrape_time);
+            }
+            if let Some(cache) = &self.descriptor_cache {
+                cache.put(&prefetched.descriptor).await;
+            }
+            return Ok((prefetched.descriptor, true));
+        }
+        if let Some(cache) = &self.descriptor_cache {
+            if let Some(descriptor) = cache.get(share_url).await {
+                return Ok((descriptor, true));
+            }
+        }
+        Ok((self.fresh_descriptor(share_url).await?, false))
+    }
+
+    async fn fresh_descriptor(&self, share_url: &str) -> Result<VideoDescriptor> {
+        let descriptor = self.scrape(share_url).await?;
+        if let Some(cache) = &self.descriptor_cache {
+            cache.put(&descriptor).await;
+        }
+        Ok(descriptor)
+    }
+
+    async fn scrape(&self, share_url: &str) -> Result<VideoDescriptor> {
+        let started = Instant::now();
+        self.simulate_request().await?;
+        let descriptor = tokio::select! {
+            descriptor = self.scraper.extract_video_descriptor(share_url) => descriptor,
+            _ = self.cancelled() => Err(Error::Cancelled),
+        };
+        if let Some(timer) = &self.item_timer {
+            timer.scraped(started.elapsed());
+        }
+        descriptor
+    }
+
+    async fn download_descriptor(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output: Option<&Path>,
+    ) -> Result<PathBuf> {
+        if let Some(slot) = &self.item_descriptor {
+            slot.set(descriptor.clone());
+        }
+        if let Some(reason) = self.skip_reason(descriptor) {
+            tracing::info!("Skipping {}: {}", share_url, reason);
+            return Err(Error::Skipped {
+                reason: SkipReason::Filtered,
+                detail: reason,
+            });
+        }
+        self.check_download_archive(&descriptor.video_id).await?;
+
+        tracing::debug!(
+            "Extracted descriptor - video_id: {}, has_download_url: {}, has_play_url: {}",
+            descriptor.video_id,
+            descriptor.download_url.is_some(),
+            descriptor.play_url.is_some()
+        );
+
+        // The `title-id` name to fall back to when a `title` name is another video's.
+        let mut fallback = None;
+        let mut output_path = match output {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let dated = self
+                    .config
+                    .output_template
+                    .as_ref()
+                    .is_some_and(|template| template.uses(Placeholder::UploadDate));
+                if dated && descriptor.create_time.is_none() {
+                    self.warn(DownloadWarning::NoUploadDate);
+                }
+                let path = build_output_path(descriptor, &self.config)?;
+                if self.names_by_title() {
+                    let id_path =
+                        styled_output_path(descriptor, &self.config, FilenameStyle::TitleId)?;
+                    if self
+                        .title_taken(&path, &id_path, &descriptor.video_id)
+                        .await
+                    {
+                        id_path
+                    } else {
+                        fallback = Some(id_path).filter(|id_path| *id_path != path);
+                        path
+                    }
+                } else {
+                    path
+                }
+            }
+        };
+
+        let Some(claims) = self.batch_claims.as_ref() else {
+            return self
+                .save_descriptor(descriptor, share_url, output_path)
+                .await;
+        };
+
+        let mut claimed = claims.claim(&output_path, share_url, &descriptor.video_id);
+        if let (Some(first), Some(id_path)) = (&claimed, fallback) {
+            if first.video_id != descriptor.video_id {
+                // Another video of the batch has the same caption.
+                output_path = id_path;
+                claimed = claims.claim(&output_path, share_url, &descriptor.video_id);
+            }
+        }
+        if let Some(Claimant { url: first_url, .. }) = claimed {
+            tracing::info!(
+                "{} resolves to {} already claimed by {}",
+                share_url,
+                output_path.display(),
+                first_url
+            );
+            // Only a file the first item actually saved makes this one a duplicate.
+            let outcome = tokio::select! {
+                outcome = claims.outcome_of(&first_url) => outcome,
+                _ = self.cancelled() => return Err(Error::Cancelled),
+            };
+            return Err(match outcome {
+                Outcome::Success | Outcome::Skipped(SkipReason::AlreadyDownloaded) => {
+                    Error::DuplicateOutput {
+                        path: output_path,
+                        first_url,
+                    }
+                }
+                Outcome::Skipped(reason) => Error::Skipped {
+                    reason,
+                    detail: format!("same output as {first_url}, which was skipped"),
+                },
+                Outcome::Failed => Error::DuplicateOfFailed {
+                    path: output_path,
+                    first_url,
+                },
+            });
+        }
+
+        let result = self
+            .save_descriptor(descriptor, share_url, output_path.clone())
+            .await;
+        if result.is_err() {
+            // Let a later item with the same target try again.
+            claims.release(&output_path, share_url);
+        }
+        result
+    }
+
+    /// Whether default names come from `--filename-style title`, which do not say which
+    /// video a file holds.
+    fn names_by_title(&self) -> bool {
+        self.config.filename_style == FilenameStyle::Title && self.config.output_template.is_none()
+    }
+
+    /// Whether the `title` name `path` already holds a video other than `video_id`, or
+    /// an earlier run fell back to `id_path` for it.
+    async fn title_taken(&self, path: &Path, id_path: &Path, video_id: &str) -> bool {
+        if path == id_path || !has_content(path).await {
+            return false;
+        }
+        if has_content(id_path).await {
+            return true;
+        }
+        // Without a sidecar naming the video, the file may be anyone's.
+        let video = path.to_path_buf();
+        let recorded = tokio::task::spawn_blocking(move || layout::info_json_video_id(&video))
+            .await
+            .ok()
+            .flatten();
+        recorded.as_deref() != Some(video_id)
+    }
+
+    fn skip_reason(&self, descriptor: &VideoDescriptor) -> Option<String> {
+        if self.config.skip_ads && descriptor.is_ad {
+            return Some("promoted content (pass --include-ads to download it)".to_string());
+        }
+        let filters = &self.config.match_filters;
+        if !filters.is_empty() && !filters.iter().any(|filter| filter.matches(descriptor)) {
+            let filters: Vec<_> = filters.iter().map(ToString::to_string).collect();
+            return Some(format!(
+                "no --match-filter matched ({})",
+                filters.join(" | ")
+            ));
+        }
+        None
+    }
+
+    async fn save_descriptor(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: PathBuf,
+    ) -> Result<PathBuf> {
+        // Skip if file already exists and has content
+        if let Ok(meta) = tokio::fs::metadata(&output_path).await {
+            if meta.len() > 0 {
+                tracing::info!("Skipping already downloaded: {}", output_path.display());
+                if let Some(slot) = &self.item_existing {
+                    slot.set(());
+                }
+                if !self.config.dry_run {
+                    self.record_in_download_archive(&descriptor.video_id).await;
+                }
+                return Ok(output_path);
+            }
+        }
+
+        let Some(limit) = self.config.max_per_author else {
+            return self.save_new_file(descriptor, share_url, output_path).await;
+        };
+        if !self.author_counts.claim(&descriptor.author, limit) {
+            let detail = format!(
+                "already downloaded {limit} videos of {} in this run (--max-per-author)",
+                descriptor.author
+            );
+            tracing::info!("Skipping {}: {}", share_url, detail);
+            return Err(Error::Skipped {
+                reason: SkipReason::AuthorLimit,
+                detail,
+            });
+        }
+        let result = self.save_new_file(descriptor, share_url, output_path).await;
+        if result.is_err() {
+            // Let a later item, or a retry, of the same creator take the slot.
+            self.author_counts.release(&descriptor.author);
+        }
+        result
+    }
+
+    /// Fetch (or hard-link) a video that is not on disk yet into `output_path`.
+    async fn save_new_file(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: PathBuf,
+    ) -> Result<PathBuf> {
+        if self.config.probe || self.config.dry_run {
+            if let Some(probe) = self.probe_media(descriptor, share_url).await {
+                tracing::debug!("Probed {}: {:?}", share_url, probe);
+                if let Some(probes) = &self.batch_probes {
+                    probes.record(share_url, probe);
+                }
+            }
+            if self.config.dry_run {
+                return Ok(output_path);
+            }
+        }
+
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        if let Some(log) = &self.layout_log {
+            log.record().await;
+        }
+
+        // Title-only names don't identify a video, so a match could be a different clip;
+        // templated paths need not sit in creator directories at all.
+        let names_identify_videos = !self.names_by_title() && self.config.output_template.is_none();
+        if self.config.dedupe == DedupeMode::Hardlink && names_identify_videos {
+            let root = self.config.output_dir.as_deref().unwrap_or(Path::new("."));
+            let existing = find_existing_copy(root, &output_path, &descriptor.video_id).await;
+            if let Some(existing) = existing {
+                match tokio::fs::hard_link(&existing, &output_path).await {
+                    Ok(()) => {
+                        tracing::info!(
+                            "Linked existing copy {} -> {}",
+                            existing.display(),
+                            output_path.display()
+                        );
+                        self.finalize_saved(descriptor, share_url, &output_path)
+                            .await;
+                        return Ok(output_path);
+                    }
+                    Err(err) => {
+                        tracing::warn!("Hard link from {} failed: {}", existing.display(), err);
+                    }
+                }
+            }
+        }
+
+        // Held for the whole transfer so one creator's CDN paths see a bounded number
+        // of connections.
+        let _author_slot = match self.config.max_concurrent_per_author {
+            Some(limit) => tokio::select! {
+                permit = self.author_slots.acquire(&descriptor.author, limit) => permit,
+                _ = self.cancelled() => return Err(Error::Cancelled),
+            },
+            None => None,
+        };
+
+        self.download_media(descriptor, share_url, &output_path)
+            .await?;
+        if self.config.content_dedupe != ContentDedupe::Off {
+            if let Some(original) = self.find_same_content(&output_path).await {
+                if self.config.content_dedupe == ContentDedupe::Skip {
+                    tokio::fs::remove_file(&output_path).await?;
+                    self.content_index.remove(&output_path);
+                    return Err(Error::Skipped {
+                        reason: SkipReason::Duplicate,
+                        detail: format!("same content as {}", original.display()),
+                    });
+                }
+                tracing::warn!(
+                    "{} has the same content as {}",
+                    output_path.display(),
+                    original.display()
+                );
+                if let Some(slot) = &self.item_content_match {
+                    slot.set(original);
+                }
+            }
+        }
+        self.finalize_saved(descriptor, share_url, &output_path)
+            .await;
+        Ok(output_path)
+    }
+
+    /// Skip `video_id` when the `download_archive` lists it.
+    async fn check_download_archive(&self, video_id: &str) -> Result<()> {
+        let Some(archive) = &self.download_archive else {
+            return Ok(());
+        };
+        if archive.contains(video_id).await? {
+            tracing::info!("Skipping {}: listed in the download archive", video_id);
+            return Err(Error::Skipped {
+                reason: SkipReason::AlreadyDownloaded,
+                detail: format!("video {video_id} is in the download archive"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Transfer the media for `descriptor` into a `.part` file and move it into place once
+    /// complete. Unless `keep_partial` is set, a failed or cancelled transfer removes it.
+    async fn download_media(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) -> Result<()> {
+        if let Some(timer) = &self.item_timer {
+            timer.media_started();
+        }
+        let partial = partial_path(output_path);
+        let result = tokio::select! {
+            result = self.transfer_media(descriptor, share_url, &partial) => result,
+            _ = self.cancelled() => Err(Error::Cancelled),
+        };
+        if let Some(timer) = &self.item_timer {
+            timer.media_finished();
+        }
+
+        match result {
+            Ok(()) => Ok(tokio::fs::rename(&partial, output_path).await?),
+            Err(err) if self.config.keep_partial => {
+                tracing::info!("Download failed, keeping {}", partial.display());
+                Err(err)
+            }
+            Err(err) => {
+                tracing::info!("Download failed, removing {}", partial.display());
+                // Separate-audio streams may have been interrupted before their own cleanup.
+                for path in [
+                    partial.clone(),
+                    part_path(&partial, "video"),
+                    part_path(&partial, "audio"),
+                ] {
+                    let _ = tokio::fs::remove_file(path).await;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Download from the descriptor's URLs. A 403 usually means the signed URLs expired,
+    /// so the share page is scraped once more before falling back to HLS.
+    async fn transfer_media(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) -> Result<()> {
+        let first = self
+            .transfer_from(descriptor, share_url, output_path, false)
+            .await;
+        match first {
+            Err(err) if is_forbidden(&err) => {
+                tracing::info!("Media URLs of {} were rejected; scraping again", share_url);
+                self.warn(DownloadWarning::Rescraped);
+                let fresh = self.fresh_descriptor(share_url).await?;
+                self.transfer_from(&fresh, share_url, output_path, true)
+                    .await
+            }
+            other => other,
+        }
+    }
+
+    /// With `refreshed` false, a 403 once every binary URL has failed is returned instead of
+    /// trying HLS.
+    async fn transfer_from(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+        refreshed: bool,
+    ) -> Result<()> {
+        let play_url = descriptor.play_url.clone();
+        let candidates: Vec<String> = descriptor
+            .download_url
+            .iter()
+            .chain(descriptor.mirror_urls.iter())
+            .cloned()
+            .collect();
+
+        if !candidates.is_empty() {
+            match self
+                .download_from_mirrors(&candidates, share_url, output_path)
+                .await
+            {
+                Ok(()) => {
+                    tracing::debug!("Binary download succeeded");
+                    return Ok(());
+                }
+                Err(err) if !refreshed && is_forbidden(&err) => return Err(err),
+                Err(err) => {
+                    tracing::warn!("Binary download failed: {}", err);
+                    if let Some(ref fallback_url) = play_url {
+                        if should_try_hls_fallback(&err) {
+                            tracing::info!("Attempting HLS fallback from: {}", fallback_url);
+                            self.download_hls_stream(fallback_url, share_url, output_path)
+                                .await?;
+                            self.warn(DownloadWarning::HlsFallback);
+                            return Ok(());
+                        } else {
+                            tracing::warn!("Error not eligible for HLS fallback");
+                        }
+                    } else {
+                        tracing::warn!("No play_url available for HLS fallback");
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        if let Some(url) = play_url {
+            tracing::info!("No download_url, attempting HLS stream from: {}", url);
+            self.warn(DownloadWarning::NoDownloadUrl);
+            self.download_hls_stream(&url, share_url, output_path)
+                .await?;
+            return Ok(());
+        }
+
+        tracing::error!("No download_url or play_url found");
+        Err(Error::VideoUrlNotFound)
+    }
+
+    /// Note a fallback on the current item's report.
+    fn warn(&self, warning: DownloadWarning) {
+        if let Some(warnings) = &self.item_warnings {
+            warnings.push(warning);
+        }
+    }
+
+    /// Apply `--simulate-network` to a request about to be sent.
+    async fn simulate_request(&self) -> Result<()> {
+        #[cfg(feature = "simulate-network")]
+        if let Some(simulation) = &self.config.simulate_network {
+            simulation.before_request().await?;
+        }
+        Ok(())
+    }
+
+    /// Apply `--simulate-network` to a chunk of a response body.
+    fn simulate_chunk(&self) -> Result<()> {
+        #[cfg(feature = "simulate-network")]
+        if let Some(simulation) = &self.config.simulate_network {
+            simulation.before_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Build a GET for CDN media with the configured header strategy applied.
+    fn media_get<U: reqwest::IntoUrl>(&self, url: U, share_url: &str) -> reqwest::RequestBuilder {
+        self.media_request(reqwest::Method::GET, url, share_url)
+    }
+
+    fn media_request<U: reqwest::IntoUrl>(
+        &self,
+        method: reqwest::Method,
+        url: U,
+        share_url: &str,
+    ) -> reqwest::RequestBuilder {
+        self.config
+            .media_headers
+            .header_pairs(share_url)
+            .into_iter()
+            .fold(
+                self.client.request(method, url),
+                |request, (name, value)| request.header(name, value),
+            )
+    }
+
+    /// HEAD the first media URL, falling back to a one-byte GET for CDNs that refuse HEAD.
+    ///
+    /// Returns `None` only when neither request got a response at all.
+    async fn probe_media(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+    ) -> Option<MediaProbe> {
+        let binary = descriptor
+            .download_url
+            .iter()
+            .chain(&descriptor.mirror_urls)
+            .next();
+        let url = binary.or(descriptor.play_url.as_ref())?;
+
+        let head =
+            http_trace::send(self.media_request(reqwest::Method::HEAD, url.as_str(), share_url))
+                .await;
+        let response = match head {
+            Ok(response) if response.status().is_success() => Ok(response),
+            _ => {
+                let request = self
+                    .media_get(url.as_str(), share_url)
+                    .header(reqwest::header::RANGE, "bytes=0-0");
+                http_trace::send(request).await
+            }
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::warn!("Probe of {} failed: {}", url, err);
+                return None;
+            }
+        };
+
+        let mut probe = MediaProbe::from_response(url, response.status(), response.headers());
+        if binary.is_none() {
+            // The playlist's own length says nothing about the video.
+            probe.size = None;
+        }
+        Some(probe)
+    }
+
+    /// Try each binary URL in turn, moving to the next mirror on CDN-side failures.
+    ///
+    /// When every mirror fails and any of them answered 403, that error is returned, so the
+    /// caller refreshes the signed URLs only once no mirror is left to try.
+    async fn download_from_mirrors(
+        &self,
+        candidates: &[String],
+        share_url: &str,
+        output_path: &Path,
+    ) -> Result<()> {
+        let mut last_err = Error::VideoUrlNotFound;
+        let mut forbidden = None;
+        let candidates = self.host_health.rank(candidates);
+
+        for (idx, url) in candidates.iter().enumerate() {
+            tracing::debug!(
+                "Attempting binary download from mirror {}/{}: {}",
+                idx + 1,
+                candidates.len(),
+                url
+            );
+            match self.download_binary(url, share_url, output_path).await {
+                Ok(()) => {
+                    self.host_health.record(url, true);
+                    if idx > 0 {
+                        self.warn(DownloadWarning::MirrorFallback);
+                    }
+                    return Ok(());
+                }
+                Err(err) => {
+                    let next_mirror = should_try_next_mirror(&err);
+                    if next_mirror {
+                        self.host_health.record(url, false);
+                    }
+                    tracing::warn!("Mirror {} failed: {}", idx + 1, err);
+                    if !next_mirror {
+                        return Err(err);
+                    }
+                    if forbidden.is_none() && is_forbidden(&err) {
+                        forbidden = Some(err);
+                    } else {
+                        last_err = err;
+                    }
+                }
+            }
+        }
+
+        Err(forbidden.unwrap_or(last_err))
+    }
+
+    /// Download `url` into `output_path`, resuming a `.part` file kept by an earlier
+    /// attempt when the server answers the range request with the bytes that follow it.
+    async fn download_binary(&self, url: &str, share_url: &str, output_path: &Path) -> Result<()> {
+        let mut resume_from = self.kept_partial_len(output_path).await;
+        let mut response = self.send_media_get(url, share_url, resume_from).await?;
+        if resume_from.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            tracing::info!("Server refused to resume {}; starting over", url);
+            resume_from = None;
+            response = self.send_media_get(url, share_url, None).await?;
+        }
+
+        if let Err(err) = response.error_for_status_ref() {
+            return Err(Error::Network(err));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+
+        if content_type.contains("text/html") {
+            tracing::warn!("Received HTML instead of video from: {}", url);
+            return Err(Error::UnsupportedStream(
+                "Server returned HTML instead of video content".to_string(),
+            ));
+        }
+
+        let resumed = resume_from.filter(|_| response.status() == StatusCode::PARTIAL_CONTENT);
+        let mut file = match resumed {
+            Some(len) => {
+                let start = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(probe::content_range_start);
+                if start != Some(len) {
+                    // Appending would misplace every byte; the next attempt starts over.
+                    let _ = tokio::fs::remove_file(output_path).await;
+                    return Err(Error::UnsupportedStream(format!(
+                        "server resumed {url} at the wrong offset"
+                    )));
+                }
+                tracing::info!("Resuming {} after {} bytes", output_path.display(), len);
+                self.append_output(output_path).await?
+            }
+            // A full response replaces whatever was kept.
+            None => self.create_output(output_path).await?,
+        };
+        let streamed = self.stream_to_file(&mut response, &mut file).await;
+        // Flush even on failure so a kept `.part` file holds everything received.
+        file.flush().await?;
+        streamed?;
+
+        Ok(())
+    }
+
+    async fn download_hls_stream(
+        &self,
+        play_url: &str,
+        share_url: &str,
+        output_path: &Path,
+    ) -> Result<()> {
+        tracing::debug!("Parsing HLS URL: {}", play_url);
+        let playlist_url =
+            Url::parse(play_url).map_err(|_| Error::InvalidUrl(play_url.to_string()))?;
+
+        tracing::debug!("Fetching content from: {}", playlist_url);
+        self.simulate_request().await?;
+        let response = http_trace::send(self.media_get(playlist_url.clone(), share_url))
+            .await?
+            .error_for_status()?;
+
+        // Check if this is actually a video file, not a playlist
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+
+        tracing::debug!("Content-Type: {}", content_type);
+
+        // If it's a direct video file, download it directly
+        if content_type.contains("video/") || content_type.contains("application/octet-stream") {
+            tracing::info!("Detected direct video download (not HLS), downloading binary content");
+            let mut file = self.create_output(output_path).await?;
+
+            let mut response = response;
+            let total_bytes = self.stream_to_file(&mut response, &mut file).await?;
+            file.flush().await?;
+            tracing::info!("Downloaded {} bytes as direct video file", total_bytes);
+            return Ok(());
+        }
+
+        // Otherwise, treat as HLS playlist
+        let playlist_body = response.text().await?;
+        tracing::debug!("Playlist size: {} bytes", playlist_body.len());
+
+        // Sanity check: ensure it looks like a playlist
+        if !playlist_body.trim_start().starts_with("#EXTM3U") {
+            tracing::warn!("Content doesn't start with #EXTM3U, may not be valid HLS playlist");
+            // Try to detect if it's binary data being misinterpreted
+            if playlist_body.starts_with("ftyp")
+                || playlist_body.as_bytes().starts_with(&[0x00, 0x00, 0x00])
+            {
+                tracing::error!("Detected binary video data instead of playlist text");
+                return Err(Error::UnsupportedStream(
+                    "Received binary video data when expecting HLS playlist".to_string(),
+                ));
+            }
+        }
+
+        let master = match Playlist::parse(&playlist_body, &playlist_url)? {
+            Playlist::Master(master) => master,
+            Playlist::Media(media) => {
+                tracing::debug!("Processing media playlist directly");
+                return self
+                    .persist_media_playlist(media, &playlist_url, share_url, output_path)
+                    .await;
+            }
+        };
+
+        tracing::debug!("Detected master playlist, selecting variant");
+        let variant = master
+            .select(self.config.quality)
+            .ok_or(Error::VideoUrlNotFound)?;
+        tracing::info!(
+            "Selected {} variant with bandwidth {} from {} variants: {}",
+            self.config.quality,
+            variant.bandwidth,
+            master.variants.len(),
+            variant.uri
+        );
+        let video = self.fetch_media_playlist(&variant.uri, share_url).await?;
+
+        let Some(audio_url) = master
+            .audio_for(variant)
+            .and_then(|audio| audio.uri.as_ref())
+        else {
+            return self
+                .persist_media_playlist(video, &variant.uri, share_url, output_path)
+                .await;
+        };
+
+        // The variant is video-only; fetch its audio rendition and mux the two.
+        tracing::info!("Variant uses separate audio rendition: {}", audio_url);
+        let audio = self.fetch_media_playlist(audio_url, share_url).await?;
+        let video_path = part_path(output_path, "video");
+        let audio_path = part_path(output_path, "audio");
+        let result = async {
+            // Fetched side by side so live renditions are recorded over the same window.
+            tokio::try_join!(
+                self.persist_media_playlist(video, &variant.uri, share_url, &video_path),
+                self.persist_media_playlist(audio, audio_url, share_url, &audio_path),
+            )?;
+            mux_audio(&self.config.ffmpeg, &video_path, &audio_path, output_path).await
+        }
+        .await;
+        if result.is_ok() || !self.config.keep_partial {
+            for path in [&video_path, &audio_path] {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+        result
+    }
+
+    async fn fetch_media_playlist(&self, url: &Url, share_url: &str) -> Result<MediaPlaylist> {
+        self.simulate_request().await?;
+        let response = http_trace::send(self.media_get(url.clone(), share_url))
+            .await?
+            .error_for_status()?;
+
+        let playlist_body = response.text().await?;
+        tracing::debug!("Variant playlist size: {} bytes", playlist_body.len());
+        match Playlist::parse(&playlist_body, url)? {
+            Playlist::Media(media) => Ok(media),
+            Playlist::Master(_) => Err(Error::UnsupportedStream(
+                "variant playlist is itself a master playlist".into(),
+            )),
+        }
+    }
+
+    /// Write every segment of `playlist` to `output_path`; live playlists (no
+    /// `#EXT-X-ENDLIST`) are reloaded from `playlist_url` and appended to until they end.
+    async fn persist_media_playlist(
+        &self,
+        playlist: MediaPlaylist,
+        playlist_url: &Url,
+        share_url: &str,
+        output_path: &Path,
+    ) -> Result<()> {
+        if playlist.segments.is_empty() && playlist.ended {
+            tracing::error!("No segments found in playlist");
+            return Err(Error::VideoUrlNotFound);
+        }
+
+        tracing::debug!("Creating output file: {:?}", output_path);
+        let mut file = self.create_output(output_path).await?;
+        let mut playlist = playlist;
+        let mut written = 0usize;
+        let mut bytes_written = 0u64;
+        let mut next_sequence = 0u64;
+        let mut last_init: Option<InitSection> = None;
+        let mut last_new_segment = Instant::now();
+
+        loop {
+            let fresh: Vec<_> = playlist
+                .segments
+                .iter()
+                .filter(|segment| segment.sequence >= next_sequence)
+                .collect();
+            let had_fresh = !fresh.is_empty();
+            let known_total = written + fresh.len();
+
+            for segment in fresh {
+                let new_init = segment
+                    .init
+                    .as_ref()
+                    .filter(|&init| last_init.as_ref() != Some(init));
+                if let Some(init) = new_init {
+                    tracing::debug!("Downloading init segment from: {}", init.uri);
+                    bytes_written += self
+                        .write_segment(&init.uri, init.range, share_url, &mut file)
+                        .await?;
+                    last_init = Some(init.clone());
+                }
+
+                written += 1;
+                tracing::debug!("Downloading segment {} from: {}", written, segment.uri);
+                bytes_written += self
+                    .write_segment(&segment.uri, segment.range, share_url, &mut file)
+                    .await?;
+                next_sequence = segment.sequence + 1;
+                self.emit_progress(ProgressEvent::Segment {
+                    url: share_url.to_string(),
+                    index: written,
+                    total: known_total,
+                    bytes: bytes_written,
+                    estimated_bytes: progress::estimate_total(bytes_written, written, known_total),
+                });
+            }
+
+            if playlist.ended {
+                break;
+            }
+
+            let target = playlist.target_duration.unwrap_or(LIVE_DEFAULT_TARGET);
+            if had_fresh {
+                last_new_segment = Instant::now();
+            } else if last_new_segment.elapsed() >= target * LIVE_IDLE_TARGETS {
+                tracing::warn!(
+                    "Live playlist produced no new segments for {}s; keeping what was recorded",
+                    last_new_segment.elapsed().as_secs()
+                );
+                break;
+            }
+            // Reload after one target duration, or half of one if nothing changed.
+            sleep(if had_fresh { target } else { target / 2 }).await;
+            tracing::debug!("Reloading live playlist: {}", playlist_url);
+            playlist = self.fetch_media_playlist(playlist_url, share_url).await?;
+        }
+
+        if written == 0 {
+            tracing::error!("No segments found in playlist");
+            return Err(Error::VideoUrlNotFound);
+        }
+        tracing::info!("Downloaded {} segments successfully", written);
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Append one segment to `file`, resuming with a `Range` request from where a failed
+    /// or short attempt stopped so the segment is neither truncated nor written twice.
+    async fn write_segment(
+        &self,
+        segment_url: &Url,
+        range: Option<ByteRange>,
+        share_url: &str,
+        file: &mut OutputFile,
+    ) -> Result<u64> {
+        let max_segment_retries = self.config.max_retries;
+        let mut attempt = 0;
+        file.flush().await?;
+        let start = file.stream_position().await?;
+
+        loop {
+            match self
+                .fetch_segment(segment_url, range, start, share_url, file)
+                .await
+            {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) => {
+                    attempt += 1;
+                    if attempt > max_segment_retries || !should_retry(&err) {
+                        return Err(err);
+                    }
+                    let backoff_ms = self
+                        .config
+                        .initial_backoff_ms
+                        .saturating_mul(1u64 << (attempt.saturating_sub(1)));
+                    tracing::warn!(
+                        "Segment retry {}/{} after {}ms: {}",
+                        attempt,
+                        max_segment_retries,
+                        backoff_ms,
+                        err
+                    );
+                    sleep(Duration::from_millis(backoff_ms)).await;
+                }
+            }
+        }
+    }
+
+    /// Fetch the rest of a segment whose bytes start at `start` in `file`, returning the
+    /// segment's total length once it is complete.
+    async fn fetch_segment(
+        &self,
+        segment_url: &Url,
+        range: Option<ByteRange>,
+        start: u64,
+        share_url: &str,
+        file: &mut OutputFile,
+    ) -> Result<u64> {
+        file.flush().await?;
+        let mut written = file.stream_position().await? - start;
+        if range.is_some_and(|range| written >= range.length) {
+            return Ok(written);
+        }
+        let resume_from = match range {
+            Some(range) => {
+                let offset = range.offset.checked_add(written).ok_or_else(|| {
+                    Error::UnsupportedStream(format!("invalid byte range for {segment_url}"))
+                })?;
+                Some(
+                    ByteRange {
+                        offset,
+                        length: range.length - written,
+                    }
+                    .header_value(),
+                )
+            }
+            None if written > 0 => Some(format!("bytes={written}-")),
+            None => None,
+        };
+        if written > 0 {
+            tracing::debug!("Resuming segment {} after {} bytes", segment_url, written);
+        }
+
+        let mut request = self.media_get(segment_url.clone(), share_url);
+        if let Some(value) = &resume_from {
+            request = request.header(reqwest::header::RANGE, value);
+        }
+        self.simulate_request().await?;
+        let mut response = http_trace::send(request).await?;
+
+        if let Err(err) = response.error_for_status_ref() {
+            tracing::error!("Segment download failed with status: {:?}", err);
+            return Err(Error::Network(err));
+        }
+        let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        if range.is_some() && !partial {
+            // Writing the whole resource would duplicate every other sub-range.
+            return Err(Error::UnsupportedStream(format!(
+                "server ignored the byte range for {segment_url}"
+            )));
+        }
+        if written > 0 && !partial {
+            // The server sent the whole segment again, so drop the partial copy.
+            file.get_mut().set_len(start).await?;
+            file.seek(std::io::SeekFrom::Start(start)).await?;
+            written = 0;
+        }
+
+        let expected = match range {
+            Some(range) => Some(range.length),
+            None if partial => response
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(probe::content_range_total),
+            None => response.content_length(),
+        };
+        written += self.stream_to_file(&mut response, file).await?;
+
+        if let Some(expected) = expected.filter(|&expected| written < expected) {
+            tracing::warn!(
+                "Segment {} ended after {} of {} bytes",
+                segment_url,
+                written,
+                expected
+            );
+            return Err(Error::Truncated {
+                expected,
+                received: written,
+            });
+        }
+        tracing::debug!("Wrote {} bytes for segment", written);
+        Ok(written)
+    }
+
+    fn emit_progress(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.config.progress {
+            // A dropped receiver just means nobody is watching.
+            let _ = progress.send(event);
+        }
+    }
+
+    async fn create_output(&self, path: &Path) -> Result<OutputFile> {
+        let file = tokio::fs::File::create(path).await?;
+        Ok(BufWriter::with_capacity(
+            self.config.write_buffer_size.max(1),
+            file,
+        ))
+    }
+
+    async fn append_output(&self, path: &Path) -> Result<OutputFile> {
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(BufWriter::with_capacity(
+            self.config.write_buffer_size.max(1),
+            file,
+        ))
+    }
+
+    /// Length of a non-empty `.part` file left at `partial` by a failed attempt, when
+    /// `keep_partial` asks for it to be resumed.
+    async fn kept_partial_len(&self, partial: &Path) -> Option<u64> {
+        if !self.config.keep_partial {
+            return None;
+        }
+        let len = tokio::fs::metadata(partial).await.ok()?.len();
+        (len > 0).then_some(len)
+    }
+
+    /// Send a media GET, asking for the bytes from `resume_from` on when given.
+    async fn send_media_get(
+        &self,
+        url: &str,
+        share_url: &str,
+        resume_from: Option<u64>,
+    ) -> Result<reqwest::Response> {
+        let mut request = self.media_get(url, share_url);
+        if let Some(len) = resume_from {
+            request = request.header(reqwest::header::RANGE, format!("bytes={len}-"));
+        }
+        self.simulate_request().await?;
+        Ok(http_trace::send(request).await?)
+    }
+
+    /// Copy a response body into `file`, aborting if no data arrives within the stall timeout.
+    ///
+    /// Not `tokio::io::copy`: each chunk is also counted, paced and checked for stalls. The
+    /// caller flushes `file`, including after an error so a kept `.part` holds every byte.
+    async fn stream_to_file(
+        &self,
+        response: &mut reqwest::Response,
+        file: &mut OutputFile,
+    ) -> Result<u64> {
+        let mut total = 0u64;
+        let mut monitor = self.config.min_speed.map(ThroughputMonitor::new);
+
+        loop {
+            let chunk = match self.config.stall_timeout {
+                Some(limit) => match timeout(limit, response.chunk()).await {
+                    Ok(chunk) => chunk?,
+                    Err(_) => {
+                        tracing::warn!(
+                            "No data received for {}s, aborting transfer",
+                            limit.as_secs()
+                        );
+                        return Err(Error::Stalled { timeout: limit });
+                    }
+                },
+                None => response.chunk().await?,
+            };
+
+            let Some(chunk) = chunk else {
+                return Ok(total);
+            };
+            self.simulate_chunk()?;
+
+            if total == 0 {
+                if let Some(timer) = &self.item_timer {
+                    timer.bytes_received();
+                }
+            }
+            total += chunk.len() as u64;
+            self.record_bytes(chunk.len());
+            file.write_all(&chunk).await?;
+
+            if let Some(control) = self.control.as_ref() {
+                control.wait_if_paused().await;
+            }
+
+            if let Some(monitor) = monitor.as_mut() {
+                if let Some(rate) = monitor.observe(chunk.len() as u64, Instant::now()) {
+                    tracing::warn!("Throughput {} B/s below minimum, abandoning mirror", rate);
+                    return Err(Error::TooSlow {
+                        bytes_per_sec: rate,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A media file being written, buffered so small chunks do not each cost a syscall.
+type OutputFile = BufWriter<tokio::fs::File>;
+
+/// Tracks transfer throughput over fixed windows to detect slow mirrors.
+struct ThroughputMonitor {
+    min_bytes_per_sec: u64,
+    window_start: Instant,
+    window_bytes: u64,
+}
+
+impl ThroughputMonitor {
+    fn new(min_bytes_per_sec: u64) -> Self {
+        Self {
+            min_bytes_per_sec,
+            window_start: Instant::now(),
+            window_bytes: 0,
+        }
+    }
+
+    /// Record received bytes; returns the measured rate when a full window fell below the minimum.
+    fn observe(&mut self, bytes: u64, now: Instant) -> Option<u64> {
+        self.window_bytes += bytes;
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed < SPEED_WINDOW {
+            return None;
+        }
+
+        let rate = (self.window_bytes as f64 / elapsed.as_secs_f64()) as u64;
+        self.window_start = now;
+        self.window_bytes = 0;
+        (rate < self.min_bytes_per_sec).then_some(rate)
+    }
+}
+
+fn should_try_hls_fallback(err: &Error) -> bool {
+    match err {
+        Error::Network(inner) => {
+            inner.status().is_some_and(|status| {
+                matches!(
+                    status,
+                    StatusCode::FORBIDDEN
+                        | StatusCode::UNAUTHORIZED
+                        | StatusCode::NOT_FOUND
+                        | StatusCode::GONE
+                        | StatusCode::LOCKED
+                        | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS
+                )
+            }) || inner.is_builder()
+        }
+        Error::TooSlow { .. } => true,
+        _ => false,
+    }
+}
+
+/// Errors that point at a bad CDN node rather than the video itself.
+fn should_try_next_mirror(err: &Error) -> bool {
+    match err {
+        Error::Network(inner) => {
+            inner.is_timeout()
+                || inner.is_connect()
+                || inner.status().is_some_and(|status| {
+                    status == StatusCode::FORBIDDEN
+                        || status == StatusCode::NOT_FOUND
+                        || status == StatusCode::GONE
+                        || status.is_server_error()
+                })
+        }
+        Error::Stalled { .. } | Error::TooSlow { .. } => true,
+        _ => false,
+    }
+}
+
+/// Reload interval for live playlists that do not declare `#EXT-X-TARGETDURATION`.
+const LIVE_DEFAULT_TARGET: Duration = Duration::from_secs(6);
+/// Stop recording a live playlist after this many target durations without new segments.
+const LIVE_IDLE_TARGETS: u32 = 3;
+
+/// Write `contents` to `path` through a `.part` file.
+async fn write_via_partial(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let partial = partial_path(path);
+    tokio::fs::write(&partial, contents).await?;
+    tokio::fs::rename(&partial, path).await
+}
+
+/// Whether `path` is a file with something in it, as downloads that finished are.
+async fn has_content(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|meta| meta.len() > 0)
+}
+
+/// Where a download is written until it is complete, e.g. `123.mp4.part`.
+fn partial_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    output_path.with_file_name(name)
+}
+
+/// One stream of a separate-audio download next to its partial file, e.g. `123.mp4.video.part`.
+fn part_path(partial: &Path, stream: &str) -> PathBuf {
+    partial.with_extension(format!("{stream}.part"))
+}
+
+/// Copy the video and audio streams into one file without re-encoding.
+async fn mux_audio(ffmpeg: &Path, video: &Path, audio: &Path, output: &Path) -> Result<()> {
+    let result = tokio::process::Command::new(ffmpeg)
+        .arg("-y")
+        .args(["-loglevel", "error"])
+        .arg("-i")
+        .arg(video)
+        .arg("-i")
+        .arg(audio)
+        .args(["-map", "0:v", "-map", "1:a", "-c", "copy"])
+        // The `.part` output name says nothing about the container.
+        .args(["-f", "mp4"])
+        .arg(output)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await;
+
+    match result {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => Err(Error::Mux(format!(
+            "ffmpeg exited with {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(Error::Mux(format!(
+            "this stream keeps its audio in a separate rendition; install ffmpeg or pass --ffmpeg (tried `{}`)",
+            ffmpeg.display()
+        ))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn build_output_path(descriptor: &VideoDescriptor, config: &DownloadConfig) -> Result<PathBuf> {
+    styled_output_path(descriptor, config, config.filename_style)
+}
+
+/// [`build_output_path`] with default names in `style` rather than the configured one.
+fn styled_output_path(
+    descriptor: &VideoDescriptor,
+    config: &DownloadConfig,
+    style: FilenameStyle,
+) -> Result<PathBuf> {
+    let video = sanitize_component(&descriptor.video_id, config.sanitize);
+    if video.is_empty() {
+        return Err(Error::InvalidUrl("missing video id".into()));
+    }
+    let transliterate = config.transliterate || config.sanitize == SanitizePolicy::StrictAscii;
+    let (dirs, stem) = match &config.output_template {
+        Some(output_template) => {
+            let mut components = output_template.render(|placeholder| match placeholder {
+                Placeholder::Author => descriptor.author.clone(),
+                Placeholder::VideoId => descriptor.video_id.clone(),
+                Placeholder::UploadDate => descriptor
+                    .create_time
+                    .map_or_else(|| "unknown".to_string(), template::upload_date),
+                Placeholder::Desc => naming::title_slug(&descriptor.description, transliterate),
+                Placeholder::Title => naming::caption_title(
+                    &descriptor.description,
+                    &descriptor.video_id,
+                    transliterate,
+                ),
+            });
+            // Parsing guarantees at least one component.
+            let stem = components.pop().unwrap_or_default();
+            (components, stem)
+        }
+        None => {
+            let stem = naming::file_stem(&video, &descriptor.description, style, transliterate);
+            (vec![descriptor.author.clone()], stem)
+        }
+    };
+    let stem = match sanitize_component(&stem, config.sanitize) {
+        stem if stem.is_empty() => video,
+        stem => stem,
+    };
+
+    let mut dir = config.output_dir.clone().unwrap_or_default();
+    let too_long = |path: PathBuf, limit| Error::PathTooLong { path, limit };
+    for raw in &dirs {
+        let name = match sanitize_component(raw, config.sanitize) {
+            name if name.is_empty() => "unknown".to_string(),
+            name => name,
+        };
+        let name = naming::fit_name(&name, raw, config.max_name_length)
+            .ok_or_else(|| too_long(dir.join(&name), config.max_name_length))?;
+        dir.push(name);
+    }
+
+    let suffix = naming::LONGEST_SUFFIX.len();
+    let mut budget = (
+        config.max_name_length.saturating_sub(suffix),
+        config.max_name_length,
+    );
+    if let Some(max_path) = config.max_path_length {
+        // One more byte for the separator before the file name.
+        let room = max_path.saturating_sub(dir.as_os_str().len() + 1 + suffix);
+        if room < budget.0 {
+            budget = (room, max_path);
+        }
+    }
+    let fitted = naming::fit_name(&stem, &descriptor.video_id, budget.0)
+        .ok_or_else(|| too_long(dir.join(format!("{stem}.mp4")), budget.1))?;
+    Ok(dir.join(format!("{fitted}.mp4")))
+}
+
+/// Look for a non-empty download of `video_id` in any other author directory under
+/// `root`, saved under the same name or as `<video id>.mp4`.
+///
+/// Only names that carry `video_id` match, so a same-named file of another video is never
+/// linked over the download.
+async fn find_existing_copy(root: &Path, output_path: &Path, video_id: &str) -> Option<PathBuf> {
+    let file_name = output_path.file_name()?;
+    let id_name = format!("{video_id}.mp4");
+    let mut entries = tokio::fs::read_dir(root).await.ok()?;
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        for name in [file_name, id_name.as_ref()] {
+            let candidate = entry.path().join(name);
+            if candidate == output_path || layout::video_id_of(&candidate) != Some(video_id) {
+                continue;
+            }
+            if let Ok(meta) = tokio::fs::metadata(&candidate).await {
+                if meta.is_file() && meta.len() > 0 {
+                    return Some(candidate);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn sanitize_component(input: &str, policy: SanitizePolicy) -> String {
+    let sanitized: String = match policy {
+        // Filter out characters that are problematic on any filesystem
+        // Also filters Windows-reserved characters: < > : " / \ | ? *
+        SanitizePolicy::StrictAscii => input
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            .collect(),
+        SanitizePolicy::UnicodeSafe => input.chars().filter(|&c| is_portable_char(c)).collect(),
+        SanitizePolicy::WindowsSafe => input
+            .chars()
+            .filter(|&c| is_portable_char(c))
+            .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*'))
+            .collect(),
+    };
+
+    // Strip leading/trailing dots (problematic on Windows and hidden on Unix)
+    let sanitized = match policy {
+        SanitizePolicy::StrictAscii => sanitized.trim_matches('.'),
+        SanitizePolicy::UnicodeSafe => sanitized.trim().trim_start_matches('.'),
+        // Windows silently drops trailing dots and spaces, so names would not round-trip.
+        SanitizePolicy::WindowsSafe => sanitized
+            .trim_start()
+            .trim_start_matches('.')
+            .trim_end_matches(|c: char| c == '.' || c.is_whitespace()),
+    }
+    .to_string();
+    if policy == SanitizePolicy::UnicodeSafe {
+        return sanitized;
+    }
+
+    // Handle Windows-reserved filenames (case-insensitive)
+    // CON, PRN, AUX, NUL, COM1-COM9, LPT1-LPT9
+    let windows_reserved = [
+        "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+        "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+    ];
+
+    let lowercase = sanitized.to_lowercase();
+    if windows_reserved.contains(&lowercase.as_str()) {
+        return format!("_{}", sanitized);
+    }
+
+    sanitized
+}
+
+/// Characters kept by the Unicode policies: anything but path separators, control and
+/// invisible formatting characters, and emoji.
+fn is_portable_char(c: char) -> bool {
+    let emoji = matches!(
+        c,
+        '\u{2300}'..='\u{23ff}'
+            | '\u{2600}'..='\u{27bf}'
+            | '\u{2b00}'..='\u{2bff}'
+            | '\u{3030}'
+            | '\u{303d}'
+            | '\u{1f000}'..='\u{1faff}'
+    );
+    let invisible = matches!(
+        c,
+        '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{206f}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{feff}'
+            | '\u{e0000}'..='\u{e007f}'
+    );
+    !(c == '/' || c.is_control() || emoji || invisible)
+}
+
+fn is_forbidden(err: &Error) -> bool {
+    matches!(err, Error::Network(inner) if inner.status() == Some(StatusCode::FORBIDDEN))
+}
+
+fn is_rate_limited(err: &Error) -> bool {
+    matches!(err, Error::Network(inner) if inner.status() == Some(StatusCode::TOO_MANY_REQUESTS))
+}
+
+fn should_retry(err: &Error) -> bool {
+    match err {
+        Error::Network(inner) => {
+            if inner.is_timeout() || inner.is_connect() || inner.is_body() {
+                return true;
+            }
+
+            if let Some(status) = inner.status() {
+                return status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::FORBIDDEN
+                    || status.is_server_error();
+            }
+
+            true
+        }
+        Error::Io(_) => true,
+        Error::Parsing(_) => true,
+        Error::Stalled { .. } => true,
+        Error::TooSlow { .. } => true,
+        Error::Truncated { .. } => true,
+        Error::SimulatedLoss => true,
+        Error::InvalidUrl(_) => false,
+        Error::InputConflict => false,
+        Error::MissingInput => false,
+        Error::EmptyUrlFile(_) => false,
+        Error::NoChecksums(_) => false,
+        Error::InvalidEntries { .. } => false,
+        Error::VideoUrlNotFound => false,
+        Error::DownloadSummary { .. } => false,
+        Error::UnsupportedStream(_) => false,
+        Error::PathTooLong { .. } => false,
+        Error::Mux(_) => false,
+        Error::InvalidProxy(_) => false,
+        Error::InvalidHeader(_) => false,
+        Error::Cancelled => false,
+        Error::DuplicateOutput { .. } => false,
+        Error::DuplicateOfFailed { .. } => false,
+        Error::Skipped { .. } => false,
+        Error::InvalidBatchLine { .. } => false,
+        Error::LoginRequired(_) => false,
+        Error::Login(_) => false,
+        Error::ByteBudgetExhausted { .. } => false,
+        Error::RuntimeBudgetExhausted { .. } => false,
+        Error::ArchiveQuotaExceeded { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    /// Serve `response` verbatim to each connection, then keep the socket open for `hold`.
+    async fn spawn_stub_server(response: &'static [u8], hold: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let _ = socket.read(&mut buf).await;
+                    let _ = socket.write_all(response).await;
+                    sleep(hold).await;
+                });
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    /// `sanitize_component` under the default policy.
+    fn sanitize_component(input: &str) -> String {
+        super::sanitize_component(input, SanitizePolicy::StrictAscii)
+    }
+
+    #[test]
+    fn sanitize_preserves_alphanumeric() {
+        let id = "abc123-_./!@";
+        assert_eq!(sanitize_component(id), "abc123-_");
+    }
+
+    #[test]
+    fn sanitize_filters_windows_reserved_characters() {
+        // Test that Windows-reserved characters are filtered out
+        assert_eq!(sanitize_component("video<test>"), "videotest");
+        assert_eq!(sanitize_component("file|pipe"), "filepipe");
+        assert_eq!(sanitize_component("path\\test"), "pathtest");
+        assert_eq!(sanitize_component("file:name"), "filename");
+        assert_eq!(sanitize_component("question?mark"), "questionmark");
+        assert_eq!(sanitize_component("aster*isk"), "asterisk");
+        assert_eq!(sanitize_component("quo\"te"), "quote");
+    }
+
+    #[test]
+    fn sanitize_handles_windows_reserved_filenames() {
+        // Test Windows reserved filenames (case-insensitive)
+        assert_eq!(sanitize_component("con"), "_con");
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("Con"), "_Con");
+        assert_eq!(sanitize_component("prn"), "_prn");
+        assert_eq!(sanitize_component("aux"), "_aux");
+        assert_eq!(sanitize_component("nul"), "_nul");
+        assert_eq!(sanitize_component("com1"), "_com1");
+        assert_eq!(sanitize_component("COM5"), "_COM5");
+        assert_eq!(sanitize_component("lpt1"), "_lpt1");
+        assert_eq!(sanitize_component("LPT9"), "_LPT9");
+    }
+
+    #[test]
+    fn sanitize_strips_leading_trailing_dots() {
+        assert_eq!(sanitize_component(".hidden"), "hidden");
+        assert_eq!(sanitize_component("file."), "file");
+        assert_eq!(sanitize_component("...dots..."), "dots");
+        assert_eq!(sanitize_component("."), "");
+    }
+
+    #[test]
+    fn sanitize_allows_safe_filenames() {
+        // Test that safe filenames pass through unchanged
+        assert_eq!(
+            sanitize_component("normal-file.name_123"),
+            "normal-file.name_123"
+        );
+        assert_eq!(sanitize_component("video123"), "video123");
+        assert_eq!(sanitize_component("user.name"), "user.name");
+    }
+
+    #[test]
+    fn unicode_policies_strip_emoji_and_control_characters() {
+        let unicode = |input| super::sanitize_component(input, SanitizePolicy::UnicodeSafe);
+        assert_eq!(unicode("café 🎉 time\u{7}!"), "café  time!");
+        assert_eq!(unicode("ไปกิน\u{200d}/ครับ"), "ไปกินครับ");
+        assert_eq!(unicode(".hidden what?"), "hidden what?");
+        assert_eq!(unicode("con"), "con");
+
+        let windows = |input| super::sanitize_component(input, SanitizePolicy::WindowsSafe);
+        assert_eq!(windows("what? <yes>: a|b"), "what yes ab");
+        assert_eq!(windows("ends with dots.. "), "ends with dots");
+        assert_eq!(windows("CON"), "_CON");
+        assert_eq!(windows("ไปกิน 🎉"), "ไปกิน");
+    }
+
+    #[test]
+    fn build_output_path_sanitizes_components() {
+        let descriptor = VideoDescriptor {
+            video_id: "video!@#".into(),
+            download_url: Some("https://example.com".into()),
+            mirror_urls: Vec::new(),
+            play_url: None,
+            author: "@user name".into(),
+            ..VideoDescriptor::default()
+        };
+
+        let path = build_output_path(&descriptor, &DownloadConfig::default()).unwrap();
+        assert_eq!(path, PathBuf::from("username/video.mp4"));
+    }
+
+    #[test]
+    fn build_output_path_with_output_dir() {
+        let descriptor = VideoDescriptor {
+            video_id: "123".into(),
+            download_url: Some("https://example.com".into()),
+            mirror_urls: Vec::new(),
+            play_url: None,
+            author: "user".into(),
+            ..VideoDescriptor::default()
+        };
+
+        let config = DownloadConfig {
+            output_dir: Some(PathBuf::from("/tmp/videos")),
+            ..DownloadConfig::default()
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        assert_eq!(path, PathBuf::from("/tmp/videos/user/123.mp4"));
+    }
+
+    #[test]
+    fn build_output_path_uses_the_filename_style() {
+        let descriptor = VideoDescriptor {
+            video_id: "123".into(),
+            author: "user".into(),
+            description: "ไปกิน! #food".into(),
+            ..VideoDescriptor::default()
+        };
+        let config = DownloadConfig {
+            filename_style: FilenameStyle::TitleId,
+            sanitize: SanitizePolicy::UnicodeSafe,
+            ..DownloadConfig::default()
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        assert_eq!(path, PathBuf::from("user/ไปกิน-123.mp4"));
+
+        let config = DownloadConfig {
+            transliterate: true,
+            ..config
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        assert_eq!(path, PathBuf::from("user/paikin-123.mp4"));
+
+        // Strict ASCII names can't hold Thai, so the caption is romanized regardless.
+        let config = DownloadConfig {
+            transliterate: false,
+            sanitize: SanitizePolicy::StrictAscii,
+            ..config
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        assert_eq!(path, PathBuf::from("user/paikin-123.mp4"));
+    }
+
+    #[test]
+    fn build_output_path_renders_the_output_template() {
+        let descriptor = VideoDescriptor {
+            video_id: "123".into(),
+            author: "@user name".into(),
+            description: "Morning run! #dog".into(),
+            create_time: Some(1_735_689_600),
+            ..VideoDescriptor::default()
+        };
+        let template = |template| DownloadConfig {
+            output_dir: Some(PathBuf::from("/tmp/videos")),
+            output_template: Some(OutputTemplate::parse(template).unwrap()),
+            ..DownloadConfig::default()
+        };
+        let path = build_output_path(
+            &descriptor,
+            &template("tiktok/{author}/{upload_date}_{desc}_{video_id}"),
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("/tmp/videos/tiktok/username/20250101_morning-run_123.mp4")
+        );
+
+        // Names that come out empty fall back like default paths do.
+        let descriptor = VideoDescriptor {
+            author: "@@".into(),
+            description: String::new(),
+            create_time: None,
+            ..descriptor
+        };
+        let path = build_output_path(
+            &descriptor,
+            &template("{author}/{upload_date}/{desc}{video_id}"),
+        )
+        .unwrap();
+        assert_eq!(path, PathBuf::from("/tmp/videos/unknown/unknown/123.mp4"));
+    }
+
+    #[test]
+    fn build_output_path_shortens_names_over_the_limits() {
+        let descriptor = VideoDescriptor {
+            video_id: "7551290370794016007".into(),
+            author: "user".into(),
+            description: "word ".repeat(40),
+            ..VideoDescriptor::default()
+        };
+        let config = DownloadConfig {
+            filename_style: FilenameStyle::TitleId,
+            max_name_length: 60,
+            ..DownloadConfig::default()
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("word-word-"));
+        assert_eq!(name.len() + ".video.part".len(), 60);
+
+        let config = DownloadConfig {
+            output_dir: Some(PathBuf::from("/deep/archive")),
+            max_path_length: Some(50),
+            ..config
+        };
+        let path = build_output_path(&descriptor, &config).unwrap();
+        assert_eq!(path.as_os_str().len() + ".video.part".len(), 50);
+        assert!(path.starts_with("/deep/archive/user"));
+
+        let config = DownloadConfig {
+            max_path_length: Some(30),
+            ..config
+        };
+        assert!(matches!(
+            build_output_path(&descriptor, &config),
+            Err(Error::PathTooLong { limit: 30, .. })
+        ));
+    }
+
+    #[test]
+    fn find_existing_copy_matches_other_author_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        let existing = temp.path().join("old_handle").join("123.mp4");
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        std::fs::write(&existing, b"video").unwrap();
+
+        let rt = Runtime::new().unwrap();
+        let find = |target: &Path, video_id| {
+            rt.block_on(find_existing_copy(temp.path(), target, video_id))
+        };
+        let target = temp.path().join("new_handle").join("123.mp4");
+        assert_eq!(find(&target, "123"), Some(existing.clone()));
+        // A `title-id` name finds the copy saved under the bare ID.
+        let titled = temp.path().join("new_handle").join("morning-run-123.mp4");
+        assert_eq!(find(&titled, "123"), Some(existing.clone()));
+
+        let missing = temp.path().join("new_handle").join("456.mp4");
+        assert_eq!(find(&missing, "456"), None);
+        assert_eq!(find(&existing, "123"), None);
+
+        // The same caption on another video is not a copy.
+        let other = temp.path().join("old_handle").join("morning-run-456.mp4");
+        std::fs::write(&other, b"other video").unwrap();
+        let clash = temp.path().join("new_handle").join("morning-run-456.mp4");
+        assert_eq!(find(&clash, "789"), None);
+    }
+
+    #[test]
+    fn byte_budget_blocks_new_downloads() {
+        let client = build_http_client().unwrap();
+        let config = DownloadConfig {
+            max_total_bytes: Some(10),
+            ..DownloadConfig::default()
+        };
+        let downloader = Downloader::with_client_and_config(client, config);
+        assert!(downloader.ensure_byte_budget().is_ok());
+
+        downloader.clone().record_bytes(10);
+        assert_eq!(downloader.bytes_downloaded(), 10);
+        assert!(matches!(
+            downloader.ensure_byte_budget(),
+            Err(Error::ByteBudgetExhausted { limit: 10 })
+        ));
+    }
+
+    #[test]
+    fn runtime_budget_blocks_new_downloads() {
+        let client = build_http_client().unwrap();
+        let config = DownloadConfig {
+            max_runtime: Some(Duration::ZERO),
+            ..DownloadConfig::default()
+        };
+        let downloader = Downloader::with_client_and_config(client, config);
+
+        let rt = Runtime::new().unwrap();
+        let err = rt
+            .block_on(downloader.download_one("https://www.tiktok.com/@user/video/1"))
+            .unwrap_err();
+        assert!(matches!(err, Error::RuntimeBudgetExhausted { .. }));
+    }
+
+    #[test]
+    fn stalled_transfer_is_aborted() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let base = spawn_stub_server(
+                b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 100\r\n\r\nabc",
+                Duration::from_secs(5),
+            )
+            .await;
+            let config = DownloadConfig {
+                stall_timeout: Some(Duration::from_millis(200)),
+                ..DownloadConfig::default()
+            };
+            let downloader =
+                Downloader::with_client_and_config(build_http_client().unwrap(), config);
+            let temp = tempfile::tempdir().unwrap();
+
+            let err = downloader
+                .download_binary(&base, "https://www.tiktok.com/", &temp.path().join("v.mp4"))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::Stalled { .. }));
+            assert!(should_retry(&err));
+        });
+    }
+
+    #[test]
+    fn truncated_segment_resumes_from_written_offset() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url =
+                Url::parse(&format!("http://{}/seg.ts", listener.local_addr().unwrap())).unwrap();
+            let server = tokio::spawn(async move {
+                let responses: [&[u8]; 2] = [
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\nhello",
+                    b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\n\
+                      Content-Length: 5\r\nConnection: close\r\n\r\nworld",
+                ];
+                let mut requests = Vec::new();
+                for response in responses {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let mut buf = [0u8; 4096];
+                    let read = socket.read(&mut buf).await.unwrap();
+                    requests.push(String::from_utf8_lossy(&buf[..read]).to_lowercase());
+                    socket.write_all(response).await.unwrap();
+                }
+                requests
+            });
+
+            let config = DownloadConfig {
+                initial_backoff_ms: 1,
+                ..DownloadConfig::default()
+            };
+            let downloader =
+                Downloader::with_client_and_config(build_http_client().unwrap(), config);
+            let temp = tempfile::tempdir().unwrap();
+            let path = temp.path().join("out.ts");
+            let mut file = downloader.create_output(&path).await.unwrap();
+            file.write_all(b">").await.unwrap();
+
+            let bytes = downloader
+                .write_segment(&url, None, "https://www.tiktok.com/", &mut file)
+                .await
+                .unwrap();
+            file.flush().await.unwrap();
+            assert_eq!(bytes, 10);
+            assert_eq!(std::fs::read(&path).unwrap(), b">helloworld");
+
+            let requests = server.await.unwrap();
+            assert!(!requests[0].contains("range:"));
+            assert!(requests[1].contains("range: bytes=5-"));
+        });
+    }
+
+    #[test]
+    fn throughput_monitor_flags_slow_windows() {
+        let start = Instant::now();
+        let mut monitor = ThroughputMonitor {
+            min_bytes_per_sec: 1000,
+            window_start: start,
+            window_bytes: 0,
+        };
+
+        assert_eq!(monitor.observe(100, start + Duration::from_secs(1)), None);
+        assert_eq!(monitor.observe(400, start + SPEED_WINDOW), Some(100));
+
+        let next = start + SPEED_WINDOW;
+        assert_eq!(monitor.observe(10_000, next + SPEED_WINDOW), None);
+    }
+
+    #[test]
+    fn slow_binary_download_falls_back_to_alternate() {
+        let err = Error::TooSlow { bytes_per_sec: 10 };
+        assert!(should_try_hls_fallback(&err));
+    }
+
+    #[test]
+    fn mirrors_are_tried_after_forbidden_response() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let forbidden = spawn_stub_server(
+                b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n",
+                Duration::ZERO,
+            )
+            .await;
+            let healthy = spawn_stub_server(
+                b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 5\r\n\r\nvideo",
+                Duration::ZERO,
+            )
+            .await;
+            let downloader = Downloader::with_client(build_http_client().unwrap());
+            let temp = tempfile::tempdir().unwrap();
+            let output = temp.path().join("v.mp4");
+
+            downloader
+                .download_from_mirrors(&[forbidden, healthy], "https://www.tiktok.com/", &output)
+                .await
+                .unwrap();
+            assert_eq!(std::fs::read(&output).unwrap(), b"video");
+        });
+    }
+
+    #[test]
+    fn media_header_presets() {
+        let share = "https://www.tiktok.com/@user/video/1";
+        let names = |headers: &MediaHeaders| -> Vec<(String, String)> {
+            headers
+                .header_pairs(share)
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect()
+        };
+
+        assert_eq!(
+            names(&MediaHeaders::default()),
+            vec![("referer".to_string(), share.to_string())]
+        );
+        assert_eq!(
+            names(&MediaHeaders {
+                preset: MediaHeaderPreset::Site,
+                ..MediaHeaders::default()
+            }),
+            vec![
+                ("referer".to_string(), "https://www.tiktok.com/".to_string()),
+                ("origin".to_string(), "https://www.tiktok.com".to_string()),
+            ]
+        );
+        assert_eq!(
+            names(&MediaHeaders {
+                preset: MediaHeaderPreset::None,
+                cookie: Some("tt_chain_token=abc".into()),
+                ..MediaHeaders::default()
+            }),
+            vec![("cookie".to_string(), "tt_chain_token=abc".to_string())]
+        );
+        assert_eq!(
+            names(&MediaHeaders {
+                referer: Some("https://example.com/".into()),
+                ..MediaHeaders::default()
+            }),
+            vec![("referer".to_string(), "https://example.com/".to_string())]
+        );
+    }
+
+    #[test]
+    fn separate_proxies_build_clients() {
+        let config = DownloadConfig {
+            scrape_proxy: Some("http://127.0.0.1:8080".into()),
+            ..DownloadConfig::default()
+        };
+        assert!(Downloader::with_config(config).is_ok());
+
+        let config = DownloadConfig {
+            media_proxy: Some("not a proxy url".into()),
+            ..DownloadConfig::default()
+        };
+        assert!(matches!(
+            Downloader::with_config(config),
+            Err(Error::InvalidProxy(_))
+        ));
+    }
+
+    #[test]
+    fn request_overrides_validate_headers() {
+        let downloader = Downloader::with_client(build_http_client().unwrap());
+        let request = DownloadRequest::new("https://www.tiktok.com/@user/video/1")
+            .quality(Quality::Worst)
+            .header("X-Custom", "yes");
+        let derived = downloader.for_request(&request).unwrap();
+        assert_eq!(derived.config.quality, Quality::Worst);
+        assert_eq!(
+            derived.config.media_headers.extra.get("x-custom").unwrap(),
+            "yes"
+        );
+
+        let invalid = DownloadRequest::new("https://www.tiktok.com/").header("bad header", "x");
+        assert!(matches!(
+            downloader.for_request(&invalid),
+            Err(Error::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn cancelled_request_does_not_start() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let downloader = Downloader::with_client(build_http_client().unwrap());
+            let token = CancellationToken::new();
+            token.cancel();
+
+            let request =
+                DownloadRequest::new("https://www.tiktok.com/@user/video/1").cancellation(token);
+            let err = downloader.download_request(&request).await.unwrap_err();
+            assert!(matches!(err, Error::Cancelled));
+        });
+    }
+
+    #[test]
+    fn cancellation_mid_transfer_removes_partial_file() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let base = spawn_stub_server(
+                b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 100\r\n\r\nabc",
+                Duration::from_secs(5),
+            )
+            .await;
+            let downloader = Downloader::with_client(build_http_client().unwrap());
+            let temp = tempfile::tempdir().unwrap();
+            let output = temp.path().join("v.mp4");
+            let descriptor = VideoDescriptor {
+                video_id: "1".into(),
+                download_url: Some(base),
+                mirror_urls: Vec::new(),
+                play_url: None,
+                author: "user".into(),
+                ..VideoDescriptor::default()
+            };
+
+            let token = downloader.cancellation_token();
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(200)).await;
+                token.cancel();
+            });
+
+            let err = downloader
+                .download_media(&descriptor, "https://www.tiktok.com/", &output)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::Cancelled));
+            assert!(!output.exists());
+            assert!(!partial_path(&output).exists());
+        });
+    }
+
+    #[test]
+    fn keep_partial_leaves_part_file_on_failure() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            // Promises more body than it sends, so the transfer fails mid-way.
+            let base = spawn_stub_server(
+                b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 100\r\n\r\nabc",
+                Duration::ZERO,
+            )
+            .await;
+            let config = DownloadConfig {
+                keep_partial: true,
+                ..DownloadConfig::default()
+            };
+            let downloader =
+                Downloader::with_client_and_config(build_http_client().unwrap(), config);
+            let temp = tempfile::tempdir().unwrap();
+            let output = temp.path().join("v.mp4");
+            let descriptor = VideoDescriptor {
+                video_id: "1".into(),
+                download_url: Some(base),
+                mirror_urls: Vec::new(),
+                play_url: None,
+                author: "user".into(),
+                ..VideoDescriptor::default()
+            };
+
+            downloader
+                .download_media(&descriptor, "https://www.tiktok.com/", &output)
+                .await
+                .unwrap_err();
+            assert!(!output.exists());
+            assert_eq!(std::fs::read(partial_path(&output)).unwrap(), b"abc");
+        });
+    }
+
+    #[test]
+    fn forbidden_download_url_falls_back_to_mirrors_before_rescraping() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let forbidden = spawn_stub_server(
+                b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                Duration::ZERO,
+            )
+            .await;
+            let missing = spawn_stub_server(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                Duration::ZERO,
+            )
+            .await;
+            let mirror = spawn_stub_server(
+                b"HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc",
+                Duration::ZERO,
+            )
+            .await;
+            let downloader = Downloader::with_client(build_http_client().unwrap());
+            let temp = tempfile::tempdir().unwrap();
+            let output = temp.path().join("v.mp4");
+            let descriptor = VideoDescriptor {
+                video_id: "1".into(),
+                download_url: Some(format!("{forbidden}/download")),
+                mirror_urls: vec![format!("{mirror}/mirror")],
+                author: "user".into(),
+                ..VideoDescriptor::default()
+            };
+
+            // A rescrape of this share URL would fail, so success means no refresh.
+            downloader
+                .transfer_from(&descriptor, "invalid", &output, false)
+                .await
+                .unwrap();
+            assert_eq!(std::fs::read(&output).unwrap(), b"abc");
+
+            // With no mirror left, the 403 is what decides whether to refresh.
+            let descriptor = VideoDescriptor {
+                mirror_urls: vec![format!("{missing}/mirror")],
+                ..descriptor
+            };
+            let err = downloader
+                .transfer_from(&descriptor, "invalid", &output, false)
+                .await
+                .unwrap_err();
+            assert!(is_forbidden(&err), "{err}");
+        });
+    }
+
+    #[test]
+    fn stream_parts_sit_next_to_partial_file() {
+        let partial = partial_path(Path::new("user/1.mp4"));
+        assert_eq!(partial, Path::new("user/1.mp4.part"));
+        assert_eq!(
+            part_path(&partial, "audio"),
+            Path::new("user/1.mp4.audio.part")
+        );
+    }
+
+    #[test]
+    fn download_stream_respects_pause_and_priority() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config = DownloadConfig {
+                max_concurrent_downloads: 1,
+                ..DownloadConfig::default()
+            };
+            let downloader =
+                Downloader::with_client_and_config(build_http_client().unwrap(), config);
+            let requests = ["invalid-a", "invalid-b", "invalid-c"]
+                .into_iter()
+                .map(DownloadRequest::new)
+                .collect();
+
+            let (control, reports) = downloader.download_all_stream(requests);
+            futures::pin_mut!(reports);
+
+            control.pause();
+            assert!(timeout(Duration::from_millis(100), reports.next())
+                .await
+                .is_err());
+
+            assert!(control.prioritize("invalid-c"));
+            control.resume();
+            let (idx, report) = reports.next().await.unwrap();
+            assert_eq!((idx, report.url.as_str()), (2, "invalid-c"));
+
+            let rest: Vec<usize> = reports.map(|(idx, _)| idx).collect().await;
+            assert_eq!(rest, vec![0, 1]);
+        });
+    }
+
+    #[test]
+    fn author_counts_stop_at_the_limit_until_released() {
+        let counts = AuthorCounts::default();
+        assert!(counts.claim("chef", 2));
+        assert!(counts.clone().claim("Chef", 2));
+        assert!(!counts.claim("chef", 2));
+        assert!(counts.claim("baker", 2));
+        counts.release("CHEF");
+        assert!(counts.claim("chef", 2));
+        assert!(!counts.claim("chef", 2));
+    }
+
+    #[test]
+    fn output_claims_detect_other_urls() {
+        let claims = OutputClaims::default();
+        let path = Path::new("user/1.mp4");
+
+        assert_eq!(claims.claim(path, "https://vt.tiktok.com/a/", "1"), None);
+        assert_eq!(claims.claim(path, "https://vt.tiktok.com/a/", "1"), None);
+        assert_eq!(
+            claims.claim(path, "https://vt.tiktok.com/b/", "2"),
+            Some(Claimant {
+                url: "https://vt.tiktok.com/a/".to_string(),
+                video_id: "1".to_string(),
+            })
+        );
+
+        claims.release(path, "https://vt.tiktok.com/a/");
+        assert_eq!(claims.claim(path, "https://vt.tiktok.com/b/", "2"), None);
+    }
+
+    #[test]
+    fn output_claims_wait_for_the_first_item_to_finish() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let claims = OutputClaims::default();
+            let first = "https://vt.tiktok.com/a/";
+
+            let pending = timeout(Duration::from_millis(20), claims.outcome_of(first)).await;
+            assert!(pending.is_err());
+
+            let waiter = tokio::spawn({
+                let claims = claims.clone();
+                async move { claims.outcome_of(first).await }
+            });
+            tokio::task::yield_now().await;
+            claims.finish(first, Outcome::Failed);
+            assert_eq!(waiter.await.unwrap(), Outcome::Failed);
+            assert_eq!(claims.outcome_of(first).await, Outcome::Failed);
+        });
+    }
+
+    #[test]
+    fn duplicate_outcome_is_reported_as_a_skip() {
+        let report = DownloadReport::from_outcome(
+            "https://vt.tiktok.com/b/".into(),
+            Err(Error::DuplicateOutput {
+                path: PathBuf::from("user/1.mp4"),
+                first_url: "https://vt.tiktok.com/a/".into(),
+            }),
+        );
+        assert!(!report.is_success());
+        assert!(report.is_skipped());
+        assert_eq!(report.outcome(), Outcome::Skipped(SkipReason::Duplicate));
+        assert_eq!(report.path(), Some(&PathBuf::from("user/1.mp4")));
+        assert_eq!(
+            report.duplicate_of.as_deref(),
+            Some("https://vt.tiktok.com/a/")
+        );
+    }
+
+    #[test]
+    fn download_all_accumulates_errors() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let client = build_http_client().unwrap();
+            let downloader = Downloader::with_client(client);
+
+            let urls = vec![
+                "not-a-tiktok-url".to_string(),
+                "https://example.com/".to_string(),
+            ];
+
+            let reports = downloader.download_all(&urls).await;
+            assert_eq!(reports.len(), 2);
+            assert!(reports.iter().all(|r| r.result.is_err()));
+        });
+    }
+
+    #[test]
+    fn author_slots_limit_each_creator_separately() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let slots = AuthorSlots::default();
+            let first = slots.acquire("Creator", 1).await;
+
+            let same = timeout(Duration::from_millis(20), slots.acquire("creator", 1)).await;
+            assert!(
+                same.is_err(),
+                "second transfer for the same creator must wait"
+            );
+            let other = timeout(Duration::from_millis(20), slots.acquire("someone_else", 1)).await;
+            assert!(other.is_ok_and(|permit| permit.is_some()));
+
+            drop(first);
+            let freed = timeout(Duration::from_millis(20), slots.acquire("creator", 1)).await;
+            assert!(freed.is_ok());
+        });
+    }
+}
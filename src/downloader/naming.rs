@@ -0,0 +1,328 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use super::FilenameStyle;
+
+/// Longest title slug kept in a file name, in bytes, so Thai or other multi-byte captions
+/// still leave room for the video ID and extension within common 255-byte limits.
+const MAX_TITLE_BYTES: usize = 100;
+
+/// File stem for a video whose sanitized ID is `video_id`. Styles that use the caption
+/// fall back to the ID when the caption has nothing usable left after slugification.
+pub(crate) fn file_stem(
+    video_id: &str,
+    description: &str,
+    style: FilenameStyle,
+    transliterate: bool,
+) -> String {
+    let title = match style {
+        FilenameStyle::Id => return video_id.to_string(),
+        FilenameStyle::Title | FilenameStyle::TitleId => title_slug(description, transliterate),
+    };
+    match style {
+        _ if title.is_empty() => video_id.to_string(),
+        FilenameStyle::TitleId => format!("{title}-{video_id}"),
+        _ => title,
+    }
+}
+
+/// Suffix of the longest name derived from an output path, the `<stem>.mp4.video.part`
+/// of a separate-audio download.
+pub(crate) const LONGEST_SUFFIX: &str = ".mp4.video.part";
+
+/// Shorten `name` to at most `max_bytes`, replacing the cut tail with a hash of `key` so
+/// names that share a long prefix stay distinct. `None` if not even the hash fits.
+pub(crate) fn fit_name(name: &str, key: &str, max_bytes: usize) -> Option<String> {
+    if name.len() <= max_bytes {
+        return Some(name.to_string());
+    }
+    let hash = short_hash(key);
+    if max_bytes < hash.len() {
+        return None;
+    }
+
+    let mut keep = max_bytes.saturating_sub(hash.len() + 1);
+    while !name.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    let prefix =
+        name[..keep].trim_end_matches(|c: char| matches!(c, '-' | '_' | '.') || c.is_whitespace());
+    Some(if prefix.is_empty() {
+        hash
+    } else {
+        format!("{prefix}-{hash}")
+    })
+}
+
+/// Whether `name` is what [`fit_name`] leaves of a longer name hashed with `key`.
+pub(crate) fn is_shortened_for(name: &str, key: &str) -> bool {
+    let hash = short_hash(key);
+    name == hash
+        || name
+            .strip_suffix(&hash)
+            .is_some_and(|prefix| prefix.ends_with('-'))
+}
+
+/// Eight hex digits of FNV-1a, which unlike `DefaultHasher` is stable across Rust
+/// releases, so a shortened name comes out the same on every run.
+fn short_hash(key: &str) -> String {
+    let hash = key.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    format!("{hash:08x}")
+}
+
+/// Caption reduced to lowercase words joined by `-`, without hashtags or mentions.
+///
+/// Without `transliterate`, letters and digits of any script are kept; with it, Thai is
+/// romanized, accents are folded away, and anything else that is not ASCII is dropped.
+pub(crate) fn title_slug(description: &str, transliterate: bool) -> String {
+    let words = description
+        .split_whitespace()
+        .filter(|word| !word.starts_with('#') && !word.starts_with('@'))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = if transliterate {
+        to_ascii(&words)
+    } else {
+        words
+    };
+
+    let mut slug = String::new();
+    for word in text
+        // Marks count as part of a word: Thai vowels and tones are combining marks.
+        .split(|c: char| !c.is_alphanumeric() && !is_combining_mark(c))
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        let separator = usize::from(!slug.is_empty());
+        if slug.len() + separator + word.len() > MAX_TITLE_BYTES {
+            if slug.is_empty() {
+                // A single huge word: cut it at a character boundary.
+                for c in word.chars() {
+                    if slug.len() + c.len_utf8() > MAX_TITLE_BYTES {
+                        break;
+                    }
+                    slug.push(c);
+                }
+            }
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug
+}
+
+/// Caption on one line: whitespace runs become a single space, and an empty caption
+/// becomes `TikTok video #<id>` as in yt-dlp.
+pub(crate) fn caption_line(description: &str, video_id: &str, transliterate: bool) -> String {
+    let text = if transliterate {
+        to_ascii(description)
+    } else {
+        description.to_string()
+    };
+    let title = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if title.is_empty() {
+        format!("TikTok video #{video_id}")
+    } else {
+        title
+    }
+}
+
+/// [`caption_line`] for `{title}`, cut to 100 bytes at a character boundary so the rest
+/// of the file name, such as the video ID, fits without the name being hashed.
+pub(crate) fn caption_title(description: &str, video_id: &str, transliterate: bool) -> String {
+    let mut title = caption_line(description, video_id, transliterate);
+    if title.len() > MAX_TITLE_BYTES {
+        let mut end = MAX_TITLE_BYTES;
+        while !title.is_char_boundary(end) {
+            end -= 1;
+        }
+        title.truncate(end);
+        title.truncate(title.trim_end().len());
+    }
+    title
+}
+
+/// Romanize Thai and fold other text to ASCII, dropping what has no Latin equivalent.
+fn to_ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    // Thai writes these vowels before the consonant they follow in speech.
+    let mut leading_vowel: Option<&str> = None;
+
+    for c in text.chars() {
+        if let Some(latin) = thai_leading_vowel(c) {
+            if let Some(pending) = leading_vowel.replace(latin) {
+                out.push_str(pending);
+            }
+            continue;
+        }
+        if let Some(latin) = thai_to_latin(c) {
+            out.push_str(latin);
+            if thai_is_consonant(c) {
+                if let Some(pending) = leading_vowel.take() {
+                    out.push_str(pending);
+                }
+            }
+            continue;
+        }
+        if let Some(pending) = leading_vowel.take() {
+            out.push_str(pending);
+        }
+        // Thai is romanized above rather than decomposed, since NFKD splits `ำ` in two;
+        // the accents NFKD splits off Latin letters are dropped.
+        for c in std::iter::once(c).nfkd() {
+            if c.is_ascii() {
+                out.push(c);
+            } else if !is_combining_mark(c) {
+                // Keep word boundaries where unsupported scripts are dropped.
+                out.push(' ');
+            }
+        }
+    }
+    if let Some(pending) = leading_vowel {
+        out.push_str(pending);
+    }
+    out
+}
+
+fn thai_leading_vowel(c: char) -> Option<&'static str> {
+    Some(match c {
+        'เ' => "e",
+        'แ' => "ae",
+        'โ' => "o",
+        'ใ' | 'ไ' => "ai",
+        _ => return None,
+    })
+}
+
+fn thai_is_consonant(c: char) -> bool {
+    ('ก'..='ฮ').contains(&c)
+}
+
+/// Simplified RTGS romanization of single Thai characters; tone marks map to nothing.
+fn thai_to_latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ก' => "k",
+        'ข' | 'ฃ' | 'ค' | 'ฅ' | 'ฆ' => "kh",
+        'ง' => "ng",
+        'จ' | 'ฉ' | 'ช' | 'ฌ' => "ch",
+        'ซ' | 'ศ' | 'ษ' | 'ส' => "s",
+        'ญ' | 'ย' => "y",
+        'ฎ' | 'ด' => "d",
+        'ฏ' | 'ต' => "t",
+        'ฐ' | 'ฑ' | 'ฒ' | 'ถ' | 'ท' | 'ธ' => "th",
+        'ณ' | 'น' => "n",
+        'บ' => "b",
+        'ป' => "p",
+        'ผ' | 'พ' | 'ภ' => "ph",
+        'ฝ' | 'ฟ' => "f",
+        'ม' => "m",
+        'ร' => "r",
+        'ฤ' => "rue",
+        'ล' | 'ฬ' => "l",
+        'ฦ' => "lue",
+        'ว' => "w",
+        'ห' | 'ฮ' => "h",
+        'อ' => "o",
+        'ะ' | 'ั' | 'า' => "a",
+        'ำ' => "am",
+        'ิ' | 'ี' => "i",
+        'ึ' | 'ื' => "ue",
+        'ุ' | 'ู' => "u",
+        'ๅ' | '็' | '่' | '้' | '๊' | '๋' | '์' | 'ํ' | 'ๆ' | 'ฯ' => "",
+        '๐' => "0",
+        '๑' => "1",
+        '๒' => "2",
+        '๓' => "3",
+        '๔' => "4",
+        '๕' => "5",
+        '๖' => "6",
+        '๗' => "7",
+        '๘' => "8",
+        '๙' => "9",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_drop_hashtags_and_punctuation() {
+        assert_eq!(
+            title_slug("Morning run!! #fitness #fyp with @friend", false),
+            "morning-run-with"
+        );
+        assert_eq!(title_slug("#fyp #viral", false), "");
+        assert_eq!(title_slug("กินข้าว กัน", false), "กินข้าว-กัน");
+    }
+
+    #[test]
+    fn transliteration_romanizes_thai_and_folds_accents() {
+        assert_eq!(title_slug("สวัสดี", true), "swasdi");
+        assert_eq!(title_slug("ไปกิน ๒๐๒๔", true), "paikin-2024");
+        assert_eq!(title_slug("Café Crème", true), "cafe-creme");
+        assert_eq!(title_slug("日本 trip", true), "trip");
+    }
+
+    #[test]
+    fn titles_keep_the_caption_on_one_line() {
+        assert_eq!(
+            caption_title("  Morning run!!\n#fitness  #fyp ", "123", false),
+            "Morning run!! #fitness #fyp"
+        );
+        assert_eq!(caption_title("ไปกิน ๒๐๒๔", "123", true), "paikin 2024");
+        assert_eq!(caption_title(" \n", "123", false), "TikTok video #123");
+
+        let long = format!("{} ข้าวซอย", "a".repeat(95));
+        assert_eq!(
+            caption_title(&long, "123", false),
+            format!("{} ข", "a".repeat(95))
+        );
+        assert_eq!(
+            caption_title(&format!("{} tail", "a".repeat(99)), "123", false),
+            "a".repeat(99)
+        );
+        assert_eq!(caption_line(&long, "123", false), long);
+    }
+
+    #[test]
+    fn styles_fall_back_to_the_id() {
+        let stem = |description, style| file_stem("123", description, style, false);
+        assert_eq!(stem("Hello world", FilenameStyle::Id), "123");
+        assert_eq!(stem("Hello world", FilenameStyle::Title), "hello-world");
+        assert_eq!(
+            stem("Hello world", FilenameStyle::TitleId),
+            "hello-world-123"
+        );
+        assert_eq!(stem("#only #tags", FilenameStyle::TitleId), "123");
+    }
+
+    #[test]
+    fn long_names_are_cut_and_hashed() {
+        assert_eq!(fit_name("short", "1", 10).as_deref(), Some("short"));
+
+        let name = "a-very-long-caption-slug-123";
+        let first = fit_name(name, "123", 20).unwrap();
+        assert_eq!(first, format!("a-very-long-{}", short_hash("123")));
+        let second = fit_name(name, "456", 20).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fit_name(name, "123", 20), Some(first));
+
+        assert_eq!(fit_name("ไปกินข้าว", "1", 11), Some(short_hash("1")));
+        assert_eq!(fit_name(name, "123", 7), None);
+    }
+
+    #[test]
+    fn long_titles_are_capped() {
+        let slug = title_slug(&"word ".repeat(50), false);
+        assert!(slug.len() <= MAX_TITLE_BYTES);
+        assert!(slug.ends_with("word"));
+        assert!(title_slug(&"ก".repeat(200), false).len() <= MAX_TITLE_BYTES);
+    }
+}
@@ -0,0 +1,208 @@
+use std::io;
+use std::path::Path;
+
+/// Owner given to finished files by `--chown`; `None` leaves that id unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileOwner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FileOwner {
+    /// Parse `user:group`, `user`, or `:group`. Numeric ids are used as given; names are
+    /// looked up in the system user and group databases.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (user, group) = match spec.split_once(':') {
+            Some((user, group)) => (user, group),
+            None => (spec, ""),
+        };
+        if user.is_empty() && group.is_empty() {
+            return Err(format!("expected user:group, got `{spec}`"));
+        }
+
+        let uid = match user {
+            "" => None,
+            user => {
+                Some(resolve(user, sys::user_id).ok_or_else(|| format!("unknown user: {user}"))?)
+            }
+        };
+        let gid = match group {
+            "" => None,
+            group => Some(
+                resolve(group, sys::group_id).ok_or_else(|| format!("unknown group: {group}"))?,
+            ),
+        };
+        Ok(Self { uid, gid })
+    }
+}
+
+fn resolve(name: &str, lookup: fn(&str) -> Option<u32>) -> Option<u32> {
+    name.parse().ok().or_else(|| lookup(name))
+}
+
+/// Apply `--chmod` and `--chown` to a finished file.
+pub(crate) fn apply(path: &Path, mode: Option<u32>, owner: Option<FileOwner>) -> io::Result<()> {
+    if let Some(mode) = mode {
+        sys::set_mode(path, mode)?;
+    }
+    if let Some(owner) = owner {
+        sys::chown(path, owner)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    use super::FileOwner;
+
+    pub(super) fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+
+    pub(super) fn chown(path: &Path, owner: FileOwner) -> io::Result<()> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        // -1 (all bits set) tells chown(2) to leave that id alone.
+        let uid = owner.uid.map_or(libc::uid_t::MAX, |uid| uid as libc::uid_t);
+        let gid = owner.gid.map_or(libc::gid_t::MAX, |gid| gid as libc::gid_t);
+        // SAFETY: `path` is a valid NUL-terminated string for the duration of the call.
+        if unsafe { libc::chown(path.as_ptr(), uid, gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn user_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        with_buffer(|buf| {
+            // SAFETY: all-zero is a valid `passwd`; getpwnam_r only writes into it and
+            // `buf`, both of which outlive the call.
+            let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut found = std::ptr::null_mut();
+            let rc = unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut entry,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut found,
+                )
+            };
+            (rc, (!found.is_null()).then_some(entry.pw_uid))
+        })
+    }
+
+    pub(super) fn group_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        with_buffer(|buf| {
+            // SAFETY: as in `user_id`, for `group` and getgrnam_r.
+            let mut entry: libc::group = unsafe { std::mem::zeroed() };
+            let mut found = std::ptr::null_mut();
+            let rc = unsafe {
+                libc::getgrnam_r(
+                    name.as_ptr(),
+                    &mut entry,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut found,
+                )
+            };
+            (rc, (!found.is_null()).then_some(entry.gr_gid))
+        })
+    }
+
+    /// Run a `get*nam_r` lookup, growing the scratch buffer while it reports ERANGE
+    /// (groups with many members need more than the usual few kilobytes).
+    fn with_buffer(
+        mut lookup: impl FnMut(&mut [libc::c_char]) -> (libc::c_int, Option<u32>),
+    ) -> Option<u32> {
+        let mut buf = vec![0 as libc::c_char; 1024];
+        loop {
+            match lookup(&mut buf) {
+                (libc::ERANGE, _) if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+                (0, id) => return id,
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::io;
+    use std::path::Path;
+
+    use super::FileOwner;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--chmod and --chown need a Unix system",
+        )
+    }
+
+    pub(super) fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(super) fn chown(_path: &Path, _owner: FileOwner) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(super) fn user_id(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub(super) fn group_id(_name: &str) -> Option<u32> {
+        None
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    #[test]
+    fn owner_spec_accepts_ids_and_names() {
+        assert_eq!(
+            FileOwner::parse("1000:100"),
+            Ok(FileOwner {
+                uid: Some(1000),
+                gid: Some(100)
+            })
+        );
+        assert_eq!(
+            FileOwner::parse(":100"),
+            Ok(FileOwner {
+                uid: None,
+                gid: Some(100)
+            })
+        );
+        assert_eq!(FileOwner::parse("root").unwrap().uid, Some(0));
+        assert!(FileOwner::parse("no-such-user-tikd").is_err());
+        assert!(FileOwner::parse(":").is_err());
+    }
+
+    #[test]
+    fn apply_sets_mode_and_keeps_own_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v.mp4");
+        std::fs::write(&path, "x").unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        let owner = FileOwner {
+            uid: Some(meta.uid()),
+            gid: Some(meta.gid()),
+        };
+
+        apply(&path, Some(0o640), Some(owner)).unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o7777, 0o640);
+        assert_eq!((Some(meta.uid()), Some(meta.gid())), (owner.uid, owner.gid));
+    }
+}
@@ -0,0 +1,160 @@
+//! Steps after a video is in place: file options and extra copies, the checksum manifest
+//! and download archive, `.info.json`, and content deduplication.
+
+use std::path::{Path, PathBuf};
+
+use super::{content, info, ownership, partial_path, Downloader};
+use crate::scraper::VideoDescriptor;
+
+impl Downloader {
+    /// Everything that follows a video landing at `output_path`, whether downloaded or
+    /// linked from an existing copy. Failures are logged; the video itself is saved.
+    pub(super) async fn finalize_saved(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) {
+        self.finish_file(output_path).await;
+        self.record_checksum(descriptor, output_path).await;
+        self.record_in_download_archive(&descriptor.video_id).await;
+        self.write_author_assets(descriptor, share_url, output_path)
+            .await;
+        self.write_info_json(descriptor, share_url, output_path)
+            .await;
+        self.write_thumbnails(descriptor, share_url, output_path)
+            .await;
+        self.write_subtitles(descriptor, share_url, output_path)
+            .await;
+    }
+
+    async fn record_checksum(&self, descriptor: &VideoDescriptor, path: &Path) {
+        if let Some(log) = &self.checksum_log {
+            if let Err(err) = log.record(descriptor, path).await {
+                tracing::warn!(
+                    "Could not record the checksum of {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// A failed write is logged; it only means the video may be downloaded again later.
+    pub(super) async fn record_in_download_archive(&self, video_id: &str) {
+        if let Some(archive) = &self.download_archive {
+            if let Err(err) = archive.record(video_id).await {
+                tracing::warn!(
+                    "Could not add {} to the download archive: {}",
+                    video_id,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Failures are logged; the video itself is already saved.
+    async fn write_info_json(
+        &self,
+        descriptor: &VideoDescriptor,
+        share_url: &str,
+        output_path: &Path,
+    ) {
+        if !self.config.write_info_json {
+            return;
+        }
+        let format = self.config.info_json_format;
+        match info::write_info_json(output_path, descriptor, share_url, format).await {
+            Ok(path) => self.set_file_options(&path).await,
+            Err(err) => {
+                tracing::warn!(
+                    "Could not write metadata for {}: {}",
+                    output_path.display(),
+                    err
+                )
+            }
+        }
+    }
+
+    /// An archived video with the same bytes as `path` under another video ID.
+    pub(super) async fn find_same_content(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.config.output_dir.as_deref().unwrap_or(Path::new("."));
+        let found = async {
+            let len = tokio::fs::metadata(path).await?.len();
+            if len == 0 {
+                return Ok(None);
+            }
+            let candidates = self.content_index.register(root, path, len).await?;
+            let file = path.to_path_buf();
+            tokio::task::spawn_blocking(move || content::find_same_content(&file, candidates))
+                .await
+                .map_err(std::io::Error::other)?
+        };
+        match found.await {
+            Ok(original) => original,
+            Err(err) => {
+                tracing::warn!(
+                    "Could not compare {} with the archive: {}",
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Apply `file_mode`/`file_owner` and replicate to `also_copy_to`. Failures here are
+    /// logged rather than failing a download whose file is already complete.
+    async fn finish_file(&self, output_path: &Path) {
+        self.set_file_options(output_path).await;
+        self.copy_to_extra_dirs(output_path).await;
+    }
+
+    pub(super) async fn set_file_options(&self, path: &Path) {
+        let (mode, owner) = (self.config.file_mode, self.config.file_owner);
+        if mode.is_none() && owner.is_none() {
+            return;
+        }
+        let target = path.to_path_buf();
+        let applied = tokio::task::spawn_blocking(move || ownership::apply(&target, mode, owner));
+        match applied.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                tracing::warn!("Could not set mode/owner of {}: {}", path.display(), err)
+            }
+            Err(err) => tracing::warn!("Could not set mode/owner of {}: {}", path.display(), err),
+        }
+    }
+
+    async fn copy_to_extra_dirs(&self, output_path: &Path) {
+        let root = self.config.output_dir.as_deref().unwrap_or(Path::new("."));
+        let relative = match output_path.strip_prefix(root) {
+            Ok(relative) => relative,
+            // Per-item outputs outside the archive keep only their file name.
+            Err(_) => Path::new(output_path.file_name().unwrap_or_default()),
+        };
+
+        for dir in &self.config.also_copy_to {
+            let target = dir.join(relative);
+            match copy_file(output_path, &target).await {
+                Ok(()) => self.set_file_options(&target).await,
+                Err(err) => tracing::warn!(
+                    "Could not copy {} to {}: {}",
+                    output_path.display(),
+                    target.display(),
+                    err
+                ),
+            }
+        }
+    }
+}
+
+/// Copy through a `.part` file so a synced folder never sees a half-written video.
+async fn copy_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let partial = partial_path(target);
+    tokio::fs::copy(source, &partial).await?;
+    tokio::fs::rename(&partial, target).await
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use reqwest::StatusCode;
+
+/// What a preflight request learned about an item's media URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaProbe {
+    /// Media URL that was probed.
+    pub url: String,
+    pub status: u16,
+    /// Size of the media file, when the CDN reported one; `None` for HLS playlists.
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+}
+
+impl MediaProbe {
+    pub fn is_available(&self) -> bool {
+        StatusCode::from_u16(self.status).is_ok_and(|status| status.is_success())
+    }
+
+    pub(crate) fn from_response(url: &str, status: StatusCode, headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let size = if status == StatusCode::PARTIAL_CONTENT {
+            header(CONTENT_RANGE).and_then(content_range_total)
+        } else {
+            header(CONTENT_LENGTH).and_then(|value| value.parse().ok())
+        };
+        Self {
+            url: url.to_string(),
+            status: status.as_u16(),
+            size,
+            content_type: header(CONTENT_TYPE).map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// Total length from a `Content-Range: bytes 0-0/12345` header.
+pub(crate) fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// First byte from a `Content-Range: bytes 100-12344/12345` header.
+pub(crate) fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    range.split_once('-')?.0.parse().ok()
+}
+
+/// Probe results of the current batch, keyed by share URL until the report takes them.
+#[derive(Clone, Default)]
+pub(crate) struct ProbeLog(Arc<Mutex<HashMap<String, MediaProbe>>>);
+
+impl ProbeLog {
+    pub(crate) fn record(&self, share_url: &str, probe: MediaProbe) {
+        let mut probes = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        probes.insert(share_url.to_string(), probe);
+    }
+
+    pub(crate) fn take(&self, share_url: &str) -> Option<MediaProbe> {
+        let mut probes = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        probes.remove(share_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn size_comes_from_length_or_range() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("1"));
+        headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes 0-0/5242880"));
+        let ranged = MediaProbe::from_response("u", StatusCode::PARTIAL_CONTENT, &headers);
+        assert_eq!(ranged.size, Some(5_242_880));
+        assert_eq!(content_range_start("bytes 100-5242879/5242880"), Some(100));
+        assert_eq!(content_range_start("bytes */5242880"), None);
+        assert!(ranged.is_available());
+
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("4096"));
+        let head = MediaProbe::from_response("u", StatusCode::OK, &headers);
+        assert_eq!(head.size, Some(4096));
+
+        let missing = MediaProbe::from_response("u", StatusCode::FORBIDDEN, &HeaderMap::new());
+        assert_eq!(missing.size, None);
+        assert!(!missing.is_available());
+    }
+}
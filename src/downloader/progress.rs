@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::Outcome;
+
+/// Receiver side of `DownloadConfig::progress`.
+pub type ProgressSender = UnboundedSender<ProgressEvent>;
+
+/// Transfer progress emitted while a download runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// An HLS segment finished writing.
+    Segment {
+        /// Share URL of the item being downloaded.
+        url: String,
+        /// 1-based index of the finished segment.
+        index: usize,
+        /// Number of segments listed in the media playlist.
+        total: usize,
+        /// Bytes written for this item so far, including the init segment.
+        bytes: u64,
+        /// Projected size of the finished file, extrapolated from the segments so far.
+        estimated_bytes: u64,
+    },
+    /// An item of a batch finished, successfully or not.
+    ItemFinished {
+        /// Share URL of the finished item.
+        url: String,
+        outcome: Outcome,
+        /// Batch totals including this item.
+        status: BatchStatus,
+    },
+    /// Periodic batch totals while `download_requests` runs.
+    Batch(BatchStatus),
+}
+
+/// Running totals of a batch, for a one-line status display.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchStatus {
+    /// Items finished so far, including failures.
+    pub done: usize,
+    pub total: usize,
+    pub failed: usize,
+    /// Media bytes written since the batch started.
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl BatchStatus {
+    /// Average transfer rate since the batch started.
+    pub fn bytes_per_sec(&self) -> u64 {
+        match self.elapsed.as_millis() {
+            0 => 0,
+            millis => (self.bytes as u128 * 1000 / millis) as u64,
+        }
+    }
+
+    /// Time left at the average pace per finished item so far; `None` until one finishes.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.done) as u32;
+        Some(self.elapsed / self.done as u32 * remaining)
+    }
+}
+
+/// Extrapolate the final size from `bytes` written over `index` of `total` segments.
+pub(crate) fn estimate_total(bytes: u64, index: usize, total: usize) -> u64 {
+    if index == 0 {
+        return 0;
+    }
+    let per_segment = bytes / index as u64;
+    bytes + per_segment.saturating_mul(total.saturating_sub(index) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_extrapolates_average_segment() {
+        assert_eq!(estimate_total(300, 3, 10), 1000);
+        assert_eq!(estimate_total(1000, 10, 10), 1000);
+        assert_eq!(estimate_total(0, 0, 10), 0);
+    }
+
+    #[test]
+    fn batch_status_projects_remaining_time() {
+        let mut status = BatchStatus {
+            done: 0,
+            total: 4,
+            failed: 0,
+            bytes: 3000,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(status.bytes_per_sec(), 1500);
+        assert_eq!(status.eta(), None);
+
+        status.done = 1;
+        assert_eq!(status.eta(), Some(Duration::from_secs(6)));
+        status.done = 4;
+        assert_eq!(status.eta(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::layout::{self, Layout};
+use crate::error::{Error, Result};
+
+/// Enforces `DownloadConfig::max_archive_size` across the downloader and its clones.
+#[derive(Clone)]
+pub(crate) struct ArchiveQuota {
+    root: PathBuf,
+    limit: u64,
+    prune_oldest: bool,
+    // Serializes size checks so concurrent workers don't prune the same files.
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ArchiveQuota {
+    pub(crate) fn new(root: PathBuf, limit: u64, prune_oldest: bool) -> Self {
+        Self {
+            root,
+            limit,
+            prune_oldest,
+            lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Refuse to start another download while the root is over the quota, pruning the
+    /// oldest videos first when that is enabled.
+    pub(crate) async fn check(&self) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let root = self.root.clone();
+        let (limit, prune_oldest) = (self.limit, self.prune_oldest);
+
+        let scan = tokio::task::spawn_blocking(move || -> io::Result<u64> {
+            let size = archive_size(&root)?;
+            if size < limit || !prune_oldest {
+                return Ok(size);
+            }
+            prune_to(&root, size, limit)
+        });
+        let size = scan.await.map_err(io::Error::other)??;
+
+        if size >= limit {
+            return Err(Error::ArchiveQuotaExceeded { limit, size });
+        }
+        Ok(())
+    }
+}
+
+/// Total size of the regular files under `root`; a missing root is empty.
+pub(crate) fn archive_size(root: &Path) -> io::Result<u64> {
+    Ok(files_under(root)?.iter().map(|file| file.len).sum())
+}
+
+/// Delete the oldest downloaded videos under `root` until it is below `limit`, returning
+/// the new size.
+///
+/// Only videos at paths the downloader writes are candidates, so files that merely sit
+/// in the same tree are never deleted.
+fn prune_to(root: &Path, mut size: u64, limit: u64) -> io::Result<u64> {
+    let layout = Layout::load(root)?;
+    let mut videos: Vec<_> = files_under(root)?
+        .into_iter()
+        .filter(|file| layout.recognize(&file.path).is_some())
+        .collect();
+    videos.sort_by_key(|file| file.modified);
+
+    for video in videos {
+        if size < limit {
+            break;
+        }
+        match remove_with_sidecars(&video.path) {
+            Ok(freed) => {
+                tracing::info!(
+                    "Pruned {} to stay under the archive quota",
+                    video.path.display()
+                );
+                size = size.saturating_sub(freed);
+            }
+            Err(err) => tracing::warn!("Could not prune {}: {}", video.path.display(), err),
+        }
+    }
+    Ok(size)
+}
+
+/// Delete `video` and the sidecars saved with it, returning the bytes freed.
+fn remove_with_sidecars(video: &Path) -> io::Result<u64> {
+    let sidecars = layout::sidecars(video)?;
+    let mut freed = fs::metadata(video)?.len();
+    fs::remove_file(video)?;
+    for sidecar in sidecars {
+        let len = fs::metadata(&sidecar).map_or(0, |meta| meta.len());
+        match fs::remove_file(&sidecar) {
+            Ok(()) => freed += len,
+            Err(err) => tracing::warn!("Could not prune {}: {}", sidecar.display(), err),
+        }
+    }
+    Ok(freed)
+}
+
+/// Only downloaded media is ever pruned, never session, batch, or resume files.
+pub(crate) fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mp4"))
+}
+
+/// Which downloads `prune_archive` keeps; a video is deleted if any rule rejects it.
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    /// Delete videos last modified longer ago than this.
+    pub keep_for: Option<Duration>,
+    /// Keep only this many of the newest videos in each creator's directory.
+    pub keep_per_author: Option<usize>,
+}
+
+/// Delete the videos under `root` that `policy` rejects, with their sidecars, and return
+/// the videos' paths.
+///
+/// Only videos at paths the downloader writes are considered, grouped by the creator
+/// their path names; any other file is left alone. Videos whose path names no creator
+/// are never pruned for `keep_per_author`. With `dry_run`, nothing is deleted.
+pub fn prune_archive(
+    root: &Path,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+) -> io::Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let layout = Layout::load(root)?;
+    let mut by_author: HashMap<Option<String>, Vec<ArchivedFile>> = HashMap::new();
+    for file in files_under(root)? {
+        if let Some(entry) = layout.recognize(&file.path) {
+            by_author.entry(entry.author).or_default().push(file);
+        }
+    }
+
+    let mut doomed = Vec::new();
+    for (author, mut videos) in by_author {
+        // Newest first, so the per-author limit keeps the most recent downloads.
+        videos.sort_by_key(|video| std::cmp::Reverse(video.modified));
+        for (rank, video) in videos.into_iter().enumerate() {
+            let too_old = policy
+                .keep_for
+                .is_some_and(|keep| now.duration_since(video.modified).unwrap_or_default() > keep);
+            let over_limit =
+                author.is_some() && policy.keep_per_author.is_some_and(|keep| rank >= keep);
+            if too_old || over_limit {
+                doomed.push(video.path);
+            }
+        }
+    }
+    doomed.sort();
+
+    if !dry_run {
+        for path in &doomed {
+            remove_with_sidecars(path)?;
+            tracing::info!("Pruned {}", path.display());
+        }
+    }
+    Ok(doomed)
+}
+
+/// A downloaded video found in an archive directory.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ArchivedVideo {
+    /// TikTok video ID, read from the path the downloader gave the file; the file stem
+    /// for files at paths it would not have chosen.
+    pub video_id: String,
+    /// Creator the path names, else the directory the video sits in; empty for videos
+    /// directly in the root.
+    pub author: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Last modification time in seconds since the Unix epoch.
+    pub modified_unix: u64,
+    /// Posting date as `YYYYMMDD`, from the video's `.info.json` when one was written.
+    pub upload_date: Option<String>,
+    /// Play count from the `.info.json`, as of when the video was scraped.
+    pub view_count: Option<u64>,
+    /// Like count from the `.info.json`, as of when the video was scraped.
+    pub like_count: Option<u64>,
+    /// Whether the downloader could have chosen this path; `video_id` and `author` are
+    /// only guesses from the file and directory names otherwise.
+    pub recognized: bool,
+}
+
+/// Every `.mp4` under `root`, ordered by author and then video ID.
+///
+/// Creators and IDs are read from the default layout and the templates recorded in the
+/// root's `LAYOUTS_FILE`.
+pub fn list_archive(root: &Path) -> io::Result<Vec<ArchivedVideo>> {
+    let layout = Layout::load(root)?;
+    let mut videos: Vec<_> = files_under(root)?
+        .into_iter()
+        .filter(|file| is_video(&file.path))
+        .map(|file| {
+            let name = |path: Option<&Path>| {
+                path.filter(|path| *path != root)
+                    .and_then(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            let entry = layout.recognize(&file.path);
+            let recognized = entry.is_some();
+            let (video_id, author) = match entry {
+                Some(layout::Entry {
+                    video_id,
+                    author: Some(author),
+                }) => (video_id, author),
+                Some(layout::Entry {
+                    video_id,
+                    author: None,
+                }) => (video_id, String::new()),
+                None => (
+                    name(Some(&file.path.with_extension(""))),
+                    name(file.path.parent()),
+                ),
+            };
+            let stats = layout::info_json_stats(&file.path);
+            ArchivedVideo {
+                video_id,
+                author,
+                bytes: file.len,
+                modified_unix: file
+                    .modified
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default(),
+                upload_date: stats.upload_date,
+                view_count: stats.view_count,
+                like_count: stats.like_count,
+                recognized,
+                path: file.path,
+            }
+        })
+        .collect();
+    videos.sort_by(|a, b| (&a.author, &a.video_id).cmp(&(&b.author, &b.video_id)));
+    Ok(videos)
+}
+
+pub(crate) struct ArchivedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) len: u64,
+    modified: SystemTime,
+}
+
+pub(crate) fn files_under(root: &Path) -> io::Result<Vec<ArchivedFile>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else if meta.is_file() {
+                files.push(ArchivedFile {
+                    path: entry.path(),
+                    len: meta.len(),
+                    modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Write files in the given order, leaving their modification times ascending.
+    fn write_in_order(files: &[(&Path, usize)]) {
+        for (path, len) in files {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0u8; *len]).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn pruning_removes_oldest_videos_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let foreign = dir.path().join("holiday.mp4");
+        let unnamed = dir.path().join("a/holiday.mp4");
+        let oldest = dir.path().join("a/1.mp4");
+        let info = dir.path().join("a/1.info.json");
+        let newer = dir.path().join("b/2.mp4");
+        let notes = dir.path().join("urls.txt");
+        write_in_order(&[
+            (&notes, 40),
+            (&foreign, 40),
+            (&unnamed, 40),
+            (&oldest, 40),
+            (&info, 10),
+            (&newer, 40),
+        ]);
+
+        let size = archive_size(dir.path()).unwrap();
+        assert_eq!(size, 210);
+        assert_eq!(prune_to(dir.path(), size, 200).unwrap(), 160);
+        assert!(!oldest.exists());
+        assert!(!info.exists(), "sidecars go with their video");
+        assert!(newer.exists());
+        assert!(notes.exists() && foreign.exists() && unnamed.exists());
+    }
+
+    #[test]
+    fn retention_keeps_newest_per_author() {
+        let dir = tempfile::tempdir().unwrap();
+        let a1 = dir.path().join("alice/1.mp4");
+        let a2 = dir.path().join("alice/2.mp4");
+        let a3 = dir.path().join("alice/3.mp4");
+        let b1 = dir.path().join("bob/1.mp4");
+        let a1_cover = dir.path().join("alice/1.cover.jpeg");
+        let foreign = dir.path().join("alice/holiday.mp4");
+        let loose = dir.path().join("4.mp4");
+        write_in_order(&[
+            (&foreign, 1),
+            (&loose, 1),
+            (&a1, 1),
+            (&a1_cover, 1),
+            (&b1, 1),
+            (&a2, 1),
+            (&a3, 1),
+        ]);
+
+        let policy = RetentionPolicy {
+            keep_per_author: Some(2),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            prune_archive(dir.path(), &policy, true).unwrap(),
+            vec![a1.clone()]
+        );
+        assert!(a1.exists(), "dry run deletes nothing");
+
+        assert_eq!(
+            prune_archive(dir.path(), &policy, false).unwrap(),
+            vec![a1.clone()]
+        );
+        assert!(!a1.exists() && !a1_cover.exists());
+        assert!(a2.exists() && a3.exists() && b1.exists());
+
+        let expire_all = RetentionPolicy {
+            keep_for: Some(Duration::ZERO),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            prune_archive(dir.path(), &expire_all, false).unwrap(),
+            vec![a2, a3, b1]
+        );
+        assert!(foreign.exists() && loose.exists());
+    }
+
+    #[test]
+    fn foreign_videos_with_info_json_survive_pruning() {
+        let dir = tempfile::tempdir().unwrap();
+        let clip = dir.path().join("clip.mp4");
+        let clip_info = dir.path().join("clip.info.json");
+        let nested = dir.path().join("vimeo/2024/clip.mp4");
+        let ours = dir.path().join("a/1.mp4");
+        write_in_order(&[(&clip, 40), (&nested, 40), (&ours, 40)]);
+        for info in [clip_info.clone(), nested.with_extension("info.json")] {
+            fs::write(info, r#"{"id":"123456","uploader":"someone"}"#).unwrap();
+        }
+
+        let expire_all = RetentionPolicy {
+            keep_for: Some(Duration::ZERO),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            prune_archive(dir.path(), &expire_all, false).unwrap(),
+            vec![ours]
+        );
+        let size = archive_size(dir.path()).unwrap();
+        prune_to(dir.path(), size, 1).unwrap();
+        assert!(clip.exists() && clip_info.exists() && nested.exists());
+    }
+
+    #[test]
+    fn archive_listing_reads_author_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        write_in_order(&[
+            (&dir.path().join("bob/7.mp4"), 3),
+            (&dir.path().join("alice/9.mp4"), 2),
+            (&dir.path().join("loose.mp4"), 1),
+            (&dir.path().join("alice/notes.txt"), 1),
+        ]);
+
+        let listed: Vec<_> = list_archive(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|video| (video.author, video.video_id, video.bytes, video.recognized))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (String::new(), "loose".to_string(), 1, false),
+                ("alice".to_string(), "9".to_string(), 2, true),
+                ("bob".to_string(), "7".to_string(), 3, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn quota_refuses_when_over_limit_without_pruning() {
+        let dir = tempfile::tempdir().unwrap();
+        write_in_order(&[(&dir.path().join("a/1.mp4"), 50)]);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let quota = |limit, prune_oldest| ArchiveQuota::new(dir.path().into(), limit, prune_oldest);
+        assert!(rt.block_on(quota(100, false).check()).is_ok());
+        assert!(matches!(
+            rt.block_on(quota(50, false).check()),
+            Err(Error::ArchiveQuotaExceeded {
+                limit: 50,
+                size: 50
+            })
+        ));
+        assert!(rt.block_on(quota(50, true).check()).is_ok());
+        assert_eq!(archive_size(dir.path()).unwrap(), 0);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use tokio_util::sync::CancellationToken;
+
+/// Preferred rendition when a stream offers several variants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// Highest bandwidth variant.
+    #[default]
+    Best,
+    /// Lowest bandwidth variant.
+    Worst,
+    /// Highest bandwidth variant whose short side does not exceed the given number of lines.
+    MaxHeight(u32),
+}
+
+impl Quality {
+    /// Pick one variant from `(bandwidth, height)` pairs, returning its index.
+    /// Ties keep the earliest variant in playlist order.
+    pub(crate) fn pick(&self, variants: &[(u64, Option<u32>)]) -> Option<usize> {
+        let highest =
+            |indices: Vec<usize>| indices.into_iter().rev().max_by_key(|&idx| variants[idx].0);
+        let lowest = || (0..variants.len()).min_by_key(|&idx| variants[idx].0);
+
+        match self {
+            Quality::Best => highest((0..variants.len()).collect()),
+            Quality::Worst => lowest(),
+            Quality::MaxHeight(max) => {
+                let fitting = (0..variants.len())
+                    .filter(|&idx| !matches!(variants[idx].1, Some(height) if height > *max))
+                    .collect();
+                highest(fitting).or_else(lowest)
+            }
+        }
+    }
+}
+
+impl FromStr for Quality {
+    type Err = String;
+
+    /// Accepts `best`, `worst`, or a height such as `720p` / `720`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let value = input.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "best" => Ok(Quality::Best),
+            "worst" => Ok(Quality::Worst),
+            other => other
+                .strip_suffix('p')
+                .unwrap_or(other)
+                .parse()
+                .map(Quality::MaxHeight)
+                .map_err(|_| format!("invalid quality: {input} (use best, worst, or e.g. 720p)")),
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quality::Best => f.write_str("best"),
+            Quality::Worst => f.write_str("worst"),
+            Quality::MaxHeight(height) => write!(f, "{height}p"),
+        }
+    }
+}
+
+/// One item to download, with optional overrides of the downloader-wide configuration.
+#[derive(Clone, Debug, Default)]
+pub struct DownloadRequest {
+    pub url: String,
+    /// Explicit output file; relative paths are placed under the configured output directory.
+    pub output: Option<PathBuf>,
+    pub quality: Option<Quality>,
+    /// Extra headers sent with this item's media requests.
+    pub headers: Vec<(String, String)>,
+    /// Proxy used for both scraping and media transfers of this item.
+    pub proxy: Option<String>,
+    /// Cancels this item only; the partial output file is removed.
+    pub cancel: Option<CancellationToken>,
+    /// Free-form labels for grouping results; they do not affect the download.
+    pub tags: Vec<String>,
+    /// Line of the batch file the URL was read from, for reporting bad entries.
+    pub line: Option<usize>,
+}
+
+impl DownloadRequest {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Whether this request needs anything beyond the downloader's defaults.
+    pub(crate) fn has_overrides(&self) -> bool {
+        self.quality.is_some()
+            || !self.headers.is_empty()
+            || self.proxy.is_some()
+            || self.cancel.is_some()
+    }
+}
+
+impl From<&str> for DownloadRequest {
+    fn from(url: &str) -> Self {
+        Self::new(url)
+    }
+}
+
+impl From<String> for DownloadRequest {
+    fn from(url: String) -> Self {
+        Self::new(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quality_parses_heights_and_keywords() {
+        assert_eq!("best".parse(), Ok(Quality::Best));
+        assert_eq!("Worst".parse(), Ok(Quality::Worst));
+        assert_eq!("720p".parse(), Ok(Quality::MaxHeight(720)));
+        assert_eq!("1080".parse(), Ok(Quality::MaxHeight(1080)));
+        assert!("hd".parse::<Quality>().is_err());
+    }
+
+    #[test]
+    fn quality_picks_matching_variant() {
+        let variants = [
+            (800_000, Some(720)),
+            (2_000_000, Some(1080)),
+            (300_000, Some(480)),
+        ];
+        assert_eq!(Quality::Best.pick(&variants), Some(1));
+        assert_eq!(Quality::Worst.pick(&variants), Some(2));
+        assert_eq!(Quality::MaxHeight(720).pick(&variants), Some(0));
+        assert_eq!(Quality::MaxHeight(240).pick(&variants), Some(2));
+        assert_eq!(Quality::Best.pick(&[]), None);
+    }
+
+    #[test]
+    fn builder_sets_overrides() {
+        let request = DownloadRequest::new("https://www.tiktok.com/@user/video/1")
+            .output("clips/one.mp4")
+            .quality(Quality::MaxHeight(720))
+            .header("X-Test", "1")
+            .proxy("http://127.0.0.1:3128");
+
+        assert_eq!(request.output, Some(PathBuf::from("clips/one.mp4")));
+        assert_eq!(
+            request.headers,
+            vec![("X-Test".to_string(), "1".to_string())]
+        );
+        assert!(request.has_overrides());
+        assert!(!DownloadRequest::new("https://www.tiktok.com/").has_overrides());
+    }
+}
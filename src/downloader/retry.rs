@@ -0,0 +1,203 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use crate::error::Error;
+
+/// Broad cause of a retried attempt, for telling rate limits from bot walls and CDN trouble.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RetryReason {
+    /// HTTP 429.
+    RateLimited,
+    /// HTTP 403, usually a bot wall or an expired signature.
+    Forbidden,
+    /// HTTP 5xx.
+    ServerError,
+    /// Timeouts, refused connections, and truncated bodies.
+    Network,
+    /// The stall or minimum-speed guard gave up on a transfer.
+    SlowTransfer,
+    Other,
+}
+
+impl RetryReason {
+    pub fn classify(err: &Error) -> Self {
+        match err {
+            Error::Network(inner) => match inner.status() {
+                Some(StatusCode::TOO_MANY_REQUESTS) => RetryReason::RateLimited,
+                Some(StatusCode::FORBIDDEN) => RetryReason::Forbidden,
+                Some(status) if status.is_server_error() => RetryReason::ServerError,
+                Some(_) => RetryReason::Other,
+                None => RetryReason::Network,
+            },
+            Error::Stalled { .. } | Error::TooSlow { .. } => RetryReason::SlowTransfer,
+            Error::SimulatedLoss => RetryReason::Network,
+            _ => RetryReason::Other,
+        }
+    }
+}
+
+impl fmt::Display for RetryReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RetryReason::RateLimited => "rate limited",
+            RetryReason::Forbidden => "forbidden",
+            RetryReason::ServerError => "server error",
+            RetryReason::Network => "network",
+            RetryReason::SlowTransfer => "slow transfer",
+            RetryReason::Other => "other",
+        })
+    }
+}
+
+/// One failed attempt that was retried.
+#[derive(Clone, Debug)]
+pub struct RetryRecord {
+    /// 1-based number of the attempt that failed.
+    pub attempt: usize,
+    pub reason: RetryReason,
+    /// The error message of the failed attempt.
+    pub message: String,
+    /// Delay before the next attempt.
+    pub backoff: Duration,
+}
+
+/// Longest pause after repeated rate limiting.
+const MAX_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Batch-wide slowdown after any worker is rate limited.
+///
+/// A 429 pauses every worker for the cooldown, then lets attempts through one at a time
+/// for a few cooldowns more before full concurrency resumes. Repeated 429s within that
+/// window double the cooldown.
+#[derive(Clone, Debug)]
+pub(crate) struct RateGate {
+    base_cooldown: Duration,
+    state: Arc<Mutex<GateState>>,
+    serial: Arc<Semaphore>,
+}
+
+#[derive(Debug, Default)]
+struct GateState {
+    cooldown: Duration,
+    paused_until: Option<Instant>,
+    serial_until: Option<Instant>,
+}
+
+impl RateGate {
+    pub(crate) fn new(base_cooldown: Duration) -> Self {
+        Self {
+            base_cooldown,
+            state: Arc::new(Mutex::new(GateState::default())),
+            serial: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    /// Record a 429 from any worker.
+    pub(crate) fn on_rate_limited(&self) {
+        if self.base_cooldown.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.lock();
+        let still_slow = state.serial_until.is_some_and(|until| now < until);
+        state.cooldown = if still_slow {
+            (state.cooldown * 2).min(MAX_COOLDOWN)
+        } else {
+            self.base_cooldown
+        };
+        state.paused_until = Some(now + state.cooldown);
+        state.serial_until = Some(now + state.cooldown * 4);
+        tracing::warn!(
+            "Rate limited; pausing all downloads for {:?}",
+            state.cooldown
+        );
+    }
+
+    /// Wait out any pause; while slowed down, the returned permit must be held for the attempt.
+    pub(crate) async fn enter(&self) -> Option<OwnedSemaphorePermit> {
+        loop {
+            let (paused_until, serial_until) = {
+                let state = self.lock();
+                (state.paused_until, state.serial_until)
+            };
+            let now = Instant::now();
+
+            if let Some(until) = paused_until.filter(|until| now < *until) {
+                tokio::time::sleep_until(until).await;
+                continue;
+            }
+            if serial_until.is_some_and(|until| now < until) {
+                // The semaphore is never closed.
+                let permit = self.serial.clone().acquire_owned().await.ok()?;
+                // A new 429 may have paused everything while this worker queued.
+                if self
+                    .lock()
+                    .paused_until
+                    .is_some_and(|until| Instant::now() < until)
+                {
+                    drop(permit);
+                    continue;
+                }
+                return Some(permit);
+            }
+            return None;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, GateState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_guard_errors() {
+        assert_eq!(
+            RetryReason::classify(&Error::Stalled {
+                timeout: Duration::from_secs(1)
+            }),
+            RetryReason::SlowTransfer
+        );
+        assert_eq!(
+            RetryReason::classify(&Error::Io(std::io::Error::other("disk"))),
+            RetryReason::Other
+        );
+        assert_eq!(RetryReason::RateLimited.to_string(), "rate limited");
+    }
+
+    #[tokio::test]
+    async fn rate_gate_pauses_then_serializes() {
+        let gate = RateGate::new(Duration::from_millis(50));
+        assert!(gate.enter().await.is_none());
+
+        gate.on_rate_limited();
+        let started = Instant::now();
+        let first = gate.enter().await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(first.is_some());
+
+        // A second worker waits for the first attempt while the batch is slowed down.
+        let second = tokio::time::timeout(Duration::from_millis(20), gate.enter()).await;
+        assert!(second.is_err());
+        drop(first);
+        assert!(gate.enter().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn repeated_rate_limits_double_cooldown() {
+        let gate = RateGate::new(Duration::from_millis(10));
+        gate.on_rate_limited();
+        gate.on_rate_limited();
+        assert_eq!(gate.lock().cooldown, Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+/// Random (version 4) UUID identifying one invocation, for correlating logs and reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RunId([u8; 16]);
+
+impl RunId {
+    pub fn new() -> Self {
+        let mut bytes = [0u8; 16];
+        // The OS RNG is only unavailable on exotic targets; a clock-derived id still
+        // tells runs apart there.
+        if getrandom::getrandom(&mut bytes).is_err() {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            bytes = (nanos ^ (u128::from(std::process::id()) << 96)).to_le_bytes();
+        }
+        Self::from_random(bytes)
+    }
+
+    fn from_random(mut bytes: [u8; 16]) -> Self {
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Self(bytes)
+    }
+}
+
+impl Default for RunId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if matches!(idx, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_as_version_4_uuid() {
+        let id = RunId::from_random([0xff; 16]);
+        assert_eq!(id.to_string(), "ffffffff-ffff-4fff-bfff-ffffffffffff");
+        assert_ne!(RunId::new(), RunId::new());
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Daily window of local time, `start` inclusive and `end` exclusive, in which new
+/// downloads may start. A window whose end is before its start spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveHours {
+    start: u32,
+    end: u32,
+}
+
+impl ActiveHours {
+    /// How long to wait from `seconds` past local midnight until the window opens; zero
+    /// inside the window.
+    pub fn wait_from(&self, seconds: u32) -> Duration {
+        let seconds = seconds % SECONDS_PER_DAY;
+        let inside = if self.start < self.end {
+            (self.start..self.end).contains(&seconds)
+        } else {
+            seconds >= self.start || seconds < self.end
+        };
+        if inside {
+            return Duration::ZERO;
+        }
+        let wait = (self.start + SECONDS_PER_DAY - seconds) % SECONDS_PER_DAY;
+        Duration::from_secs(u64::from(wait))
+    }
+
+    /// How long to wait from now, by the system's local time.
+    pub fn wait_now(&self) -> Duration {
+        self.wait_from(local_seconds_of_day())
+    }
+}
+
+impl FromStr for ActiveHours {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got '{value}'"))?;
+        let hours = ActiveHours {
+            start: parse_clock(start.trim())?,
+            end: parse_clock(end.trim())?,
+        };
+        if hours.start == hours.end {
+            return Err(format!(
+                "active hours '{value}' start and end at the same time"
+            ));
+        }
+        Ok(hours)
+    }
+}
+
+impl fmt::Display for ActiveHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clock = |seconds: u32| format!("{:02}:{:02}", seconds / 3600, seconds / 60 % 60);
+        write!(f, "{}-{}", clock(self.start), clock(self.end))
+    }
+}
+
+/// `HH:MM` as seconds past midnight; `24:00` is accepted as the end of the day.
+fn parse_clock(value: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid time '{value}', expected HH:MM");
+    let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes > 0) {
+        return Err(invalid());
+    }
+    Ok((hours * 60 + minutes) * 60 % SECONDS_PER_DAY)
+}
+
+fn local_seconds_of_day() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    (now + utc_offset(now)).rem_euclid(i64::from(SECONDS_PER_DAY)) as u32
+}
+
+#[cfg(unix)]
+fn utc_offset(now: i64) -> i64 {
+    let time = now as libc::time_t;
+    // SAFETY: all-zero is a valid `tm`; localtime_r only writes into it, and both it and
+    // `time` outlive the call.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Without a portable way to read the zone elsewhere, the window is taken as UTC.
+#[cfg(not(unix))]
+fn utc_offset(_now: i64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: u32, minutes: u32) -> u32 {
+        (hours * 60 + minutes) * 60
+    }
+
+    #[test]
+    fn parses_windows() {
+        let hours: ActiveHours = "01:00-07:30".parse().unwrap();
+        assert_eq!(hours.to_string(), "01:00-07:30");
+        assert_eq!(
+            "22:00-24:00".parse::<ActiveHours>().unwrap().to_string(),
+            "22:00-00:00"
+        );
+        assert!("01:00-01:00".parse::<ActiveHours>().is_err());
+        assert!("1am-7am".parse::<ActiveHours>().is_err());
+        assert!("01:00-07:60".parse::<ActiveHours>().is_err());
+        assert!("01:00".parse::<ActiveHours>().is_err());
+    }
+
+    #[test]
+    fn waits_until_the_window_opens() {
+        let hours: ActiveHours = "01:00-07:00".parse().unwrap();
+        assert_eq!(hours.wait_from(at(3, 0)), Duration::ZERO);
+        assert_eq!(hours.wait_from(at(0, 30)), Duration::from_secs(30 * 60));
+        assert_eq!(hours.wait_from(at(7, 0)), Duration::from_secs(18 * 3600));
+
+        let overnight: ActiveHours = "23:00-02:00".parse().unwrap();
+        assert_eq!(overnight.wait_from(at(23, 30)), Duration::ZERO);
+        assert_eq!(overnight.wait_from(at(1, 59)), Duration::ZERO);
+        assert_eq!(
+            overnight.wait_from(at(12, 0)),
+            Duration::from_secs(11 * 3600)
+        );
+    }
+}
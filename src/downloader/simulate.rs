@@ -0,0 +1,141 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// Degraded network conditions injected by `--simulate-network`, for exercising retry,
+/// backoff and resume against a healthy server.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NetworkSimulation {
+    /// Delay before every request is sent.
+    pub latency: Duration,
+    /// Chance, from 0 to 1, that a request or a received chunk is lost.
+    pub loss: f64,
+}
+
+impl NetworkSimulation {
+    /// Wait out the latency, then lose the request with the configured probability.
+    pub async fn before_request(&self) -> Result<()> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        self.before_chunk()
+    }
+
+    /// Lose a received chunk, cutting the transfer short, with the configured probability.
+    pub fn before_chunk(&self) -> Result<()> {
+        if self.loss > 0.0 && random_fraction() < self.loss {
+            return Err(Error::SimulatedLoss);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for NetworkSimulation {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let mut simulation = NetworkSimulation::default();
+        for setting in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, amount) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{setting}'"))?;
+            match key.trim() {
+                "latency" => simulation.latency = parse_latency(amount.trim())?,
+                "loss" => simulation.loss = parse_loss(amount.trim())?,
+                other => {
+                    return Err(format!(
+                        "unknown network setting '{other}' (use latency or loss)"
+                    ))
+                }
+            }
+        }
+        Ok(simulation)
+    }
+}
+
+impl fmt::Display for NetworkSimulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "latency={}ms,loss={}%",
+            self.latency.as_millis(),
+            self.loss * 100.0
+        )
+    }
+}
+
+/// `200ms` or `2s`.
+fn parse_latency(value: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("invalid latency '{value}', expected e.g. 200ms or 2s");
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount: u64 = digits.parse().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// `1%`, or a fraction such as `0.01`.
+fn parse_loss(value: &str) -> std::result::Result<f64, String> {
+    let invalid = || format!("invalid loss '{value}', expected a percentage such as 1%");
+    let loss = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().map_err(|_| invalid())? / 100.0,
+        None => value.parse::<f64>().map_err(|_| invalid())?,
+    };
+    if !(0.0..=1.0).contains(&loss) {
+        return Err(invalid());
+    }
+    Ok(loss)
+}
+
+/// Uniform in `[0, 1)`; a failed read of the system RNG loses nothing.
+fn random_fraction() -> f64 {
+    let mut bytes = [0u8; 4];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return 1.0;
+    }
+    f64::from(u32::from_le_bytes(bytes)) / (f64::from(u32::MAX) + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings() {
+        let simulation: NetworkSimulation = "latency=200ms,loss=1%".parse().unwrap();
+        assert_eq!(simulation.latency, Duration::from_millis(200));
+        assert_eq!(simulation.loss, 0.01);
+        assert_eq!(simulation.to_string(), "latency=200ms,loss=1%");
+
+        let simulation: NetworkSimulation = "loss=0.5".parse().unwrap();
+        assert_eq!(simulation.latency, Duration::ZERO);
+        assert_eq!(simulation.loss, 0.5);
+        assert_eq!(
+            "latency=2s".parse::<NetworkSimulation>().unwrap().latency,
+            Duration::from_secs(2)
+        );
+
+        assert!("latency=200".parse::<NetworkSimulation>().is_err());
+        assert!("loss=150%".parse::<NetworkSimulation>().is_err());
+        assert!("jitter=5ms".parse::<NetworkSimulation>().is_err());
+        assert!("latency".parse::<NetworkSimulation>().is_err());
+    }
+
+    #[test]
+    fn loses_everything_or_nothing_at_the_extremes() {
+        let lossless = NetworkSimulation::default();
+        assert!((0..100).all(|_| lossless.before_chunk().is_ok()));
+        let lossy = NetworkSimulation {
+            loss: 1.0,
+            ..NetworkSimulation::default()
+        };
+        assert!((0..100).all(|_| matches!(lossy.before_chunk(), Err(Error::SimulatedLoss))));
+    }
+}
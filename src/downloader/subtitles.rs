@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use crate::scraper::SubtitleTrack;
+
+/// Tracks matching `--sub-langs`; empty `langs` selects every track.
+///
+/// A language matches its full code or any prefix ending at a `-`, without regard to
+/// case, so `eng` selects `eng-US` and `eng-GB`.
+pub(crate) fn select<'a>(
+    tracks: &'a [SubtitleTrack],
+    langs: &'a [String],
+) -> impl Iterator<Item = &'a SubtitleTrack> {
+    tracks.iter().filter(move |track| {
+        langs.is_empty()
+            || langs
+                .iter()
+                .any(|lang| matches_language(&track.language, lang))
+    })
+}
+
+fn matches_language(language: &str, wanted: &str) -> bool {
+    let (language, wanted) = (language.to_ascii_lowercase(), wanted.to_ascii_lowercase());
+    language == wanted
+        || language
+            .strip_prefix(&wanted)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// `<stem>.<language>.vtt` next to `video_path`, the name yt-dlp gives subtitles.
+pub(crate) fn subtitle_path(video_path: &Path, language: &str) -> PathBuf {
+    // Codes come from the page, so keep only what a language tag can contain.
+    let language: String = language
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let language = if language.is_empty() {
+        "und"
+    } else {
+        &language
+    };
+    video_path.with_extension(format!("{language}.vtt"))
+}
+
+/// Whether `body` is a WebVTT file rather than, say, an error page.
+pub(crate) fn is_webvtt(body: &[u8]) -> bool {
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    body.strip_prefix(b"WEBVTT")
+        .is_some_and(|rest| rest.is_empty() || matches!(rest[0], b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(language: &str) -> SubtitleTrack {
+        SubtitleTrack {
+            language: language.into(),
+            ..SubtitleTrack::default()
+        }
+    }
+
+    #[test]
+    fn selects_languages_by_code_or_prefix() {
+        let tracks = [track("eng-US"), track("tha-TH"), track("cmn-Hans-CN")];
+        let selected = |langs: &[&str]| {
+            let langs: Vec<String> = langs.iter().map(|lang| lang.to_string()).collect();
+            select(&tracks, &langs)
+                .map(|track| track.language.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(selected(&[]), vec!["eng-US", "tha-TH", "cmn-Hans-CN"]);
+        assert_eq!(
+            selected(&["ENG", "cmn-hans"]),
+            vec!["eng-US", "cmn-Hans-CN"]
+        );
+        assert_eq!(selected(&["tha-TH"]), vec!["tha-TH"]);
+        assert!(selected(&["en", "th"]).is_empty());
+    }
+
+    #[test]
+    fn names_and_recognizes_vtt_files() {
+        let video = Path::new("out/chef/7551.mp4");
+        assert_eq!(
+            subtitle_path(video, "eng-US"),
+            PathBuf::from("out/chef/7551.eng-US.vtt")
+        );
+        assert_eq!(
+            subtitle_path(video, "../x"),
+            PathBuf::from("out/chef/7551.x.vtt")
+        );
+
+        assert!(is_webvtt(b"WEBVTT\n\n00:00.000 --> 00:01.000\nhi\n"));
+        assert!(is_webvtt(b"\xEF\xBB\xBFWEBVTT - captions\r\n"));
+        assert!(!is_webvtt(b"WEBVTTX"));
+        assert!(!is_webvtt(b"<html>Access Denied</html>"));
+    }
+}
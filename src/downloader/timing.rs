@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where the time went for one item, from its last attempt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ItemTimings {
+    /// Share-page scrape and metadata extraction; `None` when served from the descriptor cache.
+    pub scrape: Option<Duration>,
+    /// From the first media request until the first media byte arrived.
+    pub first_byte: Option<Duration>,
+    /// From the first media byte until the file was complete.
+    pub transfer: Option<Duration>,
+}
+
+/// Collects `ItemTimings` from the points of a download that observe them.
+#[derive(Clone, Default)]
+pub(crate) struct ItemTimer(Arc<Mutex<TimerState>>);
+
+#[derive(Default)]
+struct TimerState {
+    timings: ItemTimings,
+    media_started: Option<Instant>,
+    first_byte_at: Option<Instant>,
+}
+
+impl ItemTimer {
+    fn with_state<T>(&self, f: impl FnOnce(&mut TimerState) -> T) -> T {
+        let mut state = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state)
+    }
+
+    /// Start of a new attempt; earlier attempts' numbers are dropped.
+    pub(crate) fn attempt_started(&self) {
+        self.with_state(|state| *state = TimerState::default());
+    }
+
+    pub(crate) fn scraped(&self, elapsed: Duration) {
+        self.with_state(|state| state.timings.scrape = Some(elapsed));
+    }
+
+    pub(crate) fn media_started(&self) {
+        self.with_state(|state| state.media_started = Some(Instant::now()));
+    }
+
+    /// Called for the first chunk of each response; only the first one after `media_started`
+    /// counts.
+    pub(crate) fn bytes_received(&self) {
+        self.with_state(|state| {
+            if state.first_byte_at.is_some() {
+                return;
+            }
+            let now = Instant::now();
+            state.first_byte_at = Some(now);
+            state.timings.first_byte = state.media_started.map(|started| now - started);
+        });
+    }
+
+    pub(crate) fn media_finished(&self) {
+        self.with_state(|state| {
+            state.timings.transfer = state.first_byte_at.map(|first| first.elapsed());
+        });
+    }
+
+    pub(crate) fn timings(&self) -> ItemTimings {
+        self.with_state(|state| state.timings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_chunk_sets_time_to_first_byte() {
+        let timer = ItemTimer::default();
+        timer.scraped(Duration::from_millis(40));
+        timer.media_started();
+        std::thread::sleep(Duration::from_millis(5));
+        timer.bytes_received();
+        let first_byte = timer.timings().first_byte.unwrap();
+        timer.bytes_received();
+        timer.media_finished();
+
+        let timings = timer.timings();
+        assert_eq!(timings.scrape, Some(Duration::from_millis(40)));
+        assert_eq!(timings.first_byte, Some(first_byte));
+        assert!(first_byte >= Duration::from_millis(5));
+        assert!(timings.transfer.is_some());
+
+        timer.attempt_started();
+        assert_eq!(timer.timings(), ItemTimings::default());
+    }
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// Something that made a download worse than it could have been without failing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownloadWarning {
+    /// The share page offered no watermark-free binary URL, so only the HLS stream was tried.
+    NoDownloadUrl,
+    /// The binary download failed and the video was assembled from the HLS stream instead.
+    HlsFallback,
+    /// The first binary URL failed and a mirror served the video.
+    MirrorFallback,
+    /// The CDN rejected the scraped media URLs, so the share page was scraped again.
+    Rescraped,
+    /// The page gave no `createTime`, so `{upload_date}` in the output template became
+    /// `unknown`.
+    NoUploadDate,
+}
+
+impl fmt::Display for DownloadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DownloadWarning::NoDownloadUrl => "no watermark-free download URL; used the HLS stream",
+            DownloadWarning::HlsFallback => "binary download failed; fell back to the HLS stream",
+            DownloadWarning::MirrorFallback => "first media URL failed; downloaded from a mirror",
+            DownloadWarning::Rescraped => "media URLs were rejected; scraped the share page again",
+            DownloadWarning::NoUploadDate => {
+                "no upload time on the page; named with upload date `unknown`"
+            }
+        })
+    }
+}
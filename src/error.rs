@@ -2,23 +2,70 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
+use crate::downloader::SkipReason;
+
 /// Unified error type for the TikD-R application.
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Provide either a single TikTok URL or --file, not both.")]
+    #[error("Provide only one of a TikTok URL, --file, or --tiktok-export.")]
     InputConflict,
-    #[error("Provide a TikTok URL or --file with URLs to download.")]
+    #[error("Provide a TikTok URL, --file with URLs, or --tiktok-export to download.")]
     MissingInput,
     #[error("Invalid TikTok URL: {0}")]
     InvalidUrl(String),
+    #[error("Invalid request header: {0}")]
+    InvalidHeader(String),
+    #[error("Invalid proxy: {0}")]
+    InvalidProxy(String),
     #[error("No TikTok URLs found in file: {0}")]
     EmptyUrlFile(PathBuf),
+    #[error("No checksum manifest in {}; download with --write-checksums to create one.", .0.display())]
+    NoChecksums(PathBuf),
+    #[error("Invalid batch file line {line}: {message}")]
+    InvalidBatchLine { line: usize, message: String },
+    #[error("{count} of the given URLs are not TikTok links; fix them or pass --skip-invalid to download the rest.")]
+    InvalidEntries { count: usize },
+    #[error("Video {0} is age-restricted; log in and pass --session-cookie to download it.")]
+    LoginRequired(String),
+    #[error("Login failed: {0}")]
+    Login(String),
     #[error("Unable to locate TikTok video download URL from page.")]
     VideoUrlNotFound,
     #[error("Download summary: {succeeded} succeeded, {failed} failed.")]
     DownloadSummary { succeeded: usize, failed: usize },
     #[error("Unsupported stream configuration: {0}")]
     UnsupportedStream(String),
+    #[error("Could not merge separate audio into the video: {0}")]
+    Mux(String),
+    #[error("Download budget of {limit} bytes exhausted; not starting further downloads.")]
+    ByteBudgetExhausted { limit: u64 },
+    #[error("Run time budget of {}s elapsed; not starting further downloads.", limit.as_secs())]
+    RuntimeBudgetExhausted { limit: std::time::Duration },
+    #[error(
+        "Archive holds {size} bytes, over the {limit}-byte quota; not starting further downloads."
+    )]
+    ArchiveQuotaExceeded { limit: u64, size: u64 },
+    #[error("Transfer stalled: no data received for {}s.", timeout.as_secs())]
+    Stalled { timeout: std::time::Duration },
+    #[error("Transfer too slow: {bytes_per_sec} bytes/s is below the configured minimum.")]
+    TooSlow { bytes_per_sec: u64 },
+    #[error("Transfer ended early: received {received} of {expected} bytes.")]
+    Truncated { expected: u64, received: u64 },
+    #[error("Output path {} cannot be shortened to fit within {limit} bytes.", path.display())]
+    PathTooLong { path: PathBuf, limit: usize },
+    #[error("Skipped: {detail}")]
+    Skipped { reason: SkipReason, detail: String },
+    #[error("Same output as {first_url}: {}", path.display())]
+    DuplicateOutput { path: PathBuf, first_url: String },
+    #[error("Same output as {first_url}, which failed: {}", path.display())]
+    DuplicateOfFailed { path: PathBuf, first_url: String },
+    #[error("Download cancelled.")]
+    Cancelled,
+    /// A request or chunk dropped by `--simulate-network`. Only builds with the
+    /// `simulate-network` feature produce it, but the variant always exists so that
+    /// matching on `Error` does not depend on the feature set.
+    #[error("Simulated packet loss.")]
+    SimulatedLoss,
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -27,4 +74,85 @@ pub enum Error {
     Parsing(#[from] serde_json::Error),
 }
 
+impl Error {
+    /// Whether the download was never started because a run budget ran out.
+    pub fn is_budget_exhausted(&self) -> bool {
+        matches!(
+            self,
+            Error::ByteBudgetExhausted { .. }
+                | Error::RuntimeBudgetExhausted { .. }
+                | Error::ArchiveQuotaExceeded { .. }
+        )
+    }
+}
+
+/// Broad cause of a failure, for grouping a batch's failures in its summary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorClass {
+    /// The page did not yield usable video metadata.
+    Extraction,
+    /// Requests failed, stalled, or ended early.
+    Network,
+    /// The media came in a form that could not be saved.
+    UnsupportedStream,
+    /// Writing to disk failed.
+    Filesystem,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::Extraction => "extraction",
+            ErrorClass::Network => "network",
+            ErrorClass::UnsupportedStream => "unsupported stream",
+            ErrorClass::Filesystem => "filesystem",
+            ErrorClass::Other => "other",
+        }
+    }
+}
+
+impl Error {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Error::InvalidUrl(_)
+            | Error::LoginRequired(_)
+            | Error::VideoUrlNotFound
+            | Error::Parsing(_) => ErrorClass::Extraction,
+            Error::Network(_)
+            | Error::Stalled { .. }
+            | Error::TooSlow { .. }
+            | Error::Truncated { .. } => ErrorClass::Network,
+            Error::SimulatedLoss => ErrorClass::Network,
+            Error::UnsupportedStream(_) | Error::Mux(_) => ErrorClass::UnsupportedStream,
+            Error::Io(_) | Error::PathTooLong { .. } => ErrorClass::Filesystem,
+            Error::InputConflict
+            | Error::MissingInput
+            | Error::InvalidHeader(_)
+            | Error::InvalidProxy(_)
+            | Error::EmptyUrlFile(_)
+            | Error::NoChecksums(_)
+            | Error::InvalidBatchLine { .. }
+            | Error::InvalidEntries { .. }
+            | Error::Login(_)
+            | Error::DownloadSummary { .. }
+            | Error::ByteBudgetExhausted { .. }
+            | Error::RuntimeBudgetExhausted { .. }
+            | Error::ArchiveQuotaExceeded { .. }
+            | Error::Skipped { .. }
+            | Error::DuplicateOutput { .. }
+            | Error::DuplicateOfFailed { .. }
+            | Error::Cancelled => ErrorClass::Other,
+        }
+    }
+
+    /// Host of the request that failed, when the error came from one.
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Error::Network(inner) => inner.url()?.host_str(),
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,159 @@
+//! Archive metadata export for spreadsheets and dataframes.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::downloader::{yt_dlp_archive_line, ArchivedVideo};
+
+/// Output format of `tikd-r export`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line (`pandas.read_json(path, lines=True)`).
+    Jsonl,
+    /// A yt-dlp `--download-archive` file: `tiktok <video id>`, once per video ID.
+    ///
+    /// Only videos at paths the downloader could have chosen are listed, since other
+    /// file names are no TikTok video ID.
+    YtDlp,
+}
+
+const CSV_HEADER: &str =
+    "video_id,author,path,bytes,modified_unix,upload_date,view_count,like_count";
+
+/// Write one record per video in `format`.
+pub fn write_videos(
+    videos: &[ArchivedVideo],
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            writeln!(out, "{CSV_HEADER}")?;
+            for video in videos {
+                let count = |count: Option<u64>| count.map(|n| n.to_string()).unwrap_or_default();
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(&video.video_id),
+                    csv_field(&video.author),
+                    csv_field(&video.path.to_string_lossy()),
+                    video.bytes,
+                    video.modified_unix,
+                    video.upload_date.as_deref().unwrap_or_default(),
+                    count(video.view_count),
+                    count(video.like_count),
+                )?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            for video in videos {
+                serde_json::to_writer(&mut *out, video)?;
+                writeln!(out)?;
+            }
+        }
+        ExportFormat::YtDlp => {
+            let mut seen = HashSet::new();
+            let tiktok_id = |video: &&ArchivedVideo| {
+                video.recognized
+                    && !video.video_id.is_empty()
+                    && video.video_id.bytes().all(|b| b.is_ascii_digit())
+            };
+            for video in videos.iter().filter(tiktok_id) {
+                if seen.insert(video.video_id.as_str()) {
+                    writeln!(out, "{}", yt_dlp_archive_line(&video.video_id))?;
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Quote a field when it contains a delimiter, quote, or line break (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn video(author: &str) -> ArchivedVideo {
+        ArchivedVideo {
+            video_id: "7".into(),
+            author: author.into(),
+            path: PathBuf::from(format!("{author}/7.mp4")),
+            bytes: 42,
+            modified_unix: 1_700_000_000,
+            upload_date: None,
+            view_count: None,
+            like_count: None,
+            recognized: true,
+        }
+    }
+
+    #[test]
+    fn csv_quotes_awkward_fields() {
+        let mut out = Vec::new();
+        let counted = ArchivedVideo {
+            upload_date: Some("20250101".into()),
+            view_count: Some(900),
+            like_count: Some(120),
+            ..video("plain")
+        };
+        write_videos(&[counted, video("a,\"b\"")], ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "video_id,author,path,bytes,modified_unix,upload_date,view_count,like_count\n\
+             7,plain,plain/7.mp4,42,1700000000,20250101,900,120\n\
+             7,\"a,\"\"b\"\"\",\"a,\"\"b\"\"/7.mp4\",42,1700000000,,,\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_writes_one_object_per_line() {
+        let mut out = Vec::new();
+        write_videos(&[video("a"), video("b")], ExportFormat::Jsonl, &mut out).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["author"], "b");
+        assert_eq!(lines[1]["bytes"], 42);
+    }
+
+    #[test]
+    fn yt_dlp_archive_lists_each_id_once() {
+        let mut out = Vec::new();
+        write_videos(&[video("a"), video("b")], ExportFormat::YtDlp, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "tiktok 7\n");
+    }
+
+    #[test]
+    fn yt_dlp_archive_skips_unrecognized_files() {
+        let holiday = ArchivedVideo {
+            video_id: "holiday".into(),
+            path: PathBuf::from("a/holiday.mp4"),
+            recognized: false,
+            ..video("a")
+        };
+        let stray = ArchivedVideo {
+            video_id: "123".into(),
+            path: PathBuf::from("a/123.mp4"),
+            recognized: false,
+            ..video("a")
+        };
+        let mut out = Vec::new();
+        write_videos(&[holiday, stray, video("b")], ExportFormat::YtDlp, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "tiktok 7\n");
+    }
+}
@@ -0,0 +1,369 @@
+//! The optional parts of an item object: the author's profile, counters, music, covers,
+//! hashtags, stickers, and caption and subtitle tracks.
+
+use serde_json::Value;
+
+use super::{string_field, u64_field, AuthorProfile, MusicInfo, SubtitleTrack, VideoStats};
+
+/// Details from an `author` object and the item's `authorStats`.
+pub(super) fn author_profile(item: &Value) -> Option<AuthorProfile> {
+    let author = item.get("author").filter(|author| author.is_object())?;
+    let unique_id = string_field(author, "uniqueId")?;
+    let stats = item.get("authorStats").unwrap_or(&Value::Null);
+    let count = |key: &str| u64_field(stats, key).or_else(|| u64_field(author, key));
+
+    Some(AuthorProfile {
+        unique_id,
+        nickname: string_field(author, "nickname").unwrap_or_default(),
+        bio: string_field(author, "signature").unwrap_or_default(),
+        avatar_url: ["avatarLarger", "avatarMedium", "avatarThumb"]
+            .into_iter()
+            .find_map(|key| string_field(author, key)),
+        verified: author
+            .get("verified")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        follower_count: count("followerCount"),
+        following_count: count("followingCount"),
+        heart_count: count("heartCount").or_else(|| count("heart")),
+        video_count: count("videoCount"),
+    })
+}
+
+pub(super) fn video_stats(item: &Value) -> Option<VideoStats> {
+    let stats = item.get("stats").filter(|stats| stats.is_object());
+    let stats_v2 = item.get("statsV2").filter(|stats| stats.is_object());
+    if stats.is_none() && stats_v2.is_none() {
+        return None;
+    }
+    let count = |key: &str| {
+        [stats, stats_v2]
+            .into_iter()
+            .flatten()
+            .find_map(|stats| u64_field(stats, key))
+    };
+    Some(VideoStats {
+        play_count: count("playCount"),
+        like_count: count("diggCount"),
+        comment_count: count("commentCount"),
+        share_count: count("shareCount"),
+        collect_count: count("collectCount"),
+    })
+}
+
+pub(super) fn music_info(item: &Value) -> Option<MusicInfo> {
+    let music = item.get("music").filter(|music| music.is_object())?;
+    Some(MusicInfo {
+        id: string_field(music, "id")?,
+        title: string_field(music, "title").unwrap_or_default(),
+        author: string_field(music, "authorName").unwrap_or_default(),
+        original: music
+            .get("original")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        duration_secs: u64_field(music, "duration").filter(|&secs| secs > 0),
+        play_url: string_field(music, "playUrl"),
+    })
+}
+
+/// The feed cover and the uncropped first frame of a `video` object.
+pub(super) fn covers(video: &Value) -> (Option<String>, Option<String>) {
+    (
+        string_field(video, "cover"),
+        string_field(video, "originCover"),
+    )
+}
+
+/// `isAd` items, plus branded content flagged through the commerce fields.
+///
+/// `commerceInfo.adAuthorization` only says the creator allows their post to be used in
+/// ads, so it does not make an ordinary video promoted.
+pub(super) fn is_promoted(item: &Value) -> bool {
+    let flag = |value: Option<&Value>| match value {
+        Some(Value::Bool(flag)) => *flag,
+        Some(Value::Number(n)) => n.as_u64().is_some_and(|n| n > 0),
+        _ => false,
+    };
+    flag(item.get("isAd"))
+        || flag(item.pointer("/commerceInfo/brandedContentType"))
+        || flag(item.get("brandedContentType"))
+}
+
+/// Hashtags from `challenges`, plus any only tagged inline in `textExtra`.
+pub(super) fn hashtags(item: &Value) -> Vec<String> {
+    let challenges = item
+        .get("challenges")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|challenge| string_field(challenge, "title"));
+    let inline = item
+        .get("textExtra")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|extra| string_field(extra, "hashtagName"));
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in challenges.chain(inline) {
+        if !tags.iter().any(|known| known.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub(super) fn sticker_text(item: &Value) -> Vec<String> {
+    item.get("stickersOnItem")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|sticker| sticker.get("stickerText")?.as_array())
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter(|text| !text.trim().is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Subtitle languages from `subtitleInfos`, falling back to the newer `claInfo` captions.
+pub(super) fn caption_languages(video: &Value) -> Vec<String> {
+    let subtitles = video
+        .get("subtitleInfos")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|info| string_field(info, "LanguageCodeName"));
+    let captions = video
+        .pointer("/claInfo/captionInfos")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|info| string_field(info, "language"));
+
+    let mut languages: Vec<String> = Vec::new();
+    for language in subtitles.chain(captions) {
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+    languages
+}
+
+/// `subtitleInfos` and then `claInfo` tracks in WebVTT, the first one offered per language.
+pub(super) fn subtitle_tracks(video: &Value) -> Vec<SubtitleTrack> {
+    let is_webvtt = |format: Option<String>| {
+        format
+            .as_deref()
+            .unwrap_or("webvtt")
+            .eq_ignore_ascii_case("webvtt")
+    };
+    let subtitles = video
+        .get("subtitleInfos")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|info| is_webvtt(string_field(info, "Format")))
+        .filter_map(|info| {
+            Some(SubtitleTrack {
+                language: string_field(info, "LanguageCodeName")?,
+                url: string_field(info, "Url")?,
+                // `Source` is `ASR` (speech recognition) or `MT` (translation) unless the
+                // creator uploaded the track.
+                auto_generated: string_field(info, "Source")
+                    .is_some_and(|source| matches!(source.as_str(), "ASR" | "MT")),
+            })
+        });
+    let captions = video
+        .pointer("/claInfo/captionInfos")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|info| is_webvtt(string_field(info, "captionFormat")))
+        .filter_map(|info| {
+            let url = string_field(info, "url").or_else(|| {
+                info.get("urlList")?
+                    .as_array()?
+                    .iter()
+                    .find_map(Value::as_str)
+                    .map(ToOwned::to_owned)
+            })?;
+            Some(SubtitleTrack {
+                language: string_field(info, "language")?,
+                url,
+                auto_generated: info
+                    .get("isAutoGen")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+            })
+        });
+
+    let mut tracks: Vec<SubtitleTrack> = Vec::new();
+    for track in subtitles.chain(captions) {
+        if !tracks.iter().any(|known| known.language == track.language) {
+            tracks.push(track);
+        }
+    }
+    tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::extract::{build_descriptor_from_value, AuthorProfile, MusicInfo, VideoStats};
+
+    #[test]
+    fn caption_and_language_metadata_is_collected() {
+        let item: Value = serde_json::from_str(
+            r#"{"id":"7551","desc":"ทำอาหาร #food #Recipe","createTime":"1735689600","challenges":[{"title":"food"}],"textExtra":[{"hashtagName":"FOOD"},{"hashtagName":"Recipe"}],"textLanguage":"th","stickersOnItem":[{"stickerType":4,"stickerText":["สวัสดี"," "]}],"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8","subtitleInfos":[{"LanguageCodeName":"tha-TH"},{"LanguageCodeName":"eng-US"}],"claInfo":{"captionInfos":[{"language":"eng-US"},{"language":"jpn-JP"}]}}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&item, "https://www.tiktok.com/@user/video/7551").unwrap();
+        assert_eq!(descriptor.description, "ทำอาหาร #food #Recipe");
+        assert_eq!(descriptor.hashtags, vec!["food", "Recipe"]);
+        assert!(!descriptor.is_ad);
+        assert_eq!(descriptor.text_language.as_deref(), Some("th"));
+        assert_eq!(descriptor.sticker_text, vec!["สวัสดี"]);
+        assert_eq!(
+            descriptor.caption_languages,
+            vec!["tha-TH", "eng-US", "jpn-JP"]
+        );
+        assert!(
+            descriptor.subtitles.is_empty(),
+            "tracks without URLs can't be downloaded"
+        );
+        assert_eq!(descriptor.create_time, Some(1_735_689_600));
+    }
+
+    #[test]
+    fn stats_and_music_are_collected() {
+        let item: Value = serde_json::from_str(
+            r#"{"id":"7551","stats":{"diggCount":120,"playCount":4000},"statsV2":{"diggCount":"120","playCount":"4000","commentCount":"7","shareCount":"3","collectCount":"9"},"music":{"id":"6800","title":"original sound - chef","authorName":"chef","original":true,"duration":15,"playUrl":"https://sf16.tiktokcdn.com/m.mp3"},"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8","duration":15,"cover":"https://p16.tiktokcdn.com/c.image","originCover":"https://p16.tiktokcdn.com/o.image"}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&item, "https://www.tiktok.com/@chef/video/7551").unwrap();
+        assert_eq!(descriptor.duration_secs, Some(15));
+        assert_eq!(
+            descriptor.cover_url.as_deref(),
+            Some("https://p16.tiktokcdn.com/c.image")
+        );
+        assert_eq!(
+            descriptor.origin_cover_url.as_deref(),
+            Some("https://p16.tiktokcdn.com/o.image")
+        );
+        assert_eq!(
+            descriptor.stats,
+            Some(VideoStats {
+                play_count: Some(4000),
+                like_count: Some(120),
+                comment_count: Some(7),
+                share_count: Some(3),
+                collect_count: Some(9),
+            })
+        );
+        assert_eq!(
+            descriptor.music,
+            Some(MusicInfo {
+                id: "6800".into(),
+                title: "original sound - chef".into(),
+                author: "chef".into(),
+                original: true,
+                duration_secs: Some(15),
+                play_url: Some("https://sf16.tiktokcdn.com/m.mp3".into()),
+            })
+        );
+
+        let bare: Value = serde_json::from_str(
+            r#"{"id":"1","video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&bare, "https://www.tiktok.com/@chef/video/1").unwrap();
+        assert_eq!((descriptor.stats, descriptor.music), (None, None));
+    }
+
+    #[test]
+    fn subtitle_tracks_are_collected_once_per_language() {
+        let item: Value = serde_json::from_str(
+            r#"{"id":"7551","video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8","subtitleInfos":[{"LanguageCodeName":"tha-TH","Url":"https://v16.tiktokcdn.com/tha.vtt","Format":"webvtt","Source":"ASR"},{"LanguageCodeName":"eng-US","Url":"https://v16.tiktokcdn.com/eng.srt","Format":"srt"}],"claInfo":{"captionInfos":[{"language":"tha-TH","url":"https://v16.tiktokcdn.com/cla-tha.vtt"},{"language":"eng-US","urlList":["https://v16.tiktokcdn.com/cla-eng.vtt"],"isAutoGen":false},{"language":"jpn-JP","url":"https://v16.tiktokcdn.com/cla-jpn.vtt","isAutoGen":true}]}}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&item, "https://www.tiktok.com/@user/video/7551").unwrap();
+        let tracks: Vec<_> = descriptor
+            .subtitles
+            .iter()
+            .map(|track| {
+                (
+                    track.language.as_str(),
+                    track.url.as_str(),
+                    track.auto_generated,
+                )
+            })
+            .collect();
+        assert_eq!(
+            tracks,
+            vec![
+                ("tha-TH", "https://v16.tiktokcdn.com/tha.vtt", true),
+                ("eng-US", "https://v16.tiktokcdn.com/cla-eng.vtt", false),
+                ("jpn-JP", "https://v16.tiktokcdn.com/cla-jpn.vtt", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn author_profile_reads_author_and_stats() {
+        let item: Value = serde_json::from_str(
+            r#"{"id":"7551","author":{"uniqueId":"chef","nickname":"Chef","signature":"recipes daily","verified":true,"avatarThumb":"https://p16.tiktokcdn.com/thumb.jpeg","avatarLarger":"https://p16.tiktokcdn.com/large.jpeg"},"authorStats":{"followerCount":1200,"followingCount":"30","heart":99000,"videoCount":41},"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&item, "https://www.tiktok.com/@chef/video/7551").unwrap();
+        assert_eq!(
+            descriptor.author_profile,
+            Some(AuthorProfile {
+                unique_id: "chef".into(),
+                nickname: "Chef".into(),
+                bio: "recipes daily".into(),
+                avatar_url: Some("https://p16.tiktokcdn.com/large.jpeg".into()),
+                verified: true,
+                follower_count: Some(1200),
+                following_count: Some(30),
+                heart_count: Some(99000),
+                video_count: Some(41),
+            })
+        );
+
+        let inline: Value = serde_json::from_str(
+            r#"{"id":"1","author":"chef","video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            build_descriptor_from_value(&inline, "https://www.tiktok.com/@chef/video/1");
+        assert_eq!(descriptor.unwrap().author_profile, None);
+    }
+
+    #[test]
+    fn ad_and_branded_items_are_flagged() {
+        let share_url = "https://www.tiktok.com/@brand/video/1";
+        for item in [
+            r#"{"id":"1","isAd":true,"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+            r#"{"id":"1","commerceInfo":{"brandedContentType":2},"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+        ] {
+            let item: Value = serde_json::from_str(item).unwrap();
+            assert!(build_descriptor_from_value(&item, share_url).unwrap().is_ad);
+        }
+        let organic: Value = serde_json::from_str(
+            r#"{"id":"1","isAd":false,"commerceInfo":{"adAuthorization":true},"video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}"#,
+        )
+        .unwrap();
+        assert!(
+            !build_descriptor_from_value(&organic, share_url)
+                .unwrap()
+                .is_ad
+        );
+    }
+}
@@ -0,0 +1,842 @@
+//! I/O-free extraction of video metadata from share pages and API responses.
+//!
+//! Nothing here touches the network, the file system or the clock, and it only needs the
+//! HTML, JSON and regex parsers, so `cargo build --lib --no-default-features --target
+//! wasm32-unknown-unknown` builds it alone for use in a browser extension or worker.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::Value;
+use url::Url;
+
+use crate::html::Page;
+
+mod details;
+
+use details::{
+    author_profile, caption_languages, covers, hashtags, is_promoted, music_info, sticker_text,
+    subtitle_tracks, video_stats,
+};
+
+/// Information needed to perform the actual media download.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VideoDescriptor {
+    pub video_id: String,
+    pub download_url: Option<String>,
+    /// Alternate CDN URLs for the binary download, best quality first.
+    #[serde(default)]
+    pub mirror_urls: Vec<String>,
+    pub play_url: Option<String>,
+    pub author: String,
+    /// Caption text, including any hashtags.
+    #[serde(default)]
+    pub description: String,
+    /// Hashtag names without the leading `#`.
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    /// Marked by TikTok as an ad or paid promotion.
+    #[serde(default)]
+    pub is_ad: bool,
+    /// Language TikTok detected for the caption (`textLanguage`), e.g. `en`.
+    #[serde(default)]
+    pub text_language: Option<String>,
+    /// Text of the stickers overlaid on the video.
+    #[serde(default)]
+    pub sticker_text: Vec<String>,
+    /// Languages of the available subtitle tracks, e.g. `eng-US`.
+    #[serde(default)]
+    pub caption_languages: Vec<String>,
+    /// WebVTT subtitle tracks that can be downloaded, one per language.
+    #[serde(default)]
+    pub subtitles: Vec<SubtitleTrack>,
+    /// Creator details, when the page embeds a full author object.
+    #[serde(default)]
+    pub author_profile: Option<AuthorProfile>,
+    /// When the video was posted (`createTime`), in seconds since the Unix epoch.
+    #[serde(default)]
+    pub create_time: Option<u64>,
+    /// Length of the video in seconds.
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// Engagement counts as they were when the item was scraped.
+    #[serde(default)]
+    pub stats: Option<VideoStats>,
+    /// The sound the video uses.
+    #[serde(default)]
+    pub music: Option<MusicInfo>,
+    /// Static cover image shown in feeds (`video.cover`).
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    /// Uncropped first frame at full size (`video.originCover`).
+    #[serde(default)]
+    pub origin_cover_url: Option<String>,
+    /// When the media URLs were extracted, in seconds since the Unix epoch. Signed URLs
+    /// stop working a few hours later.
+    #[serde(default)]
+    pub extracted_unix: Option<u64>,
+}
+
+/// Creator details as they were when the item was scraped.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuthorProfile {
+    pub unique_id: String,
+    pub nickname: String,
+    /// Profile bio (`signature`).
+    pub bio: String,
+    /// Largest avatar image offered.
+    pub avatar_url: Option<String>,
+    pub verified: bool,
+    pub follower_count: Option<u64>,
+    pub following_count: Option<u64>,
+    /// Total likes across the creator's videos.
+    pub heart_count: Option<u64>,
+    pub video_count: Option<u64>,
+}
+
+/// A downloadable WebVTT subtitle track (`subtitleInfos` or `claInfo.captionInfos`).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SubtitleTrack {
+    /// TikTok language code, e.g. `eng-US`.
+    pub language: String,
+    pub url: String,
+    /// Speech recognition or machine translation rather than the creator's own captions.
+    pub auto_generated: bool,
+}
+
+/// Engagement counts of a video (`stats`, or the string-valued `statsV2`).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VideoStats {
+    pub play_count: Option<u64>,
+    /// Likes (`diggCount`).
+    pub like_count: Option<u64>,
+    pub comment_count: Option<u64>,
+    pub share_count: Option<u64>,
+    /// Saves to favorites (`collectCount`).
+    pub collect_count: Option<u64>,
+}
+
+/// Sound of a video (`music`).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MusicInfo {
+    pub id: String,
+    pub title: String,
+    /// Credited artist (`authorName`).
+    pub author: String,
+    /// Recorded for this video rather than picked from the sound library.
+    pub original: bool,
+    pub duration_secs: Option<u64>,
+    pub play_url: Option<String>,
+}
+
+/// Whether an archived video is still published, as its share page reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteStatus {
+    Available,
+    /// Deleted or taken down: an HTTP 404, or `statusCode` 10204.
+    Removed,
+    /// The post or its account was made private (`statusCode` 10216 or 10222).
+    Private,
+    /// Withheld with another TikTok `statusCode`, such as a regional block.
+    Unavailable(i64),
+}
+
+impl RemoteStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemoteStatus::Available => "available",
+            RemoteStatus::Removed => "removed",
+            RemoteStatus::Private => "private",
+            RemoteStatus::Unavailable(_) => "unavailable",
+        }
+    }
+}
+
+/// The `webapp.video-detail` scope of a share page's rehydration data.
+fn video_detail(html: &str) -> Option<Value> {
+    let raw_json = Page::parse(html).script("__UNIVERSAL_DATA_FOR_REHYDRATION__")?;
+    let mut value: Value = serde_json::from_str(&raw_json).ok()?;
+    Some(
+        value
+            .pointer_mut("/__DEFAULT_SCOPE__/webapp.video-detail")?
+            .take(),
+    )
+}
+
+/// Availability from a share page's `statusCode`, or from it carrying the item at all.
+pub fn status_from_page(html: &str, share_url: &str) -> Option<RemoteStatus> {
+    let code = video_detail(html)
+        .and_then(|detail| detail.get("statusCode")?.as_i64())
+        .unwrap_or(0);
+    match code {
+        0 if login_wall_video_id(html, share_url).is_some()
+            || parse_share_page(html, share_url).is_some() =>
+        {
+            Some(RemoteStatus::Available)
+        }
+        0 => None,
+        10204 => Some(RemoteStatus::Removed),
+        10216 | 10222 => Some(RemoteStatus::Private),
+        code => Some(RemoteStatus::Unavailable(code)),
+    }
+}
+
+/// Video ID of a share page that only shows the 18+ login wall.
+pub fn login_wall_video_id(html: &str, share_url: &str) -> Option<String> {
+    let detail = video_detail(html)?;
+    let item = detail.get("itemInfo")?.get("itemStruct")?;
+
+    let classified = item
+        .get("isContentClassified")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if !classified || build_descriptor_from_value(item, share_url).is_some() {
+        return None;
+    }
+
+    item.get("id")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+        .or_else(|| guess_video_id(share_url))
+}
+
+/// Descriptor from an `api/item/detail` response.
+pub fn parse_item_detail(detail: &Value, share_url: &str) -> Option<VideoDescriptor> {
+    let item = detail.get("itemInfo")?.get("itemStruct")?;
+    build_descriptor_from_value(item, share_url).map(unescape_urls)
+}
+
+/// Pull the TikTok link out of share text such as
+/// `"Check out this video! https://vt.tiktok.com/ZSabc/ #fyp"`.
+///
+/// A bare link is returned unchanged, and links without a scheme get `https://`.
+pub fn extract_share_url(text: &str) -> Option<String> {
+    static TIKTOK_URL: OnceLock<Regex> = OnceLock::new();
+    let pattern = TIKTOK_URL.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:https?://)?(?:[a-z0-9-]+\.)*tiktok\.com(?:/[^\s<>\x22'`]*)?")
+            .expect("TikTok URL pattern is valid")
+    });
+
+    let found = pattern
+        .find(text.trim())?
+        .as_str()
+        .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}']);
+    if found.contains("://") {
+        Some(found.to_string())
+    } else {
+        Some(format!("https://{found}"))
+    }
+}
+
+/// Whether `share_url` is a redirecting short link (`vm.`/`vt.tiktok.com/...` or
+/// `/t/...`) rather than a page that carries the video itself.
+pub fn is_short_link(share_url: &str) -> bool {
+    let Ok(url) = Url::parse(share_url) else {
+        return false;
+    };
+    let short_host = url.host_str().is_some_and(|host| {
+        let host = host.to_ascii_lowercase();
+        host == "vm.tiktok.com" || host == "vt.tiktok.com"
+    });
+    short_host || url.path().starts_with("/t/")
+}
+
+/// Descriptor from a share page's embedded state, with media URLs unescaped.
+pub fn parse_share_page(html: &str, share_url: &str) -> Option<VideoDescriptor> {
+    let document = Page::parse(html);
+
+    parse_universal_data(&document, share_url)
+        .or_else(|| parse_sigi_state(&document, share_url))
+        .or_else(|| parse_next_data(&document, share_url))
+        .map(unescape_urls)
+}
+
+fn unescape_urls(mut descriptor: VideoDescriptor) -> VideoDescriptor {
+    if let Some(ref mut url) = descriptor.download_url {
+        *url = unescape_media_url(url);
+    }
+    for url in descriptor.mirror_urls.iter_mut() {
+        *url = unescape_media_url(url);
+    }
+    if let Some(ref mut url) = descriptor.play_url {
+        *url = unescape_media_url(url);
+    }
+    for track in descriptor.subtitles.iter_mut() {
+        track.url = unescape_media_url(&track.url);
+    }
+    for url in [
+        descriptor.cover_url.as_mut(),
+        descriptor.origin_cover_url.as_mut(),
+        descriptor
+            .author_profile
+            .as_mut()
+            .and_then(|profile| profile.avatar_url.as_mut()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *url = unescape_media_url(url);
+    }
+    descriptor
+}
+
+/// Undo JSON string escapes (`\/`, `\uXXXX`) and HTML entities (`&amp;`, `&#38;`) left in
+/// media URLs by double-encoded page payloads.
+pub fn unescape_media_url(raw: &str) -> String {
+    decode_html_entities(&decode_json_escapes(raw))
+}
+
+fn decode_json_escapes(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some(escaped @ ('/' | '\\' | '"')) => {
+                chars.next();
+                out.push(escaped);
+            }
+            Some('u') => {
+                chars.next();
+                let hex: String = chars.by_ref().take(4).collect();
+                let Ok(unit) = u16::from_str_radix(&hex, 16) else {
+                    out.push_str("\\u");
+                    out.push_str(&hex);
+                    continue;
+                };
+                let mut units = vec![unit];
+                // A high surrogate is followed by its low half as a second `\uXXXX`.
+                if (0xD800..0xDC00).contains(&unit) {
+                    let rest: String = chars.clone().take(6).collect();
+                    if let Some(low) = rest
+                        .strip_prefix("\\u")
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                    {
+                        units.push(low);
+                        for _ in 0..6 {
+                            chars.next();
+                        }
+                    }
+                }
+                out.extend(char::decode_utf16(units).map(|c| c.unwrap_or('\u{FFFD}')));
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn decode_html_entities(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse the `/embed/v2/<id>` player page.
+/// Descriptor from the embed player page, with media URLs unescaped.
+pub fn parse_embed_page(html: &str, share_url: &str) -> Option<VideoDescriptor> {
+    parse_embed_state(html, share_url).map(unescape_urls)
+}
+
+fn parse_embed_state(html: &str, share_url: &str) -> Option<VideoDescriptor> {
+    let raw_json = Page::parse(html).script("__FRONTITY_CONNECT_STATE__")?;
+    let value: Value = serde_json::from_str(&raw_json).ok()?;
+
+    let video_data = value
+        .get("source")?
+        .get("data")?
+        .as_object()?
+        .values()
+        .find_map(|entry| entry.get("videoData"))?;
+    let item = video_data.get("itemInfos")?;
+
+    let mut urls = item
+        .get("video")?
+        .get("urls")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|url| !url.is_empty())
+        .map(ToOwned::to_owned);
+    let download_url = urls.next()?;
+    let mirror_urls = urls.collect();
+
+    let video_id = item
+        .get("id")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+        .or_else(|| guess_video_id(share_url))?;
+    let author = video_data
+        .get("authorInfos")
+        .and_then(|author| author.get("uniqueId"))
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+        .or_else(|| guess_author_id(share_url))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    Some(VideoDescriptor {
+        video_id,
+        download_url: Some(download_url),
+        mirror_urls,
+        play_url: None,
+        author,
+        ..VideoDescriptor::default()
+    })
+}
+
+fn parse_universal_data(document: &Page, share_url: &str) -> Option<VideoDescriptor> {
+    let raw_json = document.script("__UNIVERSAL_DATA_FOR_REHYDRATION__")?;
+    let value: Value = serde_json::from_str(&raw_json).ok()?;
+
+    let item = value
+        .get("__DEFAULT_SCOPE__")
+        .and_then(|scope| scope.get("webapp.video-detail"))
+        .and_then(|detail| detail.get("itemInfo"))
+        .and_then(|info| info.get("itemStruct"))?;
+
+    build_descriptor_from_value(item, share_url)
+}
+
+fn parse_sigi_state(document: &Page, share_url: &str) -> Option<VideoDescriptor> {
+    let raw_json = document.script("SIGI_STATE")?;
+    let sigi_state: Value = serde_json::from_str(&raw_json).ok()?;
+
+    let items = sigi_state.get("ItemModule")?.as_object()?;
+    resolve_descriptor_from_items(
+        items.iter().map(|(id, item)| (id.as_str(), item)),
+        share_url,
+    )
+}
+
+fn parse_next_data(document: &Page, share_url: &str) -> Option<VideoDescriptor> {
+    let raw_json = document.script("__NEXT_DATA__")?;
+    let next_data: Value = serde_json::from_str(&raw_json).ok()?;
+
+    let item = next_data.pointer("/props/pageProps/itemInfo/itemStruct")?;
+    let id = string_field(item, "id").unwrap_or_default();
+    resolve_descriptor_from_items(std::iter::once((id.as_str(), item)), share_url)
+}
+
+/// Pick the item matching the share URL, else the first item that yields a usable descriptor.
+fn resolve_descriptor_from_items<'a>(
+    items: impl Iterator<Item = (&'a str, &'a Value)>,
+    share_url: &str,
+) -> Option<VideoDescriptor> {
+    let items: Vec<_> = items.collect();
+
+    if let Some(video_id) = guess_video_id(share_url) {
+        if let Some((_, item)) = items.iter().find(|(id, _)| *id == video_id) {
+            if let Some(descriptor) = build_descriptor_from_value(item, share_url) {
+                return Some(descriptor);
+            }
+        }
+    }
+
+    items
+        .into_iter()
+        .find_map(|(_, item)| build_descriptor_from_value(item, share_url))
+}
+
+/// Read a field that TikTok sometimes encodes as a string and sometimes as a number.
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn u64_field(value: &Value, key: &str) -> Option<u64> {
+    match value.get(key)? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Build a descriptor from an item object, tolerating missing or oddly typed optional fields.
+fn build_descriptor_from_value(value: &Value, share_url: &str) -> Option<VideoDescriptor> {
+    let video_id = string_field(value, "id").or_else(|| guess_video_id(share_url))?;
+
+    let video = value.get("video").filter(|video| video.is_object())?;
+
+    let download_url = string_field(video, "downloadAddr");
+    let play_url = string_field(video, "playAddr");
+
+    let mirror_urls = collect_mirror_urls(
+        video
+            .get("bitrateInfo")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|info| {
+                let bitrate = u64_field(info, "Bitrate").unwrap_or(0);
+                let urls = info
+                    .get("PlayAddr")
+                    .and_then(|addr| addr.get("UrlList"))
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(ToOwned::to_owned)
+                    .collect();
+                (bitrate, urls)
+            })
+            .collect(),
+        download_url.as_deref(),
+    );
+
+    if download_url.is_none() && play_url.is_none() && mirror_urls.is_empty() {
+        return None;
+    }
+
+    let author = value
+        .get("author")
+        .and_then(|author| match author {
+            // Older SIGI payloads inline the handle instead of an author object.
+            Value::String(handle) if !handle.is_empty() => Some(handle.clone()),
+            other => string_field(other, "uniqueId"),
+        })
+        .or_else(|| guess_author_id(share_url))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let (cover_url, origin_cover_url) = covers(video);
+    Some(VideoDescriptor {
+        video_id,
+        download_url,
+        mirror_urls,
+        play_url,
+        author,
+        description: string_field(value, "desc").unwrap_or_default(),
+        hashtags: hashtags(value),
+        is_ad: is_promoted(value),
+        text_language: string_field(value, "textLanguage"),
+        sticker_text: sticker_text(value),
+        caption_languages: caption_languages(video),
+        subtitles: subtitle_tracks(video),
+        author_profile: author_profile(value),
+        create_time: u64_field(value, "createTime").filter(|&time| time > 0),
+        duration_secs: u64_field(video, "duration").filter(|&secs| secs > 0),
+        stats: video_stats(value),
+        music: music_info(value),
+        cover_url,
+        origin_cover_url,
+        extracted_unix: None,
+    })
+}
+
+/// Flatten per-bitrate URL lists into one mirror list, highest bitrate first and without duplicates.
+fn collect_mirror_urls(
+    mut variants: Vec<(u64, Vec<String>)>,
+    primary: Option<&str>,
+) -> Vec<String> {
+    variants.sort_by_key(|(bitrate, _)| std::cmp::Reverse(*bitrate));
+
+    let mut mirrors: Vec<String> = Vec::new();
+    for url in variants.into_iter().flat_map(|(_, urls)| urls) {
+        if url.is_empty() || Some(url.as_str()) == primary || mirrors.contains(&url) {
+            continue;
+        }
+        mirrors.push(url);
+    }
+    mirrors
+}
+
+/// Video ID from a `/video/<id>` or `/note/<id>` path, else the URL's last path segment.
+pub fn guess_video_id(share_url: &str) -> Option<String> {
+    let url = Url::parse(share_url).ok()?;
+    let segments: Vec<_> = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    for window in segments.windows(2) {
+        if let [prefix, id] = window {
+            if *prefix == "video" || *prefix == "note" {
+                return Some((*id).to_string());
+            }
+        }
+    }
+
+    segments.last().map(|value| (*value).to_string())
+}
+
+fn guess_author_id(share_url: &str) -> Option<String> {
+    let url = Url::parse(share_url).ok()?;
+    for segment in url.path_segments()? {
+        if let Some(stripped) = segment.strip_prefix('@') {
+            if !stripped.is_empty() {
+                return Some(stripped.to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classified_item_without_media_is_login_wall() {
+        let html = r#"<script id="__UNIVERSAL_DATA_FOR_REHYDRATION__">{"__DEFAULT_SCOPE__":{"webapp.video-detail":{"itemInfo":{"itemStruct":{"id":"7551","isContentClassified":true,"video":{"playAddr":""}}}}}}</script>"#;
+        let share_url = "https://www.tiktok.com/@user/video/7551";
+        assert_eq!(
+            login_wall_video_id(html, share_url).as_deref(),
+            Some("7551")
+        );
+
+        let open = html.replace("true", "false");
+        assert_eq!(login_wall_video_id(&open, share_url), None);
+    }
+
+    #[test]
+    fn remote_status_reads_share_page_status_code() {
+        let share_url = "https://www.tiktok.com/@user/video/7551";
+        let page = |detail: &str| {
+            format!(
+                r#"<script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{detail}}}}}</script>"#
+            )
+        };
+
+        let available = page(
+            r#"{"statusCode":0,"itemInfo":{"itemStruct":{"id":"7551","video":{"playAddr":"https://v16.tiktokcdn.com/p.m3u8"}}}}"#,
+        );
+        assert_eq!(
+            status_from_page(&available, share_url),
+            Some(RemoteStatus::Available)
+        );
+        assert_eq!(
+            status_from_page(&page(r#"{"statusCode":10204,"statusMsg":""}"#), share_url),
+            Some(RemoteStatus::Removed)
+        );
+        assert_eq!(
+            status_from_page(&page(r#"{"statusCode":10222}"#), share_url),
+            Some(RemoteStatus::Private)
+        );
+        assert_eq!(
+            status_from_page(&page(r#"{"statusCode":10101}"#), share_url),
+            Some(RemoteStatus::Unavailable(10101))
+        );
+        assert_eq!(status_from_page("<html>captcha</html>", share_url), None);
+    }
+
+    #[test]
+    fn item_detail_response_yields_descriptor() {
+        let detail: Value = serde_json::from_str(
+            r#"{"statusCode":0,"itemInfo":{"itemStruct":{"id":"7551","author":{"uniqueId":"user"},"video":{"downloadAddr":"https://v16.tiktokcdn.com/a?x=1\\u0026y=2"}}}}"#,
+        )
+        .unwrap();
+        let descriptor =
+            parse_item_detail(&detail, "https://www.tiktok.com/@user/video/7551").unwrap();
+        assert_eq!(descriptor.video_id, "7551");
+        assert_eq!(
+            descriptor.download_url.as_deref(),
+            Some("https://v16.tiktokcdn.com/a?x=1&y=2")
+        );
+
+        let stub: Value = serde_json::from_str(
+            r#"{"itemInfo":{"itemStruct":{"id":"7551","isContentClassified":true,"video":{}}}}"#,
+        )
+        .unwrap();
+        assert!(parse_item_detail(&stub, "https://www.tiktok.com/@user/video/7551").is_none());
+    }
+
+    #[test]
+    fn share_url_extracted_from_share_text() {
+        assert_eq!(
+            extract_share_url("https://vt.tiktok.com/ZSyB3RCuJ/").as_deref(),
+            Some("https://vt.tiktok.com/ZSyB3RCuJ/")
+        );
+        assert_eq!(
+            extract_share_url("Check out this video! https://vm.tiktok.com/ZMabc123/ #fyp #travel")
+                .as_deref(),
+            Some("https://vm.tiktok.com/ZMabc123/")
+        );
+        assert_eq!(
+            extract_share_url("watch www.tiktok.com/@user/video/123.").as_deref(),
+            Some("https://www.tiktok.com/@user/video/123")
+        );
+        assert_eq!(extract_share_url("https://nottiktok.com/video/1"), None);
+        assert_eq!(extract_share_url("https://example.com/video/1"), None);
+    }
+
+    #[test]
+    fn short_links_recognized() {
+        assert!(is_short_link("https://vt.tiktok.com/ZSyB3RCuJ/"));
+        assert!(is_short_link("https://VM.tiktok.com/ZMabc123/"));
+        assert!(is_short_link("https://www.tiktok.com/t/ZTabc/"));
+        assert!(!is_short_link("https://www.tiktok.com/@user/video/123"));
+        assert!(!is_short_link("not a url"));
+    }
+
+    #[test]
+    fn parse_descriptor_from_sigi_state() {
+        let html = include_str!("../../tests/fixtures/sample_sigi_state.html");
+        let document = Page::parse(html);
+        let descriptor =
+            parse_sigi_state(&document, "https://www.tiktok.com/@user/video/1234567890");
+        assert!(descriptor.is_some());
+        let descriptor = descriptor.unwrap();
+        assert_eq!(descriptor.video_id, "1234567890");
+        assert!(descriptor
+            .download_url
+            .as_deref()
+            .unwrap()
+            .contains("example.com"));
+        assert_eq!(descriptor.author, "sigi_author");
+    }
+
+    #[test]
+    fn parse_descriptor_from_universal_data() {
+        let html = include_str!("../../tests/fixtures/sample_universal_data.html");
+        let document = Page::parse(html);
+        let descriptor =
+            parse_universal_data(&document, "https://www.tiktok.com/@user/video/9876543210");
+        assert!(descriptor.is_some());
+        let descriptor = descriptor.unwrap();
+        assert_eq!(descriptor.video_id, "9876543210");
+        assert!(descriptor
+            .download_url
+            .as_deref()
+            .unwrap()
+            .contains("example.com"));
+        assert_eq!(descriptor.author, "sample_author");
+    }
+
+    #[test]
+    fn parse_descriptor_from_embed_page() {
+        let html = include_str!("../../tests/fixtures/sample_embed_v2.html");
+        let descriptor =
+            parse_embed_page(html, "https://www.tiktok.com/@user/video/5555555555").unwrap();
+        assert_eq!(descriptor.video_id, "5555555555");
+        assert_eq!(descriptor.author, "embed_author");
+        assert!(descriptor
+            .download_url
+            .as_deref()
+            .unwrap()
+            .starts_with("https://example.com/embed.mp4"));
+        assert_eq!(
+            descriptor.mirror_urls,
+            vec!["https://mirror.example.com/embed.mp4"]
+        );
+    }
+
+    #[test]
+    fn malformed_fields_do_not_discard_sigi_items() {
+        let html = r#"<script id="SIGI_STATE">{"ItemModule":{
+            "1":{"id":"1","video":"unavailable"},
+            "2":{"id":2,"author":"inline_author","stats":{"playCount":"n/a"},
+                 "video":{"playAddr":null,"downloadAddr":"https://example.com/2.mp4",
+                          "bitrateInfo":[{"Bitrate":"900","PlayAddr":{"UrlList":["https://m.example.com/2.mp4"]}},{"Bitrate":{}}]}}
+        }}</script>"#;
+        let document = Page::parse(html);
+        let descriptor =
+            parse_sigi_state(&document, "https://www.tiktok.com/@user/video/1").unwrap();
+
+        assert_eq!(descriptor.video_id, "2");
+        assert_eq!(descriptor.author, "inline_author");
+        assert_eq!(descriptor.play_url, None);
+        assert_eq!(descriptor.mirror_urls, vec!["https://m.example.com/2.mp4"]);
+    }
+
+    #[test]
+    fn media_urls_are_fully_unescaped() {
+        assert_eq!(
+            unescape_media_url(r"https:\/\/v16.tiktokcdn.com\/v.mp4?a=1\u0026b=2\u002Fc"),
+            "https://v16.tiktokcdn.com/v.mp4?a=1&b=2/c"
+        );
+        assert_eq!(
+            unescape_media_url("https://cdn.example.com/v.mp4?a=1&amp;b=2&#38;c=3&#x26;d=4"),
+            "https://cdn.example.com/v.mp4?a=1&b=2&c=3&d=4"
+        );
+        assert_eq!(
+            unescape_media_url(r"https://cdn.example.com/\ud83c\udfb5?q=a&b"),
+            "https://cdn.example.com/\u{1F3B5}?q=a&b"
+        );
+        assert_eq!(
+            unescape_media_url("https://cdn.example.com/v.mp4?x=1&unknown;y=2"),
+            "https://cdn.example.com/v.mp4?x=1&unknown;y=2"
+        );
+    }
+
+    #[test]
+    fn mirror_urls_follow_bitrate_order() {
+        let item = serde_json::json!({
+            "id": "42",
+            "video": {
+                "downloadAddr": "https://a.example.com/v.mp4",
+                "bitrateInfo": [
+                    {"Bitrate": 500, "PlayAddr": {"UrlList": ["https://low.example.com/v.mp4"]}},
+                    {"Bitrate": 900, "PlayAddr": {"UrlList": [
+                        "https://a.example.com/v.mp4",
+                        "https://b.example.com/v.mp4"
+                    ]}}
+                ]
+            },
+            "author": {"uniqueId": "someone"}
+        });
+
+        let descriptor =
+            build_descriptor_from_value(&item, "https://www.tiktok.com/@someone/video/42").unwrap();
+        assert_eq!(
+            descriptor.mirror_urls,
+            vec![
+                "https://b.example.com/v.mp4".to_string(),
+                "https://low.example.com/v.mp4".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn guess_id_handles_numeric_path() {
+        let id = guess_video_id("https://www.tiktok.com/@user/video/987654321");
+        assert_eq!(id, Some("987654321".into()));
+    }
+
+    #[test]
+    fn guess_id_falls_back_to_last_segment() {
+        let id = guess_video_id("https://www.tiktok.com/t/ZT8abcd/");
+        assert_eq!(id, Some("ZT8abcd".into()));
+    }
+}
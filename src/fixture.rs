@@ -0,0 +1,178 @@
+//! Minimized, scrubbed share-page fixtures for the scraper tests.
+//!
+//! `tikd-r fixture <URL>` fetches a live share page and keeps only the embedded JSON the
+//! parsers read, with account tokens and CDN signatures replaced by `REDACTED`.
+
+use serde_json::{Map, Value};
+
+use crate::html::Page;
+
+/// Embedded state scripts and the branches of each that the parsers read.
+const KEPT_SCRIPTS: &[(&str, &[&str])] = &[
+    (
+        "__UNIVERSAL_DATA_FOR_REHYDRATION__",
+        &["/__DEFAULT_SCOPE__/webapp.video-detail"],
+    ),
+    ("SIGI_STATE", &["/ItemModule"]),
+    ("__NEXT_DATA__", &["/props/pageProps/itemInfo"]),
+    ("__FRONTITY_CONNECT_STATE__", &["/source/data"]),
+];
+
+/// Object keys whose values identify the visitor or their session.
+const SECRET_KEYS: &[&str] = &[
+    "msToken",
+    "csrfToken",
+    "tt_chain_token",
+    "sessionid",
+    "sid_tt",
+    "wid",
+    "odinId",
+    "webIdCreatedTime",
+    "encryptedWebid",
+    "userId",
+    "device_id",
+    "deviceId",
+    "iid",
+];
+
+/// CDN query parameters that sign a URL to one viewer.
+const SECRET_QUERY_PARAMS: &[&str] = &["signature", "x-signature", "tk", "policy", "l"];
+
+const REDACTED: &str = "REDACTED";
+
+/// Reduce a share page to a fixture, or `None` if it has no recognizable state script.
+pub fn minimize_share_page(html: &str) -> Option<String> {
+    let document = Page::parse(html);
+    let mut scripts = Vec::new();
+
+    for (id, branches) in KEPT_SCRIPTS {
+        let Some(raw_json) = document.script(id) else {
+            continue;
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&raw_json) else {
+            continue;
+        };
+
+        let mut kept = Value::Object(Map::new());
+        for pointer in branches.iter() {
+            if let Some(branch) = value.pointer(pointer) {
+                insert_at(&mut kept, pointer, branch.clone());
+            }
+        }
+        scrub(&mut kept);
+        scripts.push(format!(
+            "    <script id=\"{id}\" type=\"application/json\">\n      {}\n    </script>\n",
+            // `</` cannot appear inside the script body.
+            serde_json::to_string(&kept).ok()?.replace("</", "<\\/")
+        ));
+    }
+
+    if scripts.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n  <head>\n    <meta charset=\"utf-8\" />\n{}  </head>\n  <body></body>\n</html>\n",
+        scripts.concat()
+    ))
+}
+
+/// Set `value` at a JSON pointer, creating intermediate objects.
+fn insert_at(root: &mut Value, pointer: &str, value: Value) {
+    let mut keys = pointer.split('/').skip(1).peekable();
+    let mut current = root;
+    while let Some(key) = keys.next() {
+        let Value::Object(map) = current else {
+            return;
+        };
+        if keys.peek().is_none() {
+            map.insert(key.to_string(), value);
+            return;
+        }
+        current = map
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+fn scrub(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && !child.is_null() {
+                    *child = Value::String(REDACTED.into());
+                } else {
+                    scrub(child);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub),
+        Value::String(text) if text.starts_with("http") => *text = scrub_url(text),
+        _ => {}
+    }
+}
+
+fn scrub_url(raw: &str) -> String {
+    let Ok(mut url) = url::Url::parse(raw) else {
+        return raw.to_string();
+    };
+    if url.query().is_none() {
+        return raw.to_string();
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if SECRET_QUERY_PARAMS.contains(&key.as_ref()) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_keeps_parser_branches_and_scrubs_tokens() {
+        let html = r#"<html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__">{
+            "__DEFAULT_SCOPE__":{
+                "webapp.app-context":{"csrfToken":"secret","user":{"uid":"1"}},
+                "webapp.video-detail":{"itemInfo":{"itemStruct":{"id":"42","msToken":"abc",
+                    "video":{"playAddr":"https://v16.tiktokcdn.com/v.mp4?mime_type=video_mp4&signature=s1g&tk=tt"}}}}
+            }}</script><script>analytics()</script></head><body><div>page</div></body></html>"#;
+
+        let fixture = minimize_share_page(html).unwrap();
+        assert!(!fixture.contains("webapp.app-context"));
+        assert!(!fixture.contains("secret"));
+        assert!(!fixture.contains("analytics"));
+        assert!(!fixture.contains("s1g"));
+        assert!(fixture.contains("mime_type=video_mp4"));
+        assert!(fixture.contains("\"msToken\":\"REDACTED\""));
+
+        let json: Value = serde_json::from_str(
+            &Page::parse(&fixture)
+                .script("__UNIVERSAL_DATA_FOR_REHYDRATION__")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json.pointer("/__DEFAULT_SCOPE__/webapp.video-detail/itemInfo/itemStruct/id"),
+            Some(&Value::String("42".into()))
+        );
+    }
+
+    #[test]
+    fn pages_without_state_are_rejected() {
+        assert_eq!(
+            minimize_share_page("<html><body>blocked</body></html>"),
+            None
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! The embedded state scripts of a page, the only part of its HTML the parsers read.
+//!
+//! With the `html-parser` feature the page goes through a full HTML5 parser. Without it a
+//! small scanner finds `<script id=...>` tags instead, which is enough for TikTok's pages
+//! and much smaller in a `minimal` build.
+
+/// A parsed page whose `<script>` elements can be looked up by `id`.
+#[cfg(feature = "html-parser")]
+pub(crate) struct Page(scraper::Html);
+
+#[cfg(feature = "html-parser")]
+impl Page {
+    pub(crate) fn parse(html: &str) -> Self {
+        Self(scraper::Html::parse_document(html))
+    }
+
+    /// Text of the first `<script id="{id}">`.
+    pub(crate) fn script(&self, id: &str) -> Option<String> {
+        let selector = scraper::Selector::parse(&format!("script#{id}")).ok()?;
+        let element = self.0.select(&selector).next()?;
+        Some(element.text().collect())
+    }
+}
+
+/// A page whose `<script>` elements can be looked up by `id`.
+#[cfg(not(feature = "html-parser"))]
+pub(crate) struct Page<'a>(&'a str);
+
+#[cfg(not(feature = "html-parser"))]
+impl<'a> Page<'a> {
+    pub(crate) fn parse(html: &'a str) -> Self {
+        Self(html)
+    }
+
+    /// Text of the first `<script id="{id}">`.
+    pub(crate) fn script(&self, id: &str) -> Option<String> {
+        find_script(self.0, id)
+    }
+}
+
+/// Scan `html` for the first `<script>` tag whose `id` attribute is `id` and return its
+/// contents up to the closing tag. Script contents are raw text in HTML, so nothing in
+/// them needs decoding.
+#[cfg(any(test, not(feature = "html-parser")))]
+fn find_script(html: &str, id: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find("<script") {
+        let attrs_start = from + found + "<script".len();
+        let rest = &html[attrs_start..];
+        if !rest.starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/') {
+            from = attrs_start;
+            continue;
+        }
+        let attrs_end = attrs_start + tag_end(rest)?;
+        let body_start = attrs_end + 1;
+        let body_end = lower[body_start..]
+            .find("</script")
+            .map_or(html.len(), |end| body_start + end);
+        if attribute(&html[attrs_start..attrs_end], "id") == Some(id) {
+            return Some(html[body_start..body_end].to_string());
+        }
+        from = body_end;
+    }
+    None
+}
+
+/// Offset of the `>` closing a start tag, skipping any inside quoted attribute values.
+#[cfg(any(test, not(feature = "html-parser")))]
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (idx, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Value of attribute `name` in the attribute part of a start tag.
+#[cfg(any(test, not(feature = "html-parser")))]
+fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let (attr, after) = rest.split_at(name_end);
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+                        (&value[1..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value
+                            .find(|c: char| c.is_ascii_whitespace())
+                            .unwrap_or(value.len());
+                        value.split_at(end)
+                    }
+                }
+            }
+            None => ("", after),
+        };
+        if attr.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+        rest = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanner_finds_scripts_by_id() {
+        let html = r#"<html><head><SCRIPT type="application/json" id='SIGI_STATE'>{"a":"</b>"}</script>
+            <script src="x.js" data-note="a > b"></script><scripts id="__NEXT_DATA__">no</scripts>
+            <script defer id=__NEXT_DATA__ >{"b":1}</SCRIPT></head></html>"#;
+        assert_eq!(
+            find_script(html, "SIGI_STATE").as_deref(),
+            Some(r#"{"a":"</b>"}"#)
+        );
+        assert_eq!(
+            find_script(html, "__NEXT_DATA__").as_deref(),
+            Some(r#"{"b":1}"#)
+        );
+        assert_eq!(
+            find_script(html, "__UNIVERSAL_DATA_FOR_REHYDRATION__"),
+            None
+        );
+        assert_eq!(find_script("<script id=\"x\"", "x"), None);
+    }
+
+    #[test]
+    fn scanner_agrees_with_the_page_on_fixtures() {
+        for (html, id) in [
+            (
+                include_str!("../tests/fixtures/sample_sigi_state.html"),
+                "SIGI_STATE",
+            ),
+            (
+                include_str!("../tests/fixtures/sample_universal_data.html"),
+                "__UNIVERSAL_DATA_FOR_REHYDRATION__",
+            ),
+            (
+                include_str!("../tests/fixtures/sample_embed_v2.html"),
+                "__FRONTITY_CONNECT_STATE__",
+            ),
+        ] {
+            let page = Page::parse(html).script(id);
+            assert!(page.is_some(), "{id}");
+            assert_eq!(find_script(html, id), page, "{id}");
+        }
+    }
+}
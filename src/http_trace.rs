@@ -0,0 +1,241 @@
+//! Structured spans around outgoing HTTP requests, and the `--trace-http` log built from them.
+//!
+//! Every request goes through [`send`], which runs it inside a `DEBUG` span named `http`
+//! that carries its method, host, status, `Content-Length`, and time to response headers.
+//! [`HttpTraceLayer`] turns each finished span into one JSON line, so a CDN problem can be
+//! reported with exactly the requests that hit it.
+
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::{RequestBuilder, Response};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Instrument, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::schema::HttpTraceRecord;
+
+const SPAN_NAME: &str = "http";
+
+/// Number of the next request, unique within the process.
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(1);
+
+/// Send `request` inside an `http` span, recording how it went.
+///
+/// The span ends when the response headers arrive; body bytes streamed afterwards are
+/// not timed, so `bytes` is the announced `Content-Length`.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let span = tracing::debug_span!(
+        SPAN_NAME,
+        id = NEXT_REQUEST.fetch_add(1, Ordering::Relaxed),
+        method = %request.method(),
+        host = request.url().host_str().unwrap_or_default(),
+        url = %without_query(request.url()),
+        status = tracing::field::Empty,
+        bytes = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+        error = tracing::field::Empty,
+    );
+
+    let started = Instant::now();
+    let response = client.execute(request).instrument(span.clone()).await;
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    match &response {
+        Ok(response) => {
+            span.record("status", response.status().as_u16());
+            if let Some(bytes) = response.content_length() {
+                span.record("bytes", bytes);
+            }
+        }
+        Err(err) => {
+            span.record("error", tracing::field::display(err));
+        }
+    }
+    response
+}
+
+/// Query strings carry CDN signatures and device IDs, so traces leave them out.
+fn without_query(url: &reqwest::Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+/// Writes every finished `http` span as an [`HttpTraceRecord`] JSON line.
+pub struct HttpTraceLayer {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl HttpTraceLayer {
+    /// Log to a new file at `path`, replacing any earlier trace.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(LineWriter::new(File::create(path)?)))
+    }
+
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+}
+
+impl<S> Layer<S> for HttpTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let metadata = attrs.metadata();
+        if metadata.name() != SPAN_NAME || metadata.target() != module_path!() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut record = HttpTraceRecord::started(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+        );
+        attrs.record(&mut Fields(&mut record));
+        span.extensions_mut().insert(record);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(record) = extensions.get_mut::<HttpTraceRecord>() {
+            values.record(&mut Fields(record));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(record) = span.extensions_mut().remove::<HttpTraceRecord>() else {
+            return;
+        };
+        let Ok(line) = serde_json::to_string(&record) else {
+            return;
+        };
+        let mut out = self
+            .out
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(err) = writeln!(out, "{line}") {
+            // Logging the failure would only recurse into another trace write.
+            eprintln!("Could not write HTTP trace: {err}");
+        }
+    }
+}
+
+/// Copies span fields into the record by name.
+struct Fields<'a>(&'a mut HttpTraceRecord);
+
+impl Visit for Fields<'_> {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "id" => self.0.request_id = value,
+            "status" => self.0.status = u16::try_from(value).ok(),
+            "bytes" => self.0.bytes = Some(value),
+            "duration_ms" => self.0.duration_ms = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_text(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // `%value` fields arrive here, formatted with their `Display`.
+        self.record_text(field, format!("{value:?}"));
+    }
+}
+
+impl Fields<'_> {
+    fn record_text(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "method" => self.0.method = value,
+            "host" => self.0.host = value,
+            "url" => self.0.url = value,
+            "error" => self.0.error = Some(value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn traces_requests_as_json_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 4\r\n\r\ngone")
+                .await
+                .unwrap();
+        });
+
+        let out = Shared::default();
+        let subscriber = tracing_subscriber::registry().with(HttpTraceLayer::new(out.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let client = reqwest::Client::new();
+        let response = send(client.get(format!("http://{addr}/media/1.mp4?signature=secret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        drop(response);
+
+        let log = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        let entry = &lines[0];
+        assert_eq!(entry["method"], "GET");
+        assert_eq!(entry["host"], "127.0.0.1");
+        assert_eq!(entry["url"], format!("http://{addr}/media/1.mp4"));
+        assert_eq!(entry["status"], 404);
+        assert_eq!(entry["bytes"], 4);
+        assert!(entry["duration_ms"].is_u64());
+        assert!(entry["error"].is_null());
+        crate::schema::tests::assert_matches_schema(entry, crate::schema::HTTP_TRACE_SCHEMA);
+    }
+}
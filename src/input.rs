@@ -0,0 +1,676 @@
+//! Batch URL file format.
+//!
+//! Each non-empty line holds one URL, optionally followed by `|` and comma-separated
+//! per-item options:
+//!
+//! ```text
+//! # tag: travel
+//! https://vt.tiktok.com/ZSabc/ | quality=720p, output=trips/lisbon.mp4
+//! https://www.tiktok.com/@user/video/1
+//! # tag:
+//! https://www.tiktok.com/@user/video/2 | header=X-Test: 1
+//! ```
+//!
+//! `# tag: <name>` labels every following item until the next tag directive; an empty
+//! tag clears it. Any other line starting with `#` is a comment, so plain URL lists
+//! keep working unchanged.
+//!
+//! URLs may be surrounded by other text, as in a pasted chat log; every `http(s)://`
+//! link on the line is extracted, and lines of plain prose without a link are skipped.
+//!
+//! TikTok's own "Download your data" export can be read with [`parse_tiktok_export`].
+
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::downloader::{DownloadRequest, Quality};
+use crate::error::{Error, Result};
+
+/// Parse a newline-delimited batch file into requests, folding later duplicates of the same
+/// URL into the first; see `merge_duplicate`.
+pub fn parse_batch(contents: &str) -> Result<Vec<DownloadRequest>> {
+    parse_entries(contents.lines())
+}
+
+/// Parse NUL-delimited entries, as written by `find -print0` or `xargs -0` style tools.
+pub fn parse_batch_nul(contents: &str) -> Result<Vec<DownloadRequest>> {
+    parse_entries(contents.split('\0'))
+}
+
+fn parse_entries<'a>(entries: impl Iterator<Item = &'a str>) -> Result<Vec<DownloadRequest>> {
+    let mut seen = HashMap::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut requests = Vec::new();
+
+    for (idx, raw) in entries.enumerate() {
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(value) = directive(comment, "tag") {
+                tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect();
+            }
+            continue;
+        }
+
+        let parsed = parse_line(line).map_err(|message| Error::InvalidBatchLine {
+            line: idx + 1,
+            message,
+        })?;
+        for mut request in parsed {
+            request.tags.extend(tags.iter().cloned());
+            request.line = Some(idx + 1);
+            push_unique(&mut requests, &mut seen, dedup_key(&request.url), request);
+        }
+    }
+
+    Ok(requests)
+}
+
+/// Parse one `URL [| key=value, ...]` line; the options apply to every URL found on it.
+pub fn parse_line(line: &str) -> std::result::Result<Vec<DownloadRequest>, String> {
+    let (text, options) = match line.split_once('|') {
+        Some((text, options)) => (text.trim(), Some(options)),
+        None => (line.trim(), None),
+    };
+    if text.is_empty() {
+        return Err("missing URL before options".into());
+    }
+
+    let mut urls = extract_urls(text);
+    if urls.is_empty() {
+        if text.contains(char::is_whitespace) {
+            // Chat-log chatter around the links.
+            return Ok(Vec::new());
+        }
+        // Keep bare tokens so the downloader reports them as invalid URLs.
+        urls.push(text);
+    }
+
+    let mut template = DownloadRequest::default();
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        let option = option.trim();
+        if option.is_empty() {
+            continue;
+        }
+        let (key, value) = option
+            .split_once('=')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| format!("expected key=value, got `{option}`"))?;
+
+        template = match key {
+            "quality" => template.quality(value.parse::<Quality>()?),
+            "output" => template.output(value),
+            "proxy" => template.proxy(value),
+            "tag" => template.tag(value),
+            "header" => {
+                let (name, header_value) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected header=Name: value, got `{value}`"))?;
+                template.header(name.trim(), header_value.trim())
+            }
+            other => return Err(format!("unknown option `{other}`")),
+        };
+    }
+
+    Ok(urls
+        .into_iter()
+        .map(|url| DownloadRequest {
+            url: url.to_string(),
+            ..template.clone()
+        })
+        .collect())
+}
+
+/// Write `requests` in batch file syntax, so [`parse_batch`] reads back the same URLs,
+/// overrides, and tags.
+///
+/// Tags become `# tag:` directives. The syntax has no escaping, so an option or tag whose
+/// value holds a comma or line break is left out with a warning.
+pub fn write_batch<'a>(requests: impl IntoIterator<Item = &'a DownloadRequest>) -> String {
+    let writable = |url: &str, what: &str, value: &str| {
+        let ok = !value.contains([',', '\n', '\r']);
+        if !ok {
+            tracing::warn!("Leaving {what} `{value}` of {url} out of the batch file");
+        }
+        ok
+    };
+
+    let mut out = String::new();
+    let mut tags: Vec<&str> = Vec::new();
+    for request in requests {
+        let url = request.url.as_str();
+        let request_tags: Vec<&str> = request
+            .tags
+            .iter()
+            .map(String::as_str)
+            .filter(|tag| writable(url, "tag", tag))
+            .collect();
+        if request_tags != tags {
+            tags = request_tags;
+            match tags.is_empty() {
+                true => out.push_str("# tag:\n"),
+                false => out.push_str(&format!("# tag: {}\n", tags.join(", "))),
+            }
+        }
+
+        let mut options = Vec::new();
+        if let Some(output) = &request.output {
+            options.push(("output", output.to_string_lossy().into_owned()));
+        }
+        if let Some(quality) = &request.quality {
+            options.push(("quality", quality.to_string()));
+        }
+        for (name, value) in &request.headers {
+            options.push(("header", format!("{name}: {value}")));
+        }
+        if let Some(proxy) = &request.proxy {
+            options.push(("proxy", proxy.clone()));
+        }
+        let options: Vec<String> = options
+            .into_iter()
+            .filter(|(key, value)| writable(url, key, value))
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+
+        out.push_str(url);
+        if !options.is_empty() {
+            out.push_str(" | ");
+            out.push_str(&options.join(", "));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// History sections of TikTok's data export that hold video links.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportSection {
+    /// Videos you liked ("Like List").
+    Liked,
+    /// Videos you saved ("Favorite Videos").
+    Favorites,
+    /// Videos you watched ("Video Browsing History").
+    History,
+}
+
+impl ExportSection {
+    /// Key of the list holding the section's `{Date, Link}` entries.
+    fn list_key(self) -> &'static str {
+        match self {
+            ExportSection::Liked => "ItemFavoriteList",
+            ExportSection::Favorites => "FavoriteVideoList",
+            ExportSection::History => "VideoList",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            ExportSection::Liked => "liked",
+            ExportSection::Favorites => "favorites",
+            ExportSection::History => "history",
+        }
+    }
+}
+
+/// Queue the videos listed in `sections` of a `user_data.json` export, tagged by section.
+///
+/// The lists are looked up by key wherever they sit, since the surrounding layout
+/// (`Activity` vs. `Your Activity`, capitalization of `Link`) differs between exports.
+pub fn parse_tiktok_export(json: &str, sections: &[ExportSection]) -> Result<Vec<DownloadRequest>> {
+    let data: Value = serde_json::from_str(json)?;
+    let mut seen = HashSet::new();
+    let mut requests = Vec::new();
+
+    for &section in sections {
+        let mut lists = Vec::new();
+        find_lists(&data, section.list_key(), &mut lists);
+        for entry in lists.into_iter().flatten() {
+            let link = entry
+                .get("Link")
+                .or_else(|| entry.get("link"))
+                .and_then(Value::as_str);
+            let Some(url) = link.and_then(export_link_url) else {
+                continue;
+            };
+            if seen.insert(dedup_key(&url)) {
+                requests.push(DownloadRequest::new(url).tag(section.tag()));
+            }
+        }
+    }
+
+    Ok(requests)
+}
+
+fn find_lists<'a>(value: &'a Value, key: &str, found: &mut Vec<&'a Vec<Value>>) {
+    match value {
+        Value::Object(map) => {
+            for (name, child) in map {
+                match child {
+                    Value::Array(items) if name == key => found.push(items),
+                    _ => find_lists(child, key, found),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| find_lists(item, key, found)),
+        _ => {}
+    }
+}
+
+/// Export links point at `tiktokv.com/share/video/<id>/`; rewrite them to a share page
+/// the scraper accepts.
+fn export_link_url(link: &str) -> Option<String> {
+    let url = url::Url::parse(link.trim()).ok()?;
+    let host = url.host_str()?;
+    if host == "tiktok.com" || host.ends_with(".tiktok.com") {
+        return Some(url.to_string());
+    }
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    let id = segments
+        .find(|segment| *segment == "video")
+        .and_then(|_| segments.next())?;
+    id.bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| format!("https://www.tiktok.com/@_/video/{id}"))
+}
+
+/// Every `http(s)://` link in `text`, without trailing sentence punctuation.
+pub fn extract_urls(text: &str) -> Vec<&str> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let pattern = URL.get_or_init(|| {
+        Regex::new(r#"(?i)https?://[^\s<>"'`|\x00]+"#).expect("URL pattern is valid")
+    });
+
+    pattern
+        .find_iter(text)
+        .map(|found| {
+            found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}'])
+        })
+        .collect()
+}
+
+/// A batch entry that is not a TikTok link.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidEntry {
+    /// Line of the batch file, when the entry came from one.
+    pub line: Option<usize>,
+    pub text: String,
+}
+
+/// Rewrite every URL to its canonical form and split off the entries that are not
+/// TikTok links, so bad input is reported before any network request is made. Entries
+/// that only become duplicates once canonicalized are folded into the first.
+pub fn validate_requests(
+    requests: Vec<DownloadRequest>,
+) -> (Vec<DownloadRequest>, Vec<InvalidEntry>) {
+    let mut seen = HashMap::new();
+    let mut valid = Vec::new();
+    let mut invalid = Vec::new();
+
+    for mut request in requests {
+        match canonical_url(&request.url) {
+            Some(url) => {
+                let key = dedup_key(&url);
+                request.url = url;
+                push_unique(&mut valid, &mut seen, key, request);
+            }
+            None => invalid.push(InvalidEntry {
+                line: request.line,
+                text: request.url,
+            }),
+        }
+    }
+    (valid, invalid)
+}
+
+/// Add `request` to `requests` unless an earlier entry has the same `key`, in which case
+/// the two are merged.
+fn push_unique(
+    requests: &mut Vec<DownloadRequest>,
+    seen: &mut HashMap<String, usize>,
+    key: String,
+    request: DownloadRequest,
+) {
+    match seen.get(&key) {
+        Some(&first) => merge_duplicate(&mut requests[first], request),
+        None => {
+            seen.insert(key, requests.len());
+            requests.push(request);
+        }
+    }
+}
+
+/// Fold a repeated entry into the first one for its URL. Options only the repeat sets are
+/// taken over and tags are combined; where both set an option differently, the first
+/// entry's value is kept with a warning.
+fn merge_duplicate(first: &mut DownloadRequest, repeat: DownloadRequest) {
+    let mut ignored = Vec::new();
+    if let Some(output) = repeat.output {
+        match &first.output {
+            None => first.output = Some(output),
+            Some(kept) if *kept != output => ignored.push("output"),
+            Some(_) => {}
+        }
+    }
+    if let Some(quality) = repeat.quality {
+        match first.quality {
+            None => first.quality = Some(quality),
+            Some(kept) if kept != quality => ignored.push("quality"),
+            Some(_) => {}
+        }
+    }
+    if !repeat.headers.is_empty() {
+        if first.headers.is_empty() {
+            first.headers = repeat.headers;
+        } else if first.headers != repeat.headers {
+            ignored.push("header");
+        }
+    }
+    if let Some(proxy) = repeat.proxy {
+        match &first.proxy {
+            None => first.proxy = Some(proxy),
+            Some(kept) if *kept != proxy => ignored.push("proxy"),
+            Some(_) => {}
+        }
+    }
+    if first.cancel.is_none() {
+        first.cancel = repeat.cancel;
+    }
+    for tag in repeat.tags {
+        if !first.tags.contains(&tag) {
+            first.tags.push(tag);
+        }
+    }
+
+    if !ignored.is_empty() {
+        let place = |line: Option<usize>, unknown: &str| match line {
+            Some(line) => format!("line {line}"),
+            None => unknown.to_string(),
+        };
+        tracing::warn!(
+            "{} repeats {} from {}; keeping the first entry's {}",
+            place(repeat.line, "A later entry"),
+            first.url,
+            place(first.line, "an earlier entry"),
+            ignored.join(", ")
+        );
+    }
+}
+
+/// `https://` form of a link to `tiktok.com` or one of its subdomains, without a
+/// fragment; `None` for anything else. A missing scheme is allowed.
+pub fn canonical_url(text: &str) -> Option<String> {
+    let text = text.trim();
+    let with_scheme = if text.contains("://") {
+        text.to_string()
+    } else {
+        format!("https://{text}")
+    };
+    let mut url = url::Url::parse(&with_scheme).ok()?;
+    let host = url.host_str()?;
+    let is_tiktok = host == "tiktok.com" || host.ends_with(".tiktok.com");
+    if !matches!(url.scheme(), "http" | "https") || !is_tiktok {
+        return None;
+    }
+    url.set_scheme("https").ok()?;
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+/// Key under which two spellings of the same URL compare equal.
+///
+/// Scheme and host are case-insensitive and trailing slashes are dropped; the path
+/// keeps its case because video IDs and short codes are case-sensitive.
+pub fn dedup_key(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => {
+            let mut key = format!(
+                "{}://{}{}",
+                parsed.scheme(),
+                parsed.host_str().unwrap_or_default(),
+                parsed.path().trim_end_matches('/')
+            );
+            if let Some(query) = parsed.query() {
+                key.push('?');
+                key.push_str(query);
+            }
+            key
+        }
+        Err(_) => url.trim_end_matches('/').to_string(),
+    }
+}
+
+/// Value of a `name: value` directive inside a comment, if the comment is one.
+fn directive<'a>(comment: &'a str, name: &str) -> Option<&'a str> {
+    let (key, value) = comment.split_once(':')?;
+    key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn plain_lists_still_parse() {
+        let requests = parse_batch("https://a\n\n# comment\nhttps://b\n").unwrap();
+        let urls: Vec<_> = requests.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a", "https://b"]);
+        assert!(requests
+            .iter()
+            .all(|r| !r.has_overrides() && r.tags.is_empty()));
+    }
+
+    #[test]
+    fn inline_options_become_overrides() {
+        let requests = parse_batch(
+            "https://a | quality=720p, output=clips/a.mp4, header=X-Test: 1, proxy=socks5://127.0.0.1:1080\n",
+        )
+        .unwrap();
+
+        let request = &requests[0];
+        assert_eq!(request.url, "https://a");
+        assert_eq!(request.quality, Some(Quality::MaxHeight(720)));
+        assert_eq!(request.output, Some(PathBuf::from("clips/a.mp4")));
+        assert_eq!(request.headers, vec![("X-Test".into(), "1".into())]);
+        assert_eq!(request.proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+    }
+
+    #[test]
+    fn written_batches_parse_back_the_same_requests() {
+        let batch = "# tag: travel\n\
+                     https://a | quality=720p, output=clips/a.mp4, header=X-Test: 1, \
+                     header=Referer: https://www.tiktok.com/, proxy=socks5://127.0.0.1:1080\n\
+                     https://b | tag=food\n\
+                     # tag:\n\
+                     https://c\n";
+        let requests = parse_batch(batch).unwrap();
+        let reread = parse_batch(&write_batch(&requests)).unwrap();
+
+        let fields = |requests: &[DownloadRequest]| {
+            requests
+                .iter()
+                .map(|r| {
+                    (
+                        r.url.clone(),
+                        r.output.clone(),
+                        r.quality,
+                        r.headers.clone(),
+                        r.proxy.clone(),
+                        r.tags.clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fields(&reread), fields(&requests));
+        assert_eq!(reread[1].tags, vec!["food", "travel"]);
+        assert!(reread[2].tags.is_empty());
+    }
+
+    #[test]
+    fn written_batches_drop_values_with_commas() {
+        let request = DownloadRequest::new("https://a")
+            .header("Accept", "video/mp4, */*")
+            .quality(Quality::Best)
+            .tag("a,b");
+        assert_eq!(write_batch([&request]), "https://a | quality=best\n");
+    }
+
+    #[test]
+    fn tag_directive_applies_until_cleared() {
+        let requests =
+            parse_batch("# tag: travel\nhttps://a\nhttps://b | tag=food\n# tag:\nhttps://c\n")
+                .unwrap();
+        assert_eq!(requests[0].tags, vec!["travel"]);
+        assert_eq!(requests[1].tags, vec!["food", "travel"]);
+        assert!(requests[2].tags.is_empty());
+    }
+
+    #[test]
+    fn urls_are_extracted_from_surrounding_text() {
+        let requests = parse_batch(
+            "[10:02] alice: lol look https://vt.tiktok.com/ZSabc/.\nbob: nice!\n(https://www.tiktok.com/@u/video/1) and https://vt.tiktok.com/ZSdef/ | quality=worst\n",
+        )
+        .unwrap();
+
+        let urls: Vec<_> = requests.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://vt.tiktok.com/ZSabc/",
+                "https://www.tiktok.com/@u/video/1",
+                "https://vt.tiktok.com/ZSdef/"
+            ]
+        );
+        assert_eq!(requests[2].quality, Some(Quality::Worst));
+    }
+
+    #[test]
+    fn nul_delimited_entries_parse() {
+        let requests = parse_batch_nul("https://a\0  https://b \0\0https://a\0").unwrap();
+        let urls: Vec<_> = requests.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a", "https://b"]);
+    }
+
+    #[test]
+    fn tiktok_export_sections_are_queued() {
+        let export = r#"{
+            "Activity": {
+                "Like List": {"ItemFavoriteList": [
+                    {"Date": "2024-01-02 10:00:00", "Link": "https://www.tiktokv.com/share/video/7001/"},
+                    {"Date": "2024-01-01 10:00:00", "Link": "https://www.tiktokv.com/share/video/7002/"}
+                ]},
+                "Favorite Videos": {"FavoriteVideoList": [
+                    {"date": "2024-01-03 10:00:00", "link": "https://www.tiktokv.com/share/video/7001/"},
+                    {"date": "2024-01-03 11:00:00", "link": "https://www.tiktok.com/@user/video/7003"}
+                ]},
+                "Video Browsing History": {"VideoList": [
+                    {"Date": "2024-01-04 10:00:00", "Link": "https://www.tiktokv.com/share/video/7004/"}
+                ]}
+            }
+        }"#;
+
+        let requests =
+            parse_tiktok_export(export, &[ExportSection::Liked, ExportSection::Favorites]).unwrap();
+        let queued: Vec<_> = requests
+            .iter()
+            .map(|r| (r.url.as_str(), r.tags.clone()))
+            .collect();
+        assert_eq!(
+            queued,
+            vec![
+                (
+                    "https://www.tiktok.com/@_/video/7001",
+                    vec!["liked".to_string()]
+                ),
+                (
+                    "https://www.tiktok.com/@_/video/7002",
+                    vec!["liked".to_string()]
+                ),
+                (
+                    "https://www.tiktok.com/@user/video/7003",
+                    vec!["favorites".to_string()]
+                ),
+            ]
+        );
+
+        let history = parse_tiktok_export(export, &[ExportSection::History]).unwrap();
+        assert_eq!(history[0].url, "https://www.tiktok.com/@_/video/7004");
+    }
+
+    #[test]
+    fn validation_canonicalizes_and_reports_bad_lines() {
+        let requests = parse_batch(
+            "# tag: a\nHTTP://WWW.TikTok.com/@u/video/1#top\nnot-a-url\n\
+             https://example.com/video/2\nvt.tiktok.com/ZSabc/\nhttps://www.tiktok.com/@u/video/1\n",
+        )
+        .unwrap();
+        let (valid, invalid) = validate_requests(requests);
+
+        let urls: Vec<_> = valid.iter().map(|request| request.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://www.tiktok.com/@u/video/1",
+                "https://vt.tiktok.com/ZSabc/"
+            ]
+        );
+        assert_eq!(
+            invalid,
+            vec![
+                InvalidEntry {
+                    line: Some(3),
+                    text: "not-a-url".to_string(),
+                },
+                InvalidEntry {
+                    line: Some(4),
+                    text: "https://example.com/video/2".to_string(),
+                },
+            ]
+        );
+        assert_eq!(canonical_url("https://nottiktok.com/x"), None);
+    }
+
+    #[test]
+    fn repeated_urls_merge_their_options() {
+        let requests = parse_batch(
+            "https://www.tiktok.com/@u/video/1\n\
+             # tag: later\n\
+             https://www.tiktok.com/@u/video/1/ | output=clips/one.mp4, quality=720p\n",
+        )
+        .unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].output, Some(PathBuf::from("clips/one.mp4")));
+        assert_eq!(requests[0].quality, Some(Quality::MaxHeight(720)));
+        assert_eq!(requests[0].tags, vec!["later".to_string()]);
+        assert_eq!(requests[0].line, Some(1));
+
+        let (valid, _) = validate_requests(vec![
+            DownloadRequest::new("https://www.tiktok.com/@u/video/2").output("a.mp4"),
+            DownloadRequest::new("HTTPS://WWW.TIKTOK.COM/@u/video/2#x")
+                .output("b.mp4")
+                .proxy("socks5://127.0.0.1:1080"),
+        ]);
+        assert_eq!(valid.len(), 1);
+        assert_eq!(valid[0].output, Some(PathBuf::from("a.mp4")));
+        assert_eq!(valid[0].proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+    }
+
+    #[test]
+    fn bad_option_reports_line_number() {
+        let err = parse_batch("https://a\nhttps://b | speed=fast\n").unwrap_err();
+        assert!(matches!(err, Error::InvalidBatchLine { line: 2, .. }));
+    }
+}
@@ -1,6 +1,29 @@
 //! Library interface for TikD-R.
+
+#[cfg(all(feature = "client", not(feature = "rustls")))]
+compile_error!("tikd-r needs a TLS backend; enable the `rustls` feature");
+
+#[cfg(feature = "client")]
 pub mod downloader;
+#[cfg(feature = "client")]
 pub mod error;
+#[cfg(feature = "client")]
+pub mod export;
+pub mod extract;
+pub mod fixture;
+mod html;
+#[cfg(feature = "client")]
+pub mod http_trace;
+#[cfg(feature = "client")]
+pub mod input;
+#[cfg(feature = "client")]
+pub mod schema;
+#[cfg(feature = "client")]
 pub mod scraper;
+#[cfg(feature = "client")]
+pub mod session;
+#[cfg(feature = "client")]
+pub mod template;
 
+#[cfg(feature = "client")]
 pub mod cli;
@@ -1,51 +1,376 @@
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::Parser;
+use tracing::level_filters::LevelFilter;
+use tracing::Instrument;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 
-use tikd_r::cli::Cli;
-use tikd_r::downloader::{DownloadConfig, Downloader};
-use tikd_r::error::{Error, Result};
+use tikd_r::cli::{Cli, Command, Compat};
+use tikd_r::downloader::{
+    build_http_client, list_archive, prune_archive, verify_checksums, BatchStatus, DownloadConfig,
+    DownloadReport, DownloadRequest, Downloader, FileCheck, InfoJsonFormat, Outcome,
+    PipelineConfig, ProgressEvent, RetentionPolicy, RetryReason, RunId, SanitizePolicy, SkipReason,
+    CHECKSUM_FILE,
+};
+use tikd_r::error::{Error, ErrorClass, Result};
+use tikd_r::export::{self, ExportFormat};
+use tikd_r::http_trace::HttpTraceLayer;
+use tikd_r::input::InvalidEntry;
+use tikd_r::schema::{RemoteCheckRecord, ReportRecord};
+use tikd_r::scraper::{RemoteStatus, Scraper};
+#[cfg(feature = "cookies")]
+use tikd_r::session::{QrLogin, QrTicket, Session, DEFAULT_SESSION_FILE};
+use tikd_r::template::{self, OutputTemplate};
+use tikd_r::{fixture, input, scraper};
 
 #[tokio::main]
 async fn main() {
-    if let Err(err) = run().await {
+    let cli = Cli::parse();
+    if let Err(err) = init_tracing(cli.trace_http.as_deref()) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+
+    // Every log line of the run carries its id, as do `--report-json` records.
+    let run_id = RunId::new();
+    let span = tracing::info_span!("run", id = %run_id);
+    if let Err(err) = run(cli, run_id).instrument(span).await {
         eprintln!("{err}");
         std::process::exit(1);
     }
 }
 
-async fn run() -> Result<()> {
-    let _ = tracing_subscriber::fmt::try_init();
+/// Log to stderr as filtered by `RUST_LOG` (default `info`), and trace HTTP requests into
+/// `trace_http` when given.
+fn init_tracing(trace_http: Option<&Path>) -> Result<()> {
+    let targets = match std::env::var("RUST_LOG") {
+        Ok(value) => value.parse().unwrap_or_else(|err| {
+            eprintln!("Ignoring `RUST_LOG={value:?}`: {err}");
+            Targets::new().with_default(LevelFilter::INFO)
+        }),
+        Err(_) => Targets::new().with_default(LevelFilter::INFO),
+    };
+    let trace_layer = trace_http.map(HttpTraceLayer::create).transpose()?;
+    let _ = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(targets))
+        .with(trace_layer)
+        .try_init();
+    Ok(())
+}
 
-    let cli = Cli::parse();
+async fn run(cli: Cli, run_id: RunId) -> Result<()> {
     cli.validate()?;
 
-    let urls = gather_urls(&cli)?;
-    let mut config = DownloadConfig::default();
+    match &cli.command {
+        #[cfg(feature = "cookies")]
+        Some(Command::Login) => return login(&cli).await,
+        Some(Command::Prune {
+            dir,
+            keep_days,
+            keep_per_author,
+            dry_run,
+        }) => {
+            let policy = RetentionPolicy {
+                keep_for: keep_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                keep_per_author: *keep_per_author,
+            };
+            return prune(dir, &policy, *dry_run);
+        }
+        Some(Command::Export {
+            dir,
+            format,
+            output,
+        }) => return export(dir, *format, output.as_deref()),
+        Some(Command::VerifyRemote {
+            dir,
+            report_json,
+            delay,
+        }) => return verify_remote(dir, report_json.as_deref(), *delay).await,
+        Some(Command::Verify { dir, requeue }) => return verify(dir, requeue.as_deref()),
+        Some(Command::Fixture { url, dir, name }) => {
+            return write_fixture(url, dir, name.as_deref()).await
+        }
+        None => {}
+    }
+
+    let (requests, invalid) = input::validate_requests(gather_urls(&cli)?);
+    if !invalid.is_empty() {
+        eprintln!("{}", invalid_entries(&invalid));
+        if !cli.skip_invalid || requests.is_empty() {
+            return Err(Error::InvalidEntries {
+                count: invalid.len(),
+            });
+        }
+        eprintln!("Skipping them; {} valid URLs remain.", requests.len());
+    }
+    let mut config = DownloadConfig {
+        run_id,
+        ..DownloadConfig::default()
+    };
     if let Some(max) = cli.max_concurrent {
         config.max_concurrent_downloads = max.max(1);
     }
+    config.max_concurrent_per_author = cli.max_concurrent_per_author.map(|max| max.max(1));
+    config.max_per_author = cli.max_per_author.map(|max| max.max(1));
+    if cli.scrape_queue.is_some() || cli.scrape_workers.is_some() {
+        config.pipeline = Some(PipelineConfig {
+            scrape_workers: cli.scrape_workers.unwrap_or(2).max(1),
+            queue_depth: cli
+                .scrape_queue
+                .unwrap_or(config.max_concurrent_downloads)
+                .max(1),
+        });
+    }
     if let Some(retries) = cli.max_retries {
         config.max_retries = retries;
     }
     if let Some(backoff) = cli.backoff_ms {
         config.initial_backoff_ms = backoff.max(1);
     }
+    if let Some(passes) = cli.batch_passes {
+        config.batch_passes = passes.max(1);
+    }
+    if let Some(cooldown) = cli.pass_cooldown {
+        config.pass_cooldown = cooldown;
+    }
+    config.active_hours = cli.active_hours;
     config.output_dir = cli.output_dir.clone();
+    if let Some(style) = cli.filename_style {
+        config.filename_style = style;
+    }
+    config.output_template = cli.output_template.clone();
+    config.transliterate = cli.transliterate;
+    if let Some(policy) = cli.sanitize {
+        config.sanitize = policy;
+    }
+    if cli.compat == Some(Compat::YtDlp) {
+        if config.output_template.is_none() && cli.filename_style.is_none() {
+            config.output_template = Some(
+                OutputTemplate::parse(template::YT_DLP_TEMPLATE).expect("valid built-in template"),
+            );
+        }
+        // yt-dlp keeps captions as written, bar what the filesystem rejects.
+        if cli.sanitize.is_none() {
+            config.sanitize = if cfg!(windows) {
+                SanitizePolicy::WindowsSafe
+            } else {
+                SanitizePolicy::UnicodeSafe
+            };
+        }
+        config.info_json_format = InfoJsonFormat::YtDlp;
+    }
+    if let Some(max) = cli.max_filename_length {
+        config.max_name_length = max;
+    }
+    config.max_path_length = cli.max_path_length;
+    if let Some(mode) = cli.dedupe {
+        config.dedupe = mode;
+    }
+    if let Some(mode) = cli.content_dedupe {
+        config.content_dedupe = mode;
+    }
+    config.max_total_bytes = cli.max_total_bytes;
+    config.max_archive_size = cli.max_archive_size;
+    config.prune_oldest = cli.prune_oldest;
+    config.max_runtime = cli.max_runtime;
+    config.min_speed = cli.min_speed;
+    if let Some(size) = cli.write_buffer_size {
+        config.write_buffer_size = size;
+    }
+    #[cfg(feature = "simulate-network")]
+    {
+        config.simulate_network = cli.simulate_network;
+    }
+    if let Some(quality) = cli.quality {
+        config.quality = quality;
+    }
+    if let Some(preset) = cli.media_headers {
+        config.media_headers.preset = preset;
+    }
+    config.media_headers.referer = cli.referer.clone();
+    config.media_headers.origin = cli.origin.clone();
+    config.media_headers.cookie = cli.media_cookie.clone();
+    config.session_cookie = cli.session_cookie.clone();
+    #[cfg(feature = "cookies")]
+    {
+        let session_path = session_file_path(&cli);
+        let mut session = Session::load(&session_path)?;
+        if let Some(session) = session.as_mut() {
+            // Sessions saved before device IDs existed get them once and keep them.
+            if session.ensure_device() {
+                session.save(&session_path)?;
+            }
+            config.device = session.device.clone();
+        }
+        if config.session_cookie.is_none() {
+            config.session_cookie = session.map(|session| session.cookie);
+        }
+    }
+    config.geo = cli.geo.clone().map(|mut geo| {
+        if let Some(tz) = cli.geo_tz.clone() {
+            geo.tz_name = tz;
+        }
+        geo
+    });
+    config.scrape_proxy = cli.scrape_proxy.clone();
+    config.media_proxy = cli.media_proxy.clone();
+    config.env_proxy = !cli.no_env_proxy;
+    if let Some(redirects) = cli.max_redirects {
+        config.max_redirects = redirects;
+    }
+    config.short_links_via_head = cli.short_links_via_head;
+    if let Some(cooldown) = cli.rate_limit_cooldown {
+        config.rate_limit_cooldown = cooldown;
+    }
+    config.descriptor_cache_dir = cli.cache_dir.clone();
+    config.download_archive = cli.download_archive.clone();
+    config.write_checksums = cli.write_checksums;
+    config.max_url_age = cli.max_url_age;
+    if let Some(ttl) = cli.cache_ttl {
+        config.descriptor_cache_ttl = ttl;
+    }
+    if let Some(ffmpeg) = cli.ffmpeg.clone() {
+        config.ffmpeg = ffmpeg;
+    }
+    config.keep_partial = cli.keep_partial;
+    config.also_copy_to = cli.also_copy_to.clone();
+    config.file_mode = cli.chmod;
+    config.file_owner = cli.chown;
+    config.match_filters = cli.match_filter.clone();
+    // Watch history and other feed-like sources include ads that were never chosen.
+    config.skip_ads = cli.tiktok_export.is_some() && !cli.include_ads;
+    config.write_author_json = cli.write_author_json;
+    config.write_info_json = cli.write_info_json;
+    config.write_thumbnail = cli.write_thumbnail;
+    config.write_subs = cli.write_subs;
+    config.sub_langs = cli.sub_langs.clone();
+    config.write_avatar = cli.write_avatar;
+    config.probe = cli.probe;
+    config.dry_run = cli.dry_run;
+    if let Some(secs) = cli.stall_timeout {
+        config.stall_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+
+    if std::io::stderr().is_terminal() {
+        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+        config.progress = Some(progress);
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                print_progress(&event);
+            }
+        });
+    }
+
+    let downloader = Downloader::with_config(config.clone())?;
+    if !confirm_batch(&cli, &requests, &config, &downloader).await? {
+        return Err(Error::Cancelled);
+    }
 
-    let downloader = Downloader::with_config(config)?;
+    let cancel = downloader.cancellation_token();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("Interrupted, cancelling downloads...");
+            cancel.cancel();
+        }
+    });
 
-    let reports = downloader.download_all(&urls).await;
+    let reports = downloader.download_requests(&requests).await;
 
     let mut succeeded = 0usize;
     let mut failed = 0usize;
+    let mut skipped: BTreeMap<SkipReason, usize> = BTreeMap::new();
+    let mut estimate = SizeEstimate::default();
 
-    for report in &reports {
+    let tags: Vec<String> = requests
+        .iter()
+        .map(|request| match request.tags.is_empty() {
+            true => String::new(),
+            false => format!(" [{}]", request.tags.join(", ")),
+        })
+        .collect();
+
+    for (idx, report) in reports.iter().enumerate() {
+        if let Outcome::Skipped(reason) = report.outcome() {
+            *skipped.entry(reason).or_default() += 1;
+        }
         match &report.result {
+            Ok(path) if report.is_duplicate() => {
+                println!(
+                    "Duplicate {} -> {} (same video as {})",
+                    report.url,
+                    path.display(),
+                    report.duplicate_of.as_deref().unwrap_or_default()
+                );
+            }
+            Ok(path) if report.already_downloaded => {
+                println!("Already downloaded {} -> {}", report.url, path.display());
+            }
+            Ok(path) if cli.dry_run => match &report.probe {
+                Some(probe) if probe.is_available() => {
+                    succeeded += 1;
+                    estimate.add(probe.size);
+                    let size = probe
+                        .size
+                        .map(format_bytes)
+                        .unwrap_or_else(|| "unknown size".to_string());
+                    println!(
+                        "Would download {}{} -> {} ({size})",
+                        report.url,
+                        tags[idx],
+                        path.display()
+                    );
+                }
+                Some(probe) => {
+                    failed += 1;
+                    eprintln!(
+                        "Unavailable {}: media returned HTTP {}",
+                        report.url, probe.status
+                    );
+                }
+                None => {
+                    succeeded += 1;
+                    estimate.add(None);
+                    println!(
+                        "Would download {}{} -> {} (unknown size)",
+                        report.url,
+                        tags[idx],
+                        path.display()
+                    );
+                }
+            },
             Ok(path) => {
                 succeeded += 1;
-                println!("Downloaded {} -> {}", report.url, path.display());
+                match report.probe.as_ref().and_then(|probe| probe.size) {
+                    Some(size) => println!(
+                        "Downloaded {}{} -> {} ({})",
+                        report.url,
+                        tags[idx],
+                        path.display(),
+                        format_bytes(size)
+                    ),
+                    None => println!(
+                        "Downloaded {}{} -> {}",
+                        report.url,
+                        tags[idx],
+                        path.display()
+                    ),
+                }
+                if let Some(original) = &report.same_content_as {
+                    println!("  same content as {}", original.display());
+                }
+                for warning in &report.warnings {
+                    println!("  warning: {warning}");
+                }
+            }
+            Err(Error::Skipped { detail, .. }) => {
+                println!("Skipped {}: {detail}", report.url);
             }
             Err(err) => {
                 failed += 1;
@@ -54,7 +379,59 @@ async fn run() -> Result<()> {
         }
     }
 
-    println!("Summary: {succeeded} succeeded, {failed} failed.");
+    println!("{}", summary_line(succeeded, &skipped, failed));
+    if let Some(failures) = failure_summary(&reports) {
+        println!("{failures}");
+    }
+    if cli.dry_run {
+        println!("{}", estimate.summary());
+    }
+    if cli.retry_summary {
+        println!("{}", retry_summary(&reports));
+    }
+    if let Some(path) = &cli.report_json {
+        let lines: Vec<String> = reports
+            .iter()
+            .map(|report| report_json(report, run_id).map(|json| json.to_string()))
+            .collect::<Result<_>>()?;
+        fs::write(path, lines.join("\n") + "\n")?;
+    }
+    if let Some(limit) = cli.max_total_bytes {
+        println!(
+            "Transferred {} of {limit} budgeted bytes.",
+            downloader.bytes_downloaded()
+        );
+    }
+
+    let remaining: Vec<&DownloadRequest> = reports
+        .iter()
+        .zip(&requests)
+        .filter(|(report, _)| {
+            report.error().is_some_and(Error::is_budget_exhausted)
+                || report.outcome() == Outcome::Skipped(SkipReason::AuthorLimit)
+        })
+        .map(|(_, request)| request)
+        .collect();
+    if !remaining.is_empty() {
+        let limit = if reports
+            .iter()
+            .any(|report| report.error().is_some_and(Error::is_budget_exhausted))
+        {
+            "Budget"
+        } else {
+            "Per-author limit"
+        };
+        let path = resume_file_path(&cli);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, input::write_batch(remaining.iter().copied()))?;
+        println!(
+            "{limit} reached; {} URLs left. Resume with: tikd-r --file {}",
+            remaining.len(),
+            path.display()
+        );
+    }
 
     if failed > 0 {
         std::process::exit(1);
@@ -63,27 +440,580 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
-fn gather_urls(cli: &Cli) -> Result<Vec<String>> {
+#[cfg(feature = "cookies")]
+async fn login(cli: &Cli) -> Result<()> {
+    let path = session_file_path(cli);
+    let previous = Session::load(&path)?;
+    let mut session = QrLogin::new()?
+        .run(print_qr_code, Duration::from_secs(2))
+        .await?;
+    // Logging in again keeps the device identity TikTok has already seen.
+    if let Some(device) = previous.and_then(|previous| previous.device) {
+        session.device = Some(device);
+    }
+    session.save(&path)?;
+    println!("Logged in. Session saved to {}", path.display());
+    Ok(())
+}
+
+fn prune(dir: &Path, policy: &RetentionPolicy, dry_run: bool) -> Result<()> {
+    let pruned = prune_archive(dir, policy, dry_run)?;
+    let verb = if dry_run { "Would delete" } else { "Deleted" };
+    for path in &pruned {
+        println!("{verb} {}", path.display());
+    }
+    println!("{verb} {} videos from {}.", pruned.len(), dir.display());
+    Ok(())
+}
+
+fn export(dir: &Path, format: ExportFormat, output: Option<&Path>) -> Result<()> {
+    let videos = list_archive(dir)?;
+    match output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(fs::File::create(path)?);
+            export::write_videos(&videos, format, &mut file)?;
+            eprintln!("Exported {} videos to {}.", videos.len(), path.display());
+        }
+        None => export::write_videos(&videos, format, &mut std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+async fn verify_remote(dir: &Path, report_json: Option<&Path>, delay: Duration) -> Result<()> {
+    let scraper = Scraper::new(build_http_client()?);
+    let videos = list_archive(dir)?;
+    let mut lines = Vec::new();
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (index, video) in videos.iter().enumerate() {
+        if index > 0 {
+            tokio::time::sleep(delay).await;
+        }
+        let checked = scraper.remote_status(&video.author, &video.video_id).await;
+        let status = match &checked {
+            Ok(status) => status.as_str(),
+            Err(_) => "unchecked",
+        };
+        *counts.entry(status).or_default() += 1;
+        match &checked {
+            Ok(RemoteStatus::Available) => {}
+            Ok(RemoteStatus::Unavailable(code)) => {
+                println!("Unavailable (status {code}) {}", video.path.display())
+            }
+            Ok(RemoteStatus::Removed) => println!("Removed {}", video.path.display()),
+            Ok(RemoteStatus::Private) => println!("Private {}", video.path.display()),
+            Err(err) => println!("Could not check {}: {err}", video.path.display()),
+        }
+
+        if report_json.is_some() {
+            let checked_unix = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+            let record = RemoteCheckRecord::new(
+                video.video_id.clone(),
+                video.author.clone(),
+                video.path.clone(),
+                &checked,
+                checked_unix,
+            );
+            lines.push(serde_json::to_string(&record)?);
+        }
+    }
+    if let Some(path) = report_json {
+        fs::write(path, lines.join("\n") + "\n")?;
+    }
+
+    let counts: Vec<_> = [
+        "available",
+        "removed",
+        "private",
+        "unavailable",
+        "unchecked",
+    ]
+    .iter()
+    .map(|status| format!("{} {status}", counts.get(status).copied().unwrap_or(0)))
+    .collect();
+    println!("Checked {} videos: {}.", videos.len(), counts.join(", "));
+    Ok(())
+}
+
+fn verify(dir: &Path, requeue: Option<&Path>) -> Result<()> {
+    let (checks, unreadable) = match verify_checksums(dir) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::NoChecksums(dir.to_path_buf()))
+        }
+        checked => checked?,
+    };
+
+    let (mut corrupted, mut missing, mut urls, mut aside) = (0, 0, Vec::new(), Vec::new());
+    for (record, check) in &checks {
+        let path = dir.join(&record.path);
+        match check {
+            FileCheck::Ok => continue,
+            FileCheck::Missing => {
+                missing += 1;
+                println!("Missing {}", path.display());
+            }
+            FileCheck::SizeChanged { bytes } => {
+                corrupted += 1;
+                println!(
+                    "Size changed ({} of {} bytes) {}",
+                    bytes,
+                    record.bytes,
+                    path.display()
+                );
+            }
+            FileCheck::Corrupted => {
+                corrupted += 1;
+                println!("Corrupted {}", path.display());
+            }
+        }
+        if requeue.is_some() {
+            if *check != FileCheck::Missing {
+                aside.push(path);
+            }
+            urls.push(format!(
+                "https://www.tiktok.com/@{}/video/{}",
+                record.author, record.video_id
+            ));
+        }
+    }
+
+    println!(
+        "Verified {} files: {} ok, {corrupted} corrupted, {missing} missing.",
+        checks.len(),
+        checks.len() - corrupted - missing
+    );
+    if unreadable > 0 {
+        println!("Ignored {unreadable} unreadable lines in {CHECKSUM_FILE}.");
+    }
+    if let Some(path) = requeue.filter(|_| !urls.is_empty()) {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, urls.join("\n") + "\n")?;
+        // Out of the way, or the download would be skipped as already done. Only once the
+        // list is written, so a failure above leaves every file where it was.
+        for path in aside {
+            let mut corrupt = path.clone().into_os_string();
+            corrupt.push(".corrupt");
+            fs::rename(&path, corrupt)?;
+        }
+        println!(
+            "Re-download {} videos with: tikd-r --file {}",
+            urls.len(),
+            path.display()
+        );
+    }
+
+    if corrupted + missing > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn write_fixture(url: &str, dir: &Path, name: Option<&str>) -> Result<()> {
+    let scraper = Scraper::new(build_http_client()?);
+    let (final_url, html) = scraper.fetch_share_page(url).await?;
+    let fixture = fixture::minimize_share_page(&html).ok_or(Error::VideoUrlNotFound)?;
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => {
+            let id = url::Url::parse(&final_url)
+                .ok()
+                .and_then(|url| {
+                    url.path_segments()?
+                        .rfind(|segment| !segment.is_empty())
+                        .map(ToOwned::to_owned)
+                })
+                .unwrap_or_else(|| "page".into());
+            let id: String = id.chars().filter(char::is_ascii_alphanumeric).collect();
+            format!("share_{id}")
+        }
+    };
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{name}.html"));
+    fs::write(&path, fixture)?;
+    println!("Wrote fixture {} (from {})", path.display(), final_url);
+    Ok(())
+}
+
+#[cfg(feature = "cookies")]
+fn print_qr_code(ticket: &QrTicket) {
+    #[cfg(feature = "qr-login")]
+    match qrcode::QrCode::new(ticket.scan_url.as_bytes()) {
+        Ok(code) => println!(
+            "{}",
+            code.render::<qrcode::render::unicode::Dense1x2>()
+                .quiet_zone(true)
+                .build()
+        ),
+        Err(err) => tracing::warn!("Unable to render QR code: {err}"),
+    }
+    println!(
+        "Scan with the TikTok app (Profile > Scan) or open: {}",
+        ticket.scan_url
+    );
+}
+
+#[cfg(feature = "cookies")]
+fn session_file_path(cli: &Cli) -> PathBuf {
+    cli.session_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SESSION_FILE))
+}
+
+/// Erases the current terminal line before redrawing it.
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+fn print_progress(event: &ProgressEvent) {
+    match event {
+        ProgressEvent::Segment {
+            url,
+            index,
+            total,
+            estimated_bytes,
+            ..
+        } => {
+            eprint!(
+                "{CLEAR_LINE}{url}: segment {index} of {total} (~{})",
+                format_bytes(*estimated_bytes)
+            );
+            if index >= total {
+                eprintln!();
+            }
+        }
+        ProgressEvent::ItemFinished {
+            url,
+            outcome,
+            status,
+        } if status.total > 1 => {
+            let outcome = match outcome {
+                Outcome::Success => "ok",
+                Outcome::Skipped(SkipReason::Duplicate) => "duplicate",
+                Outcome::Skipped(_) => "skipped",
+                Outcome::Failed => "FAILED",
+            };
+            eprintln!(
+                "{CLEAR_LINE}[{}/{}] {url} ... {outcome}",
+                status.done, status.total
+            );
+            if status.done < status.total {
+                eprint!("{}", batch_status_line(status));
+            }
+        }
+        ProgressEvent::ItemFinished { .. } => {}
+        ProgressEvent::Batch(status) if status.total > 1 => {
+            eprint!("{CLEAR_LINE}{}", batch_status_line(status))
+        }
+        ProgressEvent::Batch(_) => {}
+    }
+}
+
+/// One line such as `[3/10] 1 failed, 1.2 MiB/s, ETA 1m05s`.
+fn batch_status_line(status: &BatchStatus) -> String {
+    let eta = match status.eta() {
+        Some(eta) => format_eta(eta),
+        None => "unknown".to_string(),
+    };
+    format!(
+        "[{}/{}] {} failed, {}/s, ETA {eta}",
+        status.done,
+        status.total,
+        status.failed,
+        format_bytes(status.bytes_per_sec())
+    )
+}
+
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Running total of probed media sizes for `--dry-run`.
+#[derive(Default)]
+struct SizeEstimate {
+    bytes: u64,
+    items: usize,
+    unknown: usize,
+}
+
+impl SizeEstimate {
+    fn add(&mut self, size: Option<u64>) {
+        self.items += 1;
+        match size {
+            Some(size) => self.bytes += size,
+            None => self.unknown += 1,
+        }
+    }
+
+    /// One line such as `Estimated 12.5 MiB across 4 items (1 of unknown size).`
+    fn summary(&self) -> String {
+        let mut line = format!(
+            "Estimated {} across {} items",
+            format_bytes(self.bytes),
+            self.items
+        );
+        if self.unknown > 0 {
+            line.push_str(&format!(" ({} of unknown size)", self.unknown));
+        }
+        line + "."
+    }
+}
+
+/// One line such as `Summary: 7 succeeded, 3 skipped (2 already downloaded, 1 filtered), 1 failed.`
+fn summary_line(succeeded: usize, skipped: &BTreeMap<SkipReason, usize>, failed: usize) -> String {
+    let mut summary = format!("Summary: {succeeded} succeeded");
+    let total_skipped: usize = skipped.values().sum();
+    if total_skipped > 0 {
+        let reasons: Vec<String> = skipped
+            .iter()
+            .map(|(reason, count)| format!("{count} {}", reason.as_str()))
+            .collect();
+        summary.push_str(&format!(
+            ", {total_skipped} skipped ({})",
+            reasons.join(", ")
+        ));
+    }
+    summary + &format!(", {failed} failed.")
+}
+
+/// The list of entries `validate_requests` rejected, one per line.
+fn invalid_entries(invalid: &[InvalidEntry]) -> String {
+    let mut lines = vec![format!("Not TikTok links ({}):", invalid.len())];
+    for entry in invalid {
+        lines.push(match entry.line {
+            Some(line) => format!("  line {line}: {}", entry.text),
+            None => format!("  {}", entry.text),
+        });
+    }
+    lines.join("\n")
+}
+
+/// Ask on the terminal before starting a batch over the `--confirm-over` thresholds.
+/// Returns `false` if the user declines; runs without a terminal are never asked.
+/// What the size probe scraped is handed to `downloader` so the batch needn't repeat it.
+async fn confirm_batch(
+    cli: &Cli,
+    requests: &[DownloadRequest],
+    config: &DownloadConfig,
+    downloader: &Downloader,
+) -> Result<bool> {
+    if cli.yes || cli.dry_run || !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    let over_count = cli.confirm_over > 0 && requests.len() > cli.confirm_over;
+    let estimated = match cli.confirm_over_size {
+        Some(_) => Some(estimate_batch(requests, config, downloader).await?),
+        None => None,
+    };
+    let reasons = batch_warnings(requests.len(), over_count, estimated, cli.confirm_over_size);
+    if reasons.is_empty() {
+        return Ok(true);
+    }
+
+    eprint!("This batch has {}. Continue? [y/N] ", reasons.join(" and "));
+    std::io::Write::flush(&mut std::io::stderr())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Probe the batch the way `--dry-run` does and add up the sizes of what would download.
+async fn estimate_batch(
+    requests: &[DownloadRequest],
+    config: &DownloadConfig,
+    downloader: &Downloader,
+) -> Result<SizeEstimate> {
+    eprintln!(
+        "Probing {} URLs to estimate the batch size...",
+        requests.len()
+    );
+    // A progress channel nobody reads keeps the per-item lines off the terminal.
+    let (progress, _) = tokio::sync::mpsc::unbounded_channel();
+    // One pass per URL is enough for an estimate, and the size cap is the real run's
+    // to enforce: a full archive would otherwise fail every probe.
+    let probe_config = DownloadConfig {
+        dry_run: true,
+        progress: Some(progress),
+        batch_passes: 1,
+        max_archive_size: None,
+        ..config.clone()
+    };
+    let reports = Downloader::with_config(probe_config)?
+        .download_requests(requests)
+        .await;
+    downloader.reuse_scrapes(&reports);
+    let mut estimate = SizeEstimate::default();
+    for report in reports {
+        if report.outcome() == Outcome::Success {
+            estimate.add(report.probe.and_then(|probe| probe.size));
+        }
+    }
+    Ok(estimate)
+}
+
+/// What makes a batch large enough to ask about, e.g. `1200 URLs`.
+fn batch_warnings(
+    count: usize,
+    over_count: bool,
+    estimated: Option<SizeEstimate>,
+    size_limit: Option<u64>,
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if over_count {
+        reasons.push(format!("{count} URLs"));
+    }
+    if let (Some(estimate), Some(limit)) = (estimated, size_limit) {
+        if estimate.bytes > limit {
+            reasons.push(format!("an estimated {}", format_bytes(estimate.bytes)));
+        }
+    }
+    reasons
+}
+
+/// How many hosts `failure_summary` names.
+const TOP_FAILING_HOSTS: usize = 3;
+
+/// Failures by class, then the hosts most failed requests went to, e.g.
+/// `Failures: 9 network, 2 extraction. Most failures from v16-webapp.tiktok.com (7), ...`
+fn failure_summary(reports: &[DownloadReport]) -> Option<String> {
+    let errors: Vec<&Error> = reports
+        .iter()
+        .filter(|report| report.outcome() == Outcome::Failed)
+        .filter_map(DownloadReport::error)
+        .collect();
+    if errors.is_empty() {
+        return None;
+    }
+
+    let mut by_class: BTreeMap<ErrorClass, usize> = BTreeMap::new();
+    let mut by_host: BTreeMap<&str, usize> = BTreeMap::new();
+    for err in &errors {
+        *by_class.entry(err.class()).or_default() += 1;
+        if let Some(host) = err.host() {
+            *by_host.entry(host).or_default() += 1;
+        }
+    }
+
+    let mut classes: Vec<_> = by_class.into_iter().collect();
+    classes.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    let classes: Vec<String> = classes
+        .iter()
+        .map(|(class, count)| format!("{count} {}", class.as_str()))
+        .collect();
+    let mut line = format!("Failures: {}.", classes.join(", "));
+
+    let mut hosts: Vec<_> = by_host.into_iter().collect();
+    hosts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    if !hosts.is_empty() {
+        let hosts: Vec<String> = hosts
+            .iter()
+            .take(TOP_FAILING_HOSTS)
+            .map(|(host, count)| format!("{host} ({count})"))
+            .collect();
+        line.push_str(&format!(" Most failures from {}.", hosts.join(", ")));
+    }
+    Some(line)
+}
+
+/// The `--report-json` record for one URL, as described by `schemas/report.v1.json`.
+fn report_json(report: &DownloadReport, run_id: RunId) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(ReportRecord::new(report, run_id))?)
+}
+
+/// One line such as `Retries: 4 across 2 URLs (3 rate limited, 1 forbidden).`
+fn retry_summary(reports: &[DownloadReport]) -> String {
+    let mut by_reason: BTreeMap<RetryReason, usize> = BTreeMap::new();
+    for record in reports.iter().flat_map(|report| &report.retries) {
+        *by_reason.entry(record.reason).or_default() += 1;
+    }
+    if by_reason.is_empty() {
+        return "Retries: none.".to_string();
+    }
+
+    let total: usize = by_reason.values().sum();
+    let urls = reports
+        .iter()
+        .filter(|report| !report.retries.is_empty())
+        .count();
+    let reasons: Vec<String> = by_reason
+        .iter()
+        .map(|(reason, count)| format!("{count} {reason}"))
+        .collect();
+    format!(
+        "Retries: {total} across {urls} URLs ({}).",
+        reasons.join(", ")
+    )
+}
+
+fn resume_file_path(cli: &Cli) -> PathBuf {
+    if let Some(path) = cli.resume_file.as_ref() {
+        return path.clone();
+    }
+
+    let file_name = "tikd-r-resume.txt";
+    match cli.output_dir.as_ref() {
+        Some(dir) => dir.join(file_name),
+        None => PathBuf::from(file_name),
+    }
+}
+
+fn gather_urls(cli: &Cli) -> Result<Vec<DownloadRequest>> {
     if let Some(url) = cli.url.as_ref() {
-        return Ok(vec![url.trim().to_string()]);
+        let url = url.trim();
+        let url = scraper::extract_share_url(url).unwrap_or_else(|| url.to_string());
+        return Ok(vec![DownloadRequest::new(url)]);
     }
 
     if let Some(path) = cli.file.as_ref() {
-        let contents = fs::read_to_string(path)?;
-        let mut seen = HashSet::new();
-        let urls: Vec<String> = contents
-            .lines()
-            .map(str::trim)
-            .filter(|line| !line.is_empty() && !line.starts_with('#'))
-            .map(ToOwned::to_owned)
-            .filter(|url| seen.insert(url.clone()))
-            .collect();
+        let contents = if path.as_os_str() == "-" {
+            std::io::read_to_string(std::io::stdin())?
+        } else {
+            fs::read_to_string(path)?
+        };
+        let requests = if cli.null {
+            input::parse_batch_nul(&contents)?
+        } else {
+            input::parse_batch(&contents)?
+        };
 
-        if urls.is_empty() {
+        if requests.is_empty() {
             return Err(Error::EmptyUrlFile(path.clone()));
         }
 
-        return Ok(urls);
+        return Ok(requests);
+    }
+
+    if let Some(path) = cli.tiktok_export.as_ref() {
+        let requests =
+            input::parse_tiktok_export(&fs::read_to_string(path)?, &cli.export_sections)?;
+        if requests.is_empty() {
+            return Err(Error::EmptyUrlFile(path.clone()));
+        }
+        return Ok(requests);
     }
 
     Err(Error::MissingInput)
@@ -92,22 +1022,42 @@ fn gather_urls(cli: &Cli) -> Result<Vec<String>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tikd_r::downloader::ItemTimings;
+    use tikd_r::scraper::VideoDescriptor;
+
+    fn gathered_urls(cli: &Cli) -> Vec<String> {
+        gather_urls(cli)
+            .unwrap()
+            .into_iter()
+            .map(|request| request.url)
+            .collect()
+    }
 
     #[test]
     fn gather_single_url() {
         let cli = Cli {
             url: Some("https://www.tiktok.com/@user/video/1".into()),
             file: None,
-            max_concurrent: None,
-            max_retries: None,
-            backoff_ms: None,
-            output_dir: None,
+            ..Default::default()
         };
 
-        let urls = gather_urls(&cli).unwrap();
+        let urls = gathered_urls(&cli);
         assert_eq!(urls, vec!["https://www.tiktok.com/@user/video/1"]);
     }
 
+    #[test]
+    fn gather_single_url_from_share_text() {
+        let cli = Cli {
+            url: Some("Check out this video! https://vt.tiktok.com/ZSyB3RCuJ/ #fyp".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            gathered_urls(&cli),
+            vec!["https://vt.tiktok.com/ZSyB3RCuJ/"]
+        );
+    }
+
     #[test]
     fn gather_urls_from_file() {
         let temp = tempfile::NamedTempFile::new().unwrap();
@@ -116,13 +1066,10 @@ mod tests {
         let cli = Cli {
             url: None,
             file: Some(temp.path().to_path_buf()),
-            max_concurrent: None,
-            max_retries: None,
-            backoff_ms: None,
-            output_dir: None,
+            ..Default::default()
         };
 
-        let urls = gather_urls(&cli).unwrap();
+        let urls = gathered_urls(&cli);
         assert_eq!(urls, vec!["https://a", "https://b"]);
     }
 
@@ -134,13 +1081,10 @@ mod tests {
         let cli = Cli {
             url: None,
             file: Some(temp.path().to_path_buf()),
-            max_concurrent: None,
-            max_retries: None,
-            backoff_ms: None,
-            output_dir: None,
+            ..Default::default()
         };
 
-        let urls = gather_urls(&cli).unwrap();
+        let urls = gathered_urls(&cli);
         assert_eq!(urls, vec!["https://a"]);
     }
 
@@ -152,16 +1096,244 @@ mod tests {
         let cli = Cli {
             url: None,
             file: Some(temp.path().to_path_buf()),
-            max_concurrent: None,
-            max_retries: None,
-            backoff_ms: None,
-            output_dir: None,
+            ..Default::default()
         };
 
-        let urls = gather_urls(&cli).unwrap();
+        let urls = gathered_urls(&cli);
         assert_eq!(urls, vec!["https://a", "https://b"]);
     }
 
+    #[test]
+    fn deduplicate_case_and_trailing_slash_variants() {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        fs::write(
+            temp.path(),
+            "https://vt.tiktok.com/ZSabc/\nhttps://b\nHTTPS://VT.TikTok.com/ZSabc\nhttps://vt.tiktok.com/ZSABC\n",
+        )
+        .unwrap();
+
+        let cli = Cli {
+            file: Some(temp.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let urls = gathered_urls(&cli);
+        assert_eq!(
+            urls,
+            vec![
+                "https://vt.tiktok.com/ZSabc/",
+                "https://b",
+                "https://vt.tiktok.com/ZSABC"
+            ]
+        );
+    }
+
+    #[test]
+    fn gather_inline_options_from_file() {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        fs::write(temp.path(), "# tag: travel\nhttps://a | quality=720p\n").unwrap();
+
+        let cli = Cli {
+            file: Some(temp.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let requests = gather_urls(&cli).unwrap();
+        assert_eq!(requests[0].url, "https://a");
+        assert_eq!(requests[0].tags, vec!["travel"]);
+        assert!(requests[0].quality.is_some());
+    }
+
+    #[test]
+    fn format_bytes_uses_binary_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn batch_status_line_shows_speed_and_eta() {
+        let status = BatchStatus {
+            done: 3,
+            total: 10,
+            failed: 1,
+            bytes: 3 * 1024 * 1024,
+            elapsed: Duration::from_secs(30),
+        };
+        assert_eq!(
+            batch_status_line(&status),
+            "[3/10] 1 failed, 102.4 KiB/s, ETA 1m10s"
+        );
+        assert_eq!(format_eta(Duration::from_secs(7)), "7s");
+        assert_eq!(format_eta(Duration::from_secs(2 * 3600 + 5 * 60)), "2h05m");
+    }
+
+    #[test]
+    fn report_json_includes_timings() {
+        let report = DownloadReport {
+            url: "https://www.tiktok.com/@a/video/1".to_string(),
+            result: Ok(PathBuf::from("a/1.mp4")),
+            duplicate_of: None,
+            retries: Vec::new(),
+            probe: None,
+            timings: ItemTimings {
+                scrape: Some(Duration::from_millis(120)),
+                first_byte: Some(Duration::from_millis(45)),
+                transfer: None,
+            },
+            descriptor: Some(VideoDescriptor {
+                text_language: Some("en".to_string()),
+                caption_languages: vec!["eng-US".to_string()],
+                ..VideoDescriptor::default()
+            }),
+            same_content_as: Some(PathBuf::from("b/2.mp4")),
+            already_downloaded: false,
+            warnings: Vec::new(),
+        };
+        let run_id = RunId::new();
+        let json = report_json(&report, run_id).unwrap();
+        assert_eq!(json["run_id"], run_id.to_string());
+        assert_eq!(json["status"], "ok");
+        assert!(json["skip_reason"].is_null());
+        assert_eq!(json["path"], "a/1.mp4");
+        assert_eq!(json["scrape_ms"], 120);
+        assert_eq!(json["first_byte_ms"], 45);
+        assert!(json["transfer_ms"].is_null());
+        assert!(json["error"].is_null());
+        assert_eq!(json["same_content_as"], "b/2.mp4");
+        assert_eq!(json["text_language"], "en");
+        assert_eq!(json["caption_languages"], serde_json::json!(["eng-US"]));
+        assert_eq!(json["sticker_text"], serde_json::json!([]));
+
+        let existing = DownloadReport {
+            already_downloaded: true,
+            ..report
+        };
+        let json = report_json(&existing, run_id).unwrap();
+        assert_eq!(json["status"], "skipped");
+        assert_eq!(json["skip_reason"], "already-downloaded");
+        assert_eq!(json["path"], "a/1.mp4");
+    }
+
+    #[tokio::test]
+    async fn failure_summary_counts_classes_and_hosts() {
+        let request_error = |url: &str| {
+            let url = url.to_string();
+            async move {
+                let client = reqwest::Client::new();
+                Error::Network(client.get(url).send().await.unwrap_err())
+            }
+        };
+        let failure = |err| DownloadReport {
+            url: "https://www.tiktok.com/@a/video/1".to_string(),
+            result: Err(err),
+            duplicate_of: None,
+            retries: Vec::new(),
+            probe: None,
+            timings: ItemTimings::default(),
+            descriptor: None,
+            same_content_as: None,
+            already_downloaded: false,
+            warnings: Vec::new(),
+        };
+
+        assert_eq!(failure_summary(&[]), None);
+        let reports = vec![
+            failure(request_error("http://127.0.0.1:1/a.mp4").await),
+            failure(request_error("http://127.0.0.1:1/b.mp4").await),
+            failure(Error::VideoUrlNotFound),
+            failure(Error::Skipped {
+                reason: SkipReason::Filtered,
+                detail: "filtered".to_string(),
+            }),
+        ];
+        assert_eq!(
+            failure_summary(&reports).unwrap(),
+            "Failures: 2 network, 1 extraction. Most failures from 127.0.0.1 (2)."
+        );
+    }
+
+    #[test]
+    fn invalid_entries_are_listed_with_line_numbers() {
+        let invalid = [
+            InvalidEntry {
+                line: Some(3),
+                text: "not-a-url".to_string(),
+            },
+            InvalidEntry {
+                line: None,
+                text: "https://example.com".to_string(),
+            },
+        ];
+        assert_eq!(
+            invalid_entries(&invalid),
+            "Not TikTok links (2):\n  line 3: not-a-url\n  https://example.com"
+        );
+    }
+
+    #[test]
+    fn large_batches_are_flagged_by_count_or_size() {
+        let estimate = |bytes| SizeEstimate {
+            bytes,
+            items: 1,
+            unknown: 0,
+        };
+        assert!(batch_warnings(10, false, None, None).is_empty());
+        assert_eq!(batch_warnings(900, true, None, None), vec!["900 URLs"]);
+        assert!(batch_warnings(2, false, Some(estimate(1024)), Some(2048)).is_empty());
+        assert_eq!(
+            batch_warnings(900, true, Some(estimate(4096)), Some(2048)),
+            vec!["900 URLs".to_string(), "an estimated 4.0 KiB".to_string()]
+        );
+    }
+
+    #[test]
+    fn summary_breaks_skips_down_by_reason() {
+        let mut skipped = BTreeMap::new();
+        assert_eq!(
+            summary_line(3, &skipped, 0),
+            "Summary: 3 succeeded, 0 failed."
+        );
+        skipped.insert(SkipReason::Duplicate, 1);
+        skipped.insert(SkipReason::AlreadyDownloaded, 2);
+        assert_eq!(
+            summary_line(3, &skipped, 1),
+            "Summary: 3 succeeded, 3 skipped (2 already downloaded, 1 duplicate), 1 failed."
+        );
+    }
+
+    #[test]
+    fn size_estimate_counts_unknown_sizes() {
+        let mut estimate = SizeEstimate::default();
+        estimate.add(Some(1024));
+        estimate.add(Some(512));
+        assert_eq!(estimate.summary(), "Estimated 1.5 KiB across 2 items.");
+        estimate.add(None);
+        assert_eq!(
+            estimate.summary(),
+            "Estimated 1.5 KiB across 3 items (1 of unknown size)."
+        );
+    }
+
+    #[test]
+    fn resume_file_defaults_to_output_dir() {
+        let cli = Cli {
+            output_dir: Some(PathBuf::from("videos")),
+            ..Default::default()
+        };
+        assert_eq!(
+            resume_file_path(&cli),
+            PathBuf::from("videos/tikd-r-resume.txt")
+        );
+
+        let cli = Cli {
+            resume_file: Some(PathBuf::from("left.txt")),
+            output_dir: Some(PathBuf::from("videos")),
+            ..Default::default()
+        };
+        assert_eq!(resume_file_path(&cli), PathBuf::from("left.txt"));
+    }
+
     #[test]
     fn empty_file_is_error() {
         let temp = tempfile::NamedTempFile::new().unwrap();
@@ -169,10 +1341,7 @@ mod tests {
         let cli = Cli {
             url: None,
             file: Some(temp.path().to_path_buf()),
-            max_concurrent: None,
-            max_retries: None,
-            backoff_ms: None,
-            output_dir: None,
+            ..Default::default()
         };
 
         let err = gather_urls(&cli).unwrap_err();
@@ -0,0 +1,268 @@
+//! Versioned JSON records written for other programs to read.
+//!
+//! Each record carries `schema_version`. Within a version, fields are only ever added,
+//! and always as optional; renaming or removing a field, or changing its type, bumps
+//! `SCHEMA_VERSION`. The JSON Schemas under `schemas/` document each record and are kept
+//! in step with these structs by the tests below.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::downloader::{DownloadReport, DownloadWarning, Outcome, RunId, SkipReason};
+use crate::scraper::RemoteStatus;
+
+/// Version of every record in this module.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// JSON Schema of a `--report-json` line written by a download run.
+pub const REPORT_SCHEMA: &str = include_str!("../schemas/report.v1.json");
+/// JSON Schema of a `--report-json` line written by `verify-remote`.
+pub const REMOTE_CHECK_SCHEMA: &str = include_str!("../schemas/remote-check.v1.json");
+/// JSON Schema of the `author.json` written by `--write-author-json`.
+pub const AUTHOR_SCHEMA: &str = include_str!("../schemas/author.v1.json");
+/// JSON Schema of the `<video>.info.json` written by `--write-info-json`.
+pub const INFO_SCHEMA: &str = include_str!("../schemas/info.v1.json");
+/// JSON Schema of a `--trace-http` line.
+pub const HTTP_TRACE_SCHEMA: &str = include_str!("../schemas/http-trace.v1.json");
+/// JSON Schema of a line of the `--write-checksums` manifest.
+pub const CHECKSUM_SCHEMA: &str = include_str!("../schemas/checksum.v1.json");
+
+/// Final state of one URL in a download report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Ok,
+    /// Resolved to the same output file as an earlier URL of the batch.
+    Duplicate,
+    Skipped,
+    Failed,
+}
+
+/// One `--report-json` line of a download run; durations are in milliseconds.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReportRecord {
+    pub schema_version: u32,
+    pub run_id: String,
+    pub url: String,
+    pub status: ReportStatus,
+    pub skip_reason: Option<SkipReason>,
+    pub path: Option<PathBuf>,
+    pub error: Option<String>,
+    pub duplicate_of: Option<String>,
+    pub same_content_as: Option<PathBuf>,
+    pub retries: usize,
+    pub size: Option<u64>,
+    pub scrape_ms: Option<u64>,
+    pub first_byte_ms: Option<u64>,
+    pub transfer_ms: Option<u64>,
+    pub text_language: Option<String>,
+    pub caption_languages: Vec<String>,
+    pub sticker_text: Vec<String>,
+    pub warnings: Vec<DownloadWarning>,
+}
+
+impl ReportRecord {
+    pub fn new(report: &DownloadReport, run_id: RunId) -> Self {
+        let millis = |duration: Option<std::time::Duration>| {
+            duration.map(|duration| duration.as_millis() as u64)
+        };
+        let descriptor = report.descriptor.as_ref();
+        let (status, skip_reason) = match report.outcome() {
+            Outcome::Success => (ReportStatus::Ok, None),
+            Outcome::Skipped(SkipReason::Duplicate) if report.is_duplicate() => {
+                (ReportStatus::Duplicate, Some(SkipReason::Duplicate))
+            }
+            Outcome::Skipped(reason) => (ReportStatus::Skipped, Some(reason)),
+            Outcome::Failed => (ReportStatus::Failed, None),
+        };
+        Self {
+            schema_version: SCHEMA_VERSION,
+            run_id: run_id.to_string(),
+            url: report.url.clone(),
+            status,
+            skip_reason,
+            path: report.result.as_ref().ok().cloned(),
+            error: report.error().map(ToString::to_string),
+            duplicate_of: report.duplicate_of.clone(),
+            same_content_as: report.same_content_as.clone(),
+            retries: report.retries.len(),
+            size: report.probe.as_ref().and_then(|probe| probe.size),
+            scrape_ms: millis(report.timings.scrape),
+            first_byte_ms: millis(report.timings.first_byte),
+            transfer_ms: millis(report.timings.transfer),
+            text_language: descriptor.and_then(|d| d.text_language.clone()),
+            caption_languages: descriptor.map_or_else(Vec::new, |d| d.caption_languages.clone()),
+            sticker_text: descriptor.map_or_else(Vec::new, |d| d.sticker_text.clone()),
+            warnings: report.warnings.clone(),
+        }
+    }
+}
+
+/// One `--report-json` line of `verify-remote`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RemoteCheckRecord {
+    pub schema_version: u32,
+    pub video_id: String,
+    pub author: String,
+    pub path: PathBuf,
+    /// `available`, `removed`, `private`, `unavailable`, or `unchecked` after an error.
+    pub status: &'static str,
+    /// TikTok's `statusCode` for `unavailable` videos.
+    pub status_code: Option<i64>,
+    pub error: Option<String>,
+    pub checked_unix: u64,
+}
+
+impl RemoteCheckRecord {
+    pub fn new(
+        video_id: String,
+        author: String,
+        path: PathBuf,
+        checked: &crate::error::Result<RemoteStatus>,
+        checked_unix: u64,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            video_id,
+            author,
+            path,
+            status: checked.as_ref().map_or("unchecked", RemoteStatus::as_str),
+            status_code: match checked {
+                Ok(RemoteStatus::Unavailable(code)) => Some(*code),
+                _ => None,
+            },
+            error: checked.as_ref().err().map(ToString::to_string),
+            checked_unix,
+        }
+    }
+}
+
+/// One `--trace-http` line: an outgoing request, up to its response headers.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HttpTraceRecord {
+    pub schema_version: u32,
+    /// Number of the request within the run, in the order they were sent.
+    pub request_id: u64,
+    pub started_unix_ms: u64,
+    pub method: String,
+    pub host: String,
+    /// Request URL without its query string.
+    pub url: String,
+    pub status: Option<u16>,
+    /// `Content-Length` of the response, when it had one.
+    pub bytes: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl HttpTraceRecord {
+    pub fn started(started_unix_ms: u64) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            started_unix_ms,
+            ..Self::default()
+        }
+    }
+}
+
+/// One line of the checksum manifest: a finished download as it was written.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecksumRecord {
+    pub schema_version: u32,
+    pub video_id: String,
+    pub author: String,
+    /// File path relative to the manifest's directory.
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// CRC-32 (IEEE) of the file, as eight lowercase hex digits.
+    pub crc32: String,
+    pub recorded_unix: u64,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// Keys of `record` must match the schema's properties, and include all it requires.
+    pub(crate) fn assert_matches_schema(record: &Value, schema: &str) {
+        let schema: Value = serde_json::from_str(schema).expect("schema is valid JSON");
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+        let mut properties: Vec<_> = schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let mut keys: Vec<_> = record.as_object().unwrap().keys().cloned().collect();
+        properties.sort();
+        keys.sort();
+        assert_eq!(keys, properties);
+        for required in schema["required"].as_array().unwrap() {
+            assert!(record.get(required.as_str().unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn report_record_matches_schema() {
+        let report = DownloadReport {
+            url: "https://www.tiktok.com/@a/video/1".to_string(),
+            result: Ok(PathBuf::from("a/1.mp4")),
+            duplicate_of: None,
+            retries: Vec::new(),
+            probe: None,
+            timings: Default::default(),
+            descriptor: None,
+            same_content_as: None,
+            already_downloaded: true,
+            warnings: vec![DownloadWarning::HlsFallback],
+        };
+        let record = ReportRecord::new(&report, RunId::new());
+        assert_eq!(record.status, ReportStatus::Skipped);
+        assert_eq!(record.skip_reason, Some(SkipReason::AlreadyDownloaded));
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["skip_reason"], "already-downloaded");
+        assert_eq!(json["warnings"], serde_json::json!(["hls-fallback"]));
+        assert_matches_schema(&json, REPORT_SCHEMA);
+    }
+
+    #[test]
+    fn checksum_record_matches_schema() {
+        let record = ChecksumRecord {
+            schema_version: SCHEMA_VERSION,
+            video_id: "1".to_string(),
+            author: "a".to_string(),
+            path: PathBuf::from("a/1.mp4"),
+            bytes: 4,
+            crc32: "d87f7e0c".to_string(),
+            recorded_unix: 1_700_000_000,
+        };
+        let json = serde_json::to_value(&record).unwrap();
+        assert_matches_schema(&json, CHECKSUM_SCHEMA);
+        assert_eq!(
+            serde_json::from_value::<ChecksumRecord>(json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn remote_check_record_matches_schema() {
+        let record = RemoteCheckRecord::new(
+            "1".to_string(),
+            "a".to_string(),
+            PathBuf::from("a/1.mp4"),
+            &Ok(RemoteStatus::Unavailable(10101)),
+            1_700_000_000,
+        );
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["status"], "unavailable");
+        assert_eq!(json["status_code"], 10101);
+        assert_matches_schema(&json, REMOTE_CHECK_SCHEMA);
+    }
+}
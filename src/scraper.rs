@@ -1,282 +1,322 @@
-use std::collections::HashMap;
-
 use reqwest::Client;
-use scraper::{Html, Selector};
-use serde::Deserialize;
 use serde_json::Value;
 use url::Url;
 
 use crate::error::{Error, Result};
-
-/// Information needed to perform the actual media download.
-#[derive(Debug, Clone)]
-pub struct VideoDescriptor {
-    pub video_id: String,
-    pub download_url: Option<String>,
-    pub play_url: Option<String>,
-    pub author: String,
+pub use crate::extract::{
+    extract_share_url, unescape_media_url, AuthorProfile, MusicInfo, RemoteStatus, SubtitleTrack,
+    VideoDescriptor, VideoStats,
+};
+use crate::extract::{
+    guess_video_id, is_short_link, login_wall_video_id, parse_embed_page, parse_item_detail,
+    parse_share_page, status_from_page,
+};
+use crate::http_trace;
+use crate::session::DeviceIds;
+
+impl VideoDescriptor {
+    /// Time since extraction, if it was recorded.
+    pub fn age(&self) -> Option<std::time::Duration> {
+        let extracted =
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.extracted_unix?);
+        Some(
+            std::time::SystemTime::now()
+                .duration_since(extracted)
+                .unwrap_or_default(),
+        )
+    }
 }
 
-/// Extracts direct video URLs from TikTok share links.
-#[derive(Clone)]
-pub struct Scraper {
-    client: Client,
+/// Region parameters presented to the item API instead of the caller's real location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Geo {
+    /// Two-letter country code sent as `region` and `priority_region`.
+    pub region: String,
+    /// IANA time zone sent as `tz_name`.
+    pub tz_name: String,
 }
 
-impl Scraper {
-    pub fn new(client: Client) -> Self {
-        Self { client }
-    }
-
-    /// Fetch and resolve the downloadable media URL for a TikTok share link.
-    pub async fn extract_video_descriptor(&self, share_url: &str) -> Result<VideoDescriptor> {
-        if !share_url.contains("tiktok.com") {
-            return Err(Error::InvalidUrl(share_url.to_string()));
+impl Geo {
+    /// Geo parameters for `region`, using the country's main time zone where known.
+    pub fn new(region: &str) -> Self {
+        let region = region.trim().to_ascii_uppercase();
+        let tz_name = match region.as_str() {
+            "TH" => "Asia/Bangkok",
+            "VN" => "Asia/Ho_Chi_Minh",
+            "ID" => "Asia/Jakarta",
+            "MY" => "Asia/Kuala_Lumpur",
+            "SG" => "Asia/Singapore",
+            "PH" => "Asia/Manila",
+            "JP" => "Asia/Tokyo",
+            "KR" => "Asia/Seoul",
+            "TW" => "Asia/Taipei",
+            "IN" => "Asia/Kolkata",
+            "AU" => "Australia/Sydney",
+            "GB" => "Europe/London",
+            "DE" => "Europe/Berlin",
+            "FR" => "Europe/Paris",
+            "ES" => "Europe/Madrid",
+            "IT" => "Europe/Rome",
+            "US" => "America/New_York",
+            "CA" => "America/Toronto",
+            "MX" => "America/Mexico_City",
+            "BR" => "America/Sao_Paulo",
+            _ => "UTC",
         }
-
-        let response = self
-            .client
-            .get(share_url)
-            .send()
-            .await?
-            .error_for_status()?;
-        let final_url = response.url().to_string();
-        let html = response.text().await?;
-
-        parse_share_page(&html, &final_url).ok_or(Error::VideoUrlNotFound)
+        .to_string();
+        Self { region, tz_name }
     }
 }
 
-fn parse_share_page(html: &str, share_url: &str) -> Option<VideoDescriptor> {
-    let document = Html::parse_document(html);
+impl std::str::FromStr for Geo {
+    type Err = String;
 
-    parse_universal_data(&document, share_url)
-        .or_else(|| parse_sigi_state(&document, share_url))
-        .or_else(|| parse_next_data(&document, share_url))
-        .map(|mut descriptor| {
-            if let Some(ref mut url) = descriptor.download_url {
-                *url = url.replace("\\u0026", "&");
-            }
-            if let Some(ref mut url) = descriptor.play_url {
-                *url = url.replace("\\u0026", "&");
-            }
-            descriptor
-        })
+    fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
+        let code = input.trim();
+        if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!(
+                "invalid region: {input} (use a country code such as TH)"
+            ));
+        }
+        Ok(Geo::new(code))
+    }
 }
 
-fn parse_universal_data(document: &Html, share_url: &str) -> Option<VideoDescriptor> {
-    let selector = Selector::parse("script#__UNIVERSAL_DATA_FOR_REHYDRATION__").ok()?;
-    let element = document.select(&selector).next()?;
-    let raw_json = element.text().collect::<String>();
-    let value: Value = serde_json::from_str(&raw_json).ok()?;
-
-    let item = value
-        .get("__DEFAULT_SCOPE__")
-        .and_then(|scope| scope.get("webapp.video-detail"))
-        .and_then(|detail| detail.get("itemInfo"))
-        .and_then(|info| info.get("itemStruct"))?;
-
-    build_descriptor_from_value(item, share_url)
+/// Extracts direct video URLs from TikTok share links.
+#[derive(Clone)]
+pub struct Scraper {
+    client: Client,
+    session_cookie: Option<String>,
+    device: Option<DeviceIds>,
+    geo: Option<Geo>,
+    base_url: Url,
+    short_links_via_head: bool,
 }
 
-fn parse_sigi_state(document: &Html, share_url: &str) -> Option<VideoDescriptor> {
-    let selector = Selector::parse("script#SIGI_STATE").ok()?;
-    let element = document.select(&selector).next()?;
-    let raw_json = element.text().collect::<String>();
-    let sigi_state: SigiState = serde_json::from_str(&raw_json).ok()?;
-
-    resolve_descriptor_from_items(sigi_state.item_module, share_url)
-}
+const DEFAULT_BASE_URL: &str = "https://www.tiktok.com/";
+const ITEM_DETAIL_API: &str = "api/item/detail/";
+const EMBED_PLAYER: &str = "embed/v2/";
 
-fn parse_next_data(document: &Html, share_url: &str) -> Option<VideoDescriptor> {
-    let selector = Selector::parse("script#__NEXT_DATA__").ok()?;
-    let element = document.select(&selector).next()?;
-    let raw_json = element.text().collect::<String>();
-    let next_data: NextData = serde_json::from_str(&raw_json).ok()?;
-
-    let items = next_data
-        .props
-        .page_props
-        .item_info
-        .item_struct
-        .map(|item| {
-            let mut map = HashMap::new();
-            if let Some(id) = item.id.clone() {
-                map.insert(id, item);
-            }
-            map
-        })
-        .unwrap_or_default();
+impl Scraper {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            session_cookie: None,
+            device: None,
+            geo: None,
+            base_url: Url::parse(DEFAULT_BASE_URL).expect("default base URL is valid"),
+            short_links_via_head: false,
+        }
+    }
 
-    resolve_descriptor_from_items(items, share_url)
-}
+    /// Serve share pages and API endpoints from another site root, such as a local mock
+    /// server; share URLs must then point at that host.
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
 
-fn resolve_descriptor_from_items(
-    mut items: HashMap<String, ItemStruct>,
-    share_url: &str,
-) -> Option<VideoDescriptor> {
-    if items.is_empty() {
-        return None;
+    fn uses_tiktok(&self) -> bool {
+        self.base_url
+            .host_str()
+            .is_some_and(|host| host == "tiktok.com" || host.ends_with(".tiktok.com"))
     }
 
-    if let Some(video_id) = guess_video_id(share_url) {
-        if let Some(item) = items.remove(&video_id) {
-            return build_descriptor_from_item(item, share_url);
+    /// Find the share link in `text` that belongs to the configured site.
+    fn resolve_share_url(&self, text: &str) -> Option<String> {
+        if self.uses_tiktok() {
+            return extract_share_url(text);
         }
+        crate::input::extract_urls(text)
+            .into_iter()
+            .find(|candidate| {
+                Url::parse(candidate).is_ok_and(|url| {
+                    url.host_str() == self.base_url.host_str()
+                        && url.port_or_known_default() == self.base_url.port_or_known_default()
+                })
+            })
+            .map(ToOwned::to_owned)
     }
 
-    // fallback to first entry
-    let (_, item) = items.into_iter().next()?;
-    build_descriptor_from_item(item, share_url)
-}
-
-fn build_descriptor_from_value(value: &Value, share_url: &str) -> Option<VideoDescriptor> {
-    let video_id = value
-        .get("id")
-        .and_then(Value::as_str)
-        .map(|s| s.to_string())
-        .or_else(|| guess_video_id(share_url))?;
-
-    let video = value.get("video")?;
-
-    let download_url = video
-        .get("downloadAddr")
-        .and_then(Value::as_str)
-        .map(|s| s.to_string())
-        .filter(|s| !s.is_empty());
-
-    let play_url = video
-        .get("playAddr")
-        .and_then(Value::as_str)
-        .map(|s| s.to_string())
-        .filter(|s| !s.is_empty());
-
-    if download_url.is_none() && play_url.is_none() {
-        return None;
+    fn endpoint(&self, path: &str) -> Result<Url> {
+        self.base_url
+            .join(path)
+            .map_err(|err| Error::InvalidUrl(format!("{path}: {err}")))
     }
 
-    let author = value
-        .get("author")
-        .and_then(|auth| auth.get("uniqueId"))
-        .and_then(Value::as_str)
-        .map(|s| s.to_string())
-        .or_else(|| guess_author_id(share_url))
-        .unwrap_or_else(|| "unknown".to_string());
-
-    Some(VideoDescriptor {
-        video_id,
-        download_url,
-        play_url,
-        author,
-    })
-}
+    /// Device identity sent as `device_id`/`iid` on API requests.
+    pub fn with_device(mut self, device: DeviceIds) -> Self {
+        self.device = Some(device);
+        self
+    }
 
-fn build_descriptor_from_item(item: ItemStruct, share_url: &str) -> Option<VideoDescriptor> {
-    let video = item.video?;
+    /// Cookies of a logged-in session, sent when a video sits behind the login wall.
+    pub fn with_session_cookie(mut self, cookie: impl Into<String>) -> Self {
+        self.session_cookie = Some(cookie.into());
+        self
+    }
 
-    let download_url = video.download_addr.filter(|s| !s.is_empty());
+    /// Follow short links' redirects with HEAD requests, then GET only the page they lead
+    /// to, instead of downloading the body of every redirect on the way.
+    pub fn with_short_links_via_head(mut self) -> Self {
+        self.short_links_via_head = true;
+        self
+    }
 
-    let play_url = video.play_addr.filter(|s| !s.is_empty());
+    /// Region to claim on API requests; also makes the API a fallback when the share page
+    /// has no usable media, as happens for region-restricted items.
+    pub fn with_geo(mut self, geo: Geo) -> Self {
+        self.geo = Some(geo);
+        self
+    }
 
-    if download_url.is_none() && play_url.is_none() {
-        return None;
+    /// Fetch and resolve the downloadable media URL for a TikTok share link.
+    /// `share_url` may also be a whole pasted share message containing the link.
+    pub async fn extract_video_descriptor(&self, share_url: &str) -> Result<VideoDescriptor> {
+        let mut descriptor = self.extract(share_url).await?;
+        descriptor.extracted_unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+        Ok(descriptor)
     }
 
-    let author = item
-        .author
-        .and_then(|a| a.unique_id)
-        .or_else(|| guess_author_id(share_url))
-        .unwrap_or_else(|| "unknown".to_string());
-
-    Some(VideoDescriptor {
-        video_id: item.id?,
-        download_url,
-        play_url,
-        author,
-    })
-}
+    async fn extract(&self, share_url: &str) -> Result<VideoDescriptor> {
+        let (final_url, html) = self.fetch_share_page(share_url).await?;
 
-fn guess_video_id(share_url: &str) -> Option<String> {
-    let url = Url::parse(share_url).ok()?;
-    let segments: Vec<_> = url
-        .path_segments()?
-        .filter(|segment| !segment.is_empty())
-        .collect();
-
-    for window in segments.windows(2) {
-        if let [prefix, id] = window {
-            if *prefix == "video" || *prefix == "note" {
-                return Some((*id).to_string());
+        if let Some(video_id) = login_wall_video_id(&html, &final_url) {
+            if self.session_cookie.is_none() {
+                return Err(Error::LoginRequired(video_id));
             }
+            // An expired session gets the same classified stub without media URLs.
+            return self
+                .fetch_item_detail(&video_id, &final_url)
+                .await?
+                .ok_or(Error::LoginRequired(video_id));
         }
-    }
 
-    segments.last().map(|value| (*value).to_string())
-}
+        if let Some(descriptor) = parse_share_page(&html, &final_url) {
+            return Ok(descriptor);
+        }
+
+        if let Some(descriptor) = self.fetch_embed(&final_url).await {
+            return Ok(descriptor);
+        }
 
-fn guess_author_id(share_url: &str) -> Option<String> {
-    let url = Url::parse(share_url).ok()?;
-    for segment in url.path_segments()? {
-        if let Some(stripped) = segment.strip_prefix('@') {
-            if !stripped.is_empty() {
-                return Some(stripped.to_string());
+        if self.geo.is_some() {
+            if let Some(video_id) = guess_video_id(&final_url) {
+                if let Some(descriptor) = self.fetch_item_detail(&video_id, &final_url).await? {
+                    return Ok(descriptor);
+                }
             }
         }
+
+        Err(Error::VideoUrlNotFound)
     }
-    None
-}
 
-#[derive(Debug, Deserialize)]
-struct SigiState {
-    #[serde(rename = "ItemModule", default)]
-    item_module: HashMap<String, ItemStruct>,
-}
+    /// Check whether `author`'s video `video_id` is still on TikTok, without resolving media.
+    ///
+    /// Pages that neither carry the item nor a status code (bot walls, unknown layouts)
+    /// are an error rather than a guess.
+    pub async fn remote_status(&self, author: &str, video_id: &str) -> Result<RemoteStatus> {
+        let url = self.endpoint(&format!("@{author}/video/{video_id}"))?;
+        let response = http_trace::send(self.client.get(url)).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(RemoteStatus::Removed);
+        }
+        let response = response.error_for_status()?;
+        let final_url = response.url().to_string();
+        let html = response.text().await?;
+        status_from_page(&html, &final_url).ok_or(Error::VideoUrlNotFound)
+    }
 
-#[derive(Debug, Deserialize)]
-struct NextData {
-    props: Props,
-}
+    /// Fetch the raw share page, returning the URL it redirected to and its HTML.
+    pub async fn fetch_share_page(&self, share_url: &str) -> Result<(String, String)> {
+        let share_url = self
+            .resolve_share_url(share_url)
+            .ok_or_else(|| Error::InvalidUrl(share_url.to_string()))?;
+        let share_url = if self.short_links_via_head && is_short_link(&share_url) {
+            self.resolve_short_link(&share_url).await?
+        } else {
+            share_url
+        };
+
+        let response = http_trace::send(self.client.get(&share_url))
+            .await?
+            .error_for_status()?;
+        let final_url = response.url().to_string();
+        Ok((final_url, response.text().await?))
+    }
 
-#[derive(Debug, Deserialize)]
-struct Props {
-    #[serde(rename = "pageProps")]
-    page_props: PageProps,
-}
+    /// Where a short link ends up, following its redirects without fetching any bodies.
+    ///
+    /// The final status is not checked; the GET of the resolved page reports it.
+    async fn resolve_short_link(&self, short_url: &str) -> Result<String> {
+        let response = http_trace::send(self.client.head(short_url)).await?;
+        tracing::debug!("Short link {} resolved to {}", short_url, response.url());
+        Ok(response.url().to_string())
+    }
 
-#[derive(Debug, Deserialize)]
-struct PageProps {
-    #[serde(rename = "itemInfo")]
-    item_info: ItemInfo,
-}
+    /// Try the embed player page, which is often served when the share page is bot-walled.
+    async fn fetch_embed(&self, share_url: &str) -> Option<VideoDescriptor> {
+        let video_id =
+            guess_video_id(share_url).filter(|id| id.bytes().all(|b| b.is_ascii_digit()))?;
+        let embed_url = self.endpoint(&format!("{EMBED_PLAYER}{video_id}")).ok()?;
+
+        let html = match self.fetch_text(embed_url.as_str()).await {
+            Ok(html) => html,
+            Err(err) => {
+                tracing::debug!("embed page {} unavailable: {}", embed_url, err);
+                return None;
+            }
+        };
+        parse_embed_page(&html, share_url)
+    }
 
-#[derive(Debug, Deserialize)]
-struct ItemInfo {
-    #[serde(rename = "itemStruct")]
-    item_struct: Option<ItemStruct>,
-}
+    async fn fetch_text(&self, url: &str) -> Result<String> {
+        Ok(http_trace::send(self.client.get(url))
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
 
-#[derive(Debug, Deserialize, Clone)]
-struct ItemStruct {
-    #[serde(default)]
-    id: Option<String>,
-    #[serde(default)]
-    video: Option<VideoStruct>,
-    #[serde(default)]
-    author: Option<AuthorStruct>,
-}
+    /// Load an item through the item detail API, with session, device, and geo parameters.
+    async fn fetch_item_detail(
+        &self,
+        video_id: &str,
+        share_url: &str,
+    ) -> Result<Option<VideoDescriptor>> {
+        let mut request = self
+            .client
+            .get(self.endpoint(ITEM_DETAIL_API)?)
+            .query(&self.api_params(video_id))
+            .header(reqwest::header::REFERER, share_url);
+        if let Some(cookie) = self.session_cookie.as_deref() {
+            request = request.header(reqwest::header::COOKIE, cookie);
+        }
 
-#[derive(Debug, Deserialize, Clone)]
-struct VideoStruct {
-    #[serde(rename = "downloadAddr", default)]
-    download_addr: Option<String>,
-    #[serde(rename = "playAddr", default)]
-    play_addr: Option<String>,
-}
+        let detail: Value = http_trace::send(request)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(parse_item_detail(&detail, share_url))
+    }
 
-#[derive(Debug, Deserialize, Clone)]
-struct AuthorStruct {
-    #[serde(rename = "uniqueId", default)]
-    unique_id: Option<String>,
+    fn api_params(&self, video_id: &str) -> Vec<(&'static str, String)> {
+        let mut params = vec![("itemId", video_id.to_string())];
+        if let Some(device) = &self.device {
+            params.push(("device_id", device.device_id.clone()));
+            params.push(("iid", device.install_id.clone()));
+        }
+        if let Some(geo) = &self.geo {
+            params.push(("region", geo.region.clone()));
+            params.push(("priority_region", geo.region.clone()));
+            params.push(("tz_name", geo.tz_name.clone()));
+        }
+        params
+    }
 }
 
 #[cfg(test)]
@@ -284,48 +324,41 @@ mod tests {
     use super::*;
 
     #[test]
-    fn parse_descriptor_from_sigi_state() {
-        let html = include_str!("../tests/fixtures/sample_sigi_state.html");
-        let document = Html::parse_document(html);
-        let descriptor =
-            parse_sigi_state(&document, "https://www.tiktok.com/@user/video/1234567890");
-        assert!(descriptor.is_some());
-        let descriptor = descriptor.unwrap();
-        assert_eq!(descriptor.video_id, "1234567890");
-        assert!(descriptor
-            .download_url
-            .as_deref()
-            .unwrap()
-            .contains("example.com"));
-        assert_eq!(descriptor.author, "sigi_author");
-    }
-
-    #[test]
-    fn parse_descriptor_from_universal_data() {
-        let html = include_str!("../tests/fixtures/sample_universal_data.html");
-        let document = Html::parse_document(html);
-        let descriptor =
-            parse_universal_data(&document, "https://www.tiktok.com/@user/video/9876543210");
-        assert!(descriptor.is_some());
-        let descriptor = descriptor.unwrap();
-        assert_eq!(descriptor.video_id, "9876543210");
-        assert!(descriptor
-            .download_url
-            .as_deref()
-            .unwrap()
-            .contains("example.com"));
-        assert_eq!(descriptor.author, "sample_author");
-    }
-
-    #[test]
-    fn guess_id_handles_numeric_path() {
-        let id = guess_video_id("https://www.tiktok.com/@user/video/987654321");
-        assert_eq!(id, Some("987654321".into()));
+    fn geo_overrides_api_params() {
+        let geo: Geo = "th".parse().unwrap();
+        assert_eq!(geo.region, "TH");
+        assert_eq!(geo.tz_name, "Asia/Bangkok");
+        assert!("Thailand".parse::<Geo>().is_err());
+
+        let scraper = Scraper::new(Client::new()).with_geo(geo);
+        let params = scraper.api_params("7551");
+        assert!(params.contains(&("region", "TH".into())));
+        assert!(params.contains(&("priority_region", "TH".into())));
+        assert!(params.contains(&("tz_name", "Asia/Bangkok".into())));
     }
 
     #[test]
-    fn guess_id_falls_back_to_last_segment() {
-        let id = guess_video_id("https://www.tiktok.com/t/ZT8abcd/");
-        assert_eq!(id, Some("ZT8abcd".into()));
+    fn base_url_moves_allowed_host_and_endpoints() {
+        let scraper = Scraper::new(Client::new())
+            .with_base_url(Url::parse("http://127.0.0.1:8080/").unwrap());
+
+        assert_eq!(
+            scraper
+                .resolve_share_url("see http://127.0.0.1:8080/@user/video/1 now")
+                .as_deref(),
+            Some("http://127.0.0.1:8080/@user/video/1")
+        );
+        assert_eq!(
+            scraper.resolve_share_url("http://127.0.0.1:9090/@user/video/1"),
+            None
+        );
+        assert_eq!(
+            scraper.resolve_share_url("https://vt.tiktok.com/ZSabc/"),
+            None
+        );
+        assert_eq!(
+            scraper.endpoint(ITEM_DETAIL_API).unwrap().as_str(),
+            "http://127.0.0.1:8080/api/item/detail/"
+        );
     }
 }
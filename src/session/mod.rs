@@ -0,0 +1,185 @@
+//! Logged-in session state and, with the `cookies` feature, the QR-code login flow that
+//! produces it and the session file it is kept in.
+
+#[cfg(feature = "cookies")]
+use std::fs;
+#[cfg(feature = "cookies")]
+use std::io::Write;
+#[cfg(feature = "cookies")]
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "cookies")]
+use crate::error::Result;
+
+#[cfg(feature = "cookies")]
+mod qr;
+
+#[cfg(feature = "cookies")]
+pub use qr::{QrLogin, QrStatus, QrTicket};
+
+/// Session file used when `--session-file` is not given.
+#[cfg(feature = "cookies")]
+pub const DEFAULT_SESSION_FILE: &str = "tikd-r-session.json";
+
+/// Cookies of a logged-in TikTok account, persisted between runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Cookie header sent with authenticated requests.
+    pub cookie: String,
+    /// Device identity presented to API endpoints; kept stable across runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceIds>,
+}
+
+/// Device and install IDs in the shape the TikTok apps register.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceIds {
+    pub device_id: String,
+    pub install_id: String,
+}
+
+impl DeviceIds {
+    /// Random 19-digit IDs starting with 7, like those issued to current app installs.
+    pub fn generate() -> Self {
+        Self {
+            device_id: random_id(),
+            install_id: random_id(),
+        }
+    }
+}
+
+fn random_id() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    // Each RandomState is seeded independently, which is enough for an opaque identifier.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default(),
+    );
+    format!("7{:018}", hasher.finish() % 1_000_000_000_000_000_000)
+}
+
+impl Session {
+    /// Attach device IDs if the session has none; returns whether it changed.
+    pub fn ensure_device(&mut self) -> bool {
+        if self.device.is_some() {
+            return false;
+        }
+        self.device = Some(DeviceIds::generate());
+        true
+    }
+
+    /// Read a session file, returning `None` when it does not exist yet.
+    #[cfg(feature = "cookies")]
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Write the session file, readable by the current user only on Unix.
+    ///
+    /// The file is created under a `.part` name with its final permissions and renamed
+    /// over `path`, so the cookies are never readable by others, not even briefly.
+    #[cfg(feature = "cookies")]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        // Left by an interrupted save; `create_new` refuses to reuse it.
+        match fs::remove_file(&partial) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let written = options.open(&partial).and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        });
+        if let Err(err) = written.and_then(|()| fs::rename(&partial, path)) {
+            let _ = fs::remove_file(&partial);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "cookies")]
+    #[test]
+    fn session_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/session.json");
+        assert_eq!(Session::load(&path).unwrap(), None);
+
+        let mut session = Session {
+            cookie: "sessionid=abc; sid_tt=abc".into(),
+            device: None,
+        };
+        assert!(session.ensure_device());
+        assert!(!session.ensure_device());
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), Some(session));
+    }
+
+    #[cfg(all(unix, feature = "cookies"))]
+    #[test]
+    fn saved_sessions_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::write(dir.path().join("session.json.part"), "stale").unwrap();
+
+        let session = Session {
+            cookie: "sessionid=abc".into(),
+            device: None,
+        };
+        session.save(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(Session::load(&path).unwrap(), Some(session));
+        assert!(!dir.path().join("session.json.part").exists());
+    }
+
+    #[test]
+    fn sessions_without_device_ids_still_load() {
+        let session: Session = serde_json::from_str(r#"{"cookie":"sessionid=abc"}"#).unwrap();
+        assert_eq!(session.device, None);
+    }
+
+    #[test]
+    fn generated_ids_look_like_app_ids() {
+        let ids = DeviceIds::generate();
+        for id in [&ids.device_id, &ids.install_id] {
+            assert_eq!(id.len(), 19);
+            assert!(id.starts_with('7'));
+            assert!(id.bytes().all(|b| b.is_ascii_digit()));
+        }
+        assert_ne!(ids.device_id, ids.install_id);
+    }
+}
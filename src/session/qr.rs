@@ -0,0 +1,232 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client;
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::Value;
+use url::Url;
+
+use super::Session;
+use crate::downloader::{build_http_client_with, new_cookie_store};
+use crate::error::{Error, Result};
+use crate::http_trace;
+
+const PASSPORT_BASE: &str = "https://www.tiktok.com/";
+const WEB_AID: &str = "1459";
+
+/// A QR code issued by TikTok, to be scanned with the mobile app.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrTicket {
+    /// URL encoded in the QR code.
+    pub scan_url: String,
+    pub token: String,
+}
+
+/// State of a pending QR login.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QrStatus {
+    Waiting,
+    Scanned,
+    Confirmed { redirect_url: Option<String> },
+    Expired,
+}
+
+/// Drives TikTok's web QR login and collects the resulting session cookies.
+pub struct QrLogin {
+    client: Client,
+    cookie_store: Arc<CookieStoreMutex>,
+    base: Url,
+}
+
+impl QrLogin {
+    pub fn new() -> Result<Self> {
+        let cookie_store = new_cookie_store();
+        let client = build_http_client_with(cookie_store.clone(), None)?;
+        Ok(Self {
+            client,
+            cookie_store,
+            base: Url::parse(PASSPORT_BASE).expect("passport base URL is valid"),
+        })
+    }
+
+    /// Ask TikTok for a fresh QR code.
+    pub async fn request_ticket(&self) -> Result<QrTicket> {
+        let response = self.passport_get("passport/web/get_qrcode/", &[]).await?;
+        parse_ticket(&response)
+    }
+
+    pub async fn poll(&self, ticket: &QrTicket) -> Result<QrStatus> {
+        let response = self
+            .passport_get("passport/web/check_qrconnect/", &[("token", &ticket.token)])
+            .await?;
+        parse_status(&response)
+    }
+
+    /// Show a QR code through `show`, wait for the user to confirm it, and return the session.
+    pub async fn run(
+        &self,
+        show: impl FnOnce(&QrTicket),
+        poll_interval: Duration,
+    ) -> Result<Session> {
+        let ticket = self.request_ticket().await?;
+        show(&ticket);
+
+        let mut announced_scan = false;
+        loop {
+            match self.poll(&ticket).await? {
+                QrStatus::Waiting => {}
+                QrStatus::Scanned => {
+                    if !announced_scan {
+                        tracing::info!("QR code scanned, waiting for confirmation");
+                        announced_scan = true;
+                    }
+                }
+                QrStatus::Confirmed { redirect_url } => {
+                    if let Some(url) = redirect_url {
+                        // Following the redirect sets the session cookies.
+                        http_trace::send(self.client.get(url))
+                            .await?
+                            .error_for_status()?;
+                    }
+                    return self.session();
+                }
+                QrStatus::Expired => {
+                    return Err(Error::Login(
+                        "QR code expired before it was confirmed".into(),
+                    ))
+                }
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Session built from the cookies collected for tiktok.com so far.
+    pub fn session(&self) -> Result<Session> {
+        let store = self
+            .cookie_store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let cookie = store
+            .matches(&self.base)
+            .into_iter()
+            .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        if !cookie
+            .split("; ")
+            .any(|pair| pair.starts_with("sessionid="))
+        {
+            return Err(Error::Login(
+                "login finished without a session cookie".into(),
+            ));
+        }
+        let mut session = Session {
+            cookie,
+            device: None,
+        };
+        session.ensure_device();
+        Ok(session)
+    }
+
+    async fn passport_get(&self, path: &str, extra: &[(&str, &str)]) -> Result<Value> {
+        let url = self
+            .base
+            .join(path)
+            .map_err(|err| Error::InvalidUrl(err.to_string()))?;
+        let request = self
+            .client
+            .get(url)
+            .query(&[
+                ("aid", WEB_AID),
+                ("account_sdk_source", "web"),
+                ("next", PASSPORT_BASE),
+            ])
+            .query(extra);
+        let response = http_trace::send(request).await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+fn passport_data(response: &Value) -> Result<&Value> {
+    if response.get("message").and_then(Value::as_str) != Some("success") {
+        let reason = response
+            .pointer("/data/description")
+            .and_then(Value::as_str)
+            .unwrap_or("unexpected passport response");
+        return Err(Error::Login(reason.to_string()));
+    }
+    response
+        .get("data")
+        .ok_or_else(|| Error::Login("passport response without data".into()))
+}
+
+fn parse_ticket(response: &Value) -> Result<QrTicket> {
+    let data = passport_data(response)?;
+    let field = |name: &str| {
+        data.get(name)
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Login(format!("QR code response without {name}")))
+    };
+    Ok(QrTicket {
+        scan_url: field("qrcode_index_url")?,
+        token: field("token")?,
+    })
+}
+
+fn parse_status(response: &Value) -> Result<QrStatus> {
+    let data = passport_data(response)?;
+    match data.get("status").and_then(Value::as_str) {
+        Some("new") => Ok(QrStatus::Waiting),
+        Some("scanned") => Ok(QrStatus::Scanned),
+        Some("confirmed") => Ok(QrStatus::Confirmed {
+            redirect_url: data
+                .get("redirect_url")
+                .and_then(Value::as_str)
+                .filter(|url| !url.is_empty())
+                .map(ToOwned::to_owned),
+        }),
+        Some("expired") => Ok(QrStatus::Expired),
+        other => Err(Error::Login(format!(
+            "unknown QR login status: {}",
+            other.unwrap_or("none")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn passport_responses_parse() {
+        let ticket = parse_ticket(&json!({
+            "message": "success",
+            "data": {"qrcode_index_url": "https://www.tiktok.com/t/qr", "token": "tok"}
+        }))
+        .unwrap();
+        assert_eq!(ticket.token, "tok");
+
+        let status = parse_status(&json!({
+            "message": "success",
+            "data": {"status": "confirmed", "redirect_url": "https://www.tiktok.com/login/done"}
+        }))
+        .unwrap();
+        assert_eq!(
+            status,
+            QrStatus::Confirmed {
+                redirect_url: Some("https://www.tiktok.com/login/done".into())
+            }
+        );
+
+        let err = parse_status(&json!({
+            "message": "error",
+            "data": {"description": "Too many attempts"}
+        }))
+        .unwrap_err();
+        assert!(matches!(err, Error::Login(reason) if reason == "Too many attempts"));
+    }
+}
@@ -0,0 +1,384 @@
+//! `--output-template` paths such as `{author}/{upload_date}-{video_id}`.
+//!
+//! A template is parsed once into literal text and placeholders, split into path
+//! components at `/`. Rendering fills in the placeholders; sanitizing and shortening the
+//! resulting names is left to the downloader, which does the same for default paths.
+
+use std::fmt;
+
+use regex::Regex;
+
+/// Value a template can refer to by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `{author}`: the creator's handle.
+    Author,
+    /// `{video_id}`.
+    VideoId,
+    /// `{upload_date}`: the day the video was posted, as `YYYYMMDD` in UTC.
+    UploadDate,
+    /// `{desc}`: the caption as a slug, like `--filename-style title-id` names.
+    Desc,
+    /// `{title}`: the caption as written, on one line, or `TikTok video #<id>` without
+    /// one, like yt-dlp's `%(title)s`.
+    Title,
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 5] = [
+        Placeholder::Author,
+        Placeholder::VideoId,
+        Placeholder::UploadDate,
+        Placeholder::Desc,
+        Placeholder::Title,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Placeholder::Author => "author",
+            Placeholder::VideoId => "video_id",
+            Placeholder::UploadDate => "upload_date",
+            Placeholder::Desc => "desc",
+            Placeholder::Title => "title",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// yt-dlp's default output template, `%(title)s [%(id)s]`.
+pub const YT_DLP_TEMPLATE: &str = "{title} [{video_id}]";
+
+/// Output path of a video relative to the output directory, without its extension.
+///
+/// `{` and `}` are written `{{` and `}}`. The last component names the file; the ones
+/// before it are directories.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTemplate {
+    components: Vec<Vec<Piece>>,
+}
+
+impl OutputTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.starts_with('/') {
+            return Err(format!(
+                "output template '{template}' must be relative to the output directory"
+            ));
+        }
+        let components = template
+            .split('/')
+            .map(|component| match component {
+                "" => Err(format!(
+                    "output template '{template}' has an empty path component"
+                )),
+                "." | ".." => Err(format!(
+                    "output template '{template}' may not contain '{component}'"
+                )),
+                _ => parse_component(component),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let file = components.last().map_or(&[][..], |pieces| &pieces[..]);
+        if !file.contains(&Piece::Placeholder(Placeholder::VideoId)) {
+            return Err(format!(
+                "output template '{template}' must name files with {{video_id}}, or two videos could share a path"
+            ));
+        }
+        Ok(Self { components })
+    }
+
+    /// Whether a file's directory is named by `{author}` alone, so that it only ever holds
+    /// one creator's videos.
+    pub fn dir_is_author(&self) -> bool {
+        let dirs = &self.components[..self.components.len().saturating_sub(1)];
+        dirs.last()
+            .is_some_and(|pieces| pieces[..] == [Piece::Placeholder(Placeholder::Author)])
+    }
+
+    /// Whether `placeholder` appears anywhere in the template.
+    pub fn uses(&self, placeholder: Placeholder) -> bool {
+        self.components
+            .iter()
+            .flatten()
+            .any(|piece| *piece == Piece::Placeholder(placeholder))
+    }
+
+    /// Matcher for the paths this template renders, with literal text passed through
+    /// `literal` the way rendered names are sanitized.
+    pub fn matcher(&self, literal: impl Fn(&str) -> String) -> PathMatcher {
+        let mut named = Vec::new();
+        let components = self
+            .components
+            .iter()
+            .map(|pieces| {
+                let mut pattern = String::from("^");
+                for piece in pieces {
+                    match piece {
+                        Piece::Text(text) => pattern.push_str(&regex::escape(&literal(text))),
+                        Piece::Placeholder(placeholder) => {
+                            let value = match placeholder {
+                                Placeholder::VideoId => r"\d+",
+                                Placeholder::UploadDate => r"\d{8}|unknown",
+                                Placeholder::Author => ".+?",
+                                _ => ".*?",
+                            };
+                            let captured =
+                                matches!(placeholder, Placeholder::VideoId | Placeholder::Author);
+                            if captured && !named.contains(placeholder) {
+                                named.push(*placeholder);
+                                pattern.push_str(&format!("(?P<{}>{value})", placeholder.name()));
+                            } else {
+                                pattern.push_str(&format!("(?:{value})"));
+                            }
+                        }
+                    }
+                }
+                pattern.push('$');
+                Regex::new(&pattern).expect("escaped template pattern is valid")
+            })
+            .collect();
+        PathMatcher { components }
+    }
+
+    /// Path components with every placeholder replaced by `value(placeholder)`.
+    pub fn render(&self, value: impl Fn(Placeholder) -> String) -> Vec<String> {
+        self.components
+            .iter()
+            .map(|pieces| {
+                pieces
+                    .iter()
+                    .map(|piece| match piece {
+                        Piece::Text(text) => text.clone(),
+                        Piece::Placeholder(placeholder) => value(*placeholder),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn parse_component(component: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed '{{' in output template near '{{{rest}'"))?;
+                let name = &rest[..end];
+                let placeholder = Placeholder::ALL
+                    .into_iter()
+                    .find(|placeholder| placeholder.name() == name)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown placeholder '{{{name}}}' (use {{author}}, {{video_id}}, \
+                             {{upload_date}}, {{desc}}, or {{title}})"
+                        )
+                    })?;
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Placeholder(placeholder));
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err("unmatched '}' in output template; write '}}' for a brace".into()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, pieces) in self.components.iter().enumerate() {
+            if index > 0 {
+                f.write_str("/")?;
+            }
+            for piece in pieces {
+                match piece {
+                    Piece::Text(text) => {
+                        f.write_str(&text.replace('{', "{{").replace('}', "}}"))?
+                    }
+                    Piece::Placeholder(placeholder) => write!(f, "{{{}}}", placeholder.name())?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the video ID, and the creator when the template names one, back out of a path
+/// rendered by an `OutputTemplate`; see `OutputTemplate::matcher`.
+#[derive(Clone, Debug)]
+pub struct PathMatcher {
+    components: Vec<Regex>,
+}
+
+impl PathMatcher {
+    /// Number of path components the template renders.
+    pub fn depth(&self) -> usize {
+        self.components.len()
+    }
+
+    /// `(video_id, author)` of a path relative to the output directory, without its
+    /// extension, as components. Names that were shortened to fit do not match.
+    pub fn capture(&self, components: &[&str]) -> Option<(String, Option<String>)> {
+        if components.len() != self.components.len() {
+            return None;
+        }
+        let (mut video_id, mut author) = (None, None);
+        for (pattern, component) in self.components.iter().zip(components) {
+            let captures = pattern.captures(component)?;
+            if let Some(id) = captures.name("video_id") {
+                video_id = Some(id.as_str().to_string());
+            }
+            if let Some(name) = captures.name("author") {
+                author = Some(name.as_str().to_string());
+            }
+        }
+        Some((video_id?, author))
+    }
+}
+
+/// `YYYYMMDD` of a Unix timestamp, in UTC.
+pub fn upload_date(unix: u64) -> String {
+    // Days to civil date, after Howard Hinnant's `civil_from_days`.
+    let days = unix / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year:04}{month:02}{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str) -> Vec<String> {
+        OutputTemplate::parse(template)
+            .unwrap()
+            .render(|placeholder| format!("<{}>", placeholder.name()))
+    }
+
+    #[test]
+    fn renders_placeholders_per_component() {
+        assert_eq!(
+            render("{author}/{video_id}"),
+            vec!["<author>", "<video_id>"]
+        );
+        assert_eq!(
+            render("tiktok/{author}/{upload_date}-{desc} [{video_id}]"),
+            vec!["tiktok", "<author>", "<upload_date>-<desc> [<video_id>]"]
+        );
+        assert_eq!(render("{{{video_id}}}"), vec!["{<video_id>}"]);
+        assert_eq!(render(YT_DLP_TEMPLATE), vec!["<title> [<video_id>]"]);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in [
+            "{author}/{name}",
+            "{author",
+            "video}",
+            "/abs/{video_id}",
+            "{author}//{video_id}",
+            "../{video_id}",
+            "{author}/",
+            "{video_id}/{desc}",
+            "{author}/{upload_date}",
+        ] {
+            assert!(OutputTemplate::parse(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn knows_when_directories_belong_to_one_author() {
+        let owned = |template| OutputTemplate::parse(template).unwrap().dir_is_author();
+        assert!(owned("{author}/{video_id}"));
+        assert!(owned("tiktok/{author}/{upload_date}-{video_id}"));
+        assert!(!owned("{author}/{upload_date}/{video_id}"));
+        assert!(!owned("by-{author}/{video_id}"));
+        assert!(!owned(YT_DLP_TEMPLATE));
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for template in [
+            "{author}/{upload_date}-{video_id}",
+            "{{x}}/{desc}-{video_id}",
+        ] {
+            let parsed = OutputTemplate::parse(template).unwrap();
+            assert_eq!(parsed.to_string(), template);
+            assert_eq!(OutputTemplate::parse(&parsed.to_string()).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn matches_the_paths_it_renders() {
+        let capture = |template: &str, path: &str| {
+            let components: Vec<&str> = path.split('/').collect();
+            OutputTemplate::parse(template)
+                .unwrap()
+                .matcher(str::to_string)
+                .capture(&components)
+        };
+        assert_eq!(
+            capture(YT_DLP_TEMPLATE, "Pad thai [at] home [7551]"),
+            Some(("7551".into(), None))
+        );
+        assert_eq!(
+            capture(
+                "tiktok/{author}/{upload_date}_{video_id}",
+                "tiktok/chef/20250101_7551"
+            ),
+            Some(("7551".into(), Some("chef".into())))
+        );
+        assert_eq!(capture(YT_DLP_TEMPLATE, "Pad thai [7551"), None);
+        assert_eq!(capture("{author}/{video_id}", "chef/extra/7551"), None);
+        assert_eq!(capture("clips/{video_id}", "other/7551"), None);
+
+        let stripped = OutputTemplate::parse(YT_DLP_TEMPLATE)
+            .unwrap()
+            .matcher(|text| text.replace([' ', '[', ']'], ""));
+        assert_eq!(
+            stripped.capture(&["Padthai7551"]).map(|(id, _)| id),
+            Some("7551".into())
+        );
+    }
+
+    #[test]
+    fn upload_dates_are_utc_days() {
+        assert_eq!(upload_date(0), "19700101");
+        assert_eq!(upload_date(951_782_400), "20000229");
+        // 2024-12-31T23:59:59Z and one second later.
+        assert_eq!(upload_date(1_735_689_599), "20241231");
+        assert_eq!(upload_date(1_735_689_600), "20250101");
+    }
+}
@@ -1,16 +1,16 @@
+#![cfg(feature = "client")]
+
 use std::path::PathBuf;
+use std::time::Duration;
 
-use tikd_r::cli::Cli;
+use tikd_r::cli::{parse_byte_size, parse_duration, parse_file_mode, parse_write_buffer_size, Cli};
 
 #[test]
 fn cli_requires_either_url_or_file() {
     let cli = Cli {
         url: None,
         file: None,
-        max_concurrent: None,
-        max_retries: None,
-        backoff_ms: None,
-        output_dir: None,
+        ..Default::default()
     };
 
     assert!(cli.validate().is_err());
@@ -21,10 +21,7 @@ fn cli_rejects_conflicting_inputs() {
     let cli = Cli {
         url: Some("https://www.tiktok.com/@user/video/123".into()),
         file: Some(PathBuf::from("urls.txt")),
-        max_concurrent: None,
-        max_retries: None,
-        backoff_ms: None,
-        output_dir: None,
+        ..Default::default()
     };
 
     assert!(cli.validate().is_err());
@@ -35,11 +32,46 @@ fn cli_accepts_single_url() {
     let cli = Cli {
         url: Some("https://www.tiktok.com/@user/video/123".into()),
         file: None,
-        max_concurrent: None,
-        max_retries: None,
-        backoff_ms: None,
-        output_dir: None,
+        ..Default::default()
     };
 
     assert!(cli.validate().is_ok());
 }
+
+#[test]
+fn parse_byte_size_accepts_suffixes() {
+    assert_eq!(parse_byte_size("1024"), Ok(1024));
+    assert_eq!(parse_byte_size("2K"), Ok(2048));
+    assert_eq!(parse_byte_size("500M"), Ok(500 * 1024 * 1024));
+    assert_eq!(parse_byte_size("1gb"), Ok(1024 * 1024 * 1024));
+    assert!(parse_byte_size("12X").is_err());
+    assert!(parse_byte_size("M").is_err());
+}
+
+#[test]
+fn write_buffer_size_is_capped() {
+    assert_eq!(parse_write_buffer_size("256K"), Ok(256 * 1024));
+    assert_eq!(parse_write_buffer_size("64M"), Ok(64 * 1024 * 1024));
+    assert!(parse_write_buffer_size("65M").is_err());
+    assert!(parse_write_buffer_size("1T").is_err());
+}
+
+#[test]
+fn parse_file_mode_reads_octal() {
+    assert_eq!(parse_file_mode("644"), Ok(0o644));
+    assert_eq!(parse_file_mode("0640"), Ok(0o640));
+    assert_eq!(parse_file_mode("0o2775"), Ok(0o2775));
+    assert!(parse_file_mode("0999").is_err());
+    assert!(parse_file_mode("17777").is_err());
+    assert!(parse_file_mode("").is_err());
+}
+
+#[test]
+fn parse_duration_accepts_units() {
+    assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+    assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+    assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+    assert!(parse_duration("10x").is_err());
+    assert!(parse_duration("1h30").is_err());
+}
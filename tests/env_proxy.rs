@@ -0,0 +1,46 @@
+//! Proxy environment variables; kept in their own test binary because they are process-wide.
+
+#![cfg(feature = "client")]
+
+use tikd_r::downloader::{DownloadConfig, Downloader};
+use url::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn env_proxy_is_used_unless_disabled() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    // Nothing listens on port 9, so every proxied request fails to connect.
+    std::env::set_var("HTTP_PROXY", "http://127.0.0.1:9");
+    std::env::remove_var("NO_PROXY");
+    std::env::remove_var("no_proxy");
+
+    let share_url = format!("{}/@mock_user/video/1", server.uri());
+    for env_proxy in [true, false] {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            output_dir: Some(dir.path().to_path_buf()),
+            max_retries: 0,
+            env_proxy,
+            scrape_base_url: Some(Url::parse(&server.uri()).unwrap()),
+            ..DownloadConfig::default()
+        };
+        Downloader::with_config(config)
+            .unwrap()
+            .download_one(&share_url)
+            .await
+            .expect_err("the mock page has no video");
+    }
+
+    let direct = server.received_requests().await.unwrap().len();
+    assert_eq!(
+        direct, 1,
+        "only the run without env proxies reaches the server"
+    );
+}
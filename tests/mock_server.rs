@@ -0,0 +1,1621 @@
+//! Full download flow against a local mock of TikTok and its CDN.
+
+#![cfg(feature = "client")]
+
+use std::path::Path;
+
+use tikd_r::downloader::{
+    build_http_client, list_archive, prune_archive, verify_checksums, ContentDedupe,
+    DownloadConfig, DownloadWarning, Downloader, FileCheck, FilenameStyle, InfoJsonFormat,
+    MatchFilter, Outcome, PipelineConfig, ProgressEvent, Quality, RetentionPolicy, RetryReason,
+    SanitizePolicy, SkipReason, LAYOUTS_FILE,
+};
+use tikd_r::scraper::{RemoteStatus, Scraper};
+use tikd_r::template::{OutputTemplate, YT_DLP_TEMPLATE};
+use url::Url;
+use wiremock::matchers::{header, method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn share_page(video_id: &str, download_url: &str, play_url: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"{video_id}","author":{{"uniqueId":"mock_user"}},"video":{{"downloadAddr":"{download_url}","playAddr":"{play_url}"}}}}}}}}}}}}</script></head><body></body></html>"#
+    )
+}
+
+fn mock_config(server: &MockServer, output_dir: &Path) -> DownloadConfig {
+    DownloadConfig {
+        output_dir: Some(output_dir.to_path_buf()),
+        initial_backoff_ms: 1,
+        scrape_base_url: Some(Url::parse(&server.uri()).unwrap()),
+        ..DownloadConfig::default()
+    }
+}
+
+fn downloader(server: &MockServer, output_dir: &Path, quality: Quality) -> Downloader {
+    let config = DownloadConfig {
+        quality,
+        ..mock_config(server, output_dir)
+    };
+    Downloader::with_config(config).expect("build downloader")
+}
+
+async fn mount_share_page(server: &MockServer, video_id: &str) {
+    let uri = server.uri();
+    Mock::given(method("GET"))
+        .and(path(format!("/@mock_user/video/{video_id}")))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page(
+                video_id,
+                &format!("{uri}/media/{video_id}.mp4"),
+                &format!("{uri}/hls/master.m3u8"),
+            ),
+            "text/html",
+        ))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn downloads_binary_from_mock_share_page() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1001").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1001.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4-bytes".to_vec(), "video/mp4"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = downloader(&server, dir.path(), Quality::Best)
+        .download_one(&format!("{}/@mock_user/video/1001", server.uri()))
+        .await
+        .expect("download succeeds");
+
+    assert_eq!(path, dir.path().join("mock_user/1001.mp4"));
+    assert_eq!(std::fs::read(path).unwrap(), b"mp4-bytes");
+}
+
+#[tokio::test]
+async fn kept_partial_files_are_resumed() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1009").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1009.mp4"))
+        .and(header("range", "bytes=4-"))
+        .respond_with(
+            ResponseTemplate::new(206)
+                .insert_header("content-range", "bytes 4-8/9")
+                .set_body_raw(b"bytes".to_vec(), "video/mp4"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("mock_user")).unwrap();
+    std::fs::write(dir.path().join("mock_user/1009.mp4.part"), b"mp4-").unwrap();
+    let config = DownloadConfig {
+        keep_partial: true,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1009", server.uri()))
+        .await
+        .expect("download resumes");
+
+    assert_eq!(std::fs::read(path).unwrap(), b"mp4-bytes");
+}
+
+#[tokio::test]
+async fn batch_reports_record_timings() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1010").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1010.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"timed".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let reports = downloader(&server, dir.path(), Quality::Best)
+        .download_all(&[format!("{}/@mock_user/video/1010", server.uri())])
+        .await;
+
+    assert!(reports[0].is_success());
+    let timings = reports[0].timings;
+    assert!(timings.scrape.is_some());
+    assert!(timings.first_byte.is_some());
+    assert!(timings.transfer.is_some());
+}
+
+#[tokio::test]
+async fn copies_finished_file_to_extra_dirs() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1011").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1011.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"copied".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mirror = tempfile::tempdir().unwrap();
+    // A regular file where a directory is needed, so this copy fails.
+    let blocked = mirror.path().join("blocked");
+    std::fs::write(&blocked, "").unwrap();
+    let config = DownloadConfig {
+        also_copy_to: vec![blocked, mirror.path().join("synced")],
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1011", server.uri()))
+        .await
+        .expect("a failed copy does not fail the download");
+
+    assert_eq!(std::fs::read(path).unwrap(), b"copied");
+    let copy = mirror.path().join("synced/mock_user/1011.mp4");
+    assert_eq!(std::fs::read(copy).unwrap(), b"copied");
+}
+
+#[tokio::test]
+async fn writes_author_assets_once_per_creator() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    for id in ["1014", "1015"] {
+        let page = format!(
+            r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"{id}","author":{{"uniqueId":"mock_user","signature":"bio","verified":true,"avatarLarger":"{uri}/avatar/large.jpeg"}},"authorStats":{{"followerCount":7}},"video":{{"downloadAddr":"{uri}/media/{id}.mp4"}}}}}}}}}}}}</script></head><body></body></html>"#
+        );
+        Mock::given(method("GET"))
+            .and(path(format!("/@mock_user/video/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/media/{id}.mp4")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("/avatar/large.jpeg"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"img".to_vec(), "image/webp"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        write_author_json: true,
+        write_avatar: true,
+        ..mock_config(&server, dir.path())
+    };
+    let urls: Vec<String> = ["1014", "1015"]
+        .iter()
+        .map(|id| format!("{uri}/@mock_user/video/{id}"))
+        .collect();
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    assert!(reports.iter().all(|report| report.is_success()));
+    let author_dir = dir.path().join("mock_user");
+    assert_eq!(
+        std::fs::read(author_dir.join("avatar.webp")).unwrap(),
+        b"img"
+    );
+    let snapshot: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(author_dir.join("author.json")).unwrap()).unwrap();
+    assert_eq!(snapshot["bio"], "bio");
+    assert_eq!(snapshot["follower_count"], 7);
+    assert_eq!(snapshot["avatar_url"], format!("{uri}/avatar/large.jpeg"));
+}
+
+#[tokio::test]
+async fn creators_sharing_a_directory_keep_separate_author_files() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    for (id, author) in [("1016", "chef"), ("1017", "baker")] {
+        let page = format!(
+            r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"{id}","author":{{"uniqueId":"{author}","signature":"{author} bio"}},"video":{{"downloadAddr":"{uri}/media/{id}.mp4"}}}}}}}}}}}}</script></head><body></body></html>"#
+        );
+        Mock::given(method("GET"))
+            .and(path(format!("/@{author}/video/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/media/{id}.mp4")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        write_author_json: true,
+        output_template: Some(OutputTemplate::parse(YT_DLP_TEMPLATE).unwrap()),
+        ..mock_config(&server, dir.path())
+    };
+    let urls = [
+        format!("{uri}/@chef/video/1016"),
+        format!("{uri}/@baker/video/1017"),
+    ];
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    assert!(reports.iter().all(|report| report.is_success()));
+    assert!(!dir.path().join("author.json").exists());
+    for author in ["chef", "baker"] {
+        let snapshot: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(dir.path().join(format!("{author}.author.json"))).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(snapshot["bio"], format!("{author} bio"));
+    }
+}
+
+#[tokio::test]
+async fn writes_cover_images_next_to_the_video() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    let page = format!(
+        r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"1051","author":{{"uniqueId":"mock_user"}},"video":{{"downloadAddr":"{uri}/media/1051.mp4","cover":"{uri}/covers/1051~tplv-cover.image","originCover":"{uri}/covers/1051-origin.jpeg"}}}}}}}}}}}}</script></head><body></body></html>"#
+    );
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1051"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1051.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/covers/1051~tplv-cover.image"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"cover".to_vec(), "image/webp"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/covers/1051-origin.jpeg"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(b"origin".to_vec(), "application/octet-stream"),
+        )
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        write_thumbnail: true,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{uri}/@mock_user/video/1051"))
+        .await
+        .unwrap();
+
+    let author_dir = dir.path().join("mock_user");
+    assert_eq!(path, author_dir.join("1051.mp4"));
+    assert_eq!(
+        std::fs::read(author_dir.join("1051.cover.webp")).unwrap(),
+        b"cover"
+    );
+    assert_eq!(
+        std::fs::read(author_dir.join("1051.origin-cover.jpg")).unwrap(),
+        b"origin"
+    );
+}
+
+#[tokio::test]
+async fn writes_the_selected_subtitle_tracks() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    let page = format!(
+        r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"1052","author":{{"uniqueId":"mock_user"}},"video":{{"downloadAddr":"{uri}/media/1052.mp4","subtitleInfos":[{{"LanguageCodeName":"eng-US","Url":"{uri}/subs/1052-eng-US.vtt","Format":"webvtt","Source":"ASR"}}],"claInfo":{{"captionInfos":[{{"language":"eng-GB","url":"{uri}/subs/1052-eng-GB.vtt"}},{{"language":"tha-TH","url":"{uri}/subs/1052-tha-TH.vtt"}}]}}}}}}}}}}}}}}</script></head><body></body></html>"#
+    );
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1052"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1052.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+    let vtt = "WEBVTT\n\n00:00.000 --> 00:01.500\nhello\n";
+    Mock::given(method("GET"))
+        .and(path("/subs/1052-eng-US.vtt"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(vtt, "text/vtt"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/subs/1052-eng-GB.vtt"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("<html>denied</html>", "text/html"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/subs/1052-tha-TH.vtt"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        write_subs: true,
+        sub_langs: vec!["eng".to_string()],
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{uri}/@mock_user/video/1052"))
+        .await
+        .unwrap();
+
+    let author_dir = dir.path().join("mock_user");
+    assert_eq!(path, author_dir.join("1052.mp4"));
+    assert_eq!(
+        std::fs::read_to_string(author_dir.join("1052.eng-US.vtt")).unwrap(),
+        vtt
+    );
+    // Not a WebVTT file, so nothing is kept.
+    assert!(!author_dir.join("1052.eng-GB.vtt").exists());
+    assert!(!author_dir.join("1052.eng-GB.vtt.part").exists());
+}
+
+#[tokio::test]
+async fn yt_dlp_layout_matches_yt_dlp_names() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    let page = format!(
+        r#"<!DOCTYPE html><html><head><script id="__UNIVERSAL_DATA_FOR_REHYDRATION__" type="application/json">{{"__DEFAULT_SCOPE__":{{"webapp.video-detail":{{"itemInfo":{{"itemStruct":{{"id":"1053","desc":"Night market: ข้าวซอย #food","createTime":1735689600,"author":{{"uniqueId":"mock_user","nickname":"Mock User"}},"stats":{{"playCount":42}},"video":{{"downloadAddr":"{uri}/media/1053.mp4"}}}}}}}}}}}}</script></head><body></body></html>"#
+    );
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1053"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1053.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    // What `--compat yt-dlp` sets up on Unix.
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        output_template: Some(OutputTemplate::parse(YT_DLP_TEMPLATE).unwrap()),
+        sanitize: SanitizePolicy::UnicodeSafe,
+        write_info_json: true,
+        info_json_format: InfoJsonFormat::YtDlp,
+        ..mock_config(&server, dir.path())
+    };
+    let share_url = format!("{uri}/@mock_user/video/1053");
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&share_url)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        path,
+        dir.path().join("Night market: ข้าวซอย #food [1053].mp4")
+    );
+    let info: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(
+            dir.path()
+                .join("Night market: ข้าวซอย #food [1053].info.json"),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(info["id"], "1053");
+    assert_eq!(info["title"], "Night market: ข้าวซอย #food");
+    assert_eq!(info["uploader"], "mock_user");
+    assert_eq!(info["channel"], "Mock User");
+    assert_eq!(info["upload_date"], "20250101");
+    assert_eq!(info["view_count"], 42);
+    assert_eq!(info["webpage_url"], share_url.as_str());
+    assert_eq!(info["_filename"], path.to_string_lossy().as_ref());
+
+    let listed = list_archive(dir.path()).unwrap();
+    assert_eq!(
+        (listed[0].video_id.as_str(), listed[0].author.as_str()),
+        ("1053", "mock_user")
+    );
+}
+
+#[tokio::test]
+async fn missing_upload_time_is_reported_as_a_warning() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1055").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1055.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        output_template: Some(OutputTemplate::parse("{author}/{upload_date}-{video_id}").unwrap()),
+        ..mock_config(&server, dir.path())
+    };
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&[format!("{}/@mock_user/video/1055", server.uri())])
+        .await;
+
+    assert_eq!(
+        reports[0].path(),
+        Some(&dir.path().join("mock_user/unknown-1055.mp4"))
+    );
+    assert_eq!(reports[0].warnings, vec![DownloadWarning::NoUploadDate]);
+}
+
+#[tokio::test]
+async fn title_names_of_same_caption_videos_stay_distinct() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    for id in ["1059", "1060"] {
+        let page = share_page(id, &format!("{uri}/media/{id}.mp4"), "")
+            .replace(r#""author":"#, r#""desc":"Night run #fyp","author":"#);
+        Mock::given(method("GET"))
+            .and(path(format!("/@mock_user/video/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/media/{id}.mp4")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(id.as_bytes(), "video/mp4"))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let urls = [
+        format!("{uri}/@mock_user/video/1059"),
+        format!("{uri}/@mock_user/video/1060"),
+    ];
+    let run = || async {
+        let config = DownloadConfig {
+            filename_style: FilenameStyle::Title,
+            write_info_json: true,
+            ..mock_config(&server, dir.path())
+        };
+        Downloader::with_config(config)
+            .unwrap()
+            .download_all(&urls)
+            .await
+            .into_iter()
+            .map(|report| report.path().cloned().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let paths = run().await;
+    let user = dir.path().join("mock_user");
+    let mut names: Vec<_> = paths
+        .iter()
+        .map(|path| path.strip_prefix(&user).unwrap())
+        .collect();
+    names.sort();
+    assert!(
+        names == [Path::new("night-run-1059.mp4"), Path::new("night-run.mp4")]
+            || names == [Path::new("night-run-1060.mp4"), Path::new("night-run.mp4")],
+        "{names:?}"
+    );
+    for (path, id) in paths.iter().zip(["1059", "1060"]) {
+        assert_eq!(std::fs::read(path).unwrap(), id.as_bytes());
+    }
+    assert_eq!(run().await, paths, "a second run finds both files again");
+}
+
+#[tokio::test]
+async fn dry_runs_leave_a_full_archive_untouched() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("mock_user").join("1057.mp4");
+    std::fs::create_dir_all(old.parent().unwrap()).unwrap();
+    std::fs::write(&old, vec![0u8; 64]).unwrap();
+    std::fs::write(old.with_extension("info.json"), "{}").unwrap();
+
+    let config = DownloadConfig {
+        dry_run: true,
+        max_archive_size: Some(32),
+        prune_oldest: true,
+        ..mock_config(&server, dir.path())
+    };
+    let err = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1058", server.uri()))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, tikd_r::error::Error::ArchiveQuotaExceeded { .. }),
+        "{err}"
+    );
+    assert!(old.exists());
+    assert!(old.with_extension("info.json").exists());
+}
+
+#[tokio::test]
+async fn archive_tools_read_templated_paths() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1056").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1056.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        output_template: Some(
+            OutputTemplate::parse("{author}-videos/{title} [{video_id}]").unwrap(),
+        ),
+        sanitize: SanitizePolicy::UnicodeSafe,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1056", server.uri()))
+        .await
+        .unwrap();
+    assert!(dir.path().join(LAYOUTS_FILE).exists());
+
+    let listed = list_archive(dir.path()).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].video_id, "1056");
+    assert_eq!(listed[0].author, "mock_user");
+    let policy = RetentionPolicy {
+        keep_per_author: Some(0),
+        ..RetentionPolicy::default()
+    };
+    assert_eq!(
+        prune_archive(dir.path(), &policy, true).unwrap(),
+        vec![path]
+    );
+}
+
+#[tokio::test]
+async fn match_filter_skips_before_media_request() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1012").await;
+    mount_share_page(&server, "1013").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1013.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"kept".to_vec(), "video/mp4"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1012.mp4"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        match_filters: vec![
+            MatchFilter::parse("author=someone_else").unwrap(),
+            MatchFilter::parse("id~=3$").unwrap(),
+        ],
+        ..mock_config(&server, dir.path())
+    };
+    let urls: Vec<String> = ["1012", "1013"]
+        .iter()
+        .map(|id| format!("{}/@mock_user/video/{id}", server.uri()))
+        .collect();
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    assert!(reports[0].is_skipped());
+    assert_eq!(reports[0].outcome(), Outcome::Skipped(SkipReason::Filtered));
+    assert!(reports[1].is_success());
+}
+
+#[tokio::test]
+async fn cached_descriptor_skips_second_scrape() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1008"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page(
+                "1008",
+                &format!("{uri}/media/1008.mp4"),
+                &format!("{uri}/hls/master.m3u8"),
+            ),
+            "text/html",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1008.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"cached".to_vec(), "video/mp4"))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let cache = tempfile::tempdir().unwrap();
+    for _ in 0..2 {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            descriptor_cache_dir: Some(cache.path().to_path_buf()),
+            ..mock_config(&server, dir.path())
+        };
+        let path = Downloader::with_config(config)
+            .unwrap()
+            .download_one(&format!("{uri}/@mock_user/video/1008"))
+            .await
+            .expect("download succeeds");
+        assert_eq!(std::fs::read(path).unwrap(), b"cached");
+    }
+}
+
+#[tokio::test]
+async fn probed_batches_download_without_scraping_again() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1054"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page(
+                "1054",
+                &format!("{uri}/media/1054.mp4"),
+                &format!("{uri}/hls/master.m3u8"),
+            ),
+            "text/html",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("HEAD"))
+        .and(path("/media/1054.mp4"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-length", "6"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1054.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"probed".to_vec(), "video/mp4"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let urls = [format!("{uri}/@mock_user/video/1054")];
+    let probe = Downloader::with_config(DownloadConfig {
+        dry_run: true,
+        ..mock_config(&server, dir.path())
+    })
+    .unwrap()
+    .download_all(&urls)
+    .await;
+    let downloader = Downloader::with_config(mock_config(&server, dir.path())).unwrap();
+    downloader.reuse_scrapes(&probe);
+    let reports = downloader.download_all(&urls).await;
+
+    assert!(reports[0].is_success());
+    let path = reports[0].path().expect("downloaded");
+    assert_eq!(std::fs::read(path).unwrap(), b"probed");
+}
+
+#[tokio::test]
+async fn dry_run_probes_size_without_writing() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1009").await;
+    Mock::given(method("HEAD"))
+        .and(path("/media/1009.mp4"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-length", "4096"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/1009.mp4"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        dry_run: true,
+        ..mock_config(&server, dir.path())
+    };
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&[format!("{}/@mock_user/video/1009", server.uri())])
+        .await;
+
+    let probe = reports[0].probe.as_ref().expect("media was probed");
+    assert!(probe.is_available());
+    assert_eq!(probe.size, Some(4096));
+    let path = reports[0].result.as_ref().expect("dry run succeeds");
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn falls_back_to_selected_hls_variant() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1002").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1002.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1080x1920\nhigh.m3u8\n#EXT-X-STREAM-INF:BANDWIDTH=400000,RESOLUTION=480x854\nlow.m3u8\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/high.m3u8"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/low.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2,\nseg0.ts\n#EXTINF:2,\nseg1.ts\n#EXT-X-ENDLIST\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(&server)
+        .await;
+    for (name, body) in [("seg0.ts", "first-"), ("seg1.ts", "second")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/hls/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "video/mp2t"))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+    let config = DownloadConfig {
+        quality: Quality::Worst,
+        progress: Some(progress),
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1002", server.uri()))
+        .await
+        .expect("HLS fallback succeeds");
+
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "first-second");
+
+    let mut segments = Vec::new();
+    while let Ok(ProgressEvent::Segment {
+        index,
+        total,
+        bytes,
+        ..
+    }) = events.try_recv()
+    {
+        segments.push((index, total, bytes));
+    }
+    assert_eq!(segments, vec![(1, 2, 6), (2, 2, 12)]);
+}
+
+#[tokio::test]
+async fn fallbacks_are_reported_as_warnings() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1040").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1040.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2,\nseg0.ts\n#EXT-X-ENDLIST\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/seg0.ts"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("segment", "video/mp2t"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let reports = downloader(&server, dir.path(), Quality::Best)
+        .download_all(&[format!("{}/@mock_user/video/1040", server.uri())])
+        .await;
+
+    assert!(reports[0].is_success());
+    assert_eq!(
+        reports[0].warnings,
+        vec![DownloadWarning::Rescraped, DownloadWarning::HlsFallback]
+    );
+}
+
+#[tokio::test]
+async fn requests_byte_range_segments() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1004").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1004.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MAP:URI=\"all.mp4\",BYTERANGE=\"4@0\"\n#EXTINF:2,\n#EXT-X-BYTERANGE:6@4\nall.mp4\n#EXTINF:2,\n#EXT-X-BYTERANGE:6\nall.mp4\n#EXT-X-ENDLIST\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(&server)
+        .await;
+    for (range, body) in [
+        ("bytes=0-3", "init"),
+        ("bytes=4-9", "first-"),
+        ("bytes=10-15", "second"),
+    ] {
+        Mock::given(method("GET"))
+            .and(path("/hls/all.mp4"))
+            .and(header("range", range))
+            .respond_with(ResponseTemplate::new(206).set_body_raw(body, "video/mp4"))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = downloader(&server, dir.path(), Quality::Best)
+        .download_one(&format!("{}/@mock_user/video/1004", server.uri()))
+        .await
+        .expect("byte-range playlist succeeds");
+
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "initfirst-second");
+}
+
+async fn mount_split_audio_stream(server: &MockServer, video_id: &str) {
+    mount_share_page(server, video_id).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/media/{video_id}.mp4")))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Main\",DEFAULT=YES,URI=\"audio.m3u8\"\n#EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO=\"aac\"\nvideo.m3u8\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(server)
+        .await;
+    for stream in ["video", "audio"] {
+        Mock::given(method("GET"))
+            .and(path(format!("/hls/{stream}.m3u8")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                format!("#EXTM3U\n#EXTINF:2,\n{stream}0.ts\n#EXT-X-ENDLIST\n"),
+                "application/vnd.apple.mpegurl",
+            ))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/hls/{stream}0.ts")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(stream, "video/mp2t"))
+            .mount(server)
+            .await;
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn muxes_separate_audio_rendition() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = MockServer::start().await;
+    mount_split_audio_stream(&server, "1005").await;
+
+    // Stand-in for ffmpeg that concatenates its two inputs into the output.
+    let dir = tempfile::tempdir().unwrap();
+    let ffmpeg = dir.path().join("fake-ffmpeg");
+    std::fs::write(&ffmpeg, "#!/bin/sh\ncat \"$5\" \"$7\" > \"${16}\"\n").unwrap();
+    std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let config = DownloadConfig {
+        ffmpeg,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1005", server.uri()))
+        .await
+        .expect("muxed download succeeds");
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "videoaudio");
+    let leftovers: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(leftovers, vec![path.file_name().unwrap().to_os_string()]);
+}
+
+#[tokio::test]
+async fn missing_ffmpeg_for_separate_audio_is_a_clear_error() {
+    let server = MockServer::start().await;
+    mount_split_audio_stream(&server, "1006").await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        ffmpeg: dir.path().join("no-such-ffmpeg"),
+        ..mock_config(&server, dir.path())
+    };
+    let result = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1006", server.uri()))
+        .await;
+
+    assert!(matches!(result, Err(tikd_r::error::Error::Mux(_))));
+}
+
+#[tokio::test]
+async fn records_live_playlist_until_it_ends() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1007").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1007.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    // First load is mid-stream; the reload slides the window and ends the stream.
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:7\n#EXTINF:1,\nseg7.ts\n#EXTINF:1,\nseg8.ts\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:8\n#EXTINF:1,\nseg8.ts\n#EXTINF:1,\nseg9.ts\n#EXT-X-ENDLIST\n",
+            "application/vnd.apple.mpegurl",
+        ))
+        .mount(&server)
+        .await;
+    for (name, body) in [("seg7.ts", "7"), ("seg8.ts", "8"), ("seg9.ts", "9")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/hls/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "video/mp2t"))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = downloader(&server, dir.path(), Quality::Best)
+        .download_one(&format!("{}/@mock_user/video/1007", server.uri()))
+        .await
+        .expect("live recording succeeds");
+
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "789");
+}
+
+#[tokio::test]
+async fn retries_transient_share_page_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1003"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .expect(2)
+        .mount(&server)
+        .await;
+    mount_share_page(&server, "1003").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1003.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"third".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let reports = downloader(&server, dir.path(), Quality::Best)
+        .download_all(&[format!("{}/@mock_user/video/1003", server.uri())])
+        .await;
+
+    let report = &reports[0];
+    assert_eq!(std::fs::read(report.path().unwrap()).unwrap(), b"third");
+    let reasons: Vec<_> = report
+        .retries
+        .iter()
+        .map(|r| (r.attempt, r.reason))
+        .collect();
+    assert_eq!(
+        reasons,
+        vec![(1, RetryReason::ServerError), (2, RetryReason::ServerError)]
+    );
+}
+
+#[cfg(feature = "simulate-network")]
+#[tokio::test]
+async fn retries_simulated_packet_loss() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        max_retries: 2,
+        initial_backoff_ms: 1,
+        simulate_network: Some(tikd_r::downloader::NetworkSimulation {
+            latency: std::time::Duration::from_millis(5),
+            loss: 1.0,
+        }),
+        ..mock_config(&server, dir.path())
+    };
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&[format!("{}/@mock_user/video/1041", server.uri())])
+        .await;
+
+    let report = &reports[0];
+    assert!(matches!(
+        report.error(),
+        Some(tikd_r::error::Error::SimulatedLoss)
+    ));
+    let reasons: Vec<_> = report.retries.iter().map(|r| r.reason).collect();
+    assert_eq!(reasons, vec![RetryReason::Network, RetryReason::Network]);
+    // Lost before sending, nothing reached the server.
+    assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn resolves_short_links_with_head_requests() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    Mock::given(method("HEAD"))
+        .and(path("/t/ZT1042/"))
+        .respond_with(ResponseTemplate::new(302).insert_header("location", "/t/hop/"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("HEAD"))
+        .and(path("/t/hop/"))
+        .respond_with(
+            ResponseTemplate::new(301).insert_header("location", "/@mock_user/video/1042"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/t/"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    mount_share_page(&server, "1042").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1042.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"short".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        short_links_via_head: true,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{uri}/t/ZT1042/"))
+        .await
+        .expect("download succeeds");
+    assert_eq!(std::fs::read(path).unwrap(), b"short");
+}
+
+#[tokio::test]
+async fn redirect_loops_stop_at_the_limit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/t/loop/"))
+        .respond_with(ResponseTemplate::new(302).insert_header("location", "/t/loop/"))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        max_retries: 0,
+        max_redirects: 3,
+        ..mock_config(&server, dir.path())
+    };
+    let err = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/t/loop/", server.uri()))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, tikd_r::error::Error::Network(ref inner) if inner.is_redirect()));
+}
+
+#[tokio::test]
+async fn download_archive_skips_listed_videos_and_records_new_ones() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1043"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    mount_share_page(&server, "1044").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1044.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"new".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("archive.txt");
+    std::fs::write(&archive, "youtube abc\ntiktok 1043\n").unwrap();
+    let config = DownloadConfig {
+        download_archive: Some(archive.clone()),
+        ..mock_config(&server, dir.path())
+    };
+    let urls: Vec<String> = ["1043", "1044"]
+        .iter()
+        .map(|id| format!("{}/@mock_user/video/{id}", server.uri()))
+        .collect();
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    assert_eq!(
+        reports[0].outcome(),
+        Outcome::Skipped(SkipReason::AlreadyDownloaded)
+    );
+    assert!(reports[1].is_success());
+    assert_eq!(
+        std::fs::read_to_string(&archive).unwrap(),
+        "youtube abc\ntiktok 1043\ntiktok 1044\n"
+    );
+}
+
+#[tokio::test]
+async fn max_per_author_skips_the_rest_of_a_creators_videos() {
+    let server = MockServer::start().await;
+    for id in ["1046", "1047", "1048", "1049", "1050"] {
+        mount_share_page(&server, id).await;
+    }
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/media/10(48|49|50)\.mp4$"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"video".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("mock_user")).unwrap();
+    std::fs::write(dir.path().join("mock_user/1046.mp4"), "old").unwrap();
+    let config = DownloadConfig {
+        max_per_author: Some(2),
+        max_concurrent_downloads: 1,
+        max_retries: 0,
+        ..mock_config(&server, dir.path())
+    };
+    let urls: Vec<String> = ["1046", "1047", "1048", "1049", "1050"]
+        .iter()
+        .map(|id| format!("{}/@mock_user/video/{id}", server.uri()))
+        .collect();
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    let outcomes: Vec<_> = reports.iter().map(|report| report.outcome()).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            // Files already on disk and failed downloads leave the creator's slots free.
+            Outcome::Skipped(SkipReason::AlreadyDownloaded),
+            Outcome::Failed,
+            Outcome::Success,
+            Outcome::Success,
+            Outcome::Skipped(SkipReason::AuthorLimit),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn written_checksums_verify_until_the_file_changes() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1045").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1045.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"intact".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        write_checksums: true,
+        ..mock_config(&server, dir.path())
+    };
+    let path = Downloader::with_config(config)
+        .unwrap()
+        .download_one(&format!("{}/@mock_user/video/1045", server.uri()))
+        .await
+        .unwrap();
+
+    let (checks, unreadable) = verify_checksums(dir.path()).unwrap();
+    assert_eq!(unreadable, 0);
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].0.video_id, "1045");
+    assert_eq!(checks[0].0.bytes, 6);
+    assert_eq!(checks[0].1, FileCheck::Ok);
+
+    std::fs::write(&path, "broken").unwrap();
+    let (checks, _) = verify_checksums(dir.path()).unwrap();
+    assert_eq!(checks[0].1, FileCheck::Corrupted);
+}
+
+#[tokio::test]
+async fn rejects_share_urls_for_other_hosts() {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let result = downloader(&server, dir.path(), Quality::Best)
+        .download_one("https://vt.tiktok.com/ZSabc/")
+        .await;
+
+    assert!(matches!(result, Err(tikd_r::error::Error::InvalidUrl(_))));
+}
+
+#[tokio::test]
+async fn remote_status_detects_removed_videos() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1016").await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1017"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let scraper = Scraper::new(build_http_client().unwrap())
+        .with_base_url(Url::parse(&server.uri()).unwrap());
+    assert_eq!(
+        scraper.remote_status("mock_user", "1016").await.unwrap(),
+        RemoteStatus::Available
+    );
+    assert_eq!(
+        scraper.remote_status("mock_user", "1017").await.unwrap(),
+        RemoteStatus::Removed
+    );
+}
+
+#[tokio::test]
+async fn content_dedupe_skips_reuploads_under_new_ids() {
+    let server = MockServer::start().await;
+    for id in ["1018", "1019"] {
+        mount_share_page(&server, id).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/media/{id}.mp4")))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(b"reupload".to_vec(), "video/mp4"),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        content_dedupe: ContentDedupe::Skip,
+        ..mock_config(&server, dir.path())
+    };
+    let downloader = Downloader::with_config(config).unwrap();
+    let original = downloader
+        .download_one(&format!("{}/@mock_user/video/1018", server.uri()))
+        .await
+        .unwrap();
+    let reports = downloader
+        .download_all(&[format!("{}/@mock_user/video/1019", server.uri())])
+        .await;
+
+    assert!(reports[0].is_skipped());
+    assert_eq!(
+        reports[0].outcome(),
+        Outcome::Skipped(SkipReason::Duplicate)
+    );
+    assert!(original.exists());
+    assert!(!dir.path().join("mock_user/1019.mp4").exists());
+}
+
+#[tokio::test]
+async fn existing_files_are_reported_as_already_downloaded() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1020").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1020.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"video".to_vec(), "video/mp4"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let downloader = Downloader::with_config(mock_config(&server, dir.path())).unwrap();
+    let url = format!("{}/@mock_user/video/1020", server.uri());
+    let first = downloader.download_all(std::slice::from_ref(&url)).await;
+    let second = downloader.download_all(&[url]).await;
+
+    assert_eq!(first[0].outcome(), Outcome::Success);
+    assert_eq!(
+        second[0].outcome(),
+        Outcome::Skipped(SkipReason::AlreadyDownloaded)
+    );
+    assert_eq!(second[0].path(), first[0].path());
+}
+
+#[tokio::test]
+async fn pipeline_scrapes_each_item_once_and_stays_bounded() {
+    let server = MockServer::start().await;
+    let ids = ["1020", "1021", "1022", "1023", "1024", "1025"];
+    for id in ids {
+        Mock::given(method("GET"))
+            .and(path(format!("/@mock_user/video/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                share_page(id, &format!("{}/media/{id}.mp4", server.uri()), ""),
+                "text/html",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/media/{id}.mp4")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(b"mp4".to_vec(), "video/mp4")
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        max_concurrent_downloads: 1,
+        pipeline: Some(PipelineConfig {
+            scrape_workers: 1,
+            queue_depth: 1,
+        }),
+        ..mock_config(&server, dir.path())
+    };
+    let downloader = Downloader::with_config(config).unwrap();
+    let urls: Vec<String> = ids
+        .iter()
+        .map(|id| format!("{}/@mock_user/video/{id}", server.uri()))
+        .collect();
+    let batch = tokio::spawn(async move { downloader.download_all(&urls).await });
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let scraped = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path().starts_with("/@mock_user/"))
+        .count();
+    // One item downloading, one queued, and one scraped item waiting for queue space.
+    assert!(scraped <= 3, "scrape stage ran {scraped} items ahead");
+
+    let reports = batch.await.unwrap();
+    assert!(reports.iter().all(|report| report.is_success()));
+    assert!(reports.iter().all(|report| report.timings.scrape.is_some()));
+}
+
+#[tokio::test]
+async fn rejected_cached_url_is_scraped_again() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1026"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page("1026", &format!("{uri}/media/stale.mp4"), ""),
+            "text/html",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1026"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page("1026", &format!("{uri}/media/fresh.mp4"), ""),
+            "text/html",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/stale.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"first".to_vec(), "video/mp4"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/stale.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/fresh.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"fresh".to_vec(), "video/mp4"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let cache = tempfile::tempdir().unwrap();
+    let mut contents = Vec::new();
+    for _ in 0..2 {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            descriptor_cache_dir: Some(cache.path().to_path_buf()),
+            max_retries: 0,
+            ..mock_config(&server, dir.path())
+        };
+        let path = Downloader::with_config(config)
+            .unwrap()
+            .download_one(&format!("{uri}/@mock_user/video/1026"))
+            .await
+            .expect("download succeeds");
+        contents.push(std::fs::read(path).unwrap());
+    }
+    assert_eq!(contents, vec![b"first".to_vec(), b"fresh".to_vec()]);
+}
+
+#[tokio::test]
+async fn old_cached_urls_are_refreshed_before_downloading() {
+    let server = MockServer::start().await;
+    mount_share_page(&server, "1027").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1027.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"mp4".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+
+    let cache = tempfile::tempdir().unwrap();
+    for _ in 0..2 {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DownloadConfig {
+            descriptor_cache_dir: Some(cache.path().to_path_buf()),
+            max_url_age: Some(std::time::Duration::ZERO),
+            ..mock_config(&server, dir.path())
+        };
+        Downloader::with_config(config)
+            .unwrap()
+            .download_one(&format!("{}/@mock_user/video/1027", server.uri()))
+            .await
+            .expect("download succeeds");
+    }
+    let scrapes = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/@mock_user/video/1027")
+        .count();
+    assert_eq!(scrapes, 2);
+}
+
+#[tokio::test]
+async fn forbidden_binary_is_scraped_again_before_hls() {
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1028"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page(
+                "1028",
+                &format!("{uri}/media/expired.mp4"),
+                &format!("{uri}/hls/master.m3u8"),
+            ),
+            "text/html",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1028"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            share_page(
+                "1028",
+                &format!("{uri}/media/signed.mp4"),
+                &format!("{uri}/hls/master.m3u8"),
+            ),
+            "text/html",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/expired.mp4"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/media/signed.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"signed".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hls/master.m3u8"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = downloader(&server, dir.path(), Quality::Best)
+        .download_one(&format!("{uri}/@mock_user/video/1028"))
+        .await
+        .expect("download succeeds");
+    assert_eq!(std::fs::read(path).unwrap(), b"signed");
+}
+
+#[tokio::test]
+async fn later_passes_rerun_only_retryable_failures() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1021"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_share_page(&server, "1021").await;
+    Mock::given(method("GET"))
+        .and(path("/media/1021.mp4"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(b"second".to_vec(), "video/mp4"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/@mock_user/video/1022"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig {
+        max_retries: 0,
+        batch_passes: 3,
+        pass_cooldown: std::time::Duration::from_millis(10),
+        ..mock_config(&server, dir.path())
+    };
+    let urls: Vec<String> = ["1021", "1022"]
+        .iter()
+        .map(|id| format!("{}/@mock_user/video/{id}", server.uri()))
+        .collect();
+    let reports = Downloader::with_config(config)
+        .unwrap()
+        .download_all(&urls)
+        .await;
+
+    assert_eq!(reports[0].outcome(), Outcome::Success);
+    assert_eq!(
+        std::fs::read(reports[0].path().unwrap()).unwrap(),
+        b"second"
+    );
+    assert_eq!(reports[1].outcome(), Outcome::Failed);
+}